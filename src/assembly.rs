@@ -0,0 +1,116 @@
+use crate::data::Scaffold;
+
+/// Summary statistics of an assembly split into contigs at gap runs, see
+/// `assembly_stats`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct AssemblyStats {
+    /// Number of scaffolds.
+    pub scaffolds: usize,
+    /// Total length of all scaffolds.
+    pub total_len: usize,
+    /// Number of gap runs.
+    pub gaps: usize,
+    /// Total length of all gap runs.
+    pub gap_len: usize,
+    /// Number of non-empty contigs, i.e. stretches between gap runs.
+    pub contigs: usize,
+    /// Total length of all contigs, which may contain gaps shorter than
+    /// the minimum gap length.
+    pub contig_len: usize,
+    pub longest_contig: usize,
+    /// Largest contig length such that contigs at least this long cover at
+    /// least half of `contig_len`, zero if there are no contigs.
+    pub contig_n50: usize,
+    /// Smallest number of contigs covering at least half of `contig_len`.
+    pub contig_l50: usize,
+}
+
+/// Computes assembly statistics splitting scaffolds into contigs at gap
+/// runs of at least `min_gap_len` symbols, see `Scaffold::gap_runs`. Runs
+/// touching scaffold ends are gaps too, scaffolds entirely made of a gap
+/// contain no contig.
+pub fn assembly_stats(scaffolds: &[Scaffold], min_gap_len: usize) -> AssemblyStats {
+    let mut stats = AssemblyStats {
+        scaffolds: scaffolds.len(),
+        ..AssemblyStats::default()
+    };
+
+    let mut contig_lens = Vec::new();
+    for scaffold in scaffolds {
+        stats.total_len += scaffold.len();
+        let mut start = 0;
+        for gap in scaffold.gap_runs(min_gap_len) {
+            stats.gaps += 1;
+            stats.gap_len += gap.len();
+            if start < gap.start {
+                contig_lens.push(gap.start - start);
+            }
+            start = gap.end;
+        }
+        if start < scaffold.len() {
+            contig_lens.push(scaffold.len() - start);
+        }
+    }
+
+    stats.contigs = contig_lens.len();
+    stats.contig_len = contig_lens.iter().sum();
+    contig_lens.sort_unstable_by(|a, b| b.cmp(a));
+    stats.longest_contig = contig_lens.first().copied().unwrap_or(0);
+
+    let mut covered = 0;
+    for (i, &len) in contig_lens.iter().enumerate() {
+        covered += len;
+        if 2 * covered >= stats.contig_len {
+            stats.contig_n50 = len;
+            stats.contig_l50 = i + 1;
+            break;
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::AssemblyStats;
+    use crate::data::{parse_sequence, Scaffold};
+
+    fn scaffold(sequence: &str) -> Scaffold {
+        Scaffold::new(String::from("s"), parse_sequence(sequence).unwrap())
+    }
+
+    #[test]
+    fn test_assembly_stats() {
+        let scaffolds = vec![
+            // Contigs of length 7, 2 and 3, the single N is not a gap.
+            scaffold("NNACGTNACNNNGTNNNAAC"),
+            // Entirely a gap.
+            scaffold("NNNNN"),
+            // A single contig of length 10.
+            scaffold("ACGTACGTAC"),
+        ];
+        let stats = super::assembly_stats(&scaffolds, 2);
+        assert_eq!(
+            stats,
+            AssemblyStats {
+                scaffolds: 3,
+                total_len: 35,
+                gaps: 4,
+                gap_len: 13,
+                contigs: 4,
+                contig_len: 22,
+                longest_contig: 10,
+                contig_n50: 7,
+                contig_l50: 2,
+            }
+        );
+
+        let stats = super::assembly_stats(&scaffolds[1..2], 1);
+        assert_eq!(stats.gap_len, 5);
+        assert_eq!(stats.contigs, 0);
+        assert_eq!(stats.contig_n50, 0);
+        assert_eq!(stats.contig_l50, 0);
+
+        assert_eq!(super::assembly_stats(&[], 1), AssemblyStats::default());
+    }
+}
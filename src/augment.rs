@@ -0,0 +1,268 @@
+use crate::data::{reverse_complement_in_place, Annotation, Scaffold, Symbol};
+use crate::window::Window;
+
+/// Returns the reverse complement of a scaffold together with its
+/// annotations mirrored onto it, see `Scaffold::reverse_complement`. An
+/// annotation spanning `start..end` is moved to `len - end..len - start` of
+/// the new scaffold, its strand is flipped with `Strand::opposite`. On
+/// circular scaffolds, annotations wrapping around the origin are mirrored
+/// modulo the scaffold length, so that they keep wrapping around it where
+/// needed.
+///
+/// Phases are kept as they are: the 5' end of a feature on its flipped
+/// strand is the same base as before, so the number of bases to skip to
+/// the first full codon does not change.
+///
+/// # Panics
+///
+/// Panics if an annotation is not on the scaffold or ends past its end, see
+/// `Scaffold::contains_range`.
+pub fn augment_reverse_complement(
+    scaffold: &Scaffold,
+    annotations: &[Annotation],
+) -> (Scaffold, Vec<Annotation>) {
+    let augmented = scaffold.reverse_complement();
+    let len = scaffold.len();
+    let annotations = annotations
+        .iter()
+        .map(|annotation| {
+            assert_eq!(
+                annotation.scaffold(),
+                scaffold.name(),
+                "Annotation is not on scaffold {}.",
+                scaffold.name()
+            );
+            assert!(
+                scaffold.contains_range(&(annotation.start()..annotation.end())),
+                "Annotation {}..{} ends past the end of scaffold {}.",
+                annotation.start(),
+                annotation.end(),
+                scaffold.name()
+            );
+            // The end is at most `len` past the end of circular scaffolds.
+            let start = (2 * len - annotation.end()).checked_rem(len).unwrap_or(0);
+            Annotation::new(
+                String::from(augmented.name()),
+                String::from(annotation.source()),
+                annotation.feature().clone(),
+                annotation.score(),
+                annotation.strand().opposite(),
+                annotation.phase(),
+                start,
+                start + annotation.len(),
+                String::from(annotation.attributes()),
+            )
+            .with_line(annotation.line())
+        })
+        .collect();
+    (augmented, annotations)
+}
+
+/// Reverse complements a training example in place: the sequence is
+/// reverse complemented and per-position labels are reversed. Labels are
+/// not otherwise changed, so labels computed for a single strand describe
+/// the opposite strand afterwards.
+///
+/// # Panics
+///
+/// Panics if the number of labels differs from the sequence length.
+pub fn reverse_complement_labeled(sequence: &mut [Symbol], labels: &mut [u8]) {
+    assert_eq!(
+        sequence.len(),
+        labels.len(),
+        "Number of labels differs from the sequence length."
+    );
+    reverse_complement_in_place(sequence);
+    labels.reverse();
+}
+
+/// Returns the reverse complement of a window and its labels, see
+/// `reverse_complement_labeled`. Padding moves to the window start.
+///
+/// # Panics
+///
+/// Panics if the number of labels differs from the window length.
+pub fn augment_window(window: &Window, labels: &[u8]) -> (Vec<Symbol>, Vec<u8>) {
+    let mut sequence = window.sequence().to_vec();
+    let mut labels = labels.to_vec();
+    reverse_complement_labeled(&mut sequence, &mut labels);
+    (sequence, labels)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{augment_reverse_complement, augment_window, reverse_complement_labeled};
+    use crate::data::{
+        parse_sequence, reverse_complement, sequence_to_string, AnnotationBuilder, Feature, Phase,
+        Scaffold, Strand, Symbol,
+    };
+    use crate::window::{labeled_windows, windows, Padding};
+
+    #[test]
+    fn test_augment_reverse_complement() {
+        let scaffold = Scaffold::new(
+            String::from("chr1"),
+            parse_sequence("AAATGGCCTTAGCGNAT").unwrap(),
+        );
+        let annotation = |feature, strand, start, end| {
+            AnnotationBuilder::new("chr1", feature)
+                .strand(strand)
+                .phase(Phase::One)
+                .start(start)
+                .end(end)
+                .attributes("ID=x")
+                .line(start + 1)
+                .build()
+                .unwrap()
+        };
+        let annotations = vec![
+            annotation(Feature::CDS, Strand::Positive, 2, 11),
+            annotation(Feature::Exon, Strand::Negative, 0, 4),
+            annotation(Feature::Repeat, Strand::Unstranded, 12, 17),
+            annotation(Feature::Gene, Strand::Unknown, 5, 9),
+        ];
+
+        let (augmented, mirrored) = augment_reverse_complement(&scaffold, &annotations);
+        assert_eq!(augmented.name(), "chr1_rc");
+        assert_eq!(augmented.len(), scaffold.len());
+        assert_eq!(mirrored.len(), annotations.len());
+
+        for (original, mirrored) in annotations.iter().zip(mirrored.iter()) {
+            assert_eq!(mirrored.scaffold(), "chr1_rc");
+            assert_eq!(mirrored.feature(), original.feature());
+            assert_eq!(mirrored.phase(), Some(Phase::One));
+            assert_eq!(mirrored.attributes(), "ID=x");
+            assert_eq!(mirrored.line(), original.line());
+            assert_eq!(mirrored.len(), original.len());
+            assert_eq!(mirrored.start(), scaffold.len() - original.end());
+
+            let before = scaffold
+                .region(original.start(), original.end(), original.strand())
+                .unwrap();
+            let after = augmented
+                .region(mirrored.start(), mirrored.end(), mirrored.strand())
+                .unwrap();
+            match original.strand() {
+                Strand::Positive | Strand::Negative => assert_eq!(before, after),
+                _ => assert_eq!(reverse_complement(&before), after),
+            }
+            // Regardless of strands, the positive strand sequence is
+            // reverse complemented.
+            assert_eq!(
+                reverse_complement(&scaffold.sequence()[original.start()..original.end()]),
+                &augmented.sequence()[mirrored.start()..mirrored.end()]
+            );
+        }
+        assert_eq!(
+            [
+                mirrored[0].strand(),
+                mirrored[1].strand(),
+                mirrored[2].strand(),
+                mirrored[3].strand()
+            ],
+            [
+                Strand::Negative,
+                Strand::Positive,
+                Strand::Unstranded,
+                Strand::Unknown
+            ]
+        );
+        assert_eq!((mirrored[0].start(), mirrored[0].end()), (6, 15));
+
+        let (twice, annotations_twice) = augment_reverse_complement(&augmented, &mirrored);
+        assert_eq!(twice.sequence(), scaffold.sequence());
+        for (original, twice) in annotations.iter().zip(annotations_twice.iter()) {
+            assert_eq!(twice.scaffold(), "chr1_rc_rc");
+            assert_eq!(
+                (twice.start(), twice.end(), twice.strand()),
+                (original.start(), original.end(), original.strand())
+            );
+        }
+    }
+
+    #[test]
+    fn test_augment_reverse_complement_circular() {
+        let scaffold = Scaffold::new(String::from("c"), parse_sequence("AACGTTGCA").unwrap())
+            .with_circular(true);
+        let annotation = |start, end| {
+            AnnotationBuilder::new("c", Feature::CDS)
+                .strand(Strand::Positive)
+                .start(start)
+                .end(end)
+                .build()
+                .unwrap()
+        };
+        let annotations = vec![annotation(7, 11), annotation(0, 9), annotation(2, 5)];
+
+        let (augmented, mirrored) = augment_reverse_complement(&scaffold, &annotations);
+        assert!(augmented.is_circular());
+        let coordinates: Vec<(usize, usize)> =
+            mirrored.iter().map(|a| (a.start(), a.end())).collect();
+        assert_eq!(coordinates, vec![(7, 11), (0, 9), (4, 7)]);
+
+        let circular = |scaffold: &Scaffold, start: usize, end: usize, strand| {
+            let mut sequence: Vec<Symbol> = (start..end).map(|i| scaffold.symbol(i % 9)).collect();
+            if strand == Strand::Negative {
+                sequence = reverse_complement(&sequence);
+            }
+            sequence
+        };
+        for (original, mirrored) in annotations.iter().zip(mirrored.iter()) {
+            assert_eq!(
+                circular(
+                    &scaffold,
+                    original.start(),
+                    original.end(),
+                    original.strand()
+                ),
+                circular(
+                    &augmented,
+                    mirrored.start(),
+                    mirrored.end(),
+                    mirrored.strand()
+                )
+            );
+        }
+
+        let (_, twice) = augment_reverse_complement(&augmented, &mirrored);
+        for (original, twice) in annotations.iter().zip(twice.iter()) {
+            assert_eq!(
+                (twice.start(), twice.end()),
+                (original.start(), original.end())
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_augment_reverse_complement_past_end() {
+        let scaffold = Scaffold::new(String::from("s"), parse_sequence("ACGT").unwrap());
+        let annotation = AnnotationBuilder::new("s", Feature::Exon)
+            .start(2)
+            .end(5)
+            .build()
+            .unwrap();
+        augment_reverse_complement(&scaffold, &[annotation]);
+    }
+
+    #[test]
+    fn test_augment_window() {
+        let scaffold = Scaffold::new(String::from("s"), parse_sequence("AACGTTG").unwrap());
+        let labels = [0, 0, 1, 1, 2, 2, 3];
+
+        let window = windows(&scaffold, 4, 4, Padding::Pad).nth(1).unwrap();
+        let (_, window_labels) = labeled_windows(&scaffold, &labels, 4, 4, Padding::Pad)
+            .nth(1)
+            .unwrap();
+        let (sequence, augmented_labels) = augment_window(&window, &window_labels);
+        assert_eq!(sequence_to_string(&sequence), "NCAA");
+        assert_eq!(augmented_labels, [0, 3, 2, 2]);
+
+        let mut sequence = parse_sequence("ACCGT").unwrap();
+        let mut labels = [1, 2, 3, 4, 5];
+        reverse_complement_labeled(&mut sequence, &mut labels);
+        assert_eq!(sequence_to_string(&sequence), "ACGGT");
+        assert_eq!(labels, [5, 4, 3, 2, 1]);
+    }
+}
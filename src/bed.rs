@@ -0,0 +1,172 @@
+use crate::data::{percent_encode, Annotation, Feature, Strand};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+use std::path::Path;
+
+/// Minimum number of whitespace separated columns of a BED record.
+const BED_MIN_COLUMNS: usize = 3;
+
+/// Load intervals from a BED3 to BED6 file. Further columns, e.g. of BED12,
+/// are ignored.
+///
+/// BED coordinates are already 0-based half-open. Each record is converted
+/// to a `Feature::Other("region")` annotation with source `BED`. The
+/// optional name is kept as a `Name` attribute, score and strand may be
+/// `.`. `track` and `browser` header lines, `#` comments and blank lines are
+/// skipped.
+pub fn load_bed_file(path: &Path) -> Result<Vec<Annotation>> {
+    let reader = {
+        let file =
+            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+        BufReader::new(file)
+    };
+
+    let mut annotations = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("Could not read file {}.", path.display()))?;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+
+        let annotation = parse_bed_line(&line).with_context(|| {
+            format!("Failed to parse line {} of file {}.", i + 1, path.display())
+        })?;
+        annotations.push(annotation.with_line(Some(i + 1)));
+    }
+
+    Ok(annotations)
+}
+
+fn parse_bed_line(line: &str) -> Result<Annotation> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    ensure!(
+        tokens.len() >= BED_MIN_COLUMNS,
+        "Not enough columns. Expected at least {} got {}.",
+        BED_MIN_COLUMNS,
+        tokens.len()
+    );
+
+    let start = tokens[1]
+        .parse::<usize>()
+        .with_context(|| format!("Start has to be a non-negative integer. Got: {}", tokens[1]))?;
+    let end = tokens[2]
+        .parse::<usize>()
+        .with_context(|| format!("End has to be a non-negative integer. Got: {}", tokens[2]))?;
+    ensure!(
+        start <= end,
+        "Start is greater than end. {} > {}",
+        start,
+        end
+    );
+
+    let attributes = match tokens.get(3) {
+        None | Some(&".") => String::new(),
+        Some(name) => format!("Name={}", percent_encode(name)),
+    };
+    let score = match tokens.get(4) {
+        None | Some(&".") => None,
+        Some(score) => {
+            let score = score
+                .parse::<f64>()
+                .with_context(|| format!("Score has to be a number. Got: {}", score))?;
+            ensure!(score.is_finite(), "Score has to be finite. Got: {}", score);
+            Some(score)
+        }
+    };
+    let strand = match tokens.get(5) {
+        Some(&"+") => Strand::Positive,
+        Some(&"-") => Strand::Negative,
+        None | Some(&".") => Strand::Unstranded,
+        Some(unrecognized) => bail!(
+            "Invalid strand, only +, - and . are valid. Got: {}",
+            unrecognized
+        ),
+    };
+
+    Ok(Annotation::new(
+        String::from(tokens[0]),
+        String::from("BED"),
+        Feature::Other(String::from("region")),
+        score,
+        strand,
+        None,
+        start,
+        end,
+        attributes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::data::{Feature, Strand};
+    use std::path::Path;
+
+    #[test]
+    fn test_load_bed3() {
+        let annotations = super::load_bed_file(Path::new("./tests/valid.bed3")).unwrap();
+        assert_eq!(annotations.len(), 3);
+
+        let first = &annotations[0];
+        assert_eq!(first.scaffold(), "scaffold_1");
+        assert_eq!(first.source(), "BED");
+        assert_eq!(first.feature(), &Feature::Other(String::from("region")));
+        assert_eq!((first.start(), first.end()), (0, 100));
+        assert_eq!(first.score(), None);
+        assert_eq!(first.strand(), Strand::Unstranded);
+        assert_eq!(first.attributes(), "");
+        assert_eq!(first.line(), Some(3));
+        assert_eq!(
+            (annotations[2].scaffold(), annotations[2].start()),
+            ("scaffold_2", 5)
+        );
+    }
+
+    #[test]
+    fn test_load_bed6() {
+        let annotations = super::load_bed_file(Path::new("./tests/valid.bed6")).unwrap();
+        assert_eq!(annotations.len(), 4);
+
+        let first = &annotations[0];
+        assert_eq!((first.start(), first.end()), (10, 20));
+        assert_eq!(first.score(), Some(960.));
+        assert_eq!(first.strand(), Strand::Positive);
+        assert_eq!(first.parsed_attributes().get("Name"), Some("peak_1"));
+
+        assert_eq!(annotations[1].strand(), Strand::Negative);
+        assert_eq!(annotations[2].strand(), Strand::Unstranded);
+        assert_eq!(annotations[2].score(), None);
+        assert_eq!(annotations[2].attributes(), "");
+        assert_eq!(
+            annotations[3].parsed_attributes().get("Name"),
+            Some("a;b=c")
+        );
+    }
+
+    #[test]
+    fn test_invalid_bed() {
+        let error = super::load_bed_file(Path::new("./tests/invalid.bed")).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse line 3 of file ./tests/invalid.bed."
+        );
+        assert_eq!(
+            format!("{}", error.chain().nth(1).unwrap()),
+            "End has to be a non-negative integer. Got: -5"
+        );
+
+        let error = super::parse_bed_line("scaffold_1\t0\t10\tx\t0\tC").unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Invalid strand, only +, - and . are valid. Got: C"
+        );
+        assert!(super::parse_bed_line("scaffold_1\t0").is_err());
+        assert!(super::parse_bed_line("scaffold_1\t10\t5").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+use std::ops::Range;
+use std::path::Path;
+
+/// Handling of bedGraph intervals which overlap each other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverlapPolicy {
+    Error,
+    /// Values of later lines overwrite values of earlier lines. Overlapping
+    /// lines are reported via `BedGraph::overlapping_lines`.
+    LastWins,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BedGraphOptions {
+    /// Value of positions not covered by any interval, e.g. `0.0` or
+    /// `f32::NAN`.
+    pub default: f32,
+    pub overlaps: OverlapPolicy,
+}
+
+impl Default for BedGraphOptions {
+    fn default() -> Self {
+        Self {
+            default: 0.,
+            overlaps: OverlapPolicy::Error,
+        }
+    }
+}
+
+/// Per-base values loaded from a bedGraph file.
+#[derive(Debug)]
+pub struct BedGraph {
+    values: HashMap<String, Vec<f32>>,
+    overlapping_lines: Vec<usize>,
+}
+
+impl BedGraph {
+    /// Per-base values of each scaffold.
+    pub fn values(&self) -> &HashMap<String, Vec<f32>> {
+        &self.values
+    }
+
+    pub fn into_values(self) -> HashMap<String, Vec<f32>> {
+        self.values
+    }
+
+    /// Sorted 1-based numbers of lines whose interval overlaps an interval
+    /// of an earlier line.
+    pub fn overlapping_lines(&self) -> &[usize] {
+        &self.overlapping_lines
+    }
+}
+
+/// Load a bedGraph file into per-base vectors of all scaffolds with known
+/// lengths. Uncovered positions are `0.0` and overlapping intervals are an
+/// error. See `load_bedgraph_with_options`.
+pub fn load_bedgraph(
+    path: &Path,
+    scaffold_lengths: &HashMap<String, usize>,
+) -> Result<HashMap<String, Vec<f32>>> {
+    load_bedgraph_with_options(path, scaffold_lengths, &BedGraphOptions::default())
+        .map(BedGraph::into_values)
+}
+
+/// Load a bedGraph file into per-base vectors of all scaffolds with known
+/// lengths.
+///
+/// `track` and `browser` header lines, `#` comments and blank lines are
+/// skipped. Intervals on scaffolds missing from `scaffold_lengths` or
+/// extending past the scaffold end are an error.
+pub fn load_bedgraph_with_options(
+    path: &Path,
+    scaffold_lengths: &HashMap<String, usize>,
+    options: &BedGraphOptions,
+) -> Result<BedGraph> {
+    let mut values: HashMap<String, Vec<f32>> = scaffold_lengths
+        .iter()
+        .map(|(name, &length)| (name.clone(), vec![options.default; length]))
+        .collect();
+    let mut intervals: HashMap<String, Vec<(usize, usize, usize)>> = HashMap::new();
+
+    for_each_record(path, |line_number, scaffold, start, end, value| {
+        let track = match values.get_mut(scaffold) {
+            Some(track) => track,
+            None => bail!("Unknown scaffold {}.", scaffold),
+        };
+        ensure!(
+            end <= track.len(),
+            "Interval {}..{} extends past the end of scaffold {} of length {}.",
+            start,
+            end,
+            scaffold,
+            track.len()
+        );
+
+        for v in track[start..end].iter_mut() {
+            *v = value;
+        }
+        intervals
+            .entry(String::from(scaffold))
+            .or_default()
+            .push((start, end, line_number));
+        Ok(())
+    })?;
+
+    let mut overlapping_lines = Vec::new();
+    for scaffold_intervals in intervals.values_mut() {
+        scaffold_intervals.sort_unstable();
+        let mut covered_until = 0;
+        let mut covering_line = 0;
+        for &(start, end, line_number) in scaffold_intervals.iter() {
+            if start < covered_until {
+                overlapping_lines.push(line_number.max(covering_line));
+            }
+            if end > covered_until {
+                covered_until = end;
+                covering_line = line_number;
+            }
+        }
+    }
+    overlapping_lines.sort_unstable();
+    overlapping_lines.dedup();
+
+    if options.overlaps == OverlapPolicy::Error {
+        if let Some(line_number) = overlapping_lines.first() {
+            bail!(
+                "Interval on line {} of file {} overlaps another interval.",
+                line_number,
+                path.display()
+            );
+        }
+    }
+
+    Ok(BedGraph {
+        values,
+        overlapping_lines,
+    })
+}
+
+/// Load values of a bedGraph file only for the requested regions. This
+/// avoids allocation of per-base vectors of whole scaffolds.
+///
+/// The returned vectors correspond to `regions` in order. Overlapping
+/// intervals are resolved by the later line winning.
+pub fn load_bedgraph_regions(
+    path: &Path,
+    regions: &[(String, Range<usize>)],
+    default: f32,
+) -> Result<Vec<Vec<f32>>> {
+    let mut values: Vec<Vec<f32>> = regions
+        .iter()
+        .map(|(_, range)| vec![default; range.len()])
+        .collect();
+
+    for_each_record(path, |_, scaffold, start, end, value| {
+        for ((name, range), region_values) in regions.iter().zip(values.iter_mut()) {
+            if name != scaffold {
+                continue;
+            }
+
+            let overlap_start = start.max(range.start);
+            let overlap_end = end.min(range.end);
+            if overlap_start < overlap_end {
+                for v in
+                    region_values[overlap_start - range.start..overlap_end - range.start].iter_mut()
+                {
+                    *v = value;
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(values)
+}
+
+fn for_each_record<F>(path: &Path, mut f: F) -> Result<()>
+where
+    F: FnMut(usize, &str, usize, usize, f32) -> Result<()>,
+{
+    let reader = {
+        let file =
+            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+        BufReader::new(file)
+    };
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("Could not read file {}.", path.display()))?;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+
+        parse_bedgraph_line(&line)
+            .and_then(|(scaffold, start, end, value)| f(i + 1, scaffold, start, end, value))
+            .with_context(|| {
+                format!("Failed to parse line {} of file {}.", i + 1, path.display())
+            })?;
+    }
+
+    Ok(())
+}
+
+fn parse_bedgraph_line(line: &str) -> Result<(&str, usize, usize, f32)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    ensure!(
+        tokens.len() == 4,
+        "Expected 4 whitespace separated tokens, got {}.",
+        tokens.len()
+    );
+
+    let start = tokens[1].parse::<usize>().with_context(|| {
+        format!(
+            "Interval start is not a non-negative integer: {}",
+            tokens[1]
+        )
+    })?;
+    let end = tokens[2]
+        .parse::<usize>()
+        .with_context(|| format!("Interval end is not a non-negative integer: {}", tokens[2]))?;
+    ensure!(
+        start < end,
+        "Interval start is greater or equal to end. {} >= {}",
+        start,
+        end
+    );
+    let value = tokens[3]
+        .parse::<f32>()
+        .with_context(|| format!("Value is not a number: {}", tokens[3]))?;
+
+    Ok((tokens[0], start, end, value))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{BedGraphOptions, OverlapPolicy};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn lengths() -> HashMap<String, usize> {
+        let mut lengths = HashMap::new();
+        lengths.insert(String::from("scaffold_1"), 8);
+        lengths.insert(String::from("scaffold_2"), 5);
+        lengths.insert(String::from("scaffold_3"), 2);
+        lengths
+    }
+
+    #[test]
+    fn test_load_bedgraph() {
+        let path = Path::new("./tests/valid.bedgraph");
+        let values = super::load_bedgraph(path, &lengths()).unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values["scaffold_1"],
+            vec![0.5, 0.5, 0.5, 0., 0., -1.25, -1.25, 0.]
+        );
+        assert_eq!(values["scaffold_2"], vec![0., 0., 3., 3., 0.]);
+        assert_eq!(values["scaffold_3"], vec![0., 0.]);
+
+        let options = BedGraphOptions {
+            default: f32::NAN,
+            ..BedGraphOptions::default()
+        };
+        let bedgraph = super::load_bedgraph_with_options(path, &lengths(), &options).unwrap();
+        assert!(bedgraph.values()["scaffold_2"][0].is_nan());
+        assert_eq!(bedgraph.values()["scaffold_2"][2], 3.);
+
+        let mut short = lengths();
+        short.insert(String::from("scaffold_1"), 6);
+        let error = super::load_bedgraph(path, &short).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse line 4 of file ./tests/valid.bedgraph."
+        );
+        assert_eq!(
+            format!("{}", error.root_cause()),
+            "Interval 5..7 extends past the end of scaffold scaffold_1 of length 6."
+        );
+    }
+
+    #[test]
+    fn test_overlapping_bedgraph() {
+        let path = Path::new("./tests/overlapping.bedgraph");
+        let error = super::load_bedgraph(path, &lengths()).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Interval on line 3 of file ./tests/overlapping.bedgraph overlaps another interval."
+        );
+
+        let options = BedGraphOptions {
+            overlaps: OverlapPolicy::LastWins,
+            ..BedGraphOptions::default()
+        };
+        let bedgraph = super::load_bedgraph_with_options(path, &lengths(), &options).unwrap();
+        assert_eq!(bedgraph.overlapping_lines(), &[3]);
+        assert_eq!(
+            bedgraph.values()["scaffold_1"],
+            vec![1., 1., 2., 2., 2., 2., 0., 0.]
+        );
+    }
+
+    #[test]
+    fn test_load_bedgraph_regions() {
+        let path = Path::new("./tests/valid.bedgraph");
+        let regions = vec![
+            (String::from("scaffold_1"), 2..6),
+            (String::from("scaffold_2"), 3..5),
+            (String::from("scaffold_9"), 0..1),
+        ];
+        let values = super::load_bedgraph_regions(path, &regions, -9.).unwrap();
+        assert_eq!(values[0], vec![0.5, -9., -9., -1.25]);
+        assert_eq!(values[1], vec![3., -9.]);
+        assert_eq!(values[2], vec![-9.]);
+    }
+}
@@ -0,0 +1,413 @@
+use anyhow::{Context, Result};
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Size of the fixed part of a gzip header.
+const HEADER_LEN: usize = 12;
+/// Size of the gzip trailer, i.e. CRC32 and ISIZE.
+const TRAILER_LEN: usize = 8;
+
+/// Start of a BGZF block: its offset in the compressed file and the offset
+/// of its first uncompressed byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Block {
+    compressed: u64,
+    uncompressed: u64,
+}
+
+/// Checks whether a file is BGZF compressed, i.e. a gzip file whose first
+/// member carries the `BC` extra subfield with the block size.
+pub fn is_bgzf(path: &Path) -> Result<bool> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+    let mut header = [0u8; HEADER_LEN];
+    match file.read_exact(&mut header) {
+        Ok(()) => (),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read file {}.", path.display()))
+        }
+    }
+    if header[..4] != [0x1f, 0x8b, 8, 4] {
+        return Ok(false);
+    }
+
+    let mut extra = vec![0; u16::from_le_bytes([header[10], header[11]]) as usize];
+    match file.read_exact(&mut extra) {
+        Ok(()) => Ok(block_size(&extra).is_some()),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => {
+            Err(error).with_context(|| format!("Failed to read file {}.", path.display()))
+        }
+    }
+}
+
+/// Finds the total block size in the `BC` subfield of gzip extra data.
+fn block_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if extra[..2] == *b"BC" && len == 2 {
+            return Some(u16::from_le_bytes([data[0], data[1]]) as u64 + 1);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Path of the `.gzi` block index belonging to a BGZF file.
+pub(crate) fn gzi_path(path: &Path) -> PathBuf {
+    let mut gzi = path.as_os_str().to_owned();
+    gzi.push(".gzi");
+    PathBuf::from(gzi)
+}
+
+/// Seekable reader of BGZF (blocked gzip) compressed data, as written by
+/// `bgzip`.
+///
+/// Positions are offsets in the uncompressed data. Virtual offsets as used
+/// by samtools, i.e. the compressed block offset shifted left by 16 bits
+/// combined with the offset within the uncompressed block, are supported
+/// too.
+pub struct BgzfReader<R: Read + Seek> {
+    inner: R,
+    blocks: Vec<Block>,
+    /// Index of the loaded block, `blocks.len()` before the first read.
+    current: usize,
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Creates a reader, locating all blocks by reading their headers.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut blocks = Vec::new();
+        let mut block = Block {
+            compressed: 0,
+            uncompressed: 0,
+        };
+        let end = inner
+            .seek(SeekFrom::End(0))
+            .context("Failed to read BGZF data.")?;
+
+        while block.compressed < end {
+            let size = read_block_size(&mut inner, block.compressed)?;
+            let mut isize = [0u8; 4];
+            inner
+                .seek(SeekFrom::Start(block.compressed + size - 4))
+                .and_then(|_| inner.read_exact(&mut isize))
+                .context("Truncated BGZF block.")?;
+            blocks.push(block);
+            block.compressed += size;
+            block.uncompressed += u32::from_le_bytes(isize) as u64;
+        }
+
+        Ok(Self::with_blocks(inner, blocks))
+    }
+
+    /// Creates a reader from a `.gzi` index as written by `bgzip -i`.
+    pub fn with_gzi(inner: R, gzi: &[u8]) -> Result<Self> {
+        ensure!(gzi.len() >= 8, "Truncated GZI index.");
+        let count = u64::from_le_bytes(gzi[..8].try_into().unwrap());
+        ensure!(
+            count.checked_mul(16).and_then(|len| len.checked_add(8)) == Some(gzi.len() as u64),
+            "GZI index has {} bytes but {} entries.",
+            gzi.len(),
+            count
+        );
+
+        // The first block is implicit.
+        let mut blocks = vec![Block {
+            compressed: 0,
+            uncompressed: 0,
+        }];
+        for entry in gzi[8..].chunks(16) {
+            let block = Block {
+                compressed: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                uncompressed: u64::from_le_bytes(entry[8..].try_into().unwrap()),
+            };
+            let last = blocks.last().unwrap();
+            ensure!(
+                block.compressed > last.compressed && block.uncompressed >= last.uncompressed,
+                "GZI index entries are not sorted."
+            );
+            blocks.push(block);
+        }
+
+        Ok(Self::with_blocks(inner, blocks))
+    }
+
+    fn with_blocks(inner: R, blocks: Vec<Block>) -> Self {
+        Self {
+            inner,
+            current: blocks.len(),
+            blocks,
+            data: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Writes the block index in the `.gzi` format.
+    pub fn write_gzi<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.blocks.len() as u64 - 1).to_le_bytes())?;
+        for block in self.blocks.iter().skip(1) {
+            writer.write_all(&block.compressed.to_le_bytes())?;
+            writer.write_all(&block.uncompressed.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn load_block(&mut self, index: usize) -> io::Result<()> {
+        let compressed = self.blocks[index].compressed;
+        let size = read_block_size(&mut self.inner, compressed).map_err(to_io_error)?;
+        let mut block = vec![0; size as usize];
+        self.inner.seek(SeekFrom::Start(compressed))?;
+        self.inner.read_exact(&mut block)?;
+
+        let header_len = HEADER_LEN + u16::from_le_bytes([block[10], block[11]]) as usize;
+        if block.len() < header_len + TRAILER_LEN {
+            return Err(to_io_error(anyhow!(
+                "Invalid BGZF block at {}.",
+                compressed
+            )));
+        }
+        let trailer = &block[block.len() - TRAILER_LEN..];
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let isize = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+
+        self.data.clear();
+        self.data.reserve(isize as usize);
+        DeflateDecoder::new(&block[header_len..block.len() - TRAILER_LEN])
+            .read_to_end(&mut self.data)?;
+        let mut check = Crc::new();
+        check.update(&self.data);
+        if self.data.len() != isize as usize || check.sum() != crc {
+            return Err(to_io_error(anyhow!(
+                "Corrupt BGZF block at {}.",
+                compressed
+            )));
+        }
+
+        self.current = index;
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Current position as a virtual offset.
+    pub fn virtual_offset(&self) -> u64 {
+        match self.blocks.get(self.current) {
+            Some(block) => (block.compressed << 16) | self.position as u64,
+            None => 0,
+        }
+    }
+
+    /// Seeks to a virtual offset.
+    pub fn seek_virtual(&mut self, offset: u64) -> Result<()> {
+        let compressed = offset >> 16;
+        let within = (offset & 0xffff) as usize;
+        let index = match self
+            .blocks
+            .binary_search_by_key(&compressed, |b| b.compressed)
+        {
+            Ok(index) => index,
+            Err(_) => bail!("Virtual offset {} does not point to a block.", offset),
+        };
+        self.load_block(index)?;
+        ensure!(
+            within <= self.data.len(),
+            "Virtual offset {} is past the end of its block.",
+            offset
+        );
+        self.position = within;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.data.len() {
+            let next = if self.current == self.blocks.len() {
+                0
+            } else {
+                self.current + 1
+            };
+            if next >= self.blocks.len() {
+                return Ok(0);
+            }
+            self.load_block(next)?;
+        }
+
+        let len = buf.len().min(self.data.len() - self.position);
+        buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for BgzfReader<R> {
+    /// Seeks in the uncompressed data. Seeking relative to the end is not
+    /// supported.
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let current = match self.blocks.get(self.current) {
+            Some(block) => block.uncompressed + self.position as u64,
+            None => 0,
+        };
+        let target = match position {
+            SeekFrom::Start(target) => target,
+            SeekFrom::Current(delta) => current.checked_add_signed(delta).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start.")
+            })?,
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Seeking relative to the end of BGZF data is not supported.",
+                ))
+            }
+        };
+
+        if self.blocks.is_empty() {
+            return Ok(target);
+        }
+        let index = self
+            .blocks
+            .partition_point(|b| b.uncompressed <= target)
+            .saturating_sub(1);
+        if index != self.current {
+            self.load_block(index)?;
+        }
+        self.position = (target - self.blocks[index].uncompressed) as usize;
+        Ok(target)
+    }
+}
+
+/// Reads the total size of the block starting at `offset`.
+fn read_block_size<R: Read + Seek>(inner: &mut R, offset: u64) -> Result<u64> {
+    let mut header = [0u8; HEADER_LEN];
+    inner
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| inner.read_exact(&mut header))
+        .with_context(|| format!("Truncated BGZF block header at {}.", offset))?;
+    ensure!(
+        header[..4] == [0x1f, 0x8b, 8, 4],
+        "Invalid BGZF block header at {}.",
+        offset
+    );
+    let mut extra = vec![0; u16::from_le_bytes([header[10], header[11]]) as usize];
+    inner
+        .read_exact(&mut extra)
+        .with_context(|| format!("Truncated BGZF block header at {}.", offset))?;
+    let size = block_size(&extra)
+        .with_context(|| format!("BGZF block at {} lacks the block size.", offset))?;
+    ensure!(
+        size as usize >= HEADER_LEN + extra.len() + TRAILER_LEN,
+        "Invalid BGZF block size at {}.",
+        offset
+    );
+    Ok(size)
+}
+
+fn to_io_error(error: anyhow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:#}", error))
+}
+
+/// Opens a BGZF file, using the `.gzi` index next to it if there is one.
+pub fn open_bgzf(path: &Path) -> Result<BgzfReader<File>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+    let gzi = gzi_path(path);
+    let reader = if gzi.exists() {
+        let bytes = std::fs::read(&gzi)
+            .with_context(|| format!("Failed to read file {}.", gzi.display()))?;
+        BgzfReader::with_gzi(file, &bytes)
+            .with_context(|| format!("Failed to load index {}.", gzi.display()))?
+    } else {
+        BgzfReader::new(file).with_context(|| format!("Failed to read file {}.", path.display()))?
+    };
+    Ok(reader)
+}
+
+/// Writes the block index of a BGZF file to the `.gzi` file next to it.
+pub fn save_gzi(path: &Path, reader: &BgzfReader<File>) -> Result<()> {
+    let gzi = gzi_path(path);
+    let file =
+        File::create(&gzi).with_context(|| format!("Failed to create file {}.", gzi.display()))?;
+    let mut writer = BufWriter::new(file);
+    reader
+        .write_gzi(&mut writer)
+        .and_then(|_| writer.flush())
+        .with_context(|| format!("Failed to write file {}.", gzi.display()))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+
+    use super::BgzfReader;
+    use flate2::write::DeflateEncoder;
+    use flate2::{Compression, Crc};
+    use std::io::{prelude::*, Cursor, SeekFrom};
+
+    /// Compresses data into BGZF blocks of at most `block_len` uncompressed
+    /// bytes, followed by the empty EOF block.
+    pub(crate) fn bgzip(data: &[u8], block_len: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        let chunks = data.chunks(block_len).chain(std::iter::once(&[][..]));
+        for chunk in chunks {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(chunk).unwrap();
+            let cdata = encoder.finish().unwrap();
+            let mut crc = Crc::new();
+            crc.update(chunk);
+
+            let size = (12 + 6 + cdata.len() + 8) as u16;
+            output.extend_from_slice(&[0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0]);
+            output.extend_from_slice(&[b'B', b'C', 2, 0]);
+            output.extend_from_slice(&(size - 1).to_le_bytes());
+            output.extend_from_slice(&cdata);
+            output.extend_from_slice(&crc.sum().to_le_bytes());
+            output.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        }
+        output
+    }
+
+    #[test]
+    fn test_bgzf_reader() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let compressed = bgzip(&data, 100);
+
+        let mut reader = BgzfReader::new(Cursor::new(compressed.clone())).unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+
+        let mut bytes = [0u8; 150];
+        for &start in &[0, 99, 100, 420, 850] {
+            reader.seek(SeekFrom::Start(start)).unwrap();
+            reader.read_exact(&mut bytes).unwrap();
+            assert_eq!(&bytes[..], &data[start as usize..start as usize + 150]);
+        }
+
+        reader.seek(SeekFrom::Start(420)).unwrap();
+        let offset = reader.virtual_offset();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.seek_virtual(offset).unwrap();
+        reader.read_exact(&mut bytes[..10]).unwrap();
+        assert_eq!(&bytes[..10], &data[420..430]);
+        assert!(reader.seek_virtual(offset + (1 << 16)).is_err());
+
+        let mut gzi = Vec::new();
+        reader.write_gzi(&mut gzi).unwrap();
+        let mut indexed = BgzfReader::with_gzi(Cursor::new(compressed.clone()), &gzi).unwrap();
+        indexed.seek(SeekFrom::Start(555)).unwrap();
+        indexed.read_exact(&mut bytes[..10]).unwrap();
+        assert_eq!(&bytes[..10], &data[555..565]);
+
+        let mut corrupt = compressed;
+        corrupt[30] ^= 0xff;
+        let mut reader = BgzfReader::new(Cursor::new(corrupt)).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+}
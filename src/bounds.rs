@@ -0,0 +1,184 @@
+use crate::data::{Annotation, Scaffold};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Policy for annotations extending past the end of their scaffold. All
+/// consumers checking annotation bounds should go through
+/// `BoundsPolicy::apply` so that the policy is handled consistently, see
+/// `GenomeOptions`, `ExtractOptions` and `LabelOptions`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BoundsPolicy {
+    /// Fail on the first out of bounds annotation.
+    #[default]
+    Error,
+    /// Truncate the annotation to the scaffold end, or to the length of the
+    /// scaffold on circular scaffolds. Annotations starting at or after the
+    /// scaffold end are dropped.
+    Clamp,
+    /// Drop out of bounds annotations.
+    Drop,
+}
+
+/// Outcome of applying `BoundsPolicy` to an annotation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bounded {
+    /// The annotation fits on the scaffold.
+    Within,
+    /// The annotation was truncated to `[start, end)`.
+    Clamped(usize, usize),
+    /// The annotation should be discarded.
+    Dropped,
+}
+
+impl BoundsPolicy {
+    /// Checks that `annotation` fits on `scaffold` and resolves it per the
+    /// policy otherwise. Annotations of circular scaffolds may wrap around
+    /// the origin, see `Scaffold::contains_range`.
+    pub fn apply(self, annotation: &Annotation, scaffold: &Scaffold) -> Result<Bounded> {
+        if scaffold.contains_range(&(annotation.start()..annotation.end())) {
+            return Ok(Bounded::Within);
+        }
+
+        let scaffold_len = scaffold.len();
+        let limit = if scaffold.is_circular() {
+            annotation.start() + scaffold_len
+        } else {
+            scaffold_len
+        };
+
+        match self {
+            Self::Error => bail!(
+                "Annotation {}..{}{} extends past the end of scaffold {} of length {}.",
+                annotation.start(),
+                annotation.end(),
+                annotation.line_note(),
+                annotation.scaffold(),
+                scaffold_len
+            ),
+            Self::Clamp if annotation.start() < scaffold_len => {
+                Ok(Bounded::Clamped(annotation.start(), limit))
+            }
+            Self::Clamp | Self::Drop => Ok(Bounded::Dropped),
+        }
+    }
+}
+
+/// Out of bounds annotations resolved by `apply_bounds`.
+#[derive(Debug, Default)]
+pub struct BoundsReport {
+    /// Original versions of clamped annotations.
+    pub clamped: Vec<Annotation>,
+    /// Dropped annotations.
+    pub dropped: Vec<Annotation>,
+}
+
+/// Applies `policy` to all annotations. Annotations referencing scaffolds
+/// which are not present are kept unchanged.
+pub fn apply_bounds(
+    scaffolds: &[Scaffold],
+    annotations: Vec<Annotation>,
+    policy: BoundsPolicy,
+) -> Result<(Vec<Annotation>, BoundsReport)> {
+    let by_name: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+
+    let mut report = BoundsReport::default();
+    let mut kept = Vec::with_capacity(annotations.len());
+
+    for annotation in annotations {
+        let scaffold = match by_name.get(annotation.scaffold()) {
+            Some(scaffold) => scaffold,
+            None => {
+                kept.push(annotation);
+                continue;
+            }
+        };
+
+        match policy.apply(&annotation, scaffold)? {
+            Bounded::Within => kept.push(annotation),
+            Bounded::Clamped(start, end) => {
+                kept.push(annotation.with_range(start, end));
+                report.clamped.push(annotation);
+            }
+            Bounded::Dropped => report.dropped.push(annotation),
+        }
+    }
+
+    Ok((kept, report))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Bounded, BoundsPolicy};
+    use crate::data::{Feature, Scaffold, Strand, Symbol};
+    use crate::testing;
+
+    #[test]
+    fn test_policy() {
+        let within = testing::annotation("s", Feature::Exon, Strand::Positive, 2, 10);
+        let over = testing::annotation("s", Feature::Exon, Strand::Positive, 8, 12);
+        let past = testing::annotation("s", Feature::Exon, Strand::Positive, 10, 12);
+        let scaffold = Scaffold::new(String::from("s"), vec![Symbol::Other; 10]);
+
+        for &policy in &[BoundsPolicy::Error, BoundsPolicy::Clamp, BoundsPolicy::Drop] {
+            assert_eq!(policy.apply(&within, &scaffold).unwrap(), Bounded::Within);
+        }
+
+        let error = BoundsPolicy::Error.apply(&over, &scaffold).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Annotation 8..12 extends past the end of scaffold s of length 10."
+        );
+
+        assert_eq!(
+            BoundsPolicy::Clamp.apply(&over, &scaffold).unwrap(),
+            Bounded::Clamped(8, 10)
+        );
+        assert_eq!(
+            BoundsPolicy::Clamp.apply(&past, &scaffold).unwrap(),
+            Bounded::Dropped
+        );
+        assert_eq!(
+            BoundsPolicy::Drop.apply(&over, &scaffold).unwrap(),
+            Bounded::Dropped
+        );
+
+        // Annotations of circular scaffolds may wrap around the origin once.
+        let circular = scaffold.with_circular(true);
+        assert_eq!(
+            BoundsPolicy::Error.apply(&over, &circular).unwrap(),
+            Bounded::Within
+        );
+        let around = testing::annotation("s", Feature::Exon, Strand::Positive, 8, 20);
+        assert!(BoundsPolicy::Error.apply(&around, &circular).is_err());
+        assert_eq!(
+            BoundsPolicy::Clamp.apply(&around, &circular).unwrap(),
+            Bounded::Clamped(8, 18)
+        );
+        assert_eq!(
+            BoundsPolicy::Clamp.apply(&past, &circular).unwrap(),
+            Bounded::Dropped
+        );
+    }
+
+    #[test]
+    fn test_apply_bounds() {
+        let scaffolds = vec![Scaffold::new(String::from("s"), vec![Symbol::Other; 10])];
+        let annotations = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 0, 5),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 8, 12),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 10, 11),
+        ];
+
+        assert!(super::apply_bounds(&scaffolds, annotations.clone(), BoundsPolicy::Error).is_err());
+
+        let (kept, report) =
+            super::apply_bounds(&scaffolds, annotations, BoundsPolicy::Clamp).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!((kept[1].start(), kept[1].end()), (8, 10));
+        assert_eq!(report.clamped.len(), 1);
+        assert_eq!(report.clamped[0].end(), 12);
+        assert_eq!(report.dropped.len(), 1);
+        assert_eq!(report.dropped[0].start(), 10);
+    }
+}
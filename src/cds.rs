@@ -0,0 +1,1047 @@
+use crate::data::{Annotation, Feature, Phase, Scaffold, Strand, Symbol};
+use crate::ids::{with_id, IdGenerator};
+use crate::model::{Gene, Transcript};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Problem found in a chain of CDS segments of a single transcript. Segments
+/// are identified by their 0-based half-open coordinates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CdsIssue {
+    /// Segments are not all on the same scaffold and strand.
+    MixedStrands,
+    /// A segment has no phase.
+    MissingPhase { start: usize, end: usize },
+    /// A segment's phase does not follow from the preceding segments.
+    PhaseMismatch {
+        start: usize,
+        end: usize,
+        expected: Phase,
+        found: Phase,
+    },
+    /// Total coding length after removal of the first phase is not
+    /// divisible by three.
+    Length { coding_length: usize },
+    /// A segment is not fully contained within any exon.
+    OutsideExon { start: usize, end: usize },
+}
+
+pub(crate) fn phase_to_usize(phase: Phase) -> usize {
+    match phase {
+        Phase::Zero => 0,
+        Phase::One => 1,
+        Phase::Two => 2,
+    }
+}
+
+pub(crate) fn usize_to_phase(value: usize) -> Phase {
+    match value % 3 {
+        0 => Phase::Zero,
+        1 => Phase::One,
+        _ => Phase::Two,
+    }
+}
+
+/// Returns CDS segments sorted in transcription order, i.e. by ascending
+/// coordinates on the positive strand and by descending coordinates on the
+/// negative strand.
+pub(crate) fn transcription_order(cds: &[Annotation]) -> Vec<&Annotation> {
+    transcription_indices(cds)
+        .into_iter()
+        .map(|i| &cds[i])
+        .collect()
+}
+
+/// Indices of CDS segments in transcription order, see
+/// `transcription_order`.
+fn transcription_indices(cds: &[Annotation]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..cds.len()).collect();
+    order.sort_by_key(|&i| (cds[i].start(), cds[i].end()));
+    if cds.first().map(|a| a.strand()) == Some(Strand::Negative) {
+        order.reverse();
+    }
+    order
+}
+
+/// Concatenates CDS segments of a single transcript in transcription order,
+/// reverse complements them on the negative strand and removes leading
+/// bases per the phase of the first segment.
+///
+/// Segments have to be on the same strand and within `scaffold`. Only the
+/// segments are unpacked from packed scaffolds.
+pub(crate) fn assemble_cds(scaffold: &Scaffold, cds: &[Annotation]) -> Vec<Symbol> {
+    let ordered = transcription_order(cds);
+    let mut assembled = Vec::with_capacity(cds.iter().map(Annotation::len).sum());
+
+    for segment in ordered.iter() {
+        let region = scaffold
+            .subsequence(segment.start()..segment.end())
+            .expect("CDS segments were validated against the scaffold.");
+        match segment.strand() {
+            Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+                assembled.extend_from_slice(&region)
+            }
+            Strand::Negative => assembled.extend(region.iter().rev().map(|s| s.complement())),
+        }
+    }
+
+    let skip = ordered
+        .first()
+        .and_then(|a| a.phase())
+        .map_or(0, phase_to_usize)
+        .min(assembled.len());
+    assembled.drain(..skip);
+    assembled
+}
+
+/// Phase of the segment following a segment of length `length` and phase
+/// `phase` in transcription order. Bases of the last incomplete codon of a
+/// segment are completed by the next segment.
+fn next_phase(length: usize, phase: usize) -> usize {
+    (3 - (length + 3 - phase) % 3) % 3
+}
+
+fn ensure_single_strand(cds: &[Annotation]) -> Result<()> {
+    if let Some(first) = cds.first() {
+        ensure!(
+            cds.iter()
+                .all(|a| a.scaffold() == first.scaffold() && a.strand() == first.strand()),
+            "CDS segments are not all on the same scaffold and strand."
+        );
+    }
+    Ok(())
+}
+
+/// Checks that declared phases of CDS segments of a single transcript are
+/// consistent with the cumulative length of the preceding segments in
+/// transcription order (strand-aware). The first segment may start mid-codon
+/// with any phase. Segments without a phase are not checked, the phase of
+/// the following segment is then computed from the expected one.
+///
+/// Fails on the first segment, in transcription order, whose phase
+/// disagrees.
+pub fn check_phases(transcript_cds: &[Annotation]) -> Result<()> {
+    walk_phases(transcript_cds, |i, expected| {
+        let segment = &transcript_cds[i];
+        if let (Some(expected), Some(declared)) = (expected, segment.phase()) {
+            let declared = phase_to_usize(declared);
+            ensure!(
+                expected == declared,
+                "CDS segment {}:{}-{}{} has phase {} but phase {} was expected.",
+                segment.scaffold(),
+                segment.start() + 1,
+                segment.end(),
+                segment.line_note(),
+                declared,
+                expected
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Returns copies of CDS segments of a single transcript, in the original
+/// order, with missing phases computed from the preceding segments in
+/// transcription order. A first segment without a phase gets phase 0.
+/// Declared phases are kept, see `check_phases`.
+pub fn compute_phases(transcript_cds: &[Annotation]) -> Result<Vec<Annotation>> {
+    let mut computed = transcript_cds.to_vec();
+    walk_phases(transcript_cds, |i, expected| {
+        let segment = &transcript_cds[i];
+        if segment.phase().is_none() {
+            computed[i] = segment.with_phase(Some(usize_to_phase(expected.unwrap_or(0))));
+        }
+        Ok(())
+    })?;
+    Ok(computed)
+}
+
+/// Walks CDS segments of a single transcript in transcription order and
+/// calls `f` with the index of each segment and its phase expected from the
+/// preceding segments, `None` for the first segment. The phase of the next
+/// segment is computed from the declared phase, or the expected one if
+/// missing, with 0 for a first segment without a phase.
+fn walk_phases<F>(transcript_cds: &[Annotation], mut f: F) -> Result<()>
+where
+    F: FnMut(usize, Option<usize>) -> Result<()>,
+{
+    ensure_single_strand(transcript_cds)?;
+
+    let mut expected: Option<usize> = None;
+    for i in transcription_indices(transcript_cds) {
+        f(i, expected)?;
+        let segment = &transcript_cds[i];
+        let phase = segment
+            .phase()
+            .map(phase_to_usize)
+            .or(expected)
+            .unwrap_or(0);
+        expected = Some(next_phase(segment.len(), phase));
+    }
+    Ok(())
+}
+
+/// Assembles the spliced coding sequence of a single transcript: CDS
+/// segments are concatenated in transcription order, reverse complemented on
+/// the negative strand and leading bases are trimmed per the phase of the
+/// first segment.
+///
+/// Segments have to lie on `scaffold` and the same strand, must not overlap
+/// each other and must not extend past the scaffold end.
+pub fn spliced_cds(scaffold: &Scaffold, cds_segments: &[Annotation]) -> Result<Vec<Symbol>> {
+    for segment in cds_segments {
+        ensure!(
+            segment.scaffold() == scaffold.name(),
+            "CDS segment {}:{}-{}{} is not on scaffold {}.",
+            segment.scaffold(),
+            segment.start() + 1,
+            segment.end(),
+            segment.line_note(),
+            scaffold.name()
+        );
+        ensure!(
+            segment.end() <= scaffold.len(),
+            "CDS segment {}:{}-{}{} extends past the scaffold end {}.",
+            segment.scaffold(),
+            segment.start() + 1,
+            segment.end(),
+            segment.line_note(),
+            scaffold.len()
+        );
+    }
+    ensure_single_strand(cds_segments)?;
+
+    let mut sorted: Vec<&Annotation> = cds_segments.iter().collect();
+    sorted.sort_by_key(|a| (a.start(), a.end()));
+    for pair in sorted.windows(2) {
+        ensure!(
+            pair[0].end() <= pair[1].start(),
+            "CDS segments {}:{}-{}{} and {}-{}{} overlap.",
+            pair[0].scaffold(),
+            pair[0].start() + 1,
+            pair[0].end(),
+            pair[0].line_note(),
+            pair[1].start() + 1,
+            pair[1].end(),
+            pair[1].line_note()
+        );
+    }
+
+    Ok(assemble_cds(scaffold, cds_segments))
+}
+
+/// Validates the chain of CDS segments of a transcript against its exons,
+/// see `validate_cds_segments`. Transcripts without CDS have no issues.
+pub fn validate_cds_chain(transcript: &Transcript) -> Vec<CdsIssue> {
+    let cds: Vec<Annotation> = transcript.features_of(&Feature::CDS).cloned().collect();
+    let exons: Vec<Annotation> = transcript.features_of(&Feature::Exon).cloned().collect();
+    validate_cds_segments(&cds, &exons)
+}
+
+/// CDS issues of coding transcripts, see `validate_gene_models`.
+#[derive(Clone, Debug, Default)]
+pub struct CdsReport {
+    transcripts: Vec<(String, Vec<CdsIssue>)>,
+}
+
+impl CdsReport {
+    /// IDs of all coding transcripts together with their issues, in the
+    /// order of the gene models. Valid transcripts have no issues.
+    pub fn transcripts(&self) -> &[(String, Vec<CdsIssue>)] {
+        &self.transcripts
+    }
+
+    /// Coding transcripts with at least one issue.
+    pub fn invalid(&self) -> impl Iterator<Item = &(String, Vec<CdsIssue>)> + '_ {
+        self.transcripts
+            .iter()
+            .filter(|(_, issues)| !issues.is_empty())
+    }
+}
+
+/// Validates CDS chains of all coding transcripts, i.e. transcripts with at
+/// least one CDS segment, of gene models built by `build_gene_models`.
+pub fn validate_gene_models(genes: &[Gene]) -> CdsReport {
+    let transcripts = genes
+        .iter()
+        .flat_map(|gene| gene.transcripts())
+        .filter(|transcript| transcript.features_of(&Feature::CDS).next().is_some())
+        .map(|transcript| {
+            (
+                String::from(transcript.id()),
+                validate_cds_chain(transcript),
+            )
+        })
+        .collect();
+    CdsReport { transcripts }
+}
+
+/// Validates a chain of CDS segments of a single transcript.
+///
+/// Segment phases are checked against the GFF recurrence in transcription
+/// order (strand-aware), the total coding length adjusted by the first phase
+/// has to be divisible by three and every segment has to lie within one of
+/// `exons`. The exon containment check is skipped when `exons` is empty.
+pub fn validate_cds_segments(cds: &[Annotation], exons: &[Annotation]) -> Vec<CdsIssue> {
+    let mut issues = Vec::new();
+
+    let first = match cds.first() {
+        Some(first) => first,
+        None => return issues,
+    };
+    if cds
+        .iter()
+        .any(|a| a.scaffold() != first.scaffold() || a.strand() != first.strand())
+    {
+        issues.push(CdsIssue::MixedStrands);
+        return issues;
+    }
+
+    let ordered = transcription_order(cds);
+
+    let mut expected: Option<usize> = None;
+    let mut total = 0;
+    for segment in ordered.iter() {
+        let (start, end) = (segment.start(), segment.end());
+        let length = end - start;
+        total += length;
+
+        let phase = match segment.phase() {
+            Some(phase) => phase_to_usize(phase),
+            None => {
+                issues.push(CdsIssue::MissingPhase { start, end });
+                expected = None;
+                continue;
+            }
+        };
+
+        if let Some(expected) = expected {
+            if expected != phase {
+                issues.push(CdsIssue::PhaseMismatch {
+                    start,
+                    end,
+                    expected: usize_to_phase(expected),
+                    found: usize_to_phase(phase),
+                });
+            }
+        }
+
+        expected = Some(next_phase(length, phase));
+    }
+
+    let first_phase = ordered[0].phase().map(phase_to_usize).unwrap_or(0);
+    let coding_length = total.saturating_sub(first_phase);
+    if coding_length % 3 != 0 {
+        issues.push(CdsIssue::Length { coding_length });
+    }
+
+    if !exons.is_empty() {
+        for segment in ordered {
+            let contained = exons.iter().any(|exon| {
+                exon.scaffold() == segment.scaffold()
+                    && exon.start() <= segment.start()
+                    && segment.end() <= exon.end()
+            });
+            if !contained {
+                issues.push(CdsIssue::OutsideExon {
+                    start: segment.start(),
+                    end: segment.end(),
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+/// Problem preventing synthesis of a start or a stop codon annotation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CodonIssue {
+    /// The CDS chain fails `validate_cds_segments`, is shorter than a codon,
+    /// references a missing scaffold or extends past the scaffold end.
+    InvalidChain,
+    /// The first coding triplet is not `ATG`.
+    NotStart([Symbol; 3]),
+    /// The last coding triplet is not `TAA`, `TAG` or `TGA`.
+    NotStop([Symbol; 3]),
+}
+
+/// Result of `synthesize_codon_annotations`.
+#[derive(Debug, Default)]
+pub struct CodonSynthesis {
+    /// Newly synthesized codon annotations.
+    pub annotations: Vec<Annotation>,
+    /// Problems together with indices of affected transcripts.
+    pub issues: Vec<(usize, CodonIssue)>,
+}
+
+/// Derives start and stop codon annotations of coding transcripts, each given
+/// as the list of its CDS segments.
+///
+/// The start codon is formed by the first three and the stop codon by the
+/// last three coding bases (i.e. the CDS is expected to include the stop
+/// codon as in GFF3). A codon split by an intron is emitted as two records,
+/// the second one with a phase. Codons are verified against the sequence and
+/// emitted only if they are a start or a stop codon respectively, otherwise
+/// the problem is reported. Records identical in scaffold, feature, strand
+/// and coordinates to an annotation in `existing` are not emitted.
+///
+/// Codon records copy attributes of the CDS segment they lie in. If `ids` is
+/// given, the `ID` of the segment is replaced by a generated one, shared by
+/// both records of a split codon.
+pub fn synthesize_codon_annotations(
+    scaffolds: &[Scaffold],
+    transcripts: &[Vec<Annotation>],
+    existing: &[Annotation],
+    mut ids: Option<&mut IdGenerator>,
+) -> CodonSynthesis {
+    let scaffolds: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+    let mut synthesis = CodonSynthesis::default();
+
+    for (i, cds) in transcripts.iter().enumerate() {
+        let scaffold = cds.first().and_then(|a| scaffolds.get(a.scaffold()));
+        let scaffold = match scaffold {
+            Some(scaffold)
+                if validate_cds_segments(cds, &[]).is_empty()
+                    && cds.iter().all(|a| a.end() <= scaffold.len()) =>
+            {
+                scaffold
+            }
+            _ => {
+                synthesis.issues.push((i, CodonIssue::InvalidChain));
+                continue;
+            }
+        };
+
+        let ordered = transcription_order(cds);
+        let strand = ordered[0].strand();
+        let skip = ordered[0].phase().map_or(0, phase_to_usize);
+        let positions: Vec<usize> = ordered
+            .iter()
+            .flat_map(|segment| -> Box<dyn Iterator<Item = usize>> {
+                match strand {
+                    Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+                        Box::new(segment.start()..segment.end())
+                    }
+                    Strand::Negative => Box::new((segment.start()..segment.end()).rev()),
+                }
+            })
+            .skip(skip)
+            .collect();
+        if positions.len() < 3 {
+            synthesis.issues.push((i, CodonIssue::InvalidChain));
+            continue;
+        }
+
+        let triplet = |codon: &[usize]| -> [Symbol; 3] {
+            let mut symbols = [Symbol::Other; 3];
+            for (symbol, &position) in symbols.iter_mut().zip(codon.iter()) {
+                *symbol = match strand {
+                    Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+                        scaffold.symbol(position)
+                    }
+                    Strand::Negative => scaffold.symbol(position).complement(),
+                };
+            }
+            symbols
+        };
+
+        let start = &positions[..3];
+        let stop = &positions[positions.len() - 3..];
+
+        let start_triplet = triplet(start);
+        if start_triplet == [Symbol::Adenine, Symbol::Thymine, Symbol::Guanine] {
+            emit_codon(
+                &mut synthesis.annotations,
+                &ordered,
+                start,
+                Feature::StartCodon,
+                existing,
+                ids.as_deref_mut(),
+            );
+        } else {
+            synthesis
+                .issues
+                .push((i, CodonIssue::NotStart(start_triplet)));
+        }
+
+        let stop_triplet = triplet(stop);
+        let is_stop = matches!(
+            stop_triplet,
+            [Symbol::Thymine, Symbol::Adenine, Symbol::Adenine]
+                | [Symbol::Thymine, Symbol::Adenine, Symbol::Guanine]
+                | [Symbol::Thymine, Symbol::Guanine, Symbol::Adenine]
+        );
+        if is_stop {
+            emit_codon(
+                &mut synthesis.annotations,
+                &ordered,
+                stop,
+                Feature::StopCodon,
+                existing,
+                ids.as_deref_mut(),
+            );
+        } else {
+            synthesis
+                .issues
+                .push((i, CodonIssue::NotStop(stop_triplet)));
+        }
+    }
+
+    synthesis
+}
+
+/// Emits one record per contiguous part of a codon given by its positions in
+/// transcription order.
+fn emit_codon(
+    annotations: &mut Vec<Annotation>,
+    segments: &[&Annotation],
+    codon: &[usize],
+    feature: Feature,
+    existing: &[Annotation],
+    ids: Option<&mut IdGenerator>,
+) {
+    let mut parts = Vec::new();
+    let mut emitted = 0;
+    let mut part_start = 0;
+    for i in 1..=codon.len() {
+        if i < codon.len() && codon[i].abs_diff(codon[i - 1]) == 1 {
+            continue;
+        }
+
+        let part = &codon[part_start..i];
+        let start = *part.iter().min().unwrap();
+        let end = *part.iter().max().unwrap() + 1;
+        let segment = segments
+            .iter()
+            .find(|s| s.start() <= start && end <= s.end())
+            .unwrap();
+
+        let duplicate = existing.iter().any(|a| {
+            a.scaffold() == segment.scaffold()
+                && *a.feature() == feature
+                && a.strand() == segment.strand()
+                && a.start() == start
+                && a.end() == end
+        });
+        if !duplicate {
+            let attributes = match ids {
+                Some(_) => {
+                    let mut attributes = segment.parsed_attributes();
+                    attributes.remove("ID");
+                    if attributes.is_empty() {
+                        String::new()
+                    } else {
+                        attributes.to_string()
+                    }
+                }
+                None => String::from(segment.attributes()),
+            };
+            parts.push(Annotation::new(
+                String::from(segment.scaffold()),
+                String::from(segment.source()),
+                feature.clone(),
+                None,
+                segment.strand(),
+                Some(usize_to_phase((3 - emitted) % 3)),
+                start,
+                end,
+                attributes,
+            ));
+        }
+
+        emitted += part.len();
+        part_start = i;
+    }
+
+    match (ids, parts.first()) {
+        (Some(ids), Some(first)) => {
+            let id = ids.generate(first);
+            annotations.extend(parts.into_iter().map(|part| with_id(part, &id)));
+        }
+        _ => annotations.extend(parts),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{CdsIssue, CodonIssue};
+    use crate::data::{parse_sequence, Annotation, Feature, Phase, Scaffold, Strand, Symbol};
+    use crate::ids::{IdGenerator, IdStrategy};
+    use crate::model::build_gene_models;
+    use crate::testing;
+
+    fn annotation(
+        feature: Feature,
+        strand: Strand,
+        phase: Option<Phase>,
+        start: usize,
+        end: usize,
+    ) -> Annotation {
+        let builder = testing::builder("s", feature, start, end).strand(strand);
+        match phase {
+            Some(phase) => builder.phase(phase),
+            None => builder,
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn exons(strand: Strand) -> Vec<Annotation> {
+        vec![
+            annotation(Feature::Exon, strand, None, 0, 12),
+            annotation(Feature::Exon, strand, None, 18, 30),
+            annotation(Feature::Exon, strand, None, 38, 50),
+        ]
+    }
+
+    #[test]
+    fn test_valid_positive_chain() {
+        let strand = Strand::Positive;
+        let cds = vec![
+            annotation(Feature::CDS, strand, Some(Phase::Two), 20, 28),
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 2, 12),
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 40, 46),
+        ];
+        assert_eq!(super::validate_cds_segments(&cds, &exons(strand)), vec![]);
+    }
+
+    #[test]
+    fn test_valid_negative_chain() {
+        let strand = Strand::Negative;
+        let cds = vec![
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 4, 10),
+            annotation(Feature::CDS, strand, Some(Phase::Two), 20, 28),
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 40, 50),
+        ];
+        assert_eq!(super::validate_cds_segments(&cds, &exons(strand)), vec![]);
+    }
+
+    #[test]
+    fn test_broken_chain() {
+        let strand = Strand::Positive;
+        let cds = vec![
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 2, 12),
+            annotation(Feature::CDS, strand, Some(Phase::Zero), 20, 32),
+            annotation(Feature::CDS, strand, None, 40, 46),
+        ];
+        assert_eq!(
+            super::validate_cds_segments(&cds, &exons(strand)),
+            vec![
+                CdsIssue::PhaseMismatch {
+                    start: 20,
+                    end: 32,
+                    expected: Phase::Two,
+                    found: Phase::Zero,
+                },
+                CdsIssue::MissingPhase { start: 40, end: 46 },
+                CdsIssue::Length { coding_length: 28 },
+                CdsIssue::OutsideExon { start: 20, end: 32 },
+            ]
+        );
+
+        let mixed = vec![
+            annotation(Feature::CDS, Strand::Positive, Some(Phase::Zero), 0, 3),
+            annotation(Feature::CDS, Strand::Negative, Some(Phase::Zero), 6, 9),
+        ];
+        assert_eq!(
+            super::validate_cds_segments(&mixed, &[]),
+            vec![CdsIssue::MixedStrands]
+        );
+    }
+
+    #[test]
+    fn test_validate_gene_models() {
+        let linked = |feature, strand, phase, start, end, attributes: &str| {
+            annotation(feature, strand, phase, start, end).with_attributes(String::from(attributes))
+        };
+        let mut annotations = vec![
+            linked(Feature::Gene, Strand::Positive, None, 0, 50, "ID=g1"),
+            linked(
+                Feature::MRNA,
+                Strand::Positive,
+                None,
+                0,
+                50,
+                "ID=t1;Parent=g1",
+            ),
+            linked(
+                Feature::MRNA,
+                Strand::Positive,
+                None,
+                0,
+                50,
+                "ID=t2;Parent=g1",
+            ),
+            linked(
+                Feature::MRNA,
+                Strand::Positive,
+                None,
+                0,
+                50,
+                "ID=t3;Parent=g1",
+            ),
+            linked(Feature::Gene, Strand::Negative, None, 0, 50, "ID=g2"),
+            linked(
+                Feature::MRNA,
+                Strand::Negative,
+                None,
+                0,
+                50,
+                "ID=t4;Parent=g2",
+            ),
+        ];
+        for (strand, parents) in [
+            (Strand::Positive, "Parent=t1,t2,t3"),
+            (Strand::Negative, "Parent=t4"),
+        ]
+        .iter()
+        {
+            for exon in exons(*strand) {
+                annotations.push(exon.with_attributes(String::from(*parents)));
+            }
+        }
+        annotations.extend(vec![
+            // Valid positive strand chain.
+            linked(
+                Feature::CDS,
+                Strand::Positive,
+                Some(Phase::Zero),
+                2,
+                12,
+                "Parent=t1",
+            ),
+            linked(
+                Feature::CDS,
+                Strand::Positive,
+                Some(Phase::Two),
+                20,
+                28,
+                "Parent=t1",
+            ),
+            linked(
+                Feature::CDS,
+                Strand::Positive,
+                Some(Phase::Zero),
+                40,
+                46,
+                "Parent=t1",
+            ),
+            // Broken chain with a wrong phase.
+            linked(
+                Feature::CDS,
+                Strand::Positive,
+                Some(Phase::Zero),
+                2,
+                12,
+                "Parent=t2",
+            ),
+            linked(
+                Feature::CDS,
+                Strand::Positive,
+                Some(Phase::Zero),
+                20,
+                28,
+                "Parent=t2",
+            ),
+            // Valid negative strand chain.
+            linked(
+                Feature::CDS,
+                Strand::Negative,
+                Some(Phase::Zero),
+                4,
+                10,
+                "Parent=t4",
+            ),
+            linked(
+                Feature::CDS,
+                Strand::Negative,
+                Some(Phase::Two),
+                20,
+                28,
+                "Parent=t4",
+            ),
+            linked(
+                Feature::CDS,
+                Strand::Negative,
+                Some(Phase::Zero),
+                40,
+                50,
+                "Parent=t4",
+            ),
+        ]);
+
+        let genes = build_gene_models(&annotations).unwrap();
+        assert_eq!(
+            super::validate_cds_chain(&genes[0].transcripts()[0]),
+            vec![]
+        );
+
+        let report = super::validate_gene_models(&genes);
+        // The non-coding transcript t3 is not reported.
+        let ids: Vec<&str> = report
+            .transcripts()
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(ids, ["t1", "t2", "t4"]);
+        let invalid: Vec<&(String, Vec<CdsIssue>)> = report.invalid().collect();
+        assert_eq!(
+            invalid,
+            [&(
+                String::from("t2"),
+                vec![CdsIssue::PhaseMismatch {
+                    start: 20,
+                    end: 28,
+                    expected: Phase::Two,
+                    found: Phase::Zero,
+                }]
+            )]
+        );
+    }
+
+    fn scaffold(name: &str, sequence: &str) -> Scaffold {
+        Scaffold::new(String::from(name), parse_sequence(sequence).unwrap())
+    }
+
+    fn on(scaffold: &str, annotation: Annotation) -> Annotation {
+        Annotation::new(
+            String::from(scaffold),
+            String::from(annotation.source()),
+            annotation.feature().clone(),
+            annotation.score(),
+            annotation.strand(),
+            annotation.phase(),
+            annotation.start(),
+            annotation.end(),
+            String::from(annotation.attributes()),
+        )
+    }
+
+    #[test]
+    fn test_synthesize_codon_annotations() {
+        let scaffolds = vec![
+            // ATG split by an intron: AT|CCC|G AAA TAA
+            scaffold("p", "CATCCCGAAATAAC"),
+            // Reverse complement of ATG GCC TGA.
+            scaffold("m", "CTCAGGCCATC"),
+            // ATG AAA TTT without a stop codon.
+            scaffold("b", "ATGAAATTT"),
+        ];
+        let positive = Strand::Positive;
+        let negative = Strand::Negative;
+        let transcripts = vec![
+            vec![
+                on(
+                    "p",
+                    annotation(Feature::CDS, positive, Some(Phase::Zero), 1, 3),
+                ),
+                on(
+                    "p",
+                    annotation(Feature::CDS, positive, Some(Phase::One), 6, 13),
+                ),
+            ],
+            vec![on(
+                "m",
+                annotation(Feature::CDS, negative, Some(Phase::Zero), 1, 10),
+            )],
+            vec![on(
+                "b",
+                annotation(Feature::CDS, positive, Some(Phase::Zero), 0, 9),
+            )],
+            vec![on(
+                "x",
+                annotation(Feature::CDS, positive, Some(Phase::Zero), 0, 9),
+            )],
+        ];
+        let existing = vec![on(
+            "m",
+            annotation(Feature::StopCodon, negative, Some(Phase::Zero), 1, 4),
+        )];
+
+        let synthesis =
+            super::synthesize_codon_annotations(&scaffolds, &transcripts, &existing, None);
+        assert_eq!(
+            synthesis.issues,
+            vec![
+                (2, CodonIssue::NotStop([Symbol::Thymine; 3])),
+                (3, CodonIssue::InvalidChain),
+            ]
+        );
+
+        let records: Vec<_> = synthesis
+            .annotations
+            .iter()
+            .map(|a| {
+                (
+                    a.scaffold(),
+                    a.feature().clone(),
+                    a.strand(),
+                    a.phase(),
+                    a.start(),
+                    a.end(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                ("p", Feature::StartCodon, positive, Some(Phase::Zero), 1, 3),
+                ("p", Feature::StartCodon, positive, Some(Phase::One), 6, 7),
+                ("p", Feature::StopCodon, positive, Some(Phase::Zero), 10, 13),
+                ("m", Feature::StartCodon, negative, Some(Phase::Zero), 7, 10),
+                ("b", Feature::StartCodon, positive, Some(Phase::Zero), 0, 3),
+            ]
+        );
+
+        let transcripts: Vec<Vec<Annotation>> = transcripts[..2]
+            .iter()
+            .map(|cds| {
+                cds.iter()
+                    .map(|a| a.with_attributes(String::from("ID=cds;Parent=t")))
+                    .collect()
+            })
+            .collect();
+        let mut ids = IdGenerator::new("codon", IdStrategy::Sequential);
+        let synthesis =
+            super::synthesize_codon_annotations(&scaffolds, &transcripts, &[], Some(&mut ids));
+        let attributes: Vec<&str> = synthesis
+            .annotations
+            .iter()
+            .map(|a| a.attributes())
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                "ID=codon.start_codon.1;Parent=t",
+                "ID=codon.start_codon.1;Parent=t",
+                "ID=codon.stop_codon.1;Parent=t",
+                "ID=codon.start_codon.2;Parent=t",
+                "ID=codon.stop_codon.2;Parent=t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_phases() {
+        use Phase::{One, Two, Zero};
+        let segments = |strand, phases: [Option<Phase>; 3]| {
+            let ranges = match strand {
+                Strand::Negative => [(0, 5), (20, 24), (30, 37)],
+                _ => [(0, 10), (20, 25), (30, 36)],
+            };
+            ranges
+                .iter()
+                .zip(phases.iter())
+                .map(|(&(start, end), &phase)| annotation(Feature::CDS, strand, phase, start, end))
+                .collect::<Vec<Annotation>>()
+        };
+
+        let positive = segments(Strand::Positive, [Some(Zero), Some(Two), Some(Zero)]);
+        assert!(super::check_phases(&positive).is_ok());
+        // Transcription starts at the end on the negative strand, mid-codon.
+        let negative = segments(Strand::Negative, [Some(Two), Some(Zero), Some(One)]);
+        assert!(super::check_phases(&negative).is_ok());
+        let mut reversed = negative.clone();
+        reversed.reverse();
+        assert!(super::check_phases(&reversed).is_ok());
+
+        let wrong = segments(Strand::Negative, [Some(Zero), Some(Zero), Some(One)]);
+        let error = super::check_phases(&wrong).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "CDS segment s:1-5 has phase 0 but phase 2 was expected."
+        );
+
+        let mut mixed = positive.clone();
+        mixed.extend(negative.iter().cloned());
+        assert!(super::check_phases(&mixed).is_err());
+
+        // A segment without a phase does not break the chain.
+        let partial = segments(Strand::Positive, [Some(Zero), None, Some(One)]);
+        let error = super::check_phases(&partial).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "CDS segment s:31-36 has phase 1 but phase 0 was expected."
+        );
+    }
+
+    #[test]
+    fn test_compute_phases() {
+        use Phase::{One, Two, Zero};
+        let phases = |segments: &[Annotation]| -> Vec<Option<Phase>> {
+            super::compute_phases(segments)
+                .unwrap()
+                .iter()
+                .map(|a| a.phase())
+                .collect()
+        };
+
+        let positive = vec![
+            annotation(Feature::CDS, Strand::Positive, None, 30, 36),
+            annotation(Feature::CDS, Strand::Positive, Some(Zero), 0, 10),
+            annotation(Feature::CDS, Strand::Positive, None, 20, 25),
+        ];
+        assert_eq!(phases(&positive), [Some(Zero), Some(Zero), Some(Two)]);
+
+        let negative = vec![
+            annotation(Feature::CDS, Strand::Negative, None, 0, 5),
+            annotation(Feature::CDS, Strand::Negative, None, 20, 24),
+            annotation(Feature::CDS, Strand::Negative, Some(One), 30, 37),
+        ];
+        let computed = super::compute_phases(&negative).unwrap();
+        assert_eq!(phases(&negative), [Some(Two), Some(Zero), Some(One)]);
+        assert!(super::check_phases(&computed).is_ok());
+
+        let unphased: Vec<Annotation> = negative.iter().map(|a| a.with_phase(None)).collect();
+        assert_eq!(phases(&unphased), [Some(One), Some(Two), Some(Zero)]);
+    }
+
+    #[test]
+    fn test_spliced_cds() {
+        use Symbol::{Adenine as A, Cytosine as C, Guanine as G, Thymine as T};
+        // Reverse complement of CC ATGGC GTAAG ATAA GG, a CDS split by an
+        // intron on the negative strand.
+        let sequence = vec![C, C, T, T, A, T, C, T, T, A, C, G, C, C, A, T, G, G];
+        let scaffold = Scaffold::new(String::from("s"), sequence);
+        let segments = vec![
+            annotation(Feature::CDS, Strand::Negative, Some(Phase::Two), 2, 6),
+            annotation(Feature::CDS, Strand::Negative, Some(Phase::Zero), 11, 16),
+        ];
+        let cds = super::spliced_cds(&scaffold, &segments).unwrap();
+        assert_eq!(cds, [A, T, G, G, C, A, T, A, A]);
+
+        // Phase of the first segment in transcription order trims leading
+        // bases.
+        let phased = vec![
+            segments[0].clone(),
+            segments[1].with_phase(Some(Phase::Two)),
+        ];
+        assert_eq!(
+            super::spliced_cds(&scaffold, &phased).unwrap(),
+            [G, G, C, A, T, A, A]
+        );
+
+        let mut mixed = segments.clone();
+        mixed.push(annotation(Feature::CDS, Strand::Positive, None, 14, 15));
+        assert!(super::spliced_cds(&scaffold, &mixed).is_err());
+
+        let overlapping = vec![
+            segments[0].clone(),
+            annotation(Feature::CDS, Strand::Negative, None, 5, 8),
+        ];
+        let error = super::spliced_cds(&scaffold, &overlapping).err().unwrap();
+        assert_eq!(error.to_string(), "CDS segments s:3-6 and 6-8 overlap.");
+
+        let long = vec![annotation(Feature::CDS, Strand::Negative, None, 10, 19)];
+        let error = super::spliced_cds(&scaffold, &long).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "CDS segment s:11-19 extends past the scaffold end 18."
+        );
+
+        let other = Scaffold::new(String::from("t"), vec![A; 20]);
+        assert!(super::spliced_cds(&other, &segments).is_err());
+    }
+}
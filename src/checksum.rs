@@ -0,0 +1,180 @@
+use crate::data::Scaffold;
+use std::fmt::Write;
+
+/// Per-round left rotations of MD5 (RFC 1321).
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// Per-round additive constants of MD5, integer parts of `abs(sin(i + 1)) * 2^32`.
+const CONSTANTS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Streaming MD5 digest. MD5 is only used to identify sequences, as in SAM
+/// `M5` tags and refget, not for any security purpose.
+pub(crate) struct Md5 {
+    state: [u32; 4],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Md5 {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            if self.block_len == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    /// Returns the digest as 32 lowercase hex digits.
+    pub(crate) fn finish(mut self) -> String {
+        let bits = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_le_bytes());
+
+        let mut hex = String::with_capacity(32);
+        for word in self.state.iter() {
+            for byte in word.to_le_bytes().iter() {
+                write!(hex, "{:02x}", byte).unwrap();
+            }
+        }
+        hex
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut words = [0u32; 16];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(CONSTANTS[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d].iter()) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+}
+
+/// Renders a SAM style sequence dictionary with one `@SQ` line per scaffold
+/// giving its name (`SN`), length (`LN`) and MD5 digest (`M5`), see
+/// `Scaffold::md5`.
+pub fn sequence_dictionary(scaffolds: &[Scaffold]) -> String {
+    let mut dictionary = String::from("@HD\tVN:1.0\tSO:unsorted\n");
+    for scaffold in scaffolds {
+        writeln!(
+            dictionary,
+            "@SQ\tSN:{}\tLN:{}\tM5:{}",
+            scaffold.name(),
+            scaffold.len(),
+            scaffold.md5()
+        )
+        .unwrap();
+    }
+    dictionary
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Md5;
+    use crate::fasta::{load_fasta, load_fasta_with_options, FastaOptions};
+    use std::path::Path;
+
+    fn md5(data: &[u8]) -> String {
+        let mut digest = Md5::new();
+        digest.update(data);
+        digest.finish()
+    }
+
+    #[test]
+    fn test_md5() {
+        assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+
+        let data = vec![b'A'; 1000];
+        let mut chunked = Md5::new();
+        for chunk in data.chunks(7) {
+            chunked.update(chunk);
+        }
+        assert_eq!(chunked.finish(), md5(&data));
+    }
+
+    #[test]
+    fn test_sequence_dictionary() {
+        let scaffolds = load_fasta(Path::new("./tests/valid.fasta")).unwrap();
+        let dictionary = super::sequence_dictionary(&scaffolds);
+        let mut lines = dictionary.lines();
+        assert_eq!(lines.next(), Some("@HD\tVN:1.0\tSO:unsorted"));
+        // Digests of the uppercase sequences, as reported by samtools dict.
+        assert_eq!(
+            lines.next(),
+            Some("@SQ\tSN:scaffold_1\tLN:280\tM5:7bd3269b76a55cb5962bb9ec66152548")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("@SQ\tSN:scaffold_2\tLN:7\tM5:4639fd6dd814a9a1d1421c0e1bd6ec72")
+        );
+        assert_eq!(lines.next(), None);
+
+        let options = FastaOptions {
+            pack: true,
+            ..FastaOptions::default()
+        };
+        let packed = load_fasta_with_options(Path::new("./tests/valid.fasta"), &options).unwrap();
+        assert_eq!(packed[0].md5(), scaffolds[0].md5());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::cds::{assemble_cds, phase_to_usize, validate_cds_segments};
+use crate::data::{Annotation, Phase, Scaffold, Symbol};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::slice::ChunksExact;
+
+/// Bases in the order used by the NCBI genetic code tables.
+const BASES: [char; 4] = ['T', 'C', 'A', 'G'];
+
+/// Standard genetic code (NCBI table 1) as one letter amino acid codes with
+/// `*` for stop codons. Codons are ordered by first, second and third base,
+/// each in `BASES` order.
+const STANDARD_CODE: &[u8; 64] =
+    b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+fn base_index(symbol: Symbol) -> Option<usize> {
+    match symbol {
+        Symbol::Thymine => Some(0),
+        Symbol::Cytosine => Some(1),
+        Symbol::Adenine => Some(2),
+        Symbol::Guanine => Some(3),
+        Symbol::Other => None,
+    }
+}
+
+fn codon_index(codon: &[Symbol]) -> Option<usize> {
+    codon
+        .iter()
+        .try_fold(0, |index, &symbol| Some(index * 4 + base_index(symbol)?))
+}
+
+fn codon_name(index: usize) -> String {
+    [index / 16, (index / 4) % 4, index % 4]
+        .iter()
+        .map(|&i| BASES[i])
+        .collect()
+}
+
+/// Amino acid residue of a translated coding sequence.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AminoAcid {
+    Ala,
+    Arg,
+    Asn,
+    Asp,
+    Cys,
+    Gln,
+    Glu,
+    Gly,
+    His,
+    Ile,
+    Leu,
+    Lys,
+    Met,
+    Phe,
+    Pro,
+    Ser,
+    Thr,
+    Trp,
+    Tyr,
+    Val,
+    /// Translation of a stop codon.
+    Stop,
+    /// Translation of a codon containing `Symbol::Other`.
+    Unknown,
+}
+
+impl AminoAcid {
+    /// Parses a one letter code with `*` for stop codons and `X` for
+    /// unknown residues.
+    fn from_letter(letter: u8) -> Self {
+        match letter {
+            b'A' => Self::Ala,
+            b'R' => Self::Arg,
+            b'N' => Self::Asn,
+            b'D' => Self::Asp,
+            b'C' => Self::Cys,
+            b'Q' => Self::Gln,
+            b'E' => Self::Glu,
+            b'G' => Self::Gly,
+            b'H' => Self::His,
+            b'I' => Self::Ile,
+            b'L' => Self::Leu,
+            b'K' => Self::Lys,
+            b'M' => Self::Met,
+            b'F' => Self::Phe,
+            b'P' => Self::Pro,
+            b'S' => Self::Ser,
+            b'T' => Self::Thr,
+            b'W' => Self::Trp,
+            b'Y' => Self::Tyr,
+            b'V' => Self::Val,
+            b'*' => Self::Stop,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// IUPAC one letter code, `*` for stop codons and `X` for unknown
+    /// residues.
+    pub fn letter(self) -> char {
+        match self {
+            Self::Ala => 'A',
+            Self::Arg => 'R',
+            Self::Asn => 'N',
+            Self::Asp => 'D',
+            Self::Cys => 'C',
+            Self::Gln => 'Q',
+            Self::Glu => 'E',
+            Self::Gly => 'G',
+            Self::His => 'H',
+            Self::Ile => 'I',
+            Self::Leu => 'L',
+            Self::Lys => 'K',
+            Self::Met => 'M',
+            Self::Phe => 'F',
+            Self::Pro => 'P',
+            Self::Ser => 'S',
+            Self::Thr => 'T',
+            Self::Trp => 'W',
+            Self::Tyr => 'Y',
+            Self::Val => 'V',
+            Self::Stop => '*',
+            Self::Unknown => 'X',
+        }
+    }
+}
+
+/// Renders a protein sequence in one letter codes, see `AminoAcid::letter`.
+pub fn protein_to_string(protein: &[AminoAcid]) -> String {
+    protein.iter().map(|a| a.letter()).collect()
+}
+
+/// Genetic code used for translation. Variants correspond to NCBI
+/// translation tables.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GeneticCode {
+    /// NCBI table 1.
+    #[default]
+    Standard,
+}
+
+impl GeneticCode {
+    /// Returns the genetic code of an NCBI translation table number.
+    pub fn from_ncbi_table(number: u32) -> Result<Self> {
+        match number {
+            1 => Ok(Self::Standard),
+            _ => bail!("Unsupported NCBI translation table {}.", number),
+        }
+    }
+
+    /// NCBI translation table number.
+    pub fn ncbi_table(self) -> u32 {
+        match self {
+            Self::Standard => 1,
+        }
+    }
+
+    fn table(self) -> &'static [u8; 64] {
+        match self {
+            Self::Standard => STANDARD_CODE,
+        }
+    }
+
+    /// Translates a single codon, codons containing `Symbol::Other` are
+    /// translated to `AminoAcid::Unknown`.
+    pub fn translate_codon(self, codon: [Symbol; 3]) -> AminoAcid {
+        match codon_index(&codon) {
+            Some(index) => AminoAcid::from_letter(self.table()[index]),
+            None => AminoAcid::Unknown,
+        }
+    }
+}
+
+/// Iterator over codons of a coding sequence, see `codons`.
+pub struct Codons<'a> {
+    chunks: ChunksExact<'a, Symbol>,
+}
+
+impl<'a> Codons<'a> {
+    /// Trailing bases not forming a complete codon, they are never
+    /// iterated over.
+    pub fn remainder(&self) -> &'a [Symbol] {
+        self.chunks.remainder()
+    }
+}
+
+impl<'a> Iterator for Codons<'a> {
+    type Item = [Symbol; 3];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks
+            .next()
+            .map(|codon| [codon[0], codon[1], codon[2]])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+/// Iterates over complete codons of a coding sequence, skipping `phase`
+/// leading bases, as given by the phase of the first CDS segment. A
+/// trailing incomplete codon is available via `Codons::remainder`.
+pub fn codons(seq: &[Symbol], phase: Phase) -> Codons<'_> {
+    let skip = phase_to_usize(phase).min(seq.len());
+    Codons {
+        chunks: seq[skip..].chunks_exact(3),
+    }
+}
+
+/// Translates an in-frame coding sequence codon by codon. Translation
+/// continues past stop codons, which are translated to `AminoAcid::Stop`. A
+/// trailing incomplete codon is dropped, use `codons` to detect it.
+pub fn translate(seq: &[Symbol], code: GeneticCode) -> Vec<AminoAcid> {
+    codons(seq, Phase::Zero)
+        .map(|codon| code.translate_codon(codon))
+        .collect()
+}
+
+/// Codon usage statistics over coding sequences, using the standard genetic
+/// code for amino acid level statistics.
+#[derive(Clone, Debug)]
+pub struct CodonUsage {
+    counts: [u64; 64],
+    skipped: Vec<usize>,
+}
+
+impl Default for CodonUsage {
+    fn default() -> Self {
+        Self {
+            counts: [0; 64],
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl CodonUsage {
+    /// Counts all full codons of an in-frame coding sequence. Codons
+    /// containing `Symbol::Other` and a trailing incomplete codon are
+    /// skipped.
+    pub fn add_coding_sequence(&mut self, seq: &[Symbol]) {
+        for codon in seq.chunks_exact(3) {
+            if let Some(index) = codon_index(codon) {
+                self.counts[index] += 1;
+            }
+        }
+    }
+
+    /// Number of occurrences of a codon. `None` is returned for codons
+    /// containing `Symbol::Other`.
+    pub fn count(&self, codon: [Symbol; 3]) -> Option<u64> {
+        codon_index(&codon).map(|index| self.counts[index])
+    }
+
+    /// Total number of counted codons.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Indices of transcripts excluded from the statistics, see
+    /// `codon_usage`.
+    pub fn skipped(&self) -> &[usize] {
+        &self.skipped
+    }
+
+    /// Frequency of a codon relative to all codons of the same amino acid
+    /// (or all stop codons). `None` is returned if no such codon was counted.
+    pub fn relative_frequency(&self, codon: [Symbol; 3]) -> Option<f64> {
+        self.ratios(codon_index(&codon)?)
+            .map(|(frequency, _)| frequency)
+    }
+
+    /// Relative synonymous codon usage, i.e. count of a codon divided by the
+    /// mean count of codons of the same amino acid.
+    pub fn rscu(&self, codon: [Symbol; 3]) -> Option<f64> {
+        self.ratios(codon_index(&codon)?).map(|(_, rscu)| rscu)
+    }
+
+    /// Returns relative frequency and RSCU of a codon or `None` if no
+    /// synonymous codon was counted.
+    fn ratios(&self, index: usize) -> Option<(f64, f64)> {
+        let amino_acid = STANDARD_CODE[index];
+        let (synonymous_total, synonymous_count) = STANDARD_CODE
+            .iter()
+            .zip(self.counts.iter())
+            .filter(|&(&a, _)| a == amino_acid)
+            .fold((0, 0), |(total, n), (_, &count)| (total + count, n + 1));
+
+        if synonymous_total == 0 {
+            return None;
+        }
+
+        let frequency = self.counts[index] as f64 / synonymous_total as f64;
+        Some((frequency, frequency * synonymous_count as f64))
+    }
+
+    /// Renders the table as TSV with a header line and one line per codon:
+    /// codon, amino acid, count, relative frequency and RSCU. Undefined
+    /// ratios are written as `NA`.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from("codon\tamino_acid\tcount\trelative_frequency\trscu\n");
+        for (index, &amino_acid) in STANDARD_CODE.iter().enumerate() {
+            let (frequency, rscu) = match self.ratios(index) {
+                Some((frequency, rscu)) => (format!("{:.4}", frequency), format!("{:.4}", rscu)),
+                None => (String::from("NA"), String::from("NA")),
+            };
+            writeln!(
+                tsv,
+                "{}\t{}\t{}\t{}\t{}",
+                codon_name(index),
+                amino_acid as char,
+                self.counts[index],
+                frequency,
+                rscu
+            )
+            .unwrap();
+        }
+        tsv
+    }
+}
+
+/// Computes codon usage over coding transcripts, each given as the list of
+/// its CDS segments.
+///
+/// Segments are spliced in transcription order, reverse complemented on the
+/// negative strand and the leading bases are removed per the phase of the
+/// first segment. Transcripts failing `validate_cds_segments`, referencing a
+/// missing scaffold or extending past the scaffold end are excluded and
+/// listed in `CodonUsage::skipped`. See `Genome::codon_usage` for transcripts
+/// taken from gene models.
+pub fn codon_usage(scaffolds: &[Scaffold], transcripts: &[Vec<Annotation>]) -> CodonUsage {
+    let scaffolds: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+    let mut usage = CodonUsage::default();
+
+    for (i, cds) in transcripts.iter().enumerate() {
+        let scaffold = cds.first().and_then(|a| scaffolds.get(a.scaffold()));
+        let scaffold = match scaffold {
+            Some(scaffold)
+                if validate_cds_segments(cds, &[]).is_empty()
+                    && cds.iter().all(|a| a.end() <= scaffold.len()) =>
+            {
+                scaffold
+            }
+            _ => {
+                usage.skipped.push(i);
+                continue;
+            }
+        };
+
+        usage.add_coding_sequence(&assemble_cds(scaffold, cds));
+    }
+
+    usage
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{AminoAcid, GeneticCode};
+    use crate::data::{parse_sequence, Annotation, Feature, Phase, Scaffold, Strand, Symbol};
+    use crate::testing;
+
+    const A: Symbol = Symbol::Adenine;
+    const C: Symbol = Symbol::Cytosine;
+    const G: Symbol = Symbol::Guanine;
+    const T: Symbol = Symbol::Thymine;
+
+    fn cds(strand: Strand, phase: Phase, start: usize, end: usize) -> Annotation {
+        testing::builder("s", Feature::CDS, start, end)
+            .strand(strand)
+            .phase(phase)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_codon_usage() {
+        // Positive strand: ATG AAA | AAG TAA spliced over an intron of CCC.
+        // Negative strand (reverse complement of positions 16..25): ATG CTG
+        // TTA.
+        let sequence = vec![
+            A, T, G, A, A, A, C, C, C, A, A, G, T, A, A, G, T, A, A, C, A, G, C, A, T,
+        ];
+        let scaffolds = vec![Scaffold::new(String::from("s"), sequence)];
+        let transcripts = vec![
+            vec![
+                cds(Strand::Positive, Phase::Zero, 0, 6),
+                cds(Strand::Positive, Phase::Zero, 9, 15),
+            ],
+            vec![cds(Strand::Negative, Phase::Zero, 16, 25)],
+            // Broken phase.
+            vec![cds(Strand::Positive, Phase::Zero, 0, 5)],
+        ];
+
+        let usage = super::codon_usage(&scaffolds, &transcripts);
+        assert_eq!(usage.skipped(), &[2]);
+        assert_eq!(usage.total(), 7);
+        assert_eq!(usage.count([A, T, G]), Some(2));
+        assert_eq!(usage.count([A, A, A]), Some(1));
+        assert_eq!(usage.count([A, A, G]), Some(1));
+        assert_eq!(usage.count([T, A, A]), Some(1));
+        assert_eq!(usage.count([C, T, G]), Some(1));
+        assert_eq!(usage.count([C, T, A]), Some(0));
+        assert_eq!(usage.count([C, T, Symbol::Other]), None);
+
+        // Lysine: AAA once, AAG once.
+        assert_eq!(usage.relative_frequency([A, A, A]), Some(0.5));
+        assert_eq!(usage.rscu([A, A, A]), Some(1.));
+        // Leucine has six codons: CTG once and TTA once.
+        assert_eq!(usage.relative_frequency([C, T, G]), Some(0.5));
+        assert_eq!(usage.rscu([C, T, G]), Some(3.));
+        assert_eq!(usage.rscu([C, T, C]), Some(0.));
+        // Stop codons: TAA once out of one.
+        assert_eq!(usage.relative_frequency([T, A, A]), Some(1.));
+        assert_eq!(usage.rscu([T, A, A]), Some(3.));
+        // No cysteine at all.
+        assert_eq!(usage.rscu([T, G, T]), None);
+
+        let tsv = usage.to_tsv();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines.len(), 65);
+        assert_eq!(
+            lines[0],
+            "codon\tamino_acid\tcount\trelative_frequency\trscu"
+        );
+        assert_eq!(lines[1], "TTT\tF\t0\tNA\tNA");
+        assert!(lines.contains(&"ATG\tM\t2\t1.0000\t1.0000"));
+        assert!(lines.contains(&"TTA\tL\t1\t0.5000\t3.0000"));
+    }
+
+    #[test]
+    fn test_phase_and_other() {
+        let sequence = vec![C, A, T, G, Symbol::Other, A, A, G, G, G];
+        let mut usage = super::codon_usage(
+            &[Scaffold::new(String::from("s"), sequence)],
+            &[vec![cds(Strand::Positive, Phase::One, 0, 10)]],
+        );
+        assert!(usage.skipped().is_empty());
+        // ATG NAA GGG
+        assert_eq!(usage.total(), 2);
+        assert_eq!(usage.count([G, G, G]), Some(1));
+
+        usage.add_coding_sequence(&[A, T, G, A]);
+        assert_eq!(usage.count([A, T, G]), Some(2));
+    }
+
+    #[test]
+    fn test_translate() {
+        // Human beta-globin (HBB) coding sequence.
+        let hbb = parse_sequence(
+            "ATGGTGCATCTGACTCCTGAGGAGAAGTCTGCCGTTACTGCCCTGTGGGGCAAGGTGAACGTGGATGAAGTTGGTGGTGAGG\
+             CCCTGGGCAGGCTGCTGGTGGTCTACCCTTGGACCCAGAGGTTCTTTGAGTCCTTTGGGGATCTGTCCACTCCTGATGCTG\
+             TTATGGGCAACCCTAAGGTGAAGGCTCATGGCAAGAAAGTGCTCGGTGCCTTTAGTGATGGCCTGGCTCACCTGGACAACC\
+             TCAAGGGCACCTTTGCCACACTGAGTGAGCTGCACTGTGACAAGCTGCACGTGGATCCTGAGAACTTCAGGCTCCTGGGCA\
+             ACGTGCTGGTCTGTGTGCTGGCCCATCACTTTGGCAAAGAATTCACCCCACCAGTGCAGGCTGCCTATCAGAAAGTGGTGG\
+             CTGGTGTGGCTAATGCCCTGGCCCACAAGTATCACTAA",
+        )
+        .unwrap();
+        let protein = super::translate(&hbb, GeneticCode::Standard);
+        assert_eq!(protein.len(), 148);
+        assert_eq!(protein[0], AminoAcid::Met);
+        assert_eq!(protein[147], AminoAcid::Stop);
+        assert_eq!(
+            super::protein_to_string(&protein),
+            "MVHLTPEEKSAVTALWGKVNVDEVGGEALGRLLVVYPWTQRFFESFGDLSTPDAVMGNPKVKAHGKKVLGAFSDGLAHLDNL\
+             KGTFATLSELHCDKLHVDPENFRLLGNVLVCVLAHHFGKEFTPPVQAAYQKVVAGVANALAHKYH*"
+        );
+
+        let seq = parse_sequence("CCATGNCATAAGT").unwrap();
+        let mut codons = super::codons(&seq, Phase::Two);
+        assert_eq!(codons.next(), Some([A, T, G]));
+        assert_eq!(codons.next(), Some([Symbol::Other, C, A]));
+        assert_eq!(codons.next(), Some([T, A, A]));
+        assert_eq!(codons.next(), None);
+        assert_eq!(codons.remainder(), [G, T]);
+        assert_eq!(super::codons(&seq[..1], Phase::Two).count(), 0);
+
+        let protein = super::translate(&seq[2..], GeneticCode::default());
+        assert_eq!(super::protein_to_string(&protein), "MX*");
+        assert_eq!(protein[1], AminoAcid::Unknown);
+
+        assert_eq!(
+            GeneticCode::from_ncbi_table(1).unwrap(),
+            GeneticCode::Standard
+        );
+        assert_eq!(GeneticCode::Standard.ncbi_table(), 1);
+        assert_eq!(
+            GeneticCode::from_ncbi_table(2).err().unwrap().to_string(),
+            "Unsupported NCBI translation table 2."
+        );
+    }
+}
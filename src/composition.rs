@@ -0,0 +1,233 @@
+use crate::data::Symbol;
+use std::ops::AddAssign;
+
+/// Counts of symbols of a sequence. Counts are exact, ratios are computed
+/// on demand.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Composition {
+    adenine: usize,
+    cytosine: usize,
+    guanine: usize,
+    thymine: usize,
+    other: usize,
+    masked: usize,
+}
+
+impl Composition {
+    pub fn new(seq: &[Symbol]) -> Self {
+        let mut composition = Self::default();
+        for &symbol in seq {
+            composition.add(symbol);
+        }
+        composition
+    }
+
+    pub fn add(&mut self, symbol: Symbol) {
+        *self.count_mut(symbol) += 1;
+    }
+
+    /// Removes a previously added symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such symbol.
+    pub fn remove(&mut self, symbol: Symbol) {
+        *self.count_mut(symbol) -= 1;
+    }
+
+    fn count_mut(&mut self, symbol: Symbol) -> &mut usize {
+        match symbol {
+            Symbol::Adenine => &mut self.adenine,
+            Symbol::Cytosine => &mut self.cytosine,
+            Symbol::Guanine => &mut self.guanine,
+            Symbol::Thymine => &mut self.thymine,
+            Symbol::Other => &mut self.other,
+        }
+    }
+
+    /// Number of occurrences of a symbol.
+    pub fn count(&self, symbol: Symbol) -> usize {
+        match symbol {
+            Symbol::Adenine => self.adenine,
+            Symbol::Cytosine => self.cytosine,
+            Symbol::Guanine => self.guanine,
+            Symbol::Thymine => self.thymine,
+            Symbol::Other => self.other,
+        }
+    }
+
+    /// Total number of symbols.
+    pub fn len(&self) -> usize {
+        self.bases() + self.other
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of symbols other than `Symbol::Other`.
+    pub fn bases(&self) -> usize {
+        self.adenine + self.cytosine + self.guanine + self.thymine
+    }
+
+    /// Number of soft-masked positions. Only compositions of whole
+    /// scaffolds, see `Scaffold::composition`, count them, they are zero for
+    /// plain sequences.
+    pub fn masked(&self) -> usize {
+        self.masked
+    }
+
+    pub(crate) fn with_masked(mut self, masked: usize) -> Self {
+        self.masked = masked;
+        self
+    }
+
+    /// Fraction of soft-masked positions among all symbols, zero if empty.
+    pub fn masked_fraction(&self) -> f64 {
+        match self.len() {
+            0 => 0.,
+            len => self.masked as f64 / len as f64,
+        }
+    }
+
+    /// Fraction of `G` and `C` among bases, i.e. ignoring `Symbol::Other`.
+    /// Returns `None` if there are no bases, e.g. in assembly gaps.
+    pub fn gc_fraction(&self) -> Option<f64> {
+        match self.bases() {
+            0 => None,
+            bases => Some((self.guanine + self.cytosine) as f64 / bases as f64),
+        }
+    }
+
+    /// Fraction of `Symbol::Other` among all symbols, zero if empty.
+    pub fn n_fraction(&self) -> f64 {
+        match self.len() {
+            0 => 0.,
+            len => self.other as f64 / len as f64,
+        }
+    }
+}
+
+impl AddAssign for Composition {
+    fn add_assign(&mut self, other: Self) {
+        self.adenine += other.adenine;
+        self.cytosine += other.cytosine;
+        self.guanine += other.guanine;
+        self.thymine += other.thymine;
+        self.other += other.other;
+        self.masked += other.masked;
+    }
+}
+
+/// Computes compositions of windows of length `window` starting every `step`
+/// symbols from the sequence start. Only complete windows are returned, so
+/// sequences shorter than a window yield none.
+///
+/// # Panics
+///
+/// Panics if `window` or `step` is zero.
+pub fn composition_windows(seq: &[Symbol], window: usize, step: usize) -> Vec<Composition> {
+    assert!(window > 0, "Window length must be positive.");
+    assert!(step > 0, "Window step must be positive.");
+    if seq.len() < window {
+        return Vec::new();
+    }
+
+    let mut compositions = Vec::with_capacity((seq.len() - window) / step + 1);
+    let mut current = Composition::new(&seq[..window]);
+    compositions.push(current);
+    for start in (step..=seq.len() - window).step_by(step) {
+        let previous = start - step;
+        if step < window {
+            // Slide the window, the counts stay exact.
+            seq[previous..start].iter().for_each(|&s| current.remove(s));
+            seq[previous + window..start + window]
+                .iter()
+                .for_each(|&s| current.add(s));
+        } else {
+            current = Composition::new(&seq[start..start + window]);
+        }
+        compositions.push(current);
+    }
+    compositions
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Composition;
+    use crate::data::{parse_sequence, Scaffold, Symbol};
+
+    #[test]
+    fn test_composition() {
+        let seq = parse_sequence("GGCANNTA").unwrap();
+        let composition = Composition::new(&seq);
+        assert_eq!(composition.count(Symbol::Guanine), 2);
+        assert_eq!(composition.count(Symbol::Cytosine), 1);
+        assert_eq!(composition.count(Symbol::Adenine), 2);
+        assert_eq!(composition.count(Symbol::Thymine), 1);
+        assert_eq!(composition.count(Symbol::Other), 2);
+        assert_eq!(composition.len(), 8);
+        assert_eq!(composition.bases(), 6);
+        assert_eq!(composition.gc_fraction(), Some(0.5));
+        assert_eq!(composition.n_fraction(), 0.25);
+
+        assert_eq!(composition.masked(), 0);
+
+        let scaffold = Scaffold::new(String::from("s"), seq);
+        assert_eq!(scaffold.composition(), composition);
+        let scaffold = scaffold.with_masked_regions(vec![0..1, 4..6]);
+        assert_eq!(scaffold.composition().masked(), 3);
+        assert_eq!(scaffold.composition().masked_fraction(), 0.375);
+        assert_eq!(scaffold.composition().count(Symbol::Other), 2);
+
+        let empty = Composition::default();
+        assert_eq!(empty.gc_fraction(), None);
+        assert_eq!(empty.n_fraction(), 0.);
+        assert_eq!(empty.masked_fraction(), 0.);
+    }
+
+    #[test]
+    fn test_composition_windows() {
+        let seq = parse_sequence("GCGCNNNNATAT").unwrap();
+
+        let windows = super::composition_windows(&seq, 4, 4);
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].gc_fraction(), Some(1.));
+        assert_eq!(windows[0].n_fraction(), 0.);
+        // Entirely Other.
+        assert_eq!(windows[1].gc_fraction(), None);
+        assert_eq!(windows[1].n_fraction(), 1.);
+        assert_eq!(windows[1].len(), 4);
+        assert_eq!(windows[2].gc_fraction(), Some(0.));
+
+        let windows = super::composition_windows(&seq, 4, 2);
+        let fractions: Vec<(Option<f64>, f64)> = windows
+            .iter()
+            .map(|w| (w.gc_fraction(), w.n_fraction()))
+            .collect();
+        assert_eq!(
+            fractions,
+            [
+                (Some(1.), 0.),
+                (Some(1.), 0.5),
+                (None, 1.),
+                (Some(0.), 0.5),
+                (Some(0.), 0.),
+            ]
+        );
+
+        let windows = super::composition_windows(&seq, 5, 3);
+        assert_eq!(windows.len(), 3);
+        for (i, window) in windows.iter().enumerate() {
+            assert_eq!(*window, Composition::new(&seq[3 * i..3 * i + 5]));
+        }
+
+        let windows = super::composition_windows(&seq, 3, 5);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1], Composition::new(&seq[5..8]));
+
+        assert!(super::composition_windows(&seq, 13, 1).is_empty());
+        assert_eq!(super::composition_windows(&seq, 12, 1).len(), 1);
+    }
+}
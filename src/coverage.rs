@@ -0,0 +1,188 @@
+use crate::data::Annotation;
+use std::ops::Range;
+
+/// Run-length encoded per-position depth of annotations of a single
+/// scaffold, i.e. the number of annotations covering each position.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Coverage {
+    len: usize,
+    /// Consecutive non-empty runs spanning `[0, len)`, neighbouring runs
+    /// differ in depth.
+    runs: Vec<(Range<usize>, u32)>,
+}
+
+impl Coverage {
+    /// Computes coverage of a scaffold of length `scaffold_len` by
+    /// annotations, which are all assumed to be on that scaffold. Parts of
+    /// annotations past the scaffold end are ignored.
+    pub fn new(scaffold_len: usize, annotations: &[Annotation]) -> Self {
+        let mut events: Vec<(usize, i64)> = Vec::with_capacity(2 * annotations.len());
+        for annotation in annotations {
+            let end = annotation.end().min(scaffold_len);
+            if annotation.start() < end {
+                events.push((annotation.start(), 1));
+                events.push((end, -1));
+            }
+        }
+        events.sort_unstable();
+
+        let mut runs: Vec<(Range<usize>, u32)> = Vec::new();
+        let mut depth = 0i64;
+        let mut start = 0;
+        let mut i = 0;
+        while i < events.len() {
+            let pos = events[i].0;
+            // Apply all events at a position at once so that an annotation
+            // ending where another starts is not counted twice.
+            let mut next = depth;
+            while i < events.len() && events[i].0 == pos {
+                next += events[i].1;
+                i += 1;
+            }
+            if next != depth {
+                push_run(&mut runs, start..pos, depth as u32);
+                start = pos;
+                depth = next;
+            }
+        }
+        push_run(&mut runs, start..scaffold_len, 0);
+
+        Self {
+            len: scaffold_len,
+            runs,
+        }
+    }
+
+    /// Scaffold length.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over consecutive ranges of equal depth spanning the whole
+    /// scaffold, including ranges of zero depth.
+    pub fn runs(&self) -> impl Iterator<Item = (Range<usize>, u32)> + '_ {
+        self.runs.iter().cloned()
+    }
+
+    /// Depth at position `pos`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is out of bounds.
+    pub fn depth(&self, pos: usize) -> u32 {
+        assert!(pos < self.len, "Position {} out of bounds.", pos);
+        let i = self.runs.partition_point(|(range, _)| range.end <= pos);
+        self.runs[i].1
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.runs.iter().map(|&(_, depth)| depth).max().unwrap_or(0)
+    }
+
+    /// Number of positions covered by at least one annotation.
+    pub fn covered_len(&self) -> usize {
+        self.runs
+            .iter()
+            .filter(|(_, depth)| *depth > 0)
+            .map(|(range, _)| range.len())
+            .sum()
+    }
+
+    /// Fraction of positions covered by at least one annotation, zero for
+    /// empty scaffolds.
+    pub fn covered_fraction(&self) -> f64 {
+        if self.len == 0 {
+            return 0.;
+        }
+        self.covered_len() as f64 / self.len as f64
+    }
+
+    /// Expands the coverage to one depth per position.
+    pub fn to_vec(&self) -> Vec<u32> {
+        let mut depths = Vec::with_capacity(self.len);
+        for (range, depth) in self.runs() {
+            depths.resize(range.end, depth);
+        }
+        depths
+    }
+}
+
+/// Appends a run, merging it with the last one if of equal depth. Empty
+/// runs are skipped.
+fn push_run(runs: &mut Vec<(Range<usize>, u32)>, range: Range<usize>, depth: u32) {
+    if range.start >= range.end {
+        return;
+    }
+    match runs.last_mut() {
+        Some((last, last_depth)) if *last_depth == depth => last.end = range.end,
+        _ => runs.push((range, depth)),
+    }
+}
+
+/// Per-position number of annotations covering each position of a scaffold
+/// of length `scaffold_len`, see `Coverage` for a memory efficient variant.
+pub fn coverage(scaffold_len: usize, annotations: &[Annotation]) -> Vec<u32> {
+    Coverage::new(scaffold_len, annotations).to_vec()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Coverage;
+    use crate::data::{Feature, Strand};
+    use crate::testing;
+
+    #[test]
+    fn test_coverage() {
+        let annotations = vec![
+            // Nested.
+            testing::annotation("s", Feature::CDS, Strand::Positive, 2, 10),
+            testing::annotation("s", Feature::CDS, Strand::Positive, 4, 6),
+            // Identical.
+            testing::annotation("s", Feature::CDS, Strand::Positive, 12, 14),
+            testing::annotation("s", Feature::CDS, Strand::Positive, 12, 14),
+            // Touching, must not be counted twice at 16.
+            testing::annotation("s", Feature::CDS, Strand::Positive, 14, 16),
+            testing::annotation("s", Feature::CDS, Strand::Positive, 16, 18),
+            testing::annotation("s", Feature::CDS, Strand::Positive, 19, 19),
+            testing::annotation("s", Feature::CDS, Strand::Positive, 19, 30),
+        ];
+        let coverage = Coverage::new(20, &annotations);
+        let runs: Vec<_> = coverage.runs().collect();
+        assert_eq!(
+            runs,
+            [
+                (0..2, 0),
+                (2..4, 1),
+                (4..6, 2),
+                (6..10, 1),
+                (10..12, 0),
+                (12..14, 2),
+                (14..18, 1),
+                (18..19, 0),
+                (19..20, 1),
+            ]
+        );
+        assert_eq!(coverage.len(), 20);
+        assert_eq!(coverage.max_depth(), 2);
+        assert_eq!(coverage.covered_len(), 15);
+        assert!((coverage.covered_fraction() - 0.75).abs() < 1e-9);
+        assert_eq!(coverage.depth(5), 2);
+        assert_eq!(coverage.depth(16), 1);
+        assert_eq!(coverage.depth(18), 0);
+        assert_eq!(
+            super::coverage(20, &annotations),
+            [0, 0, 1, 1, 2, 2, 1, 1, 1, 1, 0, 0, 2, 2, 1, 1, 1, 1, 0, 1]
+        );
+
+        let empty = Coverage::new(5, &[]);
+        assert_eq!(empty.runs().collect::<Vec<_>>(), [(0..5, 0)]);
+        assert_eq!(empty.max_depth(), 0);
+        assert_eq!(empty.covered_fraction(), 0.);
+        assert!(Coverage::new(0, &annotations).to_vec().is_empty());
+    }
+}
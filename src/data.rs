@@ -1,4 +1,12 @@
-use std::convert::Into;
+use crate::checksum::Md5;
+use crate::composition::Composition;
+use crate::interval::IntervalSet;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
 
 /// Symbol `Other` may represent DNA sequence gaps and misreads.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -10,102 +18,1173 @@ pub enum Symbol {
     Guanine,
 }
 
-impl Into<u8> for Symbol {
-    fn into(self) -> u8 {
+impl Symbol {
+    /// Returns the Watson-Crick complement of the symbol, `Other` is its
+    /// own complement.
+    pub fn complement(self) -> Self {
         match self {
-            Self::Adenine => 0,
-            Self::Thymine => 1,
-            Self::Cytosine => 2,
-            Self::Guanine => 3,
-            Self::Other => 4,
+            Self::Adenine => Self::Thymine,
+            Self::Thymine => Self::Adenine,
+            Self::Cytosine => Self::Guanine,
+            Self::Guanine => Self::Cytosine,
+            Self::Other => Self::Other,
         }
     }
 }
 
-impl Into<i32> for Symbol {
-    fn into(self) -> i32 {
-        let value: u8 = self.into();
-        value as i32
+/// Symbols are serialized as single characters, see `From<Symbol> for
+/// char`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(char::from(*self))
     }
 }
 
-impl Into<usize> for Symbol {
-    fn into(self) -> usize {
-        let value: u8 = self.into();
-        value as usize
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let c = char::deserialize(deserializer)?;
+        Symbol::try_from(c).map_err(serde::de::Error::custom)
     }
 }
 
+/// Sequence characters mapped to symbols, `None` marks invalid characters.
+/// This is the single source of truth of FASTA sequence parsing: `A`, `C`,
+/// `G`, `T` and `N` in either case are valid.
+pub(crate) const SYMBOL_TABLE: [Option<Symbol>; 256] = {
+    let mut table = [None; 256];
+    table[b'A' as usize] = Some(Symbol::Adenine);
+    table[b'a' as usize] = Some(Symbol::Adenine);
+    table[b'C' as usize] = Some(Symbol::Cytosine);
+    table[b'c' as usize] = Some(Symbol::Cytosine);
+    table[b'T' as usize] = Some(Symbol::Thymine);
+    table[b't' as usize] = Some(Symbol::Thymine);
+    table[b'G' as usize] = Some(Symbol::Guanine);
+    table[b'g' as usize] = Some(Symbol::Guanine);
+    table[b'N' as usize] = Some(Symbol::Other);
+    table[b'n' as usize] = Some(Symbol::Other);
+    table
+};
+
+/// Converts a sequence character to a symbol, accepting exactly the
+/// characters of FASTA sequences, see `SYMBOL_TABLE`.
+impl TryFrom<char> for Symbol {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self> {
+        let symbol = if c.is_ascii() {
+            SYMBOL_TABLE[c as usize]
+        } else {
+            None
+        };
+        symbol.ok_or_else(|| anyhow!("Encountered invalid symbol {}.", c))
+    }
+}
+
+/// Renders the symbol as an upper case DNA character.
+impl From<Symbol> for char {
+    fn from(symbol: Symbol) -> Self {
+        Alphabet::Dna.symbol_to_char(symbol)
+    }
+}
+
+/// Parses a textual DNA sequence, e.g. `ACGTN`, case insensitively.
+pub fn parse_sequence(text: &str) -> Result<Vec<Symbol>> {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            Symbol::try_from(c).with_context(|| format!("Invalid sequence at position {}.", i))
+        })
+        .collect()
+}
+
+/// Renders a sequence as upper case DNA text, the inverse of
+/// `parse_sequence` up to case.
+pub fn sequence_to_string(seq: &[Symbol]) -> String {
+    symbols_to_string(seq, Alphabet::Dna)
+}
+
+/// Replaces positions of `seq`, which starts at position `offset` of a
+/// scaffold, covered by sorted, non-overlapping scaffold ranges `masked` by
+/// `Symbol::Other`.
+pub(crate) fn hard_mask(seq: &mut [Symbol], offset: usize, masked: &[Range<usize>]) {
+    let end = offset + seq.len();
+    let first = masked.partition_point(|range| range.end <= offset);
+    for range in masked[first..].iter().take_while(|range| range.start < end) {
+        let start = range.start.max(offset) - offset;
+        let stop = range.end.min(end) - offset;
+        seq[start..stop].fill(Symbol::Other);
+    }
+}
+
+/// Returns the reverse complement of a sequence, i.e. the sequence of the
+/// opposite strand read in its 5' to 3' direction.
+pub fn reverse_complement(seq: &[Symbol]) -> Vec<Symbol> {
+    seq.iter().rev().map(|symbol| symbol.complement()).collect()
+}
+
+/// Reverse complements a sequence in place, see `reverse_complement`.
+pub fn reverse_complement_in_place(seq: &mut [Symbol]) {
+    seq.reverse();
+    for symbol in seq.iter_mut() {
+        *symbol = symbol.complement();
+    }
+}
+
+/// Integer code of a symbol, e.g. for model input. The codes are A = 0,
+/// T = 1, C = 2, G = 3 and Other = 4 and must never change.
+impl From<Symbol> for u8 {
+    fn from(symbol: Symbol) -> Self {
+        match symbol {
+            Symbol::Adenine => 0,
+            Symbol::Thymine => 1,
+            Symbol::Cytosine => 2,
+            Symbol::Guanine => 3,
+            Symbol::Other => 4,
+        }
+    }
+}
+
+impl From<Symbol> for i32 {
+    fn from(symbol: Symbol) -> Self {
+        u8::from(symbol) as i32
+    }
+}
+
+impl From<Symbol> for usize {
+    fn from(symbol: Symbol) -> Self {
+        u8::from(symbol) as usize
+    }
+}
+
+/// Decodes an integer code of a symbol, see `From<Symbol> for u8`.
+impl TryFrom<u8> for Symbol {
+    type Error = anyhow::Error;
+
+    fn try_from(code: u8) -> Result<Self> {
+        match code {
+            0 => Ok(Self::Adenine),
+            1 => Ok(Self::Thymine),
+            2 => Ok(Self::Cytosine),
+            3 => Ok(Self::Guanine),
+            4 => Ok(Self::Other),
+            _ => bail!("Invalid symbol code {}.", code),
+        }
+    }
+}
+
+/// Decodes integer codes of symbols, e.g. model output, see `From<Symbol>
+/// for u8`.
+pub fn decode_symbols(codes: &[u8]) -> Result<Vec<Symbol>> {
+    codes
+        .iter()
+        .enumerate()
+        .map(|(i, &code)| {
+            Symbol::try_from(code).with_context(|| format!("Failed to decode symbol {}.", i))
+        })
+        .collect()
+}
+
+/// Alphabet used when rendering symbols as text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Alphabet {
+    /// `A`, `C`, `G`, `T` and `N`.
+    #[default]
+    Dna,
+    /// `A`, `C`, `G`, `U` and `N`, i.e. the transcribed view of a DNA
+    /// sequence.
+    Rna,
+}
+
+impl Alphabet {
+    pub fn symbol_to_char(self, symbol: Symbol) -> char {
+        match symbol {
+            Symbol::Adenine => 'A',
+            Symbol::Cytosine => 'C',
+            Symbol::Guanine => 'G',
+            Symbol::Thymine => match self {
+                Self::Dna => 'T',
+                Self::Rna => 'U',
+            },
+            Symbol::Other => 'N',
+        }
+    }
+}
+
+/// Transcribes a coding strand sequence, e.g. as extracted for a positive or
+/// negative strand feature, into RNA text with `U` in place of `T`. Symbols
+/// do not distinguish thymine and uracil, the stored sequence is left as is
+/// and only rendered with `Alphabet::Rna`.
+pub fn transcribe(seq: &[Symbol]) -> String {
+    symbols_to_string(seq, Alphabet::Rna)
+}
+
+/// Renders a sequence as upper case text in a given alphabet.
+pub fn symbols_to_string(seq: &[Symbol], alphabet: Alphabet) -> String {
+    seq.iter()
+        .map(|&symbol| alphabet.symbol_to_char(symbol))
+        .collect()
+}
+
+/// Sequence storing A, C, G and T in two bits per symbol. Positions of
+/// `Symbol::Other` are kept separately as sorted runs, which is compact for
+/// assembly gaps.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PackedSequence {
+    len: usize,
+    bits: Vec<u8>,
+    other: Vec<Range<usize>>,
+}
+
+impl PackedSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            len: 0,
+            bits: Vec::with_capacity(capacity.div_ceil(4)),
+            other: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = (self.len + additional).div_ceil(4);
+        self.bits.reserve(needed.saturating_sub(self.bits.len()));
+    }
+
+    pub fn push(&mut self, symbol: Symbol) {
+        let position = self.len;
+        if position.is_multiple_of(4) {
+            self.bits.push(0);
+        }
+        if symbol == Symbol::Other {
+            match self.other.last_mut() {
+                Some(run) if run.end == position => run.end += 1,
+                _ => self.other.push(position..position + 1),
+            }
+        } else {
+            let code: u8 = symbol.into();
+            self.bits[position / 4] |= code << (2 * (position % 4));
+        }
+        self.len += 1;
+    }
+
+    /// Returns the symbol at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Symbol {
+        assert!(
+            index < self.len,
+            "Index {} is out of bounds of packed sequence of length {}.",
+            index,
+            self.len
+        );
+
+        let run = self.other.partition_point(|run| run.end <= index);
+        if self.other.get(run).is_some_and(|run| run.start <= index) {
+            return Symbol::Other;
+        }
+        match (self.bits[index / 4] >> (2 * (index % 4))) & 0b11 {
+            0 => Symbol::Adenine,
+            1 => Symbol::Thymine,
+            2 => Symbol::Cytosine,
+            _ => Symbol::Guanine,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Symbol> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<Symbol> {
+        let mut symbols = Vec::with_capacity(self.len);
+        symbols.extend(self.iter());
+        symbols
+    }
+}
+
+impl From<&[Symbol]> for PackedSequence {
+    fn from(symbols: &[Symbol]) -> Self {
+        let mut packed = Self::with_capacity(symbols.len());
+        symbols.iter().for_each(|&symbol| packed.push(symbol));
+        packed
+    }
+}
+
+impl From<Vec<Symbol>> for PackedSequence {
+    fn from(symbols: Vec<Symbol>) -> Self {
+        Self::from(&symbols[..])
+    }
+}
+
+impl From<PackedSequence> for Vec<Symbol> {
+    fn from(packed: PackedSequence) -> Self {
+        packed.to_vec()
+    }
+}
+
+/// Storage of a scaffold sequence.
+#[derive(Clone)]
+enum SequenceData {
+    Plain(Vec<Symbol>),
+    Packed(PackedSequence),
+}
+
 /// This struct represents an individual DNA sequencing scaffold, i.e. a
 /// continuous sequence of DNA symbols and related metadata.
+///
+/// Scaffolds are equal if their names, sequences and metadata are equal,
+/// regardless of whether the sequences are packed.
+#[derive(Clone)]
 pub struct Scaffold {
     name: String,
-    sequence: Vec<Symbol>,
+    sequence: SequenceData,
+    description: Option<String>,
+    masked: Vec<Range<usize>>,
+    circular: bool,
 }
 
 impl Scaffold {
     pub fn new(name: String, sequence: Vec<Symbol>) -> Self {
-        Self { name, sequence }
+        Self {
+            name,
+            sequence: SequenceData::Plain(sequence),
+            description: None,
+            masked: Vec::new(),
+            circular: false,
+        }
+    }
+
+    /// Creates a scaffold storing its sequence in two bits per symbol.
+    pub fn new_packed(name: String, sequence: PackedSequence) -> Self {
+        Self {
+            name,
+            sequence: SequenceData::Packed(sequence),
+            description: None,
+            masked: Vec::new(),
+            circular: false,
+        }
+    }
+
+    /// Returns the scaffold renamed to `name`.
+    pub(crate) fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// MD5 digest of the sequence as uppercase `A`, `C`, `G`, `T` and `N`
+    /// bytes in lowercase hex, matching `M5` tags of SAM sequence
+    /// dictionaries (e.g. from `samtools dict`). Note that ambiguity codes
+    /// loaded as `Symbol::Other` are digested as `N`.
+    pub fn md5(&self) -> String {
+        let mut digest = Md5::new();
+        let mut buffer = [0u8; 4096];
+        for start in (0..self.len()).step_by(buffer.len()) {
+            let end = (start + buffer.len()).min(self.len());
+            for (byte, i) in buffer.iter_mut().zip(start..end) {
+                *byte = Alphabet::Dna.symbol_to_char(self.symbol(i)) as u8;
+            }
+            digest.update(&buffer[..end - start]);
+        }
+        digest.finish()
+    }
+
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+
+    /// Sets soft-masked (lowercase in FASTA) regions as 0-based half-open
+    /// ranges of `sequence()` positions. The ranges are normalized: clamped
+    /// to the sequence, sorted and merged if overlapping or touching, and
+    /// empty ranges are dropped.
+    pub fn with_masked_regions(mut self, masked: Vec<Range<usize>>) -> Self {
+        let len = self.len();
+        let clamped = masked
+            .into_iter()
+            .map(|range| range.start.min(len)..range.end.min(len))
+            .collect();
+        self.masked = IntervalSet::new(clamped).into_ranges();
+        self
+    }
+
+    /// Marks the scaffold as circular, e.g. a mitochondrial or plasmid
+    /// sequence. Ranges of circular scaffolds may wrap around the origin,
+    /// see `contains_range`.
+    pub fn with_circular(mut self, circular: bool) -> Self {
+        self.circular = circular;
+        self
+    }
+
+    pub fn is_circular(&self) -> bool {
+        self.circular
+    }
+
+    /// Returns true if 0-based half-open `range` lies within the scaffold.
+    /// On circular scaffolds, ranges starting within the scaffold may end
+    /// past its end as long as they are not longer than the scaffold, i.e.
+    /// `range.end - len` symbols from the start of the scaffold follow.
+    pub fn contains_range(&self, range: &Range<usize>) -> bool {
+        if range.start > range.end {
+            return false;
+        }
+        range.end <= self.len()
+            || (self.circular && range.start < self.len() && range.len() <= self.len())
+    }
+
+    /// Returns symbols of 0-based half-open `range`. Empty ranges within
+    /// the scaffold, including `len..len`, yield an empty slice.
+    ///
+    /// Unlike `subsequence_owned`, ranges never wrap around the origin of
+    /// circular scaffolds. Plain scaffolds are borrowed from, only the range
+    /// is unpacked from packed scaffolds.
+    pub fn subsequence(&self, range: Range<usize>) -> Result<Cow<'_, [Symbol]>, RangeError> {
+        self.check_range(&range)?;
+        if range.end > self.len() {
+            return Err(self.out_of_bounds(range));
+        }
+        Ok(match self.sequence {
+            SequenceData::Plain(ref sequence) => Cow::Borrowed(&sequence[range]),
+            SequenceData::Packed(ref packed) => Cow::Owned(range.map(|i| packed.get(i)).collect()),
+        })
+    }
+
+    /// Copies symbols of 0-based half-open `range`, wrapping around the
+    /// origin of circular scaffolds, see `contains_range`. Empty ranges
+    /// yield an empty sequence.
+    pub fn subsequence_owned(&self, range: Range<usize>) -> Result<Vec<Symbol>, RangeError> {
+        self.check_range(&range)?;
+        if !self.contains_range(&range) {
+            return Err(self.out_of_bounds(range));
+        }
+        let len = self.len();
+        Ok(range.map(|i| self.symbol(i % len)).collect())
+    }
+
+    /// Copies symbols of 0-based half-open range `start..end` as read on
+    /// `strand`, i.e. reverse complemented on the negative strand, see
+    /// `subsequence_owned`.
+    pub fn region(
+        &self,
+        start: usize,
+        end: usize,
+        strand: Strand,
+    ) -> Result<Vec<Symbol>, RangeError> {
+        let mut sequence = self.subsequence_owned(start..end)?;
+        if strand == Strand::Negative {
+            reverse_complement_in_place(&mut sequence);
+        }
+        Ok(sequence)
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), RangeError> {
+        if range.start > range.end {
+            return Err(RangeError::Reversed {
+                scaffold: self.name.clone(),
+                start: range.start,
+                end: range.end,
+            });
+        }
+        Ok(())
+    }
+
+    fn out_of_bounds(&self, range: Range<usize>) -> RangeError {
+        RangeError::OutOfBounds {
+            scaffold: self.name.clone(),
+            start: range.start,
+            end: range.end,
+            len: self.len(),
+            circular: self.circular,
+        }
+    }
+
+    /// Returns maximal runs of `Symbol::Other`, e.g. assembly gaps, at
+    /// least `min_len` symbols long sorted by start. Packed scaffolds are
+    /// not unpacked.
+    pub fn gap_runs(&self, min_len: usize) -> Vec<Range<usize>> {
+        let sequence = match self.sequence {
+            SequenceData::Plain(ref sequence) => sequence,
+            SequenceData::Packed(ref packed) => {
+                return packed
+                    .other
+                    .iter()
+                    .filter(|run| run.len() >= min_len)
+                    .cloned()
+                    .collect();
+            }
+        };
+
+        let mut runs = Vec::new();
+        let mut start = None;
+        for (i, &symbol) in sequence.iter().enumerate() {
+            match (symbol == Symbol::Other, start) {
+                (true, None) => start = Some(i),
+                (false, Some(run_start)) => {
+                    if i - run_start >= min_len {
+                        runs.push(run_start..i);
+                    }
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(run_start) = start {
+            if sequence.len() - run_start >= min_len {
+                runs.push(run_start..sequence.len());
+            }
+        }
+        runs
+    }
+
+    /// Counts of symbols of the sequence, computed without unpacking packed
+    /// scaffolds, and the number of soft-masked positions.
+    pub fn composition(&self) -> Composition {
+        let composition = match self.sequence {
+            SequenceData::Plain(ref sequence) => Composition::new(sequence),
+            SequenceData::Packed(ref packed) => {
+                let mut composition = Composition::default();
+                packed.iter().for_each(|symbol| composition.add(symbol));
+                composition
+            }
+        };
+        composition.with_masked(self.masked_len())
+    }
+
+    /// Returns the reverse complement of the scaffold named with suffix
+    /// `_rc`. Soft-masked regions are mirrored accordingly, packed
+    /// scaffolds stay packed.
+    pub fn reverse_complement(&self) -> Self {
+        let mut sequence = self.sequence().to_vec();
+        reverse_complement_in_place(&mut sequence);
+        let name = format!("{}_rc", self.name);
+        let scaffold = match self.sequence {
+            SequenceData::Plain(_) => Self::new(name, sequence),
+            SequenceData::Packed(_) => Self::new_packed(name, PackedSequence::from(sequence)),
+        };
+
+        let len = self.len();
+        let masked = self
+            .masked
+            .iter()
+            .rev()
+            .map(|range| len - range.end..len - range.start)
+            .collect();
+        scaffold
+            .with_description(self.description.clone())
+            .with_masked_regions(masked)
+            .with_circular(self.circular)
+    }
+
+    /// Soft-masked regions. Empty unless the scaffold was loaded with
+    /// `FastaOptions::keep_mask` set.
+    pub fn masked_regions(&self) -> &[Range<usize>] {
+        &self.masked
+    }
+
+    /// Number of soft-masked positions.
+    pub fn masked_len(&self) -> usize {
+        self.masked.iter().map(|range| range.len()).sum()
+    }
+
+    /// Drops soft-masking, the sequence is not changed.
+    pub fn clear_mask(&mut self) {
+        self.masked.clear();
+    }
+
+    /// Returns a copy of the scaffold with all soft-masked positions
+    /// replaced by `Symbol::Other`, see `hard_mask_in_place`.
+    pub fn hard_masked(&self) -> Self {
+        let mut scaffold = self.clone();
+        scaffold.hard_mask_in_place();
+        scaffold
+    }
+
+    /// Replaces all soft-masked positions by `Symbol::Other`. Masked
+    /// regions are kept, use `clear_mask` to drop them. Packed scaffolds
+    /// stay packed.
+    pub fn hard_mask_in_place(&mut self) {
+        if self.masked.is_empty() {
+            return;
+        }
+        match self.sequence {
+            SequenceData::Plain(ref mut sequence) => hard_mask(sequence, 0, &self.masked),
+            SequenceData::Packed(ref packed) => {
+                let mut sequence = packed.to_vec();
+                hard_mask(&mut sequence, 0, &self.masked);
+                self.sequence = SequenceData::Packed(PackedSequence::from(sequence));
+            }
+        }
     }
 
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
 
-    pub fn sequence(&self) -> &[Symbol] {
-        &self.sequence
+    /// FASTA header text following the name. Only set if the scaffold was
+    /// loaded with `FastaOptions::split_header` set.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Sequence as a slice of symbols. Packed scaffolds are unpacked into a
+    /// new copy on every call; use `len`, `symbol`, `subsequence` or
+    /// `packed_sequence` to avoid it.
+    pub fn sequence(&self) -> Cow<'_, [Symbol]> {
+        match self.sequence {
+            SequenceData::Plain(ref sequence) => Cow::Borrowed(sequence),
+            SequenceData::Packed(ref packed) => Cow::Owned(packed.to_vec()),
+        }
+    }
+
+    /// Sequence length.
+    pub fn len(&self) -> usize {
+        match self.sequence {
+            SequenceData::Plain(ref sequence) => sequence.len(),
+            SequenceData::Packed(ref packed) => packed.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the symbol at `index` without unpacking packed scaffolds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn symbol(&self, index: usize) -> Symbol {
+        match self.sequence {
+            SequenceData::Plain(ref sequence) => sequence[index],
+            SequenceData::Packed(ref packed) => packed.get(index),
+        }
+    }
+
+    /// Packed sequence of scaffolds created via `new_packed`.
+    pub fn packed_sequence(&self) -> Option<&PackedSequence> {
+        match self.sequence {
+            SequenceData::Plain(_) => None,
+            SequenceData::Packed(ref packed) => Some(packed),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn sequence_capacity(&self) -> usize {
+        match self.sequence {
+            SequenceData::Plain(ref sequence) => sequence.capacity(),
+            SequenceData::Packed(ref packed) => packed.bits.capacity() * 4,
+        }
+    }
+}
+
+/// Invalid 0-based half-open range of a scaffold, see
+/// `Scaffold::subsequence`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RangeError {
+    /// The range starts after its end.
+    Reversed {
+        scaffold: String,
+        start: usize,
+        end: usize,
+    },
+    /// The range extends past the end of the scaffold of length `len`.
+    OutOfBounds {
+        scaffold: String,
+        start: usize,
+        end: usize,
+        len: usize,
+        circular: bool,
+    },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Reversed {
+                scaffold,
+                start,
+                end,
+            } => write!(
+                f,
+                "Range {}..{} of scaffold {} starts after its end.",
+                start, end, scaffold
+            ),
+            Self::OutOfBounds {
+                scaffold,
+                start,
+                end,
+                len,
+                circular,
+            } => write!(
+                f,
+                "Range {}..{} exceeds {} scaffold {} of length {}.",
+                start,
+                end,
+                if *circular { "circular" } else { "linear" },
+                scaffold,
+                len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// Serialized form of `Scaffold` with the sequence as a string of
+/// characters, see `sequence_to_string`. Packed scaffolds are serialized
+/// the same way and deserialize unpacked.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedScaffold<'a> {
+    name: std::borrow::Cow<'a, str>,
+    sequence: String,
+    description: Option<String>,
+    masked: Vec<Range<usize>>,
+    circular: bool,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Scaffold {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedScaffold {
+            name: std::borrow::Cow::Borrowed(&self.name),
+            sequence: (0..self.len())
+                .map(|i| char::from(self.symbol(i)))
+                .collect(),
+            description: self.description.clone(),
+            masked: self.masked.clone(),
+            circular: self.circular,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Scaffold {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let serialized = SerializedScaffold::deserialize(deserializer)?;
+        let sequence = parse_sequence(&serialized.sequence).map_err(D::Error::custom)?;
+
+        // Masked regions come from untrusted input, unlike in
+        // `with_masked_regions` they are rejected rather than normalized.
+        let mut previous_end = 0;
+        for range in serialized.masked.iter() {
+            if range.start >= range.end {
+                return Err(D::Error::custom(format!(
+                    "Masked region {}..{} of scaffold {} is empty.",
+                    range.start, range.end, serialized.name
+                )));
+            }
+            if range.end > sequence.len() {
+                return Err(D::Error::custom(format!(
+                    "Masked region {}..{} exceeds scaffold {} of length {}.",
+                    range.start,
+                    range.end,
+                    serialized.name,
+                    sequence.len()
+                )));
+            }
+            if range.start < previous_end {
+                return Err(D::Error::custom(format!(
+                    "Masked region {}..{} of scaffold {} is not sorted or overlaps.",
+                    range.start, range.end, serialized.name
+                )));
+            }
+            previous_end = range.end;
+        }
+
+        Ok(Self::new(serialized.name.into_owned(), sequence)
+            .with_description(serialized.description)
+            .with_masked_regions(serialized.masked)
+            .with_circular(serialized.circular))
+    }
+}
+
+/// Number of symbols `Display` of `Scaffold` writes unless a precision is
+/// given.
+const DISPLAY_LIMIT: usize = 100;
+
+/// Number of leading and trailing symbols shown by `Debug` of `Scaffold`.
+const DEBUG_ENDS: usize = 10;
+
+impl PartialEq for Scaffold {
+    fn eq(&self, other: &Self) -> bool {
+        if self.name != other.name
+            || self.description != other.description
+            || self.masked != other.masked
+            || self.circular != other.circular
+            || self.len() != other.len()
+        {
+            return false;
+        }
+        match (&self.sequence, &other.sequence) {
+            (SequenceData::Plain(a), SequenceData::Plain(b)) => a == b,
+            (SequenceData::Packed(a), SequenceData::Packed(b)) => a == b,
+            _ => (0..self.len()).all(|i| self.symbol(i) == other.symbol(i)),
+        }
+    }
+}
+
+/// Writes the scaffold as a single line FASTA record, i.e. `>name`, a line
+/// break and the sequence. Sequences longer than the precision, 100 by
+/// default (e.g. `{:.20}` for 20), are truncated with a marker giving the
+/// number of omitted symbols.
+impl fmt::Display for Scaffold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DISPLAY_LIMIT);
+        writeln!(f, ">{}", self.name)?;
+        for i in 0..self.len().min(limit) {
+            write!(f, "{}", char::from(self.symbol(i)))?;
+        }
+        if self.len() > limit {
+            write!(f, "...[{} more]", self.len() - limit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarizes the scaffold by its name, length, base composition and the
+/// first and last symbols rather than listing the whole sequence.
+impl fmt::Debug for Scaffold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = self.composition();
+        let composition = format!(
+            "A={} C={} G={} T={} N={}",
+            counts.count(Symbol::Adenine),
+            counts.count(Symbol::Cytosine),
+            counts.count(Symbol::Guanine),
+            counts.count(Symbol::Thymine),
+            counts.count(Symbol::Other)
+        );
+        let text =
+            |range: Range<usize>| -> String { range.map(|i| char::from(self.symbol(i))).collect() };
+        let head = self.len().min(DEBUG_ENDS);
+        let tail = self.len().saturating_sub(DEBUG_ENDS);
+
+        f.debug_struct("Scaffold")
+            .field("name", &self.name)
+            .field("len", &self.len())
+            .field("composition", &composition)
+            .field("first", &text(0..head))
+            .field("last", &text(tail..self.len()))
+            .finish()
     }
 }
 
 /// DNA feature is a human or machine annotated region of a DNA sequence
 /// serving a given biological “purpose”. Note that annotations may be mutually
 /// overlapping.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///
+/// Features are ordered by the declaration order of the variants, roughly
+/// from the outermost to the innermost.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Feature {
+    Gene,
+    /// Messenger RNA, i.e. a protein coding transcript.
+    MRNA,
+    /// Transcript of unspecified type.
+    Transcript,
     Exon,
+    Intron,
     /// Protein coding sequence.
     CDS,
     StartCodon,
     StopCodon,
+    FivePrimeUTR,
+    ThreePrimeUTR,
+    /// Interspersed or tandem repeat, e.g. a RepeatMasker hit.
+    Repeat,
+    /// Any other feature type, holding the type as given in the GFF file.
+    Other(String),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Strand {
     Positive,
     Negative,
+    /// The feature is not stranded (`.` in GFF), e.g. a repeat region.
+    /// Strand aware operations, such as sequence extraction or CDS
+    /// assembly, read it on the positive strand.
+    Unstranded,
+    /// The strand is relevant but unknown (`?` in GFF). Treated as
+    /// `Unstranded`.
+    Unknown,
+}
+
+impl Strand {
+    /// Strand of the feature on the reverse complement of its scaffold.
+    /// `Unstranded` and `Unknown` stay unchanged.
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Positive => Self::Negative,
+            Self::Negative => Self::Positive,
+            other => other,
+        }
+    }
 }
 
 /// Position of the first symbol (base) of the first full codon/triplet in the
 /// feature relative to the feature beginning. Non-zero shift may happen on CDS
 /// with start outside of scaffold.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Phase {
     Zero,
     One,
     Two,
 }
 
+/// Parsed attribute column (column 9) of a GFF record, keys in order of
+/// first appearance.
+///
+/// Both GFF3 style (`ID=x;Parent=y,z`) and GTF style (`gene_id "g1";
+/// transcriptId 416145`) entries are understood. GFF3 values are split on
+/// `,` and percent decoded, GTF values are stripped of quotes. Keys repeated
+/// in multiple entries accumulate their values.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Attributes {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl Attributes {
+    /// Parses a raw attribute column. Empty columns (`.`) yield no
+    /// attributes, entries without a value are ignored.
+    pub fn parse(column: &str) -> Self {
+        let mut attributes = Self::default();
+        if column.trim() == "." {
+            return attributes;
+        }
+
+        for entry in split_entries(column) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let gff3 = match (entry.find('='), entry.find(char::is_whitespace)) {
+                (Some(equals), Some(space)) => equals < space,
+                (equals, _) => equals.is_some(),
+            };
+            if gff3 {
+                let (key, value) = entry.split_once('=').unwrap();
+                let values = value.split(',').map(percent_decode).collect();
+                attributes.push(key.trim(), values);
+            } else if let Some((key, value)) = entry.split_once(char::is_whitespace) {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                attributes.push(key, vec![String::from(value)]);
+            }
+        }
+
+        attributes
+    }
+
+    /// Strictly parses a GTF attribute column, where every entry is a key
+    /// followed by a single, typically quoted, value (`gene_id "g1";`).
+    /// GFF3 style entries and unbalanced quotes are errors.
+    pub fn parse_gtf(column: &str) -> Result<Self> {
+        let mut attributes = Self::default();
+        if column.trim() == "." {
+            return Ok(attributes);
+        }
+
+        for entry in split_entries(column) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match entry.split_once(char::is_whitespace) {
+                Some((key, value)) => (key, value.trim()),
+                None => (entry, ""),
+            };
+            ensure!(
+                !key.contains('='),
+                "Attribute {} is not in GTF style.",
+                entry
+            );
+            ensure!(!value.is_empty(), "Attribute {} has no value.", entry);
+            let value = match value.strip_prefix('"') {
+                Some(quoted) => match quoted.strip_suffix('"') {
+                    Some(value) if !value.contains('"') => value,
+                    _ => bail!("Attribute {} has unbalanced quotes.", entry),
+                },
+                None => {
+                    ensure!(
+                        !value.contains(|c: char| c == '"' || c.is_whitespace()),
+                        "Attribute {} has an invalid value.",
+                        entry
+                    );
+                    value
+                }
+            };
+            attributes.push(key, vec![String::from(value)]);
+        }
+
+        Ok(attributes)
+    }
+
+    fn push(&mut self, key: &str, values: Vec<String>) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => existing.extend(values),
+            None => self.entries.push((String::from(key), values)),
+        }
+    }
+
+    /// First value of a key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).first().map(String::as_str)
+    }
+
+    /// All values of a key, e.g. multiple parents in `Parent=t1,t2`.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or(&[], |(_, values)| values.as_slice())
+    }
+
+    /// Removes a key together with all its values.
+    pub fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+
+    /// Iterates over keys and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries
+            .iter()
+            .map(|(key, values)| (key.as_str(), values.as_slice()))
+    }
+
+    /// Number of distinct keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Formats attributes as a GFF3 attribute column with percent encoded
+/// values, e.g. `ID=g1;Note=a%3Bb`. Empty attributes are formatted as `.`.
+impl fmt::Display for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, ".");
+        }
+        for (i, (key, values)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            let values: Vec<String> = values.iter().map(|v| percent_encode(v)).collect();
+            write!(f, "{}={}", key, values.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits an attribute column on `;` outside of double quotes.
+fn split_entries(column: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in column.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                entries.push(&column[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    entries.push(&column[start..]);
+    entries
+}
+
+/// Decodes percent encoded characters, e.g. `%3B` to `;`. Malformed escapes
+/// such as `%G1` are kept as is.
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Percent encodes characters reserved in GFF3 attribute values (`;`, `=`,
+/// `&`, `,` and `%`) and control characters such as tabs and line breaks.
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' | '=' | '&' | ',' | '%' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c if c.is_ascii_control() => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
 /// Annotation of a DNA feature.
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Annotation {
     scaffold: String,
     source: String,
     feature: Feature,
-    score: Option<u32>,
+    score: Option<f64>,
     strand: Strand,
     phase: Option<Phase>,
     start: usize,
     end: usize,
     attributes: String,
+    line: Option<usize>,
 }
 
 impl Annotation {
+    /// Creates an annotation from all its columns. Unlike
+    /// `AnnotationBuilder`, this does not check the coordinates.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         scaffold: String,
         source: String,
         feature: Feature,
-        score: Option<u32>,
+        score: Option<f64>,
         strand: Strand,
         phase: Option<Phase>,
         start: usize,
@@ -122,9 +1201,17 @@ impl Annotation {
             start,
             end,
             attributes,
+            line: None,
         }
     }
 
+    /// Sets the 1-based number of the line the annotation was loaded from,
+    /// see `line`.
+    pub fn with_line(mut self, line: Option<usize>) -> Self {
+        self.line = line;
+        self
+    }
+
     /// Returns identification of the scaffold on which this feature appears.
     pub fn scaffold(&self) -> &str {
         self.scaffold.as_str()
@@ -136,13 +1223,13 @@ impl Annotation {
         self.source.as_str()
     }
     /// Returns type of the annotated feature.
-    pub fn feature(&self) -> Feature {
-        self.feature
+    pub fn feature(&self) -> &Feature {
+        &self.feature
     }
 
     /// Returns feature quality or confidence.
     #[allow(dead_code)]
-    pub fn score(&self) -> Option<u32> {
+    pub fn score(&self) -> Option<f64> {
         self.score
     }
 
@@ -175,9 +1262,1101 @@ impl Annotation {
         self.end
     }
 
-    /// Attributes of the annotation. Note that the value is take as is and
-    /// needs to be further parsed.
+    /// Number of symbols covered by the annotation.
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the annotations are on the same scaffold and share
+    /// at least one position. Annotations merely abutting, i.e. one ending
+    /// where the other starts, and empty annotations overlap nothing.
+    /// Strands are not considered.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.overlap_len(other) > 0
+    }
+
+    /// Number of positions shared by the annotations, zero on different
+    /// scaffolds.
+    pub fn overlap_len(&self, other: &Self) -> usize {
+        if self.scaffold != other.scaffold {
+            return 0;
+        }
+        self.end
+            .min(other.end)
+            .saturating_sub(self.start.max(other.start))
+    }
+
+    /// Returns true if `other` is on the same scaffold and lies within the
+    /// annotation, i.e. `self.start() <= other.start()` and `other.end() <=
+    /// self.end()`. Every annotation contains itself.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.scaffold == other.scaffold && self.start <= other.start && other.end <= self.end
+    }
+
+    /// Returns true if 0-based position `pos` is covered by the annotation.
+    pub fn contains_pos(&self, pos: usize) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Number of positions strictly between the annotations, i.e. 0 when
+    /// they overlap or abut and `other.start() - self.end()` if `other`
+    /// follows. `None` is returned for annotations on different scaffolds.
+    pub fn distance_to(&self, other: &Self) -> Option<usize> {
+        if self.scaffold != other.scaffold {
+            return None;
+        }
+        Some(
+            other
+                .start
+                .saturating_sub(self.end)
+                .max(self.start.saturating_sub(other.end)),
+        )
+    }
+
+    /// Attributes of the annotation. Note that the value is taken as is,
+    /// i.e. still percent encoded, and needs to be further parsed, see
+    /// `parsed_attributes`.
     pub fn attributes(&self) -> &str {
         self.attributes.as_str()
     }
+
+    /// 1-based number of the line of the file the annotation was loaded
+    /// from. `None` for annotations not loaded from a file. The line is
+    /// meant for diagnostics only, it is not written to files.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Source line of the annotation formatted for messages, e.g.
+    /// ` (line 12)`, or an empty string.
+    pub(crate) fn line_note(&self) -> String {
+        line_note(self.line)
+    }
+
+    /// Parses the attributes, see `Attributes`. The raw column is parsed on
+    /// every call.
+    pub fn parsed_attributes(&self) -> Attributes {
+        Attributes::parse(&self.attributes)
+    }
+
+    /// Returns the canonical sort key of the annotation, see `AnnotationKey`.
+    pub fn key(&self) -> AnnotationKey {
+        AnnotationKey {
+            scaffold: self.scaffold.clone(),
+            start: self.start,
+            end: self.end,
+            feature: self.feature.clone(),
+            strand: self.strand,
+        }
+    }
+
+    /// Compares annotations in the canonical order, see `AnnotationKey`.
+    /// Unlike comparing keys, this does not allocate.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        (
+            &self.scaffold,
+            self.start,
+            self.end,
+            &self.feature,
+            self.strand,
+        )
+            .cmp(&(
+                &other.scaffold,
+                other.start,
+                other.end,
+                &other.feature,
+                other.strand,
+            ))
+    }
+
+    /// Returns a copy of the annotation with coordinates replaced by
+    /// `[start, end)`.
+    pub(crate) fn with_range(&self, start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the annotation with attributes replaced.
+    pub(crate) fn with_attributes(&self, attributes: String) -> Self {
+        Self {
+            attributes,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the annotation with the scaffold name replaced.
+    pub(crate) fn with_scaffold(&self, scaffold: String) -> Self {
+        Self {
+            scaffold,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the annotation with source replaced.
+    pub(crate) fn with_source(&self, source: String) -> Self {
+        Self {
+            source,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the annotation with phase replaced.
+    pub(crate) fn with_phase(&self, phase: Option<Phase>) -> Self {
+        Self {
+            phase,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the annotation with feature type replaced.
+    pub(crate) fn with_feature(&self, feature: Feature) -> Self {
+        Self {
+            feature,
+            ..self.clone()
+        }
+    }
+}
+
+/// Formats an optional source line for messages, see
+/// `Annotation::line_note`.
+pub(crate) fn line_note(line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!(" (line {})", line),
+        None => String::new(),
+    }
+}
+
+/// Canonical ordering key of annotations, usable e.g. as a `BTreeMap` key.
+/// Annotations are ordered by scaffold name, start, end, feature type and
+/// strand, in that order.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct AnnotationKey {
+    scaffold: String,
+    start: usize,
+    end: usize,
+    feature: Feature,
+    strand: Strand,
+}
+
+impl AnnotationKey {
+    pub fn scaffold(&self) -> &str {
+        self.scaffold.as_str()
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn feature(&self) -> &Feature {
+        &self.feature
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+}
+
+/// Builder of annotations with named setters, e.g.
+/// `AnnotationBuilder::new("chr1", Feature::Exon).start(10).end(20).build()`.
+///
+/// Source defaults to `.`, strand to `Strand::Unstranded`, score and phase
+/// to none and attributes to empty. Start and end have to be set.
+#[derive(Clone, Debug)]
+pub struct AnnotationBuilder {
+    scaffold: String,
+    source: String,
+    feature: Feature,
+    score: Option<f64>,
+    strand: Strand,
+    phase: Option<Phase>,
+    start: Option<usize>,
+    end: Option<usize>,
+    attributes: String,
+    line: Option<usize>,
+}
+
+impl AnnotationBuilder {
+    pub fn new(scaffold: &str, feature: Feature) -> Self {
+        Self {
+            scaffold: String::from(scaffold),
+            source: String::from("."),
+            feature,
+            score: None,
+            strand: Strand::Unstranded,
+            phase: None,
+            start: None,
+            end: None,
+            attributes: String::new(),
+            line: None,
+        }
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = String::from(source);
+        self
+    }
+
+    pub fn score(mut self, score: f64) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    pub fn strand(mut self, strand: Strand) -> Self {
+        self.strand = strand;
+        self
+    }
+
+    pub fn phase(mut self, phase: Phase) -> Self {
+        self.phase = Some(phase);
+        self
+    }
+
+    /// Sets the 0-based inclusive start.
+    pub fn start(mut self, start: usize) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Sets the 0-based exclusive end.
+    pub fn end(mut self, end: usize) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Sets the raw attribute column, see `Attributes` and its `Display`
+    /// for composing it.
+    pub fn attributes(mut self, attributes: &str) -> Self {
+        self.attributes = String::from(attributes);
+        self
+    }
+
+    /// Sets the 1-based source line number, see `Annotation::line`.
+    pub fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Builds the annotation, failing if start or end is not set or if the
+    /// annotation is empty, i.e. start is not smaller than end, like
+    /// annotations loaded from GFF files.
+    pub fn build(self) -> Result<Annotation> {
+        let start = self.start.context("Annotation start is not set.")?;
+        let end = self.end.context("Annotation end is not set.")?;
+        ensure!(
+            start < end,
+            "Feature start index is greater or equal to end index. {} >= {}",
+            start,
+            end
+        );
+        if let Some(score) = self.score {
+            ensure!(score.is_finite(), "Score is not finite. Got: {}", score);
+        }
+
+        Ok(Annotation::new(
+            self.scaffold,
+            self.source,
+            self.feature,
+            self.score,
+            self.strand,
+            self.phase,
+            start,
+            end,
+            self.attributes,
+        )
+        .with_line(self.line))
+    }
+}
+
+/// Stably sorts annotations in the canonical order, see `AnnotationKey`.
+pub fn sort_annotations(annotations: &mut [Annotation]) {
+    annotations.sort_by(Annotation::cmp_canonical);
+}
+
+/// Checks whether annotations are sorted in the canonical order, see
+/// `AnnotationKey`.
+pub fn is_sorted(annotations: &[Annotation]) -> bool {
+    annotations
+        .windows(2)
+        .all(|pair| pair[0].cmp_canonical(&pair[1]) != Ordering::Greater)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        Alphabet, Annotation, AnnotationBuilder, AnnotationKey, Attributes, Feature,
+        PackedSequence, Phase, RangeError, Scaffold, Strand, Symbol,
+    };
+    use crate::testing;
+    use std::borrow::Cow;
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_symbols_to_string() {
+        let seq = [
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Thymine,
+            Symbol::Other,
+        ];
+        assert_eq!(super::symbols_to_string(&seq, Alphabet::Dna), "ACGTN");
+        assert_eq!(super::symbols_to_string(&seq, Alphabet::Rna), "ACGUN");
+        assert_eq!(super::symbols_to_string(&[], Alphabet::Rna), "");
+        assert_eq!(super::transcribe(&seq), "ACGUN");
+        assert_eq!(super::transcribe(&[]), "");
+    }
+
+    #[test]
+    fn test_packed_sequence() {
+        let symbols = [
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Thymine,
+            Symbol::Other,
+            Symbol::Other,
+            Symbol::Guanine,
+        ];
+        for len in 0..=symbols.len() {
+            let packed = PackedSequence::from(&symbols[..len]);
+            assert_eq!(packed.len(), len);
+            assert_eq!(packed.to_vec(), &symbols[..len]);
+            assert!(packed.iter().eq(symbols[..len].iter().cloned()));
+        }
+
+        let gaps = vec![Symbol::Other; 13];
+        let packed = PackedSequence::from(gaps.clone());
+        assert_eq!(packed.get(12), Symbol::Other);
+        assert_eq!(Vec::<Symbol>::from(packed), gaps);
+
+        let scaffold = Scaffold::new_packed(String::from("s"), PackedSequence::from(&symbols[..]));
+        assert_eq!(scaffold.len(), 7);
+        assert_eq!(scaffold.symbol(4), Symbol::Other);
+        assert!(scaffold.packed_sequence().is_some());
+        assert_eq!(scaffold.sequence(), &symbols[..]);
+    }
+
+    #[test]
+    fn test_circular_subsequence() {
+        let sequence: Vec<Symbol> = "AAAACCCCGGGGTTTTACGT"
+            .chars()
+            .map(|c| match c {
+                'A' => Symbol::Adenine,
+                'C' => Symbol::Cytosine,
+                'G' => Symbol::Guanine,
+                _ => Symbol::Thymine,
+            })
+            .collect();
+        let linear = Scaffold::new(String::from("s"), sequence.clone());
+        let circular = Scaffold::new(String::from("c"), sequence).with_circular(true);
+        assert!(!linear.is_circular());
+        assert!(circular.is_circular());
+
+        for scaffold in [&linear, &circular] {
+            let symbols = scaffold.subsequence_owned(14..18).unwrap();
+            assert_eq!(super::symbols_to_string(&symbols, Alphabet::Dna), "TTAC");
+        }
+        let symbols = circular.subsequence_owned(15..25).unwrap();
+        assert_eq!(
+            super::symbols_to_string(&symbols, Alphabet::Dna),
+            "TACGTAAAAC"
+        );
+        let symbols = circular.subsequence_owned(0..20).unwrap();
+        assert_eq!(symbols.len(), 20);
+
+        assert!(!linear.contains_range(&(15..25)));
+        assert_eq!(
+            linear.subsequence_owned(15..25).err().unwrap().to_string(),
+            "Range 15..25 exceeds linear scaffold s of length 20."
+        );
+        assert!(!circular.contains_range(&(20..22)));
+        assert!(!circular.contains_range(&(5..26)));
+        assert_eq!(
+            circular.subsequence_owned(5..26).err().unwrap().to_string(),
+            "Range 5..26 exceeds circular scaffold c of length 20."
+        );
+    }
+
+    #[test]
+    fn test_char_conversions() {
+        let symbols = [
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Thymine,
+            Symbol::Other,
+        ];
+        for &symbol in symbols.iter() {
+            let c = char::from(symbol);
+            assert_eq!(Symbol::try_from(c).unwrap(), symbol);
+            assert_eq!(Symbol::try_from(c.to_ascii_lowercase()).unwrap(), symbol);
+        }
+        assert_eq!(super::sequence_to_string(&symbols), "ACGTN");
+        assert_eq!(super::parse_sequence("ACGTN").unwrap(), symbols);
+        assert_eq!(super::parse_sequence("acgtn").unwrap(), symbols);
+        assert_eq!(super::parse_sequence("").unwrap(), []);
+
+        for c in ['R', '-', '*', 'U', ' ', 'é'] {
+            assert!(Symbol::try_from(c).is_err());
+        }
+        let error = super::parse_sequence("ACXT").err().unwrap();
+        assert_eq!(error.to_string(), "Invalid sequence at position 2.");
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Encountered invalid symbol X."
+        );
+    }
+
+    #[test]
+    fn test_subsequence() {
+        let scaffold = Scaffold::new(String::from("s"), super::parse_sequence("AACCGTN").unwrap());
+        let render = super::sequence_to_string;
+
+        assert_eq!(render(&scaffold.subsequence(1..4).unwrap()), "ACC");
+        assert_eq!(render(&scaffold.subsequence(0..7).unwrap()), "AACCGTN");
+        assert!(scaffold.subsequence(3..3).unwrap().is_empty());
+        assert!(scaffold.subsequence(7..7).unwrap().is_empty());
+        assert!(matches!(
+            scaffold.subsequence(1..4).unwrap(),
+            Cow::Borrowed(_)
+        ));
+        let packed = Scaffold::new_packed(
+            String::from("p"),
+            PackedSequence::from(super::parse_sequence("AACCGTN").unwrap()),
+        );
+        let subsequence = packed.subsequence(1..4).unwrap();
+        assert!(matches!(subsequence, Cow::Owned(ref owned) if owned.len() == 3));
+        assert_eq!(render(&subsequence), "ACC");
+        assert_eq!(render(&scaffold.subsequence_owned(1..4).unwrap()), "ACC");
+        assert_eq!(
+            render(&scaffold.region(1, 4, Strand::Positive).unwrap()),
+            "ACC"
+        );
+        assert_eq!(
+            render(&scaffold.region(1, 5, Strand::Negative).unwrap()),
+            "CGGT"
+        );
+
+        let error = scaffold.subsequence(5..8).err().unwrap();
+        assert_eq!(
+            error,
+            RangeError::OutOfBounds {
+                scaffold: String::from("s"),
+                start: 5,
+                end: 8,
+                len: 7,
+                circular: false,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Range 5..8 exceeds linear scaffold s of length 7."
+        );
+        assert!(matches!(
+            scaffold.subsequence(8..8),
+            Err(RangeError::OutOfBounds { start: 8, .. })
+        ));
+        let error = scaffold.region(4, 2, Strand::Negative).err().unwrap();
+        assert!(matches!(
+            error,
+            RangeError::Reversed {
+                start: 4,
+                end: 2,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Range 4..2 of scaffold s starts after its end."
+        );
+
+        // Only owned subsequences wrap around the origin.
+        let circular = scaffold.with_circular(true);
+        assert!(circular.subsequence(5..9).is_err());
+        assert_eq!(render(&circular.subsequence_owned(5..9).unwrap()), "TNAA");
+        assert_eq!(
+            render(&circular.region(5, 9, Strand::Negative).unwrap()),
+            "TTNA"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde::de::value::{CharDeserializer, Error, StrDeserializer};
+        use serde::de::{Deserialize, IntoDeserializer};
+
+        for c in ['A', 'c', 'G', 't', 'N'] {
+            let deserializer: CharDeserializer<Error> = c.into_deserializer();
+            let symbol = Symbol::deserialize(deserializer).unwrap();
+            assert_eq!(symbol, Symbol::try_from(c).unwrap());
+        }
+        let deserializer: CharDeserializer<Error> = 'X'.into_deserializer();
+        assert_eq!(
+            Symbol::deserialize(deserializer).err().unwrap().to_string(),
+            "Encountered invalid symbol X."
+        );
+
+        let deserializer: StrDeserializer<Error> = "Negative".into_deserializer();
+        assert_eq!(Strand::deserialize(deserializer).unwrap(), Strand::Negative);
+        let deserializer: StrDeserializer<Error> = "Exon".into_deserializer();
+        assert_eq!(Feature::deserialize(deserializer).unwrap(), Feature::Exon);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let scaffolds = vec![
+            Scaffold::new(
+                String::from("chr1"),
+                super::parse_sequence("ACGTNNacgt").unwrap(),
+            )
+            .with_description(Some(String::from("chromosome 1")))
+            .with_masked_regions(vec![0..2, 6..10]),
+            Scaffold::new_packed(
+                String::from("chrM"),
+                PackedSequence::from(super::parse_sequence("GGNNTTA").unwrap()),
+            )
+            .with_circular(true),
+            Scaffold::new(String::from("empty"), Vec::new()),
+        ];
+        let annotations = vec![
+            AnnotationBuilder::new("chr1", Feature::CDS)
+                .source("maker")
+                .score(0.5)
+                .strand(Strand::Negative)
+                .phase(Phase::Two)
+                .start(1)
+                .end(9)
+                .attributes("ID=cds1;Parent=mrna1")
+                .build()
+                .unwrap(),
+            AnnotationBuilder::new("chrM", Feature::Other(String::from("D_loop")))
+                .start(0)
+                .end(7)
+                .build()
+                .unwrap(),
+        ];
+
+        let json = serde_json::to_string(&scaffolds).unwrap();
+        // Symbols are single characters, not variant names.
+        assert!(json.contains("\"ACGTNNACGT\""));
+        assert!(!json.contains("Adenine"));
+        assert_eq!(
+            serde_json::from_str::<Vec<Scaffold>>(&json).unwrap(),
+            scaffolds
+        );
+        let json = serde_json::to_string(&annotations).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<Annotation>>(&json).unwrap(),
+            annotations
+        );
+        let symbols = super::parse_sequence("ACGTN").unwrap();
+        assert_eq!(
+            serde_json::to_string(&symbols).unwrap(),
+            r#"["A","C","G","T","N"]"#
+        );
+
+        let bytes = bincode::serialize(&scaffolds).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Vec<Scaffold>>(&bytes).unwrap(),
+            scaffolds
+        );
+        let bytes = bincode::serialize(&annotations).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Vec<Annotation>>(&bytes).unwrap(),
+            annotations
+        );
+        let bytes = bincode::serialize(&symbols).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Vec<Symbol>>(&bytes).unwrap(),
+            symbols
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_serde_invalid_mask() {
+        let scaffold = |masked: &str| {
+            let json = format!(
+                r#"{{"name":"s","sequence":"ACGTA","description":null,"masked":{},"circular":false}}"#,
+                masked
+            );
+            serde_json::from_str::<Scaffold>(&json)
+        };
+        assert_eq!(
+            scaffold(r#"[{"start":1,"end":3}]"#)
+                .unwrap()
+                .masked_regions(),
+            [1..3]
+        );
+        for (masked, message) in [
+            (
+                r#"[{"start":3,"end":9}]"#,
+                "Masked region 3..9 exceeds scaffold s of length 5.",
+            ),
+            (
+                r#"[{"start":2,"end":2}]"#,
+                "Masked region 2..2 of scaffold s is empty.",
+            ),
+            (
+                r#"[{"start":2,"end":4},{"start":0,"end":1}]"#,
+                "Masked region 0..1 of scaffold s is not sorted or overlaps.",
+            ),
+        ] {
+            let error = scaffold(masked).err().unwrap().to_string();
+            assert!(error.starts_with(message), "{}", error);
+        }
+    }
+
+    fn on(scaffold: &str, start: usize, end: usize) -> Annotation {
+        AnnotationBuilder::new(scaffold, Feature::Exon)
+            .start(start)
+            .end(end)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_annotation_geometry() {
+        let a = on("s", 10, 20);
+        assert_eq!(a.len(), 10);
+        assert!(!a.is_empty());
+        assert!(!a.contains_pos(9));
+        assert!(a.contains_pos(10));
+        assert!(a.contains_pos(19));
+        assert!(!a.contains_pos(20));
+
+        // (other, overlaps, overlap_len, contains, distance)
+        let cases = [
+            (on("s", 0, 9), false, 0, false, 1),
+            (on("s", 0, 10), false, 0, false, 0),
+            (on("s", 0, 11), true, 1, false, 0),
+            (on("s", 10, 20), true, 10, true, 0),
+            (on("s", 10, 11), true, 1, true, 0),
+            (on("s", 19, 20), true, 1, true, 0),
+            (on("s", 19, 21), true, 1, false, 0),
+            (on("s", 5, 25), true, 10, false, 0),
+            (on("s", 20, 21), false, 0, false, 0),
+            (on("s", 21, 30), false, 0, false, 1),
+            (on("s", 25, 30), false, 0, false, 5),
+        ];
+        for (other, overlaps, overlap_len, contains, distance) in cases.iter() {
+            assert_eq!(a.overlaps(other), *overlaps, "{:?}", other);
+            assert_eq!(other.overlaps(&a), *overlaps, "{:?}", other);
+            assert_eq!(a.overlap_len(other), *overlap_len, "{:?}", other);
+            assert_eq!(a.contains(other), *contains, "{:?}", other);
+            assert_eq!(a.distance_to(other), Some(*distance), "{:?}", other);
+            assert_eq!(other.distance_to(&a), Some(*distance), "{:?}", other);
+        }
+        assert!(on("s", 5, 25).contains(&a));
+
+        let t = on("t", 10, 20);
+        assert!(!a.overlaps(&t));
+        assert_eq!(a.overlap_len(&t), 0);
+        assert!(!a.contains(&t));
+        assert_eq!(a.distance_to(&t), None);
+    }
+
+    #[test]
+    fn test_annotation_geometry_exhaustive() {
+        let intervals: Vec<(usize, usize)> = (0..8)
+            .flat_map(|start| (start..8).map(move |end| (start, end)))
+            .collect();
+        let annotation =
+            |(start, end)| testing::annotation("s", Feature::Exon, Strand::Positive, start, end);
+        for &(a_start, a_end) in intervals.iter() {
+            let a = annotation((a_start, a_end));
+            assert_eq!(a.len(), (a_start..a_end).count());
+            for pos in 0..10 {
+                assert_eq!(a.contains_pos(pos), (a_start..a_end).contains(&pos));
+            }
+
+            for &(b_start, b_end) in intervals.iter() {
+                let b = annotation((b_start, b_end));
+                let shared = (a_start..a_end).filter(|p| (b_start..b_end).contains(p));
+                assert_eq!(a.overlap_len(&b), shared.clone().count());
+                assert_eq!(a.overlaps(&b), shared.count() > 0);
+                assert_eq!(a.contains(&b), a_start <= b_start && b_end <= a_end);
+
+                let between = (0..10)
+                    .filter(|p| (a_end..b_start).contains(p) || (b_end..a_start).contains(p))
+                    .count();
+                assert_eq!(a.distance_to(&b), Some(between));
+            }
+        }
+    }
+
+    #[test]
+    fn test_annotation_builder() {
+        let annotation = AnnotationBuilder::new("chr1", Feature::Exon)
+            .start(10)
+            .end(20)
+            .build()
+            .unwrap();
+        assert_eq!(
+            annotation,
+            Annotation::new(
+                String::from("chr1"),
+                String::from("."),
+                Feature::Exon,
+                None,
+                Strand::Unstranded,
+                None,
+                10,
+                20,
+                String::new(),
+            )
+        );
+        assert_eq!(annotation.line(), None);
+
+        let annotation = AnnotationBuilder::new("chr1", Feature::CDS)
+            .source("JGI")
+            .score(0.5)
+            .strand(Strand::Negative)
+            .phase(Phase::Two)
+            .end(20)
+            .start(10)
+            .attributes("Parent=t1")
+            .line(7)
+            .build()
+            .unwrap();
+        assert_eq!(annotation.source(), "JGI");
+        assert_eq!(annotation.score(), Some(0.5));
+        assert_eq!(annotation.strand(), Strand::Negative);
+        assert_eq!(annotation.phase(), Some(Phase::Two));
+        assert_eq!((annotation.start(), annotation.end()), (10, 20));
+        assert_eq!(annotation.attributes(), "Parent=t1");
+        assert_eq!(annotation.line(), Some(7));
+
+        let builder = AnnotationBuilder::new("chr1", Feature::Exon);
+        let error = builder.clone().start(20).end(10).build().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Feature start index is greater or equal to end index. 20 >= 10"
+        );
+        assert!(builder.clone().start(10).end(10).build().is_err());
+        let error = builder.clone().end(10).build().err().unwrap();
+        assert_eq!(error.to_string(), "Annotation start is not set.");
+        let error = builder.clone().start(10).build().err().unwrap();
+        assert_eq!(error.to_string(), "Annotation end is not set.");
+        let error = builder.start(0).end(1).score(f64::NAN).build();
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn test_symbol_codes() {
+        // Trained models depend on these codes.
+        let codes = [
+            (Symbol::Adenine, 0),
+            (Symbol::Thymine, 1),
+            (Symbol::Cytosine, 2),
+            (Symbol::Guanine, 3),
+            (Symbol::Other, 4),
+        ];
+        for &(symbol, code) in codes.iter() {
+            assert_eq!(u8::from(symbol), code);
+            assert_eq!(i32::from(symbol), code as i32);
+            assert_eq!(usize::from(symbol), code as usize);
+            let into: u8 = symbol.into();
+            assert_eq!(into, code);
+            assert_eq!(Symbol::try_from(code).unwrap(), symbol);
+        }
+        assert_eq!(
+            Symbol::try_from(5u8).err().unwrap().to_string(),
+            "Invalid symbol code 5."
+        );
+
+        let symbols = super::decode_symbols(&[0, 1, 2, 3, 4]).unwrap();
+        assert_eq!(super::sequence_to_string(&symbols), "ATCGN");
+        assert_eq!(super::decode_symbols(&[]).unwrap(), []);
+        let error = super::decode_symbols(&[0, 3, 255, 9]).err().unwrap();
+        assert_eq!(error.to_string(), "Failed to decode symbol 2.");
+        assert_eq!(error.root_cause().to_string(), "Invalid symbol code 255.");
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_hard_mask() {
+        let parse = |text| super::parse_sequence(text).unwrap();
+        let render = super::sequence_to_string;
+
+        let scaffold = Scaffold::new(String::from("s"), parse("ACGTACGTAC"))
+            .with_masked_regions(vec![1..3, 6..7, 9..10]);
+        assert_eq!(scaffold.masked_len(), 4);
+
+        let masked = scaffold.hard_masked();
+        assert_eq!(render(&masked.sequence()), "ANNTACNTAN");
+        assert_eq!(masked.masked_regions(), scaffold.masked_regions());
+        // The original is untouched.
+        assert_eq!(render(&scaffold.sequence()), "ACGTACGTAC");
+
+        let mut packed =
+            Scaffold::new_packed(String::from("p"), PackedSequence::from(parse("ACGTACGTAC")))
+                .with_masked_regions(vec![0..4]);
+        assert_eq!(render(&packed.sequence()), "ACGTACGTAC");
+        packed.hard_mask_in_place();
+        assert!(packed.packed_sequence().is_some());
+        assert_eq!(render(&packed.sequence()), "NNNNACGTAC");
+        packed.clear_mask();
+        assert!(packed.masked_regions().is_empty());
+        assert_eq!(packed.masked_len(), 0);
+
+        let unmasked = Scaffold::new(String::from("u"), parse("ACNGT"));
+        assert_eq!(unmasked.hard_masked(), unmasked);
+
+        // Ranges are normalized and clamped to the sequence.
+        #[allow(clippy::reversed_empty_ranges)]
+        let scaffold = Scaffold::new(String::from("s"), parse("ACGTACGTAC"))
+            .with_masked_regions(vec![5..20, 1..2, 2..3, 4..4, 7..3, 30..40]);
+        assert_eq!(scaffold.masked_regions(), [1..3, 5..10]);
+        assert_eq!(render(&scaffold.hard_masked().sequence()), "ANNTANNNNN");
+
+        let mut window = parse("GTACGT");
+        super::hard_mask(&mut window, 2, &[1..3, 4..5, 7..20]);
+        assert_eq!(render(&window), "NTNCGN");
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_gap_runs() {
+        let cases = [
+            ("NNACGNTNNNAN", 1, vec![0..2, 5..6, 7..10, 11..12]),
+            ("NNACGNTNNNAN", 2, vec![0..2, 7..10]),
+            ("NNACGNTNNNAN", 3, vec![7..10]),
+            ("NNACGNTNNNAN", 0, vec![0..2, 5..6, 7..10, 11..12]),
+            ("NNNN", 4, vec![0..4]),
+            ("NNNN", 5, vec![]),
+            ("ACGT", 1, vec![]),
+            ("", 1, vec![]),
+        ];
+        for (text, min_len, expected) in cases.iter() {
+            let sequence = super::parse_sequence(text).unwrap();
+            let plain = Scaffold::new(String::from("s"), sequence.clone());
+            assert_eq!(&plain.gap_runs(*min_len), expected);
+            let packed = Scaffold::new_packed(String::from("s"), PackedSequence::from(sequence));
+            assert_eq!(&packed.gap_runs(*min_len), expected);
+        }
+    }
+
+    #[test]
+    fn test_scaffold_formatting() {
+        let short = Scaffold::new(String::from("s"), super::parse_sequence("ACGTN").unwrap());
+        assert_eq!(short.to_string(), ">s\nACGTN");
+        assert_eq!(format!("{:.5}", short), ">s\nACGTN");
+        assert_eq!(format!("{:.3}", short), ">s\nACG...[2 more]");
+        assert_eq!(
+            format!("{:?}", short),
+            "Scaffold { name: \"s\", len: 5, composition: \"A=1 C=1 G=1 T=1 N=1\", \
+             first: \"ACGTN\", last: \"ACGTN\" }"
+        );
+
+        let long = Scaffold::new(
+            String::from("l"),
+            super::parse_sequence(&"AACCGGTTAC".repeat(15)).unwrap(),
+        );
+        let text = long.to_string();
+        assert!(text.starts_with(">l\nAACCGGTTAC"));
+        assert!(text.ends_with("...[50 more]"));
+        assert!(!format!("{:.150}", long).contains("more"));
+        assert_eq!(
+            format!("{:?}", long),
+            "Scaffold { name: \"l\", len: 150, composition: \"A=45 C=45 G=30 T=30 N=0\", \
+             first: \"AACCGGTTAC\", last: \"AACCGGTTAC\" }"
+        );
+    }
+
+    #[test]
+    fn test_scaffold_eq() {
+        let sequence = super::parse_sequence("ACGTNNAC").unwrap();
+        let plain = Scaffold::new(String::from("s"), sequence.clone());
+        let packed = Scaffold::new_packed(String::from("s"), PackedSequence::from(&sequence[..]));
+        assert_eq!(plain, packed);
+        assert_eq!(plain.clone(), plain);
+        assert_eq!(packed.clone(), packed);
+        assert_ne!(plain.clone().with_circular(true), plain);
+        assert_ne!(plain.clone().with_name(String::from("t")), plain);
+        assert_ne!(
+            plain.reverse_complement().with_name(String::from("s")),
+            plain
+        );
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        let parse = |text: &str| super::parse_sequence(text).unwrap();
+        let render = super::sequence_to_string;
+
+        assert_eq!(Symbol::Adenine.complement(), Symbol::Thymine);
+        assert_eq!(Symbol::Guanine.complement(), Symbol::Cytosine);
+        assert_eq!(Symbol::Other.complement(), Symbol::Other);
+
+        for (text, expected) in [
+            ("GAATTC", "GAATTC"),
+            ("ACNTG", "CANGT"),
+            ("AACGTTNN", "NNAACGTT"),
+            ("C", "G"),
+            ("", ""),
+        ] {
+            let mut seq = parse(text);
+            assert_eq!(render(&super::reverse_complement(&seq)), expected);
+            super::reverse_complement_in_place(&mut seq);
+            assert_eq!(render(&seq), expected);
+        }
+
+        let scaffold = Scaffold::new(String::from("s"), parse("AACCGTN"))
+            .with_description(Some(String::from("chromosome")))
+            .with_masked_regions(vec![0..2, 3..4]);
+        let reversed = scaffold.reverse_complement();
+        assert_eq!(reversed.name(), "s_rc");
+        assert_eq!(reversed.description(), Some("chromosome"));
+        assert_eq!(render(&reversed.sequence()), "NACGGTT");
+        assert_eq!(reversed.masked_regions(), [3..4, 5..7]);
+
+        let packed = Scaffold::new_packed(String::from("p"), PackedSequence::from(parse("ACNTG")));
+        let reversed = packed.reverse_complement();
+        assert!(reversed.packed_sequence().is_some());
+        assert_eq!(render(&reversed.sequence()), "CANGT");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_packed_sequence_out_of_bounds() {
+        PackedSequence::from(vec![Symbol::Adenine; 4]).get(4);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let gff3 = Attributes::parse("ID=c1;Parent=t1,t2;Name=ABC%3B1;Note=50%25 %G1;");
+        assert_eq!(gff3.len(), 4);
+        assert_eq!(gff3.get("ID"), Some("c1"));
+        assert_eq!(gff3.get("Parent"), Some("t1"));
+        assert_eq!(gff3.get_all("Parent"), &["t1", "t2"]);
+        assert_eq!(gff3.get("Name"), Some("ABC;1"));
+        assert_eq!(gff3.get("Note"), Some("50% %G1"));
+        assert_eq!(gff3.get("Missing"), None);
+        assert!(gff3.get_all("Missing").is_empty());
+        let keys: Vec<&str> = gff3.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["ID", "Parent", "Name", "Note"]);
+
+        let gtf = Attributes::parse(
+            "name \"fgenesh1_kg.1_#_1;x\"; proteinId 416053; tag \"a\"; tag \"b\"; exonNumber 1",
+        );
+        assert_eq!(gtf.get("name"), Some("fgenesh1_kg.1_#_1;x"));
+        assert_eq!(gtf.get("proteinId"), Some("416053"));
+        assert_eq!(gtf.get_all("tag"), &["a", "b"]);
+        assert_eq!(gtf.get("exonNumber"), Some("1"));
+
+        assert!(Attributes::parse(".").is_empty());
+        assert!(Attributes::parse("").is_empty());
+
+        let strict = Attributes::parse_gtf(
+            "gene_id \"g1\"; transcript_id \"t;1\"; exon_number 2; tag \"basic\"; tag \"CCDS\";",
+        )
+        .unwrap();
+        assert_eq!(strict.get("gene_id"), Some("g1"));
+        assert_eq!(strict.get("transcript_id"), Some("t;1"));
+        assert_eq!(strict.get("exon_number"), Some("2"));
+        assert_eq!(strict.get_all("tag"), &["basic", "CCDS"]);
+        assert!(Attributes::parse_gtf(".").unwrap().is_empty());
+        for invalid in [
+            "gene_id \"g1\"; ID=t1",
+            "gene_id",
+            "gene_id \"g1",
+            "gene_id g 1",
+        ] {
+            assert!(Attributes::parse_gtf(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_percent_encoding() {
+        for (encoded, decoded) in [
+            ("ABC%3B1", "ABC;1"),
+            ("a%2Cb%3Dc%26d", "a,b=c&d"),
+            ("tab%09new%0Aline", "tab\tnew\nline"),
+            ("50%25", "50%"),
+            ("%3b", ";"),
+            ("%G1%+1%4", "%G1%+1%4"),
+        ] {
+            assert_eq!(super::percent_decode(encoded), decoded);
+        }
+        for value in [
+            "ABC;1",
+            "a,b=c&d",
+            "tab\tnew\nline",
+            "100% %G1",
+            "plain text",
+        ] {
+            assert_eq!(super::percent_decode(&super::percent_encode(value)), value);
+        }
+        assert_eq!(super::percent_encode("50% a;b"), "50%25 a%3Bb");
+
+        let raw = "ID=g1;Name=ABC%3B1;Note=50%25 GC%09x,%G1";
+        let attributes = Attributes::parse(raw);
+        assert_eq!(attributes.get_all("Note"), &["50% GC\tx", "%G1"]);
+        let formatted = attributes.to_string();
+        assert_eq!(formatted, "ID=g1;Name=ABC%3B1;Note=50%25 GC%09x,%25G1");
+        assert_eq!(Attributes::parse(&formatted), attributes);
+        assert_eq!(Attributes::parse(".").to_string(), ".");
+    }
+
+    #[test]
+    fn test_sort_annotations() {
+        let annotation = testing::annotation;
+        let mut annotations = vec![
+            annotation("s2", Feature::Exon, Strand::Positive, 0, 10),
+            annotation("s1", Feature::CDS, Strand::Positive, 5, 20),
+            annotation("s1", Feature::Exon, Strand::Negative, 5, 20),
+            annotation("s1", Feature::Exon, Strand::Positive, 5, 20),
+            annotation("s1", Feature::Gene, Strand::Positive, 5, 30),
+            annotation("s1", Feature::Exon, Strand::Positive, 5, 10),
+            annotation("s1", Feature::Exon, Strand::Positive, 1, 40),
+        ];
+        assert!(!super::is_sorted(&annotations));
+
+        let mut by_key = BTreeMap::new();
+        for (i, a) in annotations.iter().enumerate() {
+            by_key.insert(a.key(), i);
+        }
+
+        super::sort_annotations(&mut annotations);
+        assert!(super::is_sorted(&annotations));
+        let order: Vec<(usize, usize, &Feature, Strand)> = annotations
+            .iter()
+            .map(|a| (a.start(), a.end(), a.feature(), a.strand()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1, 40, &Feature::Exon, Strand::Positive),
+                (5, 10, &Feature::Exon, Strand::Positive),
+                (5, 20, &Feature::Exon, Strand::Positive),
+                (5, 20, &Feature::Exon, Strand::Negative),
+                (5, 20, &Feature::CDS, Strand::Positive),
+                (5, 30, &Feature::Gene, Strand::Positive),
+                (0, 10, &Feature::Exon, Strand::Positive),
+            ]
+        );
+
+        let keys: Vec<AnnotationKey> = by_key.keys().cloned().collect();
+        let sorted: Vec<AnnotationKey> = annotations.iter().map(Annotation::key).collect();
+        assert_eq!(keys, sorted);
+        assert_eq!(keys[6].scaffold(), "s2");
+        assert!(super::is_sorted(&[]));
+    }
 }
@@ -0,0 +1,226 @@
+use crate::data::{is_sorted, sort_annotations, Annotation};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Which annotations `dedup_annotations` considers redundant. Annotations
+/// on different scaffolds, strands or of different features are never
+/// redundant.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DedupPolicy {
+    /// Annotations equal in all columns, e.g. repeated lines.
+    Identical,
+    /// Annotations with equal coordinates, regardless of source, score,
+    /// phase and attributes.
+    #[default]
+    SameCoordinates,
+    /// Annotations with equal coordinates or lying within another
+    /// annotation.
+    Contained,
+}
+
+/// Summary of annotations removed by `dedup_annotations`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DedupReport {
+    removed: usize,
+    by_source: HashMap<String, usize>,
+}
+
+impl DedupReport {
+    /// Total number of removed annotations.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Number of removed annotations per source.
+    pub fn by_source(&self) -> &HashMap<String, usize> {
+        &self.by_source
+    }
+}
+
+/// Removes redundant annotations according to `policy`, keeping the first
+/// occurrence of each. Annotations are left sorted in the canonical order,
+/// see `sort_annotations`, which is stable so "first" refers to the order
+/// before sorting.
+///
+/// Under `DedupPolicy::Contained` the containing annotation is kept even if
+/// it comes after the annotations it contains, so that no covered bases are
+/// lost. Only among annotations with equal coordinates the first one wins.
+pub fn dedup_annotations(annotations: &mut Vec<Annotation>, policy: DedupPolicy) -> DedupReport {
+    if !is_sorted(annotations) {
+        sort_annotations(annotations);
+    }
+
+    // Containing annotations precede those they contain. The sort is stable
+    // so equal annotations stay in their original order.
+    let mut order: Vec<usize> = (0..annotations.len()).collect();
+    order.sort_by_key(|&i| {
+        let a = &annotations[i];
+        (
+            a.scaffold(),
+            a.feature(),
+            a.strand(),
+            a.start(),
+            Reverse(a.end()),
+        )
+    });
+
+    let mut removed = vec![false; annotations.len()];
+    // Annotations kept in the current group of equal scaffold, feature and
+    // strand with the same coordinates as the last one.
+    let mut kept: Vec<usize> = Vec::new();
+    let mut max_end = 0;
+    for &i in order.iter() {
+        let annotation = &annotations[i];
+        let same_group = kept.last().is_some_and(|&k| {
+            let last = &annotations[k];
+            last.scaffold() == annotation.scaffold()
+                && last.feature() == annotation.feature()
+                && last.strand() == annotation.strand()
+        });
+        if !same_group {
+            kept.clear();
+            max_end = 0;
+        }
+        let same_coordinates = kept.last().is_some_and(|&k| {
+            annotations[k].start() == annotation.start() && annotations[k].end() == annotation.end()
+        });
+
+        removed[i] = match policy {
+            DedupPolicy::Identical => {
+                same_coordinates && kept.iter().any(|&k| identical(&annotations[k], annotation))
+            }
+            DedupPolicy::SameCoordinates => same_coordinates,
+            DedupPolicy::Contained => same_group && annotation.end() <= max_end,
+        };
+        if !removed[i] {
+            if !same_coordinates {
+                kept.clear();
+            }
+            kept.push(i);
+            max_end = max_end.max(annotation.end());
+        }
+    }
+
+    let mut report = DedupReport::default();
+    let mut index = 0;
+    annotations.retain(|annotation| {
+        let keep = !removed[index];
+        index += 1;
+        if !keep {
+            report.removed += 1;
+            *report
+                .by_source
+                .entry(String::from(annotation.source()))
+                .or_insert(0) += 1;
+        }
+        keep
+    });
+    report
+}
+
+/// Checks whether two annotations are equal in all GFF columns, i.e.
+/// ignoring source line numbers.
+fn identical(a: &Annotation, b: &Annotation) -> bool {
+    a.cmp_canonical(b).is_eq()
+        && a.source() == b.source()
+        && a.score() == b.score()
+        && a.phase() == b.phase()
+        && a.attributes() == b.attributes()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{DedupPolicy, DedupReport};
+    use crate::data::{Annotation, Feature, Strand};
+    use crate::testing;
+
+    fn annotation(
+        scaffold: &str,
+        source: &str,
+        strand: Strand,
+        start: usize,
+        end: usize,
+        attributes: &str,
+    ) -> Annotation {
+        testing::builder(scaffold, Feature::Exon, start, end)
+            .source(source)
+            .strand(strand)
+            .attributes(attributes)
+            .build()
+            .unwrap()
+    }
+
+    fn dedup(annotations: &[Annotation], policy: DedupPolicy) -> (Vec<String>, DedupReport) {
+        let mut annotations = annotations.to_vec();
+        let report = super::dedup_annotations(&mut annotations, policy);
+        let kept = annotations
+            .iter()
+            .map(|a| String::from(a.attributes()))
+            .collect();
+        (kept, report)
+    }
+
+    #[test]
+    fn test_dedup_annotations() {
+        let annotations = vec![
+            annotation("s", "A", Strand::Positive, 50, 60, "ID=e6"),
+            annotation("s", "A", Strand::Positive, 10, 20, "ID=e1"),
+            // Repeated line.
+            annotation("s", "A", Strand::Positive, 10, 20, "ID=e1"),
+            // Differs only in attributes.
+            annotation("s", "B", Strand::Positive, 10, 20, "ID=e2"),
+            // Contained.
+            annotation("s", "B", Strand::Positive, 12, 20, "ID=e3"),
+            annotation("s", "B", Strand::Positive, 5, 30, "ID=e4"),
+            // Other strand and other scaffold are never merged.
+            annotation("s", "A", Strand::Negative, 10, 20, "ID=e5"),
+            annotation("t", "A", Strand::Positive, 10, 20, "ID=e7"),
+            annotation("t", "A", Strand::Positive, 10, 20, "ID=e7"),
+        ];
+
+        let (kept, report) = dedup(&annotations, DedupPolicy::Identical);
+        assert_eq!(
+            kept,
+            ["ID=e4", "ID=e1", "ID=e2", "ID=e5", "ID=e3", "ID=e6", "ID=e7"]
+        );
+        assert_eq!(report.removed(), 2);
+        assert_eq!(report.by_source()["A"], 2);
+
+        let (kept, report) = dedup(&annotations, DedupPolicy::SameCoordinates);
+        assert_eq!(kept, ["ID=e4", "ID=e1", "ID=e5", "ID=e3", "ID=e6", "ID=e7"]);
+        assert_eq!(report.removed(), 3);
+        assert_eq!(report.by_source()["A"], 2);
+        assert_eq!(report.by_source()["B"], 1);
+
+        let (kept, report) = dedup(&annotations, DedupPolicy::Contained);
+        assert_eq!(kept, ["ID=e4", "ID=e5", "ID=e6", "ID=e7"]);
+        assert_eq!(report.removed(), 5);
+        assert_eq!(report.by_source()["A"], 3);
+        assert_eq!(report.by_source()["B"], 2);
+    }
+
+    #[test]
+    fn test_dedup_attributes_only() {
+        let annotations = vec![
+            annotation("s", "A", Strand::Positive, 12, 18, "ID=e1"),
+            annotation("s", "A", Strand::Positive, 10, 20, "ID=e2;Note=first"),
+            annotation("s", "A", Strand::Positive, 10, 20, "ID=e2;Note=second"),
+        ];
+
+        let (kept, report) = dedup(&annotations, DedupPolicy::Identical);
+        assert_eq!(kept, ["ID=e2;Note=first", "ID=e2;Note=second", "ID=e1"]);
+        assert_eq!(report.removed(), 0);
+
+        let (kept, report) = dedup(&annotations, DedupPolicy::SameCoordinates);
+        assert_eq!(kept, ["ID=e2;Note=first", "ID=e1"]);
+        assert_eq!(report.removed(), 1);
+
+        // The container is kept although the contained annotation precedes
+        // it.
+        let (kept, report) = dedup(&annotations, DedupPolicy::Contained);
+        assert_eq!(kept, ["ID=e2;Note=first"]);
+        assert_eq!(report.removed(), 2);
+        assert_eq!(report.by_source()["A"], 2);
+    }
+}
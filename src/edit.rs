@@ -0,0 +1,300 @@
+use crate::data::{percent_decode, percent_encode, Annotation, Feature};
+
+/// Single editing operation. Attribute keys are matched exactly after
+/// trimming surrounding white space, values are given and compared
+/// unescaped.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EditOperation {
+    /// Renames attribute key `from` to `to`, keeping the value.
+    RenameKey { from: String, to: String },
+    /// Sets value of attribute `key`, adding the attribute if it is missing.
+    SetValue { key: String, value: String },
+    /// Removes all attributes with the key.
+    DeleteKey(String),
+    /// Replaces all occurrences of `from` with `to` in each value of
+    /// attribute `key`.
+    ReplaceInValue {
+        key: String,
+        from: String,
+        to: String,
+    },
+    /// Prepends `prefix` to each value of attribute `key`, e.g. to all
+    /// comma separated parents in `Parent`.
+    PrefixValue { key: String, prefix: String },
+    /// Replaces source `from` with `to`.
+    MapSource { from: String, to: String },
+    /// Replaces feature type `from` with `to`.
+    MapFeature { from: Feature, to: Feature },
+}
+
+/// Number of annotations modified by each operation, in the order of the
+/// operations.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct EditReport {
+    touched: Vec<usize>,
+}
+
+impl EditReport {
+    pub fn touched(&self) -> &[usize] {
+        &self.touched
+    }
+}
+
+/// Applies a list of operations to GFF3 annotations, in order.
+///
+/// Attributes are parsed as `;` separated `key=value` pairs with comma
+/// separated multiple values. Values written by the editor are percent
+/// encoded where GFF3 requires it so the output remains valid. Attributes
+/// not touched by any operation, including entries which are not `key=value`
+/// pairs, are kept verbatim.
+#[derive(Clone, Debug, Default)]
+pub struct AnnotationEditor {
+    operations: Vec<EditOperation>,
+}
+
+impl AnnotationEditor {
+    pub fn new(operations: Vec<EditOperation>) -> Self {
+        Self { operations }
+    }
+
+    pub fn apply(&self, annotations: Vec<Annotation>) -> (Vec<Annotation>, EditReport) {
+        let mut report = EditReport {
+            touched: vec![0; self.operations.len()],
+        };
+
+        let annotations = annotations
+            .into_iter()
+            .map(|annotation| {
+                let mut annotation = annotation;
+                for (operation, touched) in self.operations.iter().zip(report.touched.iter_mut()) {
+                    if let Some(edited) = apply_operation(operation, &annotation) {
+                        annotation = edited;
+                        *touched += 1;
+                    }
+                }
+                annotation
+            })
+            .collect();
+
+        (annotations, report)
+    }
+}
+
+/// Returns the edited annotation or `None` if the operation does not change
+/// it.
+fn apply_operation(operation: &EditOperation, annotation: &Annotation) -> Option<Annotation> {
+    match operation {
+        EditOperation::MapSource { from, to } => {
+            if annotation.source() == from && from != to {
+                Some(annotation.with_source(to.clone()))
+            } else {
+                None
+            }
+        }
+        EditOperation::MapFeature { from, to } => {
+            if annotation.feature() == from && from != to {
+                Some(annotation.with_feature(to.clone()))
+            } else {
+                None
+            }
+        }
+        _ => {
+            let entries = split_attributes(annotation.attributes());
+            let edited = edit_attributes(operation, &entries)?;
+            if edited == entries {
+                None
+            } else {
+                Some(annotation.with_attributes(edited.join(";")))
+            }
+        }
+    }
+}
+
+fn split_attributes(attributes: &str) -> Vec<String> {
+    match attributes {
+        "" | "." => Vec::new(),
+        attributes => attributes
+            .split(';')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(String::from)
+            .collect(),
+    }
+}
+
+fn entry_key(entry: &str) -> Option<&str> {
+    entry.split_once('=').map(|(key, _)| key.trim())
+}
+
+fn entry_values(entry: &str) -> Vec<String> {
+    match entry.split_once('=') {
+        Some((_, value)) => value.split(',').map(percent_decode).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn make_entry(key: &str, values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|value| percent_encode(value)).collect();
+    format!("{}={}", key, values.join(","))
+}
+
+fn edit_attributes(operation: &EditOperation, entries: &[String]) -> Option<Vec<String>> {
+    let mut entries = entries.to_vec();
+    match operation {
+        EditOperation::RenameKey { from, to } => {
+            for entry in entries.iter_mut() {
+                if entry_key(entry) == Some(from.as_str()) {
+                    let value = entry.split_once('=').unwrap().1;
+                    *entry = format!("{}={}", to, value);
+                }
+            }
+        }
+        EditOperation::SetValue { key, value } => {
+            let new_entry = make_entry(key, std::slice::from_ref(value));
+            match entries
+                .iter()
+                .position(|e| entry_key(e) == Some(key.as_str()))
+            {
+                Some(first) => {
+                    if entry_values(&entries[first]) != std::slice::from_ref(value) {
+                        entries[first] = new_entry;
+                    }
+                    entries = entries
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, e)| *i == first || entry_key(e) != Some(key.as_str()))
+                        .map(|(_, e)| e)
+                        .collect();
+                }
+                None => entries.push(new_entry),
+            }
+        }
+        EditOperation::DeleteKey(key) => entries.retain(|e| entry_key(e) != Some(key.as_str())),
+        EditOperation::ReplaceInValue { key, from, to } => {
+            if from.is_empty() {
+                return None;
+            }
+            for entry in entries.iter_mut() {
+                if entry_key(entry) == Some(key.as_str()) {
+                    let values = entry_values(entry);
+                    if values.iter().any(|v| v.contains(from.as_str())) {
+                        let values: Vec<String> = values
+                            .iter()
+                            .map(|v| v.replace(from.as_str(), to))
+                            .collect();
+                        *entry = make_entry(key, &values);
+                    }
+                }
+            }
+        }
+        EditOperation::PrefixValue { key, prefix } => {
+            if prefix.is_empty() {
+                return None;
+            }
+            for entry in entries.iter_mut() {
+                if entry_key(entry) == Some(key.as_str()) {
+                    let values: Vec<String> = entry_values(entry)
+                        .iter()
+                        .map(|v| format!("{}{}", prefix, v))
+                        .collect();
+                    *entry = make_entry(key, &values);
+                }
+            }
+        }
+        EditOperation::MapSource { .. } | EditOperation::MapFeature { .. } => return None,
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{AnnotationEditor, EditOperation};
+    use crate::data::{Annotation, Feature};
+    use crate::testing;
+
+    fn annotation(feature: Feature, attributes: &str) -> Annotation {
+        testing::builder("s", feature, 0, 10)
+            .source("maker")
+            .attributes(attributes)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let annotations = vec![
+            annotation(Feature::Exon, "ID=e1;Parent=t1,t2;name=first;note=x"),
+            annotation(Feature::CDS, "ID=c1;Parent=t1;note=a%3Bb"),
+            annotation(Feature::Exon, ""),
+            annotation(Feature::StartCodon, "gene_id \"g1\""),
+        ];
+
+        let editor = AnnotationEditor::new(vec![
+            EditOperation::RenameKey {
+                from: String::from("name"),
+                to: String::from("Name"),
+            },
+            EditOperation::PrefixValue {
+                key: String::from("ID"),
+                prefix: String::from("v2."),
+            },
+            EditOperation::PrefixValue {
+                key: String::from("Parent"),
+                prefix: String::from("v2."),
+            },
+            EditOperation::ReplaceInValue {
+                key: String::from("note"),
+                from: String::from(";"),
+                to: String::from(", "),
+            },
+            EditOperation::DeleteKey(String::from("note")),
+            EditOperation::SetValue {
+                key: String::from("Note"),
+                value: String::from("a=b"),
+            },
+            EditOperation::MapSource {
+                from: String::from("maker"),
+                to: String::from("MAKER"),
+            },
+            EditOperation::MapFeature {
+                from: Feature::StartCodon,
+                to: Feature::StopCodon,
+            },
+        ]);
+
+        let (edited, report) = editor.apply(annotations);
+        assert_eq!(report.touched(), &[1, 2, 2, 1, 2, 4, 4, 1]);
+
+        assert_eq!(
+            edited[0].attributes(),
+            "ID=v2.e1;Parent=v2.t1,v2.t2;Name=first;Note=a%3Db"
+        );
+        assert_eq!(edited[1].attributes(), "ID=v2.c1;Parent=v2.t1;Note=a%3Db");
+        assert_eq!(edited[2].attributes(), "Note=a%3Db");
+        assert_eq!(edited[3].attributes(), "gene_id \"g1\";Note=a%3Db");
+        assert_eq!(edited[3].feature(), &Feature::StopCodon);
+        assert!(edited.iter().all(|a| a.source() == "MAKER"));
+    }
+
+    #[test]
+    fn test_replace_and_set() {
+        let editor = AnnotationEditor::new(vec![
+            EditOperation::ReplaceInValue {
+                key: String::from("note"),
+                from: String::from(";"),
+                to: String::from(","),
+            },
+            EditOperation::SetValue {
+                key: String::from("ID"),
+                value: String::from("x"),
+            },
+        ]);
+        let (edited, report) = editor.apply(vec![
+            annotation(Feature::Exon, "ID=x;note=a%3Bb;ID=y"),
+            annotation(Feature::Exon, "ID=x; note=bad%zz"),
+        ]);
+        assert_eq!(report.touched(), &[1, 1]);
+        assert_eq!(edited[0].attributes(), "ID=x;note=a%2Cb");
+        assert_eq!(edited[1].attributes(), "ID=x; note=bad%zz");
+    }
+}
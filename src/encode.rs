@@ -0,0 +1,374 @@
+use crate::data::{Scaffold, Symbol};
+use anyhow::Result;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "rayon")]
+use std::time::{Duration, Instant};
+
+/// Number of one-hot channels per position.
+pub const CHANNELS: usize = 4;
+
+/// Handling of windows extending past the scaffold end.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EdgePolicy {
+    #[default]
+    Error,
+    /// Positions past the scaffold end are encoded as zeros.
+    ZeroPad,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EncodeConfig {
+    /// Number of positions per window.
+    pub window: usize,
+    pub edges: EdgePolicy,
+}
+
+impl EncodeConfig {
+    /// Number of values of a single encoded window.
+    pub fn row_len(&self) -> usize {
+        self.window * CHANNELS
+    }
+}
+
+/// Checks windows and the output buffer before anything is encoded.
+fn validate(
+    windows: &[(usize, usize)],
+    scaffolds: &[Scaffold],
+    cfg: &EncodeConfig,
+    out: &[f32],
+) -> Result<()> {
+    ensure!(cfg.window > 0, "Window length must be positive.");
+    ensure!(
+        out.len() == windows.len() * cfg.row_len(),
+        "Output buffer has {} values but {} windows of {} values are encoded.",
+        out.len(),
+        windows.len(),
+        cfg.row_len()
+    );
+
+    for (i, &(scaffold, start)) in windows.iter().enumerate() {
+        let len = match scaffolds.get(scaffold) {
+            Some(s) => s.len(),
+            None => bail!("Window {} references unknown scaffold {}.", i, scaffold),
+        };
+        ensure!(
+            cfg.edges == EdgePolicy::ZeroPad || start + cfg.window <= len,
+            "Window {} at {} exceeds scaffold {} of length {}.",
+            i,
+            start,
+            scaffold,
+            len
+        );
+    }
+
+    Ok(())
+}
+
+/// One-hot encodes a single window into `row`, position major with channels
+/// A, T, C and G. `Symbol::Other` and positions past the scaffold end are
+/// encoded as all zeros. Only the window is unpacked from packed scaffolds.
+fn encode_row(scaffold: &Scaffold, start: usize, row: &mut [f32]) {
+    let end = (start + row.len() / CHANNELS).min(scaffold.len());
+    let window = scaffold
+        .subsequence(start.min(end)..end)
+        .expect("Window was validated against the scaffold length.");
+    let (encoded, padding) = row.split_at_mut(window.len() * CHANNELS);
+    one_hot_into(&window, OtherEncoding::Zeros, encoded)
+        .expect("Row is split to the window length.");
+    padding.iter_mut().for_each(|value| *value = 0.);
+}
+
+/// One-hot encodes windows given as scaffold index and start into `out`,
+/// one row of `cfg.row_len()` values per window. Windows and the buffer size
+/// are validated before anything is written.
+pub fn encode_windows(
+    windows: &[(usize, usize)],
+    scaffolds: &[Scaffold],
+    cfg: EncodeConfig,
+    out: &mut [f32],
+) -> Result<()> {
+    validate(windows, scaffolds, &cfg, out)?;
+    for (row, &(scaffold, start)) in out.chunks_mut(cfg.row_len()).zip(windows.iter()) {
+        encode_row(&scaffolds[scaffold], start, row);
+    }
+    Ok(())
+}
+
+/// Encoding of `Symbol::Other` by `one_hot`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OtherEncoding {
+    /// All channels are zero, as in `encode_windows`.
+    #[default]
+    Zeros,
+    /// All channels are 0.25, i.e. a uniform distribution over the bases.
+    Uniform,
+    /// `Other` has its own fifth channel.
+    Channel,
+}
+
+impl OtherEncoding {
+    /// Number of channels per position.
+    pub fn channels(self) -> usize {
+        match self {
+            Self::Zeros | Self::Uniform => CHANNELS,
+            Self::Channel => CHANNELS + 1,
+        }
+    }
+}
+
+/// One-hot encodes a sequence into a row-major `[seq.len(), channels]`
+/// buffer with channels A, T, C, G and, if `other` is
+/// `OtherEncoding::Channel`, N. See `one_hot_array` for a matrix.
+pub fn one_hot(seq: &[Symbol], other: OtherEncoding) -> Vec<f32> {
+    let mut out = vec![0.; seq.len() * other.channels()];
+    one_hot_into(seq, other, &mut out).unwrap();
+    out
+}
+
+/// Same as `one_hot` but returns a `[seq.len(), channels]` matrix.
+#[cfg(feature = "ndarray")]
+pub fn one_hot_array(seq: &[Symbol], other: OtherEncoding) -> ndarray::Array2<f32> {
+    ndarray::Array2::from_shape_vec((seq.len(), other.channels()), one_hot(seq, other))
+        .expect("One-hot buffer matches its shape.")
+}
+
+/// Writes the output of `one_hot` into `out`, which has to hold exactly
+/// `seq.len() * other.channels()` values. This allows reusing buffers,
+/// e.g. in batch loops.
+pub fn one_hot_into(seq: &[Symbol], other: OtherEncoding, out: &mut [f32]) -> Result<()> {
+    let channels = other.channels();
+    ensure!(
+        out.len() == seq.len() * channels,
+        "Output buffer has {} values but a sequence of length {} is encoded into {} values.",
+        out.len(),
+        seq.len(),
+        seq.len() * channels
+    );
+
+    for (row, &symbol) in out.chunks_exact_mut(channels).zip(seq.iter()) {
+        row.iter_mut().for_each(|value| *value = 0.);
+        match (symbol, other) {
+            (Symbol::Other, OtherEncoding::Zeros) => (),
+            (Symbol::Other, OtherEncoding::Uniform) => {
+                row.iter_mut().for_each(|value| *value = 0.25)
+            }
+            (symbol, _) => {
+                let channel: usize = symbol.into();
+                row[channel] = 1.;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Work done by a single worker thread of `encode_windows_parallel`.
+#[cfg(feature = "rayon")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ThreadStats {
+    /// Index of the thread in the Rayon pool.
+    pub thread: usize,
+    pub rows: usize,
+    pub elapsed: Duration,
+}
+
+/// Parallel version of `encode_windows` producing identical output. Each
+/// window is written to its own disjoint row of `out`, so no locking is
+/// involved.
+///
+/// Returns per thread statistics sorted by thread index.
+#[cfg(feature = "rayon")]
+pub fn encode_windows_parallel(
+    windows: &[(usize, usize)],
+    scaffolds: &[Scaffold],
+    cfg: EncodeConfig,
+    out: &mut [f32],
+) -> Result<Vec<ThreadStats>> {
+    validate(windows, scaffolds, &cfg, out)?;
+
+    let partial: Vec<ThreadStats> = out
+        .par_chunks_mut(cfg.row_len())
+        .zip(windows.par_iter())
+        .fold(
+            || ThreadStats {
+                thread: rayon::current_thread_index().unwrap_or(0),
+                rows: 0,
+                elapsed: Duration::default(),
+            },
+            |mut stats, (row, &(scaffold, start))| {
+                let started = Instant::now();
+                encode_row(&scaffolds[scaffold], start, row);
+                stats.elapsed += started.elapsed();
+                stats.rows += 1;
+                stats
+            },
+        )
+        .collect();
+
+    let mut stats: Vec<ThreadStats> = Vec::new();
+    for part in partial {
+        match stats.iter_mut().find(|s| s.thread == part.thread) {
+            Some(s) => {
+                s.rows += part.rows;
+                s.elapsed += part.elapsed;
+            }
+            None => stats.push(part),
+        }
+    }
+    stats.sort_by_key(|s| s.thread);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{EdgePolicy, EncodeConfig, OtherEncoding};
+    use crate::data::{parse_sequence, Scaffold};
+
+    fn scaffold(sequence: &str) -> Scaffold {
+        Scaffold::new(String::from("s"), parse_sequence(sequence).unwrap())
+    }
+
+    #[test]
+    fn test_encode_windows() {
+        let scaffolds = vec![scaffold("ACGTN"), scaffold("GG")];
+        let cfg = EncodeConfig {
+            window: 3,
+            edges: EdgePolicy::ZeroPad,
+        };
+        let mut out = vec![f32::NAN; 3 * cfg.row_len()];
+        super::encode_windows(&[(0, 2), (1, 0), (0, 4)], &scaffolds, cfg, &mut out).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            // G T N
+            0., 0., 0., 1., 0., 1., 0., 0., 0., 0., 0., 0.,
+            // G G -
+            0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0., 0.,
+            // N - -
+            0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.,
+        ];
+        assert_eq!(out, expected);
+
+        let strict = EncodeConfig {
+            edges: EdgePolicy::Error,
+            ..cfg
+        };
+        assert!(super::encode_windows(&[(1, 0)], &scaffolds, strict, &mut out[..12]).is_err());
+        assert!(super::encode_windows(&[(2, 0)], &scaffolds, cfg, &mut out[..12]).is_err());
+        assert!(super::encode_windows(&[(0, 0)], &scaffolds, cfg, &mut out[..11]).is_err());
+    }
+
+    #[test]
+    fn test_one_hot() {
+        let scaffold = scaffold("ATNCG");
+
+        let zeros = super::one_hot(&scaffold.sequence(), OtherEncoding::Zeros);
+        #[rustfmt::skip]
+        let expected = [
+            1., 0., 0., 0.,
+            0., 1., 0., 0.,
+            0., 0., 0., 0.,
+            0., 0., 1., 0.,
+            0., 0., 0., 1.,
+        ];
+        assert_eq!(zeros, expected);
+
+        let uniform = super::one_hot(&scaffold.sequence(), OtherEncoding::Uniform);
+        assert_eq!(uniform.len(), 5 * 4);
+        assert_eq!(uniform[..8], expected[..8]);
+        assert_eq!(uniform[8..12], [0.25; 4]);
+        assert_eq!(uniform[12..], expected[12..]);
+
+        let channel = super::one_hot(&scaffold.sequence(), OtherEncoding::Channel);
+        #[rustfmt::skip]
+        let expected = [
+            1., 0., 0., 0., 0.,
+            0., 1., 0., 0., 0.,
+            0., 0., 0., 0., 1.,
+            0., 0., 1., 0., 0.,
+            0., 0., 0., 1., 0.,
+        ];
+        assert_eq!(channel, expected);
+
+        assert!(super::one_hot(&[], OtherEncoding::Channel).is_empty());
+
+        let mut out = vec![f32::NAN; 10];
+        super::one_hot_into(&scaffold.sequence()[..2], OtherEncoding::Channel, &mut out).unwrap();
+        assert_eq!(out, expected[..10]);
+        let error = super::one_hot_into(&scaffold.sequence(), OtherEncoding::Zeros, &mut out)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Output buffer has 10 values but a sequence of length 5 is encoded into 20 values."
+        );
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_one_hot_array() {
+        let scaffold = scaffold("ATNCG");
+
+        let array = super::one_hot_array(&scaffold.sequence(), OtherEncoding::Channel);
+        assert_eq!(array.shape(), [5, 5]);
+        assert_eq!(array[[2, 4]], 1.);
+        assert_eq!(array.row(3).to_vec(), [0., 0., 1., 0., 0.]);
+        assert_eq!(
+            array.into_raw_vec_and_offset().0,
+            super::one_hot(&scaffold.sequence(), OtherEncoding::Channel)
+        );
+
+        let array = super::one_hot_array(&scaffold.sequence(), OtherEncoding::Uniform);
+        assert_eq!(array.shape(), [5, 4]);
+        assert_eq!(array.row(2).to_vec(), [0.25; 4]);
+        assert_eq!(
+            super::one_hot_array(&[], OtherEncoding::Zeros).shape(),
+            [0, 4]
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_matches_sequential() {
+        use crate::simulate::Random;
+
+        let mut random = Random::new(17);
+        let mut next = |bound: usize| random.below(bound);
+
+        let scaffolds: Vec<Scaffold> = (0..5)
+            .map(|_| {
+                let sequence: String = (0..500 + next(500))
+                    .map(|_| "ACGTN".as_bytes()[next(5)] as char)
+                    .collect();
+                scaffold(&sequence)
+            })
+            .collect();
+        let windows: Vec<(usize, usize)> = (0..3000)
+            .map(|_| {
+                let scaffold = next(scaffolds.len());
+                (scaffold, next(scaffolds[scaffold].sequence().len()))
+            })
+            .collect();
+        let cfg = EncodeConfig {
+            window: 64,
+            edges: EdgePolicy::ZeroPad,
+        };
+
+        let mut sequential = vec![0.; windows.len() * cfg.row_len()];
+        super::encode_windows(&windows, &scaffolds, cfg, &mut sequential).unwrap();
+        let mut parallel = vec![f32::NAN; windows.len() * cfg.row_len()];
+        let stats =
+            super::encode_windows_parallel(&windows, &scaffolds, cfg, &mut parallel).unwrap();
+
+        assert!(sequential
+            .iter()
+            .zip(parallel.iter())
+            .all(|(a, b)| a.to_bits() == b.to_bits()));
+        assert_eq!(stats.iter().map(|s| s.rows).sum::<usize>(), windows.len());
+
+        let mut short = vec![0.; parallel.len() - 1];
+        assert!(super::encode_windows_parallel(&windows, &scaffolds, cfg, &mut short).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use crate::bounds::{Bounded, BoundsPolicy};
+use crate::data::{reverse_complement_in_place, Annotation, Feature, Scaffold, Strand};
+use crate::fasta::{write_fasta_with_options, FastaWriteOptions};
+use crate::model::Gene;
+use crate::region::Region;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Options of `extract_feature_sequences_with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ExtractOptions {
+    /// Handling of annotations extending past the end of their scaffold.
+    /// Clamped annotations are named after the clamped region.
+    pub bounds: BoundsPolicy,
+}
+
+/// Extracts sequences of all annotations of a feature type, e.g. for writing
+/// them with `write_fasta`. Sequences of negative strand annotations are
+/// reverse complemented, all others are extracted as is. Annotations of
+/// circular scaffolds may wrap around the origin.
+///
+/// Records are named after the annotated region with the strand, e.g.
+/// `chr1:101-200(-)`, and annotation attributes are kept as the record
+/// description. Annotations extending past the end of their scaffold are an
+/// error.
+pub fn extract_feature_sequences(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    feature: Feature,
+) -> Result<Vec<Scaffold>> {
+    extract_feature_sequences_with_options(
+        scaffolds,
+        annotations,
+        feature,
+        ExtractOptions::default(),
+    )
+}
+
+/// Same as `extract_feature_sequences` with further options, e.g. the
+/// handling of out of bounds annotations.
+pub fn extract_feature_sequences_with_options(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    feature: Feature,
+    options: ExtractOptions,
+) -> Result<Vec<Scaffold>> {
+    let by_name: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+
+    let mut extracted = Vec::new();
+    for (i, annotation) in annotations.iter().enumerate() {
+        if *annotation.feature() != feature {
+            continue;
+        }
+
+        let region = Region::new(annotation.scaffold(), annotation.start(), annotation.end());
+        let scaffold = match by_name.get(annotation.scaffold()) {
+            Some(scaffold) => scaffold,
+            None => bail!(
+                "Annotation {}{} at {} references unknown scaffold {}.",
+                i,
+                annotation.line_note(),
+                region,
+                annotation.scaffold()
+            ),
+        };
+        let (start, end) = match options
+            .bounds
+            .apply(annotation, scaffold)
+            .with_context(|| format!("Cannot extract annotation {} at {}.", i, region))?
+        {
+            Bounded::Within => (annotation.start(), annotation.end()),
+            Bounded::Clamped(start, end) => (start, end),
+            Bounded::Dropped => continue,
+        };
+        let region = Region::new(annotation.scaffold(), start, end);
+
+        let sequence = scaffold.region(start, end, annotation.strand())?;
+        let strand = match annotation.strand() {
+            Strand::Positive => '+',
+            Strand::Unstranded => '.',
+            Strand::Unknown => '?',
+            Strand::Negative => '-',
+        };
+        let description = match annotation.attributes() {
+            "" => None,
+            attributes => Some(String::from(attributes)),
+        };
+        extracted.push(
+            Scaffold::new(format!("{}({})", region, strand), sequence)
+                .with_description(description),
+        );
+    }
+
+    Ok(extracted)
+}
+
+/// Extracts spliced sequences of all transcripts of gene models, see
+/// `build_gene_models`. Exons of a transcript are joined in genomic order
+/// and the result is reverse complemented for negative strand transcripts,
+/// so that each sequence reads from 5' to 3'. Records are named after
+/// transcript IDs. Transcripts without exons are skipped.
+pub fn extract_transcript_sequences(
+    scaffolds: &[Scaffold],
+    genes: &[Gene],
+) -> Result<Vec<Scaffold>> {
+    let by_name: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+
+    let mut extracted = Vec::new();
+    for transcript in genes.iter().flat_map(|gene| gene.transcripts()) {
+        let exons: Vec<&Annotation> = transcript.features_of(&Feature::Exon).collect();
+        let first = match exons.first() {
+            Some(first) => first,
+            None => continue,
+        };
+        ensure!(
+            exons
+                .iter()
+                .all(|e| e.scaffold() == first.scaffold() && e.strand() == first.strand()),
+            "Exons of transcript {} are not on a single scaffold and strand.",
+            transcript.id()
+        );
+        let scaffold = match by_name.get(first.scaffold()) {
+            Some(scaffold) => scaffold,
+            None => bail!(
+                "Transcript {} references unknown scaffold {}.",
+                transcript.id(),
+                first.scaffold()
+            ),
+        };
+
+        let mut sequence = Vec::with_capacity(exons.iter().map(|e| e.len()).sum());
+        for exon in exons.iter() {
+            let exon_sequence = scaffold
+                .region(exon.start(), exon.end(), Strand::Positive)
+                .with_context(|| {
+                    format!("Cannot extract exon of transcript {}.", transcript.id())
+                })?;
+            sequence.extend(exon_sequence);
+        }
+        if first.strand() == Strand::Negative {
+            reverse_complement_in_place(&mut sequence);
+        }
+        extracted.push(Scaffold::new(String::from(transcript.id()), sequence));
+    }
+
+    Ok(extracted)
+}
+
+/// Writes spliced transcript sequences to a FASTA file, see
+/// `extract_transcript_sequences`. With `Alphabet::Rna` the sequences are
+/// written transcribed, i.e. with `U` in place of `T`.
+pub fn export_transcriptome(
+    path: &Path,
+    scaffolds: &[Scaffold],
+    genes: &[Gene],
+    options: &FastaWriteOptions,
+) -> Result<()> {
+    let transcripts = extract_transcript_sequences(scaffolds, genes)?;
+    write_fasta_with_options(path, &transcripts, options)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ExtractOptions;
+    use crate::bounds::BoundsPolicy;
+    use crate::data::{
+        parse_sequence, symbols_to_string, Alphabet, Annotation, Feature, Scaffold, Strand, Symbol,
+    };
+    use crate::fasta::FastaWriteOptions;
+    use crate::gff::load_gff3_with_sequences;
+    use crate::model::build_gene_models;
+    use crate::testing;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    fn annotation(
+        scaffold: &str,
+        feature: Feature,
+        strand: Strand,
+        start: usize,
+        end: usize,
+    ) -> Annotation {
+        testing::builder(scaffold, feature, start, end)
+            .strand(strand)
+            .attributes("ID=a")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_extract_feature_sequences() {
+        let sequence = vec![
+            Symbol::Adenine,
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Other,
+            Symbol::Thymine,
+        ];
+        let scaffolds = vec![Scaffold::new(String::from("s"), sequence)];
+        let annotations = vec![
+            annotation("s", Feature::CDS, Strand::Positive, 1, 4),
+            annotation("s", Feature::Exon, Strand::Positive, 0, 6),
+            annotation("s", Feature::CDS, Strand::Negative, 2, 6),
+        ];
+
+        let extracted =
+            super::extract_feature_sequences(&scaffolds, &annotations, Feature::CDS).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[0].name(), "s:2-4(+)");
+        assert_eq!(extracted[0].description(), Some("ID=a"));
+        assert_eq!(
+            symbols_to_string(&extracted[0].sequence(), Alphabet::Dna),
+            "ACG"
+        );
+        assert_eq!(extracted[1].name(), "s:3-6(-)");
+        assert_eq!(
+            symbols_to_string(&extracted[1].sequence(), Alphabet::Dna),
+            "ANCG"
+        );
+
+        let unknown = vec![annotation("t", Feature::CDS, Strand::Positive, 0, 1)];
+        let error = super::extract_feature_sequences(&scaffolds, &unknown, Feature::CDS)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotation 0 at t:1-1 references unknown scaffold t."
+        );
+
+        let long = vec![annotation("s", Feature::CDS, Strand::Positive, 2, 7)];
+        let error = super::extract_feature_sequences(&scaffolds, &long, Feature::CDS)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Cannot extract annotation 0 at s:3-7.");
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Annotation 2..7 extends past the end of scaffold s of length 6."
+        );
+
+        let options = ExtractOptions {
+            bounds: BoundsPolicy::Clamp,
+        };
+        let extracted =
+            super::extract_feature_sequences_with_options(&scaffolds, &long, Feature::CDS, options)
+                .unwrap();
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].name(), "s:3-6(+)");
+        assert_eq!(
+            symbols_to_string(&extracted[0].sequence(), Alphabet::Dna),
+            "CGNT"
+        );
+
+        let options = ExtractOptions {
+            bounds: BoundsPolicy::Drop,
+        };
+        let extracted =
+            super::extract_feature_sequences_with_options(&scaffolds, &long, Feature::CDS, options)
+                .unwrap();
+        assert!(extracted.is_empty());
+    }
+
+    #[test]
+    fn test_extract_circular() {
+        let (annotations, scaffolds) =
+            load_gff3_with_sequences(Path::new("./tests/circular.gff3")).unwrap();
+
+        let extracted =
+            super::extract_feature_sequences(&scaffolds, &annotations[..3], Feature::CDS).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[0].name(), "chrM:16-25(+)");
+        assert_eq!(
+            symbols_to_string(&extracted[0].sequence(), Alphabet::Dna),
+            "TACGTAAAAC"
+        );
+        assert_eq!(extracted[1].name(), "chrM:16-25(-)");
+        assert_eq!(
+            symbols_to_string(&extracted[1].sequence(), Alphabet::Dna),
+            "GTTTTACGTA"
+        );
+
+        // The same coordinates on a linear scaffold are out of bounds.
+        let error = super::extract_feature_sequences(&scaffolds, &annotations[3..], Feature::CDS)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Cannot extract annotation 0 at chr1:16-25."
+        );
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Annotation 15..25 (line 7) extends past the end of scaffold chr1 of length 20."
+        );
+    }
+
+    #[test]
+    fn test_extract_transcript_sequences() {
+        let scaffolds = vec![Scaffold::new(
+            String::from("s"),
+            parse_sequence("AACCGGTTACGT").unwrap(),
+        )];
+        let record = |feature, strand, start, end, attributes| {
+            testing::builder("s", feature, start, end)
+                .strand(strand)
+                .attributes(attributes)
+                .build()
+                .unwrap()
+        };
+        let negative = Strand::Negative;
+        let annotations = vec![
+            record(Feature::Gene, negative, 1, 10, "ID=g1"),
+            record(Feature::MRNA, negative, 1, 10, "ID=t1;Parent=g1"),
+            record(Feature::Exon, negative, 7, 10, "Parent=t1"),
+            record(Feature::Exon, negative, 1, 4, "Parent=t1"),
+            record(Feature::Gene, Strand::Positive, 0, 4, "ID=g2"),
+            record(Feature::MRNA, Strand::Positive, 0, 4, "ID=t2;Parent=g2"),
+            record(Feature::Exon, Strand::Positive, 0, 4, "Parent=t2"),
+        ];
+        let genes = build_gene_models(&annotations).unwrap();
+
+        let extracted = super::extract_transcript_sequences(&scaffolds, &genes).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[0].name(), "t1");
+        // Reverse complement of ACC + TAC.
+        assert_eq!(
+            symbols_to_string(&extracted[0].sequence(), Alphabet::Dna),
+            "GTAGGT"
+        );
+        assert_eq!(extracted[1].name(), "t2");
+        assert_eq!(
+            symbols_to_string(&extracted[1].sequence(), Alphabet::Dna),
+            "AACC"
+        );
+
+        let path = env::temp_dir().join(format!("ncrs-transcriptome-{}.fasta", std::process::id()));
+        let options = FastaWriteOptions {
+            line_width: 0,
+            alphabet: Alphabet::Rna,
+        };
+        super::export_transcriptome(&path, &scaffolds, &genes, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            ">t1\nGUAGGU\n>t2\nAACC\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
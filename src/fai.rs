@@ -0,0 +1,450 @@
+use crate::bgzf::{is_bgzf, open_bgzf, save_gzi, BgzfReader};
+use crate::data::Symbol;
+use crate::fasta::{is_gzip, is_ignored_line};
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{prelude::*, BufReader, BufWriter, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Location of a scaffold sequence in a FASTA file, equivalent to a line of
+/// a `.fai` index.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct FaiEntry {
+    pub(crate) name: String,
+    pub(crate) length: u64,
+    pub(crate) offset: u64,
+    pub(crate) line_bases: u64,
+    pub(crate) line_bytes: u64,
+}
+
+impl FaiEntry {
+    /// Byte offset of a 0-based sequence position.
+    fn byte_position(&self, position: u64) -> u64 {
+        self.offset + position / self.line_bases * self.line_bytes + position % self.line_bases
+    }
+}
+
+/// Scans a FASTA file and records the location of each scaffold sequence.
+/// Sequence lines of a scaffold must have the same length except the last
+/// one.
+pub(crate) fn scan_fasta(path: &Path) -> Result<Vec<FaiEntry>> {
+    let reader = {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+        BufReader::new(file)
+    };
+    scan_records(reader, path)
+}
+
+/// Scans uncompressed FASTA data read from `reader`, `path` is only used in
+/// error messages.
+fn scan_records<R: BufRead>(mut reader: R, path: &Path) -> Result<Vec<FaiEntry>> {
+    let mut sequences: Vec<FaiEntry> = Vec::new();
+    // Whether the last sequence line of the current scaffold was shorter
+    // than the others, i.e. no more lines may follow.
+    let mut last_line = false;
+    let mut position = 0;
+    let mut line = Vec::new();
+
+    for i in 1.. {
+        line.clear();
+        let num_bytes = reader
+            .read_until(b'\n', &mut line)
+            .with_context(|| format!("Failed to read file {}.", path.display()))?;
+        if num_bytes == 0 {
+            break;
+        }
+        position += num_bytes as u64;
+
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+
+        if let Some(name) = content.strip_prefix(b">") {
+            let name = String::from_utf8(name.to_vec()).with_context(|| {
+                format!(
+                    "Invalid scaffold name on line {} of file {}.",
+                    i,
+                    path.display()
+                )
+            })?;
+            ensure!(
+                sequences.iter().all(|s| s.name != name),
+                "Duplicate scaffold {} in file {}.",
+                name,
+                path.display()
+            );
+            sequences.push(FaiEntry {
+                name,
+                length: 0,
+                offset: position,
+                line_bases: 0,
+                line_bytes: 0,
+            });
+            last_line = false;
+            continue;
+        }
+
+        let sequence = match sequences.last_mut() {
+            Some(sequence) => sequence,
+            None if is_ignored_line(content) => continue,
+            None => bail!(
+                "Invalid FASTA file {}, sequence on line {} precedes the first header.",
+                path.display(),
+                i
+            ),
+        };
+        ensure!(
+            !content.starts_with(b";"),
+            "Comment on line {} within scaffold {} of file {} cannot be indexed.",
+            i,
+            sequence.name,
+            path.display()
+        );
+        let bases = content.len() as u64;
+        if bases == 0 {
+            last_line = true;
+            continue;
+        }
+        ensure!(
+            !last_line && (sequence.line_bases == 0 || bases <= sequence.line_bases),
+            "Scaffold {} has lines of non-uniform length in file {}.",
+            sequence.name,
+            path.display()
+        );
+
+        if sequence.line_bases == 0 {
+            sequence.line_bases = bases;
+            sequence.line_bytes = num_bytes as u64;
+        } else if bases < sequence.line_bases || num_bytes as u64 != sequence.line_bytes {
+            last_line = true;
+        }
+        sequence.length += bases;
+    }
+
+    Ok(sequences)
+}
+
+/// Reads 0-based half-open `[start, end)` of an indexed sequence. The range
+/// must lie within the sequence.
+pub(crate) fn read_range<R: Read + Seek>(
+    file: &mut R,
+    path: &Path,
+    entry: &FaiEntry,
+    start: u64,
+    end: u64,
+) -> Result<Vec<Symbol>> {
+    let mut symbols = Vec::with_capacity((end - start) as usize);
+    if start == end {
+        return Ok(symbols);
+    }
+
+    let first = entry.byte_position(start);
+    let last = entry.byte_position(end - 1) + 1;
+    let mut bytes = vec![0; (last - first) as usize];
+    file.seek(SeekFrom::Start(first))
+        .and_then(|_| file.read_exact(&mut bytes))
+        .with_context(|| format!("Failed to read file {}.", path.display()))?;
+    for &byte in bytes.iter().filter(|&&b| b != b'\n' && b != b'\r') {
+        symbols.push(Symbol::try_from(byte as char)?);
+    }
+    ensure!(
+        symbols.len() as u64 == end - start,
+        "Failed to read {}:{}-{} from file {}, is the index out of date?",
+        entry.name,
+        start + 1,
+        end,
+        path.display()
+    );
+    Ok(symbols)
+}
+
+/// Path of the `.fai` index belonging to a FASTA file.
+fn fai_path(path: &Path) -> PathBuf {
+    let mut fai = path.as_os_str().to_owned();
+    fai.push(".fai");
+    PathBuf::from(fai)
+}
+
+/// Indexed FASTA file, either uncompressed or BGZF compressed.
+enum Source {
+    Plain(File),
+    Bgzf(RefCell<BgzfReader<File>>),
+}
+
+impl Source {
+    fn open(path: &Path) -> Result<Self> {
+        if is_bgzf(path)? {
+            return Ok(Self::Bgzf(RefCell::new(open_bgzf(path)?)));
+        }
+
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+        ensure!(
+            !is_gzip(&mut file, path)?,
+            "File {} is gzip but not BGZF compressed, compress it with bgzip for random access.",
+            path.display()
+        );
+        Ok(Self::Plain(file))
+    }
+}
+
+/// FASTA file accessed via a samtools compatible `.fai` index. Fetching a
+/// region only reads its bytes from the file.
+///
+/// BGZF compressed files (e.g. from `bgzip`) are supported, with block
+/// offsets taken from the `.gzi` index next to the file if there is one,
+/// otherwise read from the block headers.
+pub struct FastaIndex {
+    path: PathBuf,
+    source: Source,
+    entries: Vec<FaiEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl FastaIndex {
+    fn new(path: &Path, source: Source, entries: Vec<FaiEntry>) -> Self {
+        let names = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.name.clone(), i))
+            .collect();
+        Self {
+            path: path.to_path_buf(),
+            source,
+            entries,
+            names,
+        }
+    }
+
+    /// Opens a FASTA file using the `.fai` index next to it (i.e.
+    /// `genome.fa.fai` for `genome.fa`). The index is built in memory if no
+    /// such file exists.
+    pub fn open(path: &Path) -> Result<Self> {
+        match load_fai(path)? {
+            Some(entries) => Ok(Self::new(path, Source::open(path)?, entries)),
+            None => build_fai(path),
+        }
+    }
+
+    /// Writes the index to the `.fai` file next to the FASTA file. For BGZF
+    /// files, the block index is written to a `.gzi` file as well.
+    pub fn save(&self) -> Result<()> {
+        if let Source::Bgzf(ref reader) = self.source {
+            save_gzi(&self.path, &reader.borrow())?;
+        }
+
+        let fai = fai_path(&self.path);
+        let file = File::create(&fai)
+            .with_context(|| format!("Failed to create file {}.", fai.display()))?;
+        let mut writer = BufWriter::new(file);
+        for entry in self.entries.iter() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                entry.name, entry.length, entry.offset, entry.line_bases, entry.line_bytes
+            )
+            .with_context(|| format!("Failed to write file {}.", fai.display()))?;
+        }
+        writer
+            .flush()
+            .with_context(|| format!("Failed to write file {}.", fai.display()))
+    }
+
+    /// Names and lengths of all scaffolds in FASTA file order.
+    pub fn scaffolds(&self) -> Vec<(&str, usize)> {
+        self.entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.length as usize))
+            .collect()
+    }
+
+    /// Returns 0-based half-open `[start, end)` of a scaffold sequence.
+    pub fn fetch(&self, scaffold: &str, start: usize, end: usize) -> Result<Vec<Symbol>> {
+        let entry = match self.names.get(scaffold) {
+            Some(&i) => &self.entries[i],
+            None => bail!(
+                "Unknown scaffold {} in file {}.",
+                scaffold,
+                self.path.display()
+            ),
+        };
+        ensure!(
+            start <= end && end as u64 <= entry.length,
+            "Range {}-{} is out of bounds of scaffold {} of length {}.",
+            start,
+            end,
+            scaffold,
+            entry.length
+        );
+        let (start, end) = (start as u64, end as u64);
+        match self.source {
+            Source::Plain(ref file) => read_range(&mut &*file, &self.path, entry, start, end),
+            Source::Bgzf(ref reader) => {
+                read_range(&mut *reader.borrow_mut(), &self.path, entry, start, end)
+            }
+        }
+    }
+}
+
+/// Loads the `.fai` index next to a FASTA file, if there is one.
+pub(crate) fn load_fai(path: &Path) -> Result<Option<Vec<FaiEntry>>> {
+    let fai = fai_path(path);
+    if !fai.exists() {
+        return Ok(None);
+    }
+
+    let reader = {
+        let file =
+            File::open(&fai).with_context(|| format!("Failed to open file {}.", fai.display()))?;
+        BufReader::new(file)
+    };
+
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read file {}.", fai.display()))?;
+        let entry = parse_fai_line(&line).with_context(|| {
+            format!("Failed to parse line {} of file {}.", i + 1, fai.display())
+        })?;
+        entries.push(entry);
+    }
+
+    Ok(Some(entries))
+}
+
+fn parse_fai_line(line: &str) -> Result<FaiEntry> {
+    let columns: Vec<&str> = line.split('\t').collect();
+    ensure!(
+        columns.len() >= 5,
+        "Not enough columns. Expected 5 got {}.",
+        columns.len()
+    );
+
+    let parse = |i: usize| {
+        columns[i]
+            .parse::<u64>()
+            .with_context(|| format!("Column {} has to be a positive integer.", i + 1))
+    };
+    let entry = FaiEntry {
+        name: String::from(columns[0]),
+        length: parse(1)?,
+        offset: parse(2)?,
+        line_bases: parse(3)?,
+        line_bytes: parse(4)?,
+    };
+    ensure!(
+        entry.length == 0 || (entry.line_bases > 0 && entry.line_bytes >= entry.line_bases),
+        "Invalid line length of scaffold {}.",
+        entry.name
+    );
+    Ok(entry)
+}
+
+/// Builds a `.fai` index of an uncompressed or BGZF compressed FASTA file.
+/// Use `FastaIndex::save` to store it next to the FASTA file.
+pub fn build_fai(path: &Path) -> Result<FastaIndex> {
+    let source = Source::open(path)?;
+    let entries = match source {
+        Source::Plain(_) => scan_fasta(path)?,
+        Source::Bgzf(_) => scan_records(BufReader::new(open_bgzf(path)?), path)?,
+    };
+    Ok(FastaIndex::new(path, source, entries))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::FastaIndex;
+    use crate::bgzf::test::bgzip;
+    use crate::fasta::load_fasta;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_fetch() {
+        let directory = env::temp_dir().join(format!("ncrs-fai-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let fasta = directory.join("genome.fasta");
+        fs::copy(Path::new("./tests/valid.fasta"), &fasta).unwrap();
+        let scaffolds = load_fasta(&fasta).unwrap();
+
+        let built = super::build_fai(&fasta).unwrap();
+        built.save().unwrap();
+        let fai = fs::read_to_string(directory.join("genome.fasta.fai")).unwrap();
+        assert_eq!(fai.lines().count(), 2);
+        assert!(fai.starts_with("scaffold_1\t280\t12\t"));
+
+        let loaded = FastaIndex::open(&fasta).unwrap();
+        assert_eq!(loaded.scaffolds(), built.scaffolds());
+        for &(start, end) in &[(0, 280), (69, 71), (139, 141), (279, 280), (5, 5)] {
+            assert_eq!(
+                loaded.fetch("scaffold_1", start, end).unwrap(),
+                &scaffolds[0].sequence()[start..end]
+            );
+        }
+        assert_eq!(
+            loaded.fetch("scaffold_2", 0, 7).unwrap(),
+            *scaffolds[1].sequence()
+        );
+
+        let error = loaded.fetch("scaffold_2", 3, 8).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Range 3-8 is out of bounds of scaffold scaffold_2 of length 7."
+        );
+        assert!(loaded.fetch("scaffold_2", 4, 3).is_err());
+        assert!(loaded
+            .fetch("scaffold_3", 0, 1)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unknown scaffold scaffold_3"));
+
+        fs::write(directory.join("genome.fasta.fai"), "scaffold_1\t280\n").unwrap();
+        assert!(FastaIndex::open(&fasta).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_bgzf() {
+        let directory = env::temp_dir().join(format!("ncrs-fai-bgzf-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let scaffolds = load_fasta(Path::new("./tests/valid.fasta")).unwrap();
+        let fasta = directory.join("genome.fasta.gz");
+        let plain = fs::read("./tests/valid.fasta").unwrap();
+        fs::write(&fasta, bgzip(&plain, 50)).unwrap();
+
+        // Streaming loading handles BGZF as multi-member gzip.
+        let loaded = load_fasta(&fasta).unwrap();
+        assert_eq!(loaded[0].sequence(), scaffolds[0].sequence());
+
+        let built = super::build_fai(&fasta).unwrap();
+        built.save().unwrap();
+        assert!(directory.join("genome.fasta.gz.gzi").exists());
+        let plain_fai = super::build_fai(Path::new("./tests/valid.fasta")).unwrap();
+        assert_eq!(built.entries, plain_fai.entries);
+
+        let index = FastaIndex::open(&fasta).unwrap();
+        for &(start, end) in &[(0, 280), (45, 55), (139, 141), (279, 280)] {
+            assert_eq!(
+                index.fetch("scaffold_1", start, end).unwrap(),
+                &scaffolds[0].sequence()[start..end]
+            );
+        }
+        assert_eq!(
+            index.fetch("scaffold_2", 0, 7).unwrap(),
+            *scaffolds[1].sequence()
+        );
+
+        let error = match super::build_fai(Path::new("./tests/valid.fasta.gz")) {
+            Ok(_) => panic!("Plain gzip was indexed."),
+            Err(error) => error,
+        };
+        assert!(error.to_string().contains("not BGZF compressed"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
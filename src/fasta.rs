@@ -1,103 +1,1085 @@
-use crate::data::{Scaffold, Symbol};
+use crate::data::{symbols_to_string, Alphabet, PackedSequence, Scaffold, Symbol, SYMBOL_TABLE};
+use crate::fai::load_fai;
+use crate::names::NameNormalizer;
+use crate::progress::{CountingReader, LoadOptions, ProgressTracker};
 use anyhow::{Context, Result};
 
+use flate2::read::MultiGzDecoder;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
-use std::path::Path;
+use std::io::{prelude::*, BufReader, BufWriter, SeekFrom};
+use std::ops::Range;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// First two bytes of every gzip member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Number of symbols per sequence line of written FASTA files.
+const LINE_WIDTH: usize = 60;
+
+/// Whether a FASTA line is blank or a legacy `;` comment, such lines are
+/// ignored.
+pub(crate) fn is_ignored_line(line: &[u8]) -> bool {
+    line.first() == Some(&b';') || line.iter().all(u8::is_ascii_whitespace)
+}
+
+/// Handling of IUPAC ambiguity codes (`R`, `Y`, `S`, `W`, `K`, `M`, `B`,
+/// `D`, `H` and `V`) in FASTA sequences.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AmbiguityPolicy {
+    #[default]
+    Error,
+    /// Ambiguity codes are loaded as `Symbol::Other`.
+    MapToOther,
+    /// Ambiguity codes are dropped from the sequence.
+    Skip,
+}
+
+fn is_ambiguity_code(c: char) -> bool {
+    matches!(
+        c.to_ascii_uppercase(),
+        'R' | 'Y' | 'S' | 'W' | 'K' | 'M' | 'B' | 'D' | 'H' | 'V'
+    )
+}
+
+/// Handling of alignment gap characters `-` and `*` in FASTA sequences.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GapPolicy {
+    #[default]
+    Error,
+    /// Gaps are loaded as `Symbol::Other`, keeping file coordinates.
+    MapToOther,
+    /// Gaps are dropped from the sequence. Coordinates of the loaded
+    /// scaffold no longer match columns of the file past the first gap.
+    Skip,
+}
+
+fn is_gap(c: char) -> bool {
+    c == '-' || c == '*'
+}
+
+/// Options of FASTA loading. The defaults are strict.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FastaOptions {
+    pub ambiguity: AmbiguityPolicy,
+    pub gap_policy: GapPolicy,
+    /// Record lowercase (soft-masked) bases in `Scaffold::masked_regions`.
+    pub keep_mask: bool,
+    /// Use only the first whitespace delimited token of FASTA headers as the
+    /// scaffold name and store the rest as `Scaffold::description`. A
+    /// `[topology=circular]` modifier in the description marks the scaffold
+    /// as circular.
+    pub split_header: bool,
+    /// Store sequences in two bits per symbol, see `PackedSequence`. The
+    /// unpacked form is never built during loading.
+    pub pack: bool,
+    /// Fail on records without any sequence instead of loading them as
+    /// empty scaffolds. See also `empty_scaffolds`.
+    pub reject_empty: bool,
+}
+
+/// FASTA header modifier of circular sequences as used by NCBI.
+const CIRCULAR_MODIFIER: &str = "[topology=circular]";
 
 struct ScaffoldBuilder {
     name: String,
+    description: Option<String>,
     sequence: Vec<Symbol>,
+    /// Used instead of `sequence` when packing while loading.
+    packed: Option<PackedSequence>,
+    masked: Vec<Range<usize>>,
+    /// 1-based line number of the record header.
+    header_line: usize,
 }
 
 impl ScaffoldBuilder {
     fn new(name: String) -> Self {
         Self {
             name,
+            description: None,
             sequence: Vec::new(),
+            packed: None,
+            masked: Vec::new(),
+            header_line: 0,
         }
     }
 
-    fn extend_from_str(&mut self, seq: &str) -> Result<()> {
-        let seq = seq
-            .chars()
-            .map(|c| match c {
-                'A' | 'a' => Ok(Symbol::Adenine),
-                'C' | 'c' => Ok(Symbol::Cytosine),
-                'T' | 't' => Ok(Symbol::Thymine),
-                'G' | 'g' => Ok(Symbol::Guanine),
-                'N' | 'n' => Ok(Symbol::Other),
-                _ => Err(anyhow!("Encountered invalid symbol {}.", c)),
-            })
-            .collect::<Result<Vec<Symbol>>>()?;
-        self.sequence.extend(seq);
+    fn len(&self) -> usize {
+        match self.packed {
+            Some(ref packed) => packed.len(),
+            None => self.sequence.len(),
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        match self.packed {
+            Some(ref mut packed) => packed.reserve(additional),
+            None => self.sequence.reserve(additional),
+        }
+    }
+
+    /// Reserves space for the whole sequence of a record of known length.
+    fn reserve_exact(&mut self, len: usize) {
+        match self.packed {
+            Some(ref mut packed) => packed.reserve(len),
+            None => self.sequence.reserve_exact(len),
+        }
+    }
+
+    fn push(&mut self, symbol: Symbol) {
+        match self.packed {
+            Some(ref mut packed) => packed.push(symbol),
+            None => self.sequence.push(symbol),
+        }
+    }
+
+    /// Creates a builder from a FASTA header without the leading `>`.
+    fn from_header(header: &str, options: &FastaOptions) -> Self {
+        let mut builder = if options.split_header {
+            let header = header.trim_start();
+            let (name, description) = match header.split_once(char::is_whitespace) {
+                Some((name, rest)) => (name, rest.trim()),
+                None => (header, ""),
+            };
+            let mut builder = Self::new(String::from(name));
+            if !description.is_empty() {
+                builder.description = Some(String::from(description));
+            }
+            builder
+        } else {
+            Self::new(String::from(header))
+        };
+
+        if options.pack {
+            builder.packed = Some(PackedSequence::new());
+        }
+        builder
+    }
+
+    /// Appends a sequence line, `line_number` is only used in error messages.
+    fn extend_from_str(
+        &mut self,
+        seq: &str,
+        line_number: usize,
+        options: &FastaOptions,
+    ) -> Result<()> {
+        self.reserve(seq.len());
+        for (i, &byte) in seq.as_bytes().iter().enumerate() {
+            let symbol = match SYMBOL_TABLE[byte as usize] {
+                Some(symbol) => symbol,
+                None => {
+                    // Bytes of multi-byte characters are all invalid, so the
+                    // first one is always at a character boundary.
+                    let c = seq[i..].chars().next().unwrap();
+                    match (options.ambiguity, options.gap_policy) {
+                        (AmbiguityPolicy::MapToOther, _) if is_ambiguity_code(c) => Symbol::Other,
+                        (AmbiguityPolicy::Skip, _) if is_ambiguity_code(c) => continue,
+                        (_, GapPolicy::MapToOther) if is_gap(c) => Symbol::Other,
+                        (_, GapPolicy::Skip) if is_gap(c) => continue,
+                        _ => bail!(
+                            "Invalid symbol '{}' at line {}, column {} in record {}.",
+                            c,
+                            line_number,
+                            seq[..i].chars().count() + 1,
+                            self.name
+                        ),
+                    }
+                }
+            };
+            if options.keep_mask && byte.is_ascii_lowercase() {
+                let position = self.len();
+                match self.masked.last_mut() {
+                    Some(range) if range.end == position => range.end += 1,
+                    _ => self.masked.push(position..position + 1),
+                }
+            }
+            self.push(symbol);
+        }
         Ok(())
     }
 
     fn build(self) -> Scaffold {
-        let Self { name, sequence } = self;
-        Scaffold::new(name, sequence)
+        let Self {
+            name,
+            description,
+            sequence,
+            packed,
+            masked,
+            ..
+        } = self;
+        let scaffold = match packed {
+            Some(packed) => Scaffold::new_packed(name, packed),
+            None => Scaffold::new(name, sequence),
+        };
+        let circular = description
+            .as_deref()
+            .is_some_and(|d| d.contains(CIRCULAR_MODIFIER));
+        scaffold
+            .with_description(description)
+            .with_masked_regions(masked)
+            .with_circular(circular)
+    }
+}
+
+/// Streaming FASTA reader yielding scaffolds one at a time, each as soon as
+/// its record ends.
+///
+/// A header immediately followed by another header (or the end of input)
+/// yields a scaffold with an empty sequence. Input without any record is an
+/// error. After the first error the iterator is exhausted.
+pub struct FastaReader<R: BufRead> {
+    reader: R,
+    source: String,
+    options: FastaOptions,
+    /// Expected sequence lengths keyed by the first word of headers.
+    capacity_hints: HashMap<String, usize>,
+    builder: Option<ScaffoldBuilder>,
+    line: String,
+    /// Number of lines read so far.
+    line_number: usize,
+    emitted: bool,
+    done: bool,
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_source(reader, String::from("input"))
+    }
+
+    /// Replaces the default, strict, options.
+    pub fn with_options(mut self, options: FastaOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets expected sequence lengths used to pre-allocate scaffold
+    /// sequences, keyed by the first word of headers.
+    fn with_capacity_hints(mut self, capacity_hints: HashMap<String, usize>) -> Self {
+        self.capacity_hints = capacity_hints;
+        self
+    }
+
+    /// Numbers lines as if `lines` lines preceded the input, e.g. for FASTA
+    /// embedded in another file.
+    pub(crate) fn with_line_offset(mut self, lines: usize) -> Self {
+        self.line_number = lines;
+        self
+    }
+
+    /// Creates a reader whose errors describe the input as `source`.
+    pub(crate) fn with_source(reader: R, source: String) -> Self {
+        Self {
+            reader,
+            source,
+            options: FastaOptions::default(),
+            capacity_hints: HashMap::new(),
+            builder: None,
+            line: String::new(),
+            line_number: 0,
+            emitted: false,
+            done: false,
+        }
+    }
+
+    fn finish(&self, builder: ScaffoldBuilder) -> Result<Scaffold> {
+        ensure!(
+            !self.options.reject_empty || builder.len() > 0,
+            "Scaffold {} with header on line {} of FASTA {} has an empty sequence.",
+            builder.name,
+            builder.header_line,
+            self.source
+        );
+        Ok(builder.build())
+    }
+
+    fn next_scaffold(&mut self) -> Result<Option<Scaffold>> {
+        loop {
+            self.line.clear();
+            let num_bytes = self.reader.read_line(&mut self.line).with_context(|| {
+                format!(
+                    "Failed to read line {} of FASTA {}.",
+                    self.line_number + 1,
+                    self.source
+                )
+            })?;
+            self.line_number += 1;
+
+            if num_bytes == 0 {
+                return match self.builder.take() {
+                    Some(builder) => Ok(Some(self.finish(builder)?)),
+                    None if self.emitted => Ok(None),
+                    None => bail!("Empty FASTA {}.", self.source),
+                };
+            }
+
+            let line = self.line.strip_suffix('\n').unwrap_or(&self.line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+
+            if is_ignored_line(line.as_bytes()) {
+                continue;
+            } else if let Some(header) = line.strip_prefix('>') {
+                let mut builder = ScaffoldBuilder::from_header(header, &self.options);
+                let key = header.split_whitespace().next().unwrap_or("");
+                if let Some(&capacity) = self.capacity_hints.get(key) {
+                    builder.reserve_exact(capacity);
+                }
+                builder.header_line = self.line_number;
+                let previous = self.builder.replace(builder);
+                if let Some(previous) = previous {
+                    return Ok(Some(self.finish(previous)?));
+                }
+            } else {
+                let (source, line_number) = (&self.source, self.line_number);
+                match self.builder {
+                    Some(ref mut b) => b
+                        .extend_from_str(line, line_number, &self.options)
+                        .with_context(|| {
+                            format!(
+                                "Invalid sequence of record {} on line {} of FASTA {}.",
+                                b.name, line_number, source
+                            )
+                        })?,
+                    None => bail!(
+                        "Invalid FASTA {}, sequence on line {} precedes the first header.",
+                        source,
+                        line_number
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl FastaReader<BufReader<Box<dyn Read>>> {
+    /// Opens a FASTA file. Gzip compressed files, recognized by their magic
+    /// bytes, are transparently decompressed.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::with_source(
+            open_maybe_gzip(path)?,
+            format!("file {}", path.display()),
+        ))
+    }
+}
+
+/// Opens a file for buffered reading, decompressing it if it starts with the
+/// gzip magic bytes. Multi-member gzip files are fully decompressed, a
+/// truncated or corrupt stream results in a read error.
+pub(crate) fn open_maybe_gzip(path: &Path) -> Result<BufReader<Box<dyn Read>>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+    let gzip = is_gzip(&mut file, path)?;
+    Ok(decompress(file, gzip))
+}
+
+/// Checks whether a file starts with the gzip magic bytes and rewinds it.
+pub(crate) fn is_gzip(file: &mut File, path: &Path) -> Result<bool> {
+    let mut magic = [0u8; 2];
+    let mut num_read = 0;
+    while num_read < magic.len() {
+        let n = file
+            .read(&mut magic[num_read..])
+            .with_context(|| format!("Failed to read file {}.", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        num_read += n;
+    }
+    file.seek(SeekFrom::Start(0))
+        .with_context(|| format!("Failed to read file {}.", path.display()))?;
+    Ok(num_read == magic.len() && magic == GZIP_MAGIC)
+}
+
+pub(crate) fn decompress<R: Read + 'static>(reader: R, gzip: bool) -> BufReader<Box<dyn Read>> {
+    let inner: Box<dyn Read> = if gzip {
+        Box::new(MultiGzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+    BufReader::new(inner)
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = Result<Scaffold>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_scaffold();
+        match result {
+            Ok(Some(scaffold)) => {
+                self.emitted = true;
+                Some(Ok(scaffold))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
     }
 }
 
 /// Load FASTA file.
 pub fn load_fasta(path: &Path) -> Result<Vec<Scaffold>> {
-    let mut reader = {
-        let file =
-            File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
-        BufReader::new(file)
+    load_fasta_with_options(path, &FastaOptions::default())
+}
+
+/// Load FASTA file with non-default options.
+pub fn load_fasta_with_options(path: &Path, options: &FastaOptions) -> Result<Vec<Scaffold>> {
+    load_fasta_with_progress(path, options, LoadOptions::default())
+}
+
+/// Load FASTA file, reporting progress per `load`. A record counts as
+/// parsed once its scaffold is complete.
+pub fn load_fasta_with_progress(
+    path: &Path,
+    options: &FastaOptions,
+    load: LoadOptions,
+) -> Result<Vec<Scaffold>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+    let gzip = is_gzip(&mut file, path)?;
+    let capacity_hints = if gzip {
+        HashMap::new()
+    } else {
+        capacity_hints(path, &file)
     };
+    let (mut tracker, bytes_read) = ProgressTracker::new(load, &file);
+    let reader = decompress(CountingReader::new(file, bytes_read), gzip);
+
+    let mut scaffolds = Vec::new();
+    for scaffold in FastaReader::with_source(reader, format!("file {}", path.display()))
+        .with_options(*options)
+        .with_capacity_hints(capacity_hints)
+    {
+        scaffolds.push(scaffold?);
+        tracker.record();
+    }
+    tracker.finish();
+
+    check_unique_names(&scaffolds, &format!("file {}", path.display()))?;
+    Ok(scaffolds)
+}
 
+/// Loads several FASTA files, e.g. one per chromosome, concatenating their
+/// scaffolds in the order of `paths`. Fails if a scaffold name appears in
+/// more than one file, naming both files.
+pub fn load_fasta_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<Scaffold>> {
     let mut scaffolds = Vec::new();
+    let mut origins: HashMap<String, usize> = HashMap::new();
+
+    for (i, path) in paths.iter().enumerate() {
+        let path = path.as_ref();
+        for scaffold in load_fasta(path)? {
+            if let Some(&first) = origins.get(scaffold.name()) {
+                bail!(
+                    "Scaffold {} is present in both file {} and file {}.",
+                    scaffold.name(),
+                    paths[first].as_ref().display(),
+                    path.display()
+                );
+            }
+            origins.insert(String::from(scaffold.name()), i);
+            scaffolds.push(scaffold);
+        }
+    }
 
-    let mut builder: Option<ScaffoldBuilder> = None;
-    let mut line = String::new();
+    Ok(scaffolds)
+}
 
-    loop {
-        let num_bytes = reader
-            .read_line(&mut line)
-            .with_context(|| format!("Failed to read file {}.", path.display()))?;
+/// Sequence lengths from the `.fai` index next to a FASTA file, if there is
+/// a readable one. The index is only a hint, lengths are capped by the file
+/// size in case it is stale.
+fn capacity_hints(path: &Path, file: &File) -> HashMap<String, usize> {
+    let entries = match load_fai(path) {
+        Ok(Some(entries)) => entries,
+        _ => return HashMap::new(),
+    };
+    let file_len = file.metadata().map_or(0, |m| m.len());
+    entries
+        .into_iter()
+        .map(|entry| (entry.name, entry.length.min(file_len) as usize))
+        .collect()
+}
+
+/// Names of scaffolds with an empty sequence, e.g. placeholder records.
+pub fn empty_scaffolds(scaffolds: &[Scaffold]) -> Vec<&str> {
+    scaffolds
+        .iter()
+        .filter(|s| s.is_empty())
+        .map(|s| s.name())
+        .collect()
+}
+
+/// Loads a FASTA file with scaffold names rewritten by `normalizer`. Names
+/// have to be unique after normalization.
+pub fn load_fasta_normalized(path: &Path, normalizer: &NameNormalizer) -> Result<Vec<Scaffold>> {
+    let scaffolds: Vec<Scaffold> = load_fasta(path)?
+        .into_iter()
+        .map(|s| {
+            let name = normalizer.normalize(s.name());
+            s.with_name(name)
+        })
+        .collect();
+    check_unique_names(&scaffolds, &format!("file {}", path.display()))?;
+    Ok(scaffolds)
+}
+
+/// Load FASTA file into a map keyed by scaffold names.
+pub fn load_fasta_map(path: &Path) -> Result<HashMap<String, Scaffold>> {
+    Ok(load_fasta(path)?
+        .into_iter()
+        .map(|scaffold| (String::from(scaffold.name()), scaffold))
+        .collect())
+}
+
+/// Fails if two scaffolds share a name, naming both (1-based) record
+/// positions.
+pub(crate) fn check_unique_names(scaffolds: &[Scaffold], source: &str) -> Result<()> {
+    let mut positions: HashMap<&str, usize> = HashMap::with_capacity(scaffolds.len());
+    for (i, scaffold) in scaffolds.iter().enumerate() {
+        if let Some(first) = positions.insert(scaffold.name(), i) {
+            bail!(
+                "Duplicate scaffold {} in FASTA {}, records {} and {}.",
+                scaffold.name(),
+                source,
+                first + 1,
+                i + 1
+            );
+        }
+    }
+    Ok(())
+}
 
-        if num_bytes == 0 {
+/// Loads a FASTA file by memory-mapping it and scanning its bytes directly,
+/// allocating each scaffold sequence once. The result is identical to
+/// `load_fasta` with default options, except that gzip compressed files are
+/// not supported.
+#[cfg(feature = "memmap2")]
+pub fn load_fasta_mmap(path: &Path) -> Result<Vec<Scaffold>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+    // Safety: the file must not be modified while it is mapped, as with any
+    // other reader of the file this results in garbage data.
+    let data = unsafe { memmap2::Mmap::map(&file) }
+        .with_context(|| format!("Failed to map file {}.", path.display()))?;
+    ensure!(
+        !data.starts_with(&GZIP_MAGIC),
+        "Memory-mapped loading of compressed file {} is not supported.",
+        path.display()
+    );
+    let scaffolds = scan_fasta_bytes(&data)
+        .with_context(|| format!("Failed to load FASTA file {}.", path.display()))?;
+    check_unique_names(&scaffolds, &format!("file {}", path.display()))?;
+    Ok(scaffolds)
+}
+
+#[cfg(feature = "memmap2")]
+fn scan_fasta_bytes(data: &[u8]) -> Result<Vec<Scaffold>> {
+    split_records(data)?
+        .iter()
+        .map(|record| decode_record(data, record))
+        .collect()
+}
+
+/// Loads a FASTA file by splitting it into records and decoding the
+/// records on the Rayon thread pool. The result is identical to `load_fasta`
+/// with default options. The whole file is held in memory during loading.
+#[cfg(feature = "rayon")]
+pub fn load_fasta_parallel(path: &Path) -> Result<Vec<Scaffold>> {
+    let mut data = Vec::new();
+    open_maybe_gzip(path)?
+        .read_to_end(&mut data)
+        .with_context(|| format!("Failed to read file {}.", path.display()))?;
+
+    let scaffolds = split_records(&data)
+        .and_then(|records| {
+            records
+                .par_iter()
+                .map(|record| decode_record(&data, record))
+                .collect::<Result<Vec<Scaffold>>>()
+        })
+        .with_context(|| format!("Failed to load FASTA file {}.", path.display()))?;
+    check_unique_names(&scaffolds, &format!("file {}", path.display()))?;
+    Ok(scaffolds)
+}
+
+/// Byte ranges of a FASTA record within the whole input.
+#[cfg(any(feature = "memmap2", feature = "rayon"))]
+struct RecordBytes {
+    /// Header without the leading `>` and the line ending.
+    header: Range<usize>,
+    body: Range<usize>,
+}
+
+#[cfg(any(feature = "memmap2", feature = "rayon"))]
+fn strip_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Splits FASTA bytes into records, each starting with a `>` at a line
+/// start.
+#[cfg(any(feature = "memmap2", feature = "rayon"))]
+fn split_records(data: &[u8]) -> Result<Vec<RecordBytes>> {
+    let mut records = Vec::new();
+    let mut position = 0;
+    // Lines preceding the first header must all be ignored lines.
+    for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+        if line.first() == Some(&b'>') {
             break;
         }
+        ensure!(
+            is_ignored_line(line),
+            "Sequence on line {} precedes the first header.",
+            i + 1
+        );
+        position = (position + line.len() + 1).min(data.len());
+    }
+    ensure!(position < data.len(), "Empty FASTA.");
+
+    while position < data.len() {
+        let line_end = data[position..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| position + i);
+        let header_end = position + 1 + strip_cr(&data[position + 1..line_end]).len();
+
+        // The record ends right before the next line starting with `>`.
+        let body_end = data[line_end..]
+            .windows(2)
+            .position(|w| w == b"\n>")
+            .map_or(data.len(), |i| line_end + i + 1);
+
+        records.push(RecordBytes {
+            header: position + 1..header_end,
+            body: (line_end + 1).min(body_end)..body_end,
+        });
+        position = body_end;
+    }
+
+    Ok(records)
+}
+
+/// Decodes a record found by `split_records`. Errors name the record and
+/// the line of the offending byte.
+#[cfg(any(feature = "memmap2", feature = "rayon"))]
+fn decode_record(data: &[u8], record: &RecordBytes) -> Result<Scaffold> {
+    let line_of = |position: usize| 1 + data[..position].iter().filter(|&&b| b == b'\n').count();
+
+    let name = match std::str::from_utf8(&data[record.header.clone()]) {
+        Ok(name) => name,
+        Err(_) => bail!(
+            "Invalid scaffold name on line {}.",
+            line_of(record.header.start)
+        ),
+    };
 
-        if line.ends_with('\n') {
-            line.pop();
-            if line.ends_with('\r') {
-                line.pop();
+    let body = &data[record.body.clone()];
+    let newlines = body.iter().filter(|&&b| b == b'\n').count();
+    let mut sequence = Vec::with_capacity(body.len() - newlines);
+    let mut line_start = record.body.start;
+    for line in body.split(|&b| b == b'\n') {
+        if is_ignored_line(line) {
+            line_start += line.len() + 1;
+            continue;
+        }
+        for (i, &byte) in strip_cr(line).iter().enumerate() {
+            match SYMBOL_TABLE[byte as usize] {
+                Some(symbol) => sequence.push(symbol),
+                None => bail!(
+                    "Invalid sequence of record {} on line {}, column {}, encountered invalid \
+                     byte 0x{:02x}.",
+                    name,
+                    line_of(line_start + i),
+                    i + 1,
+                    byte
+                ),
             }
         }
+        line_start += line.len() + 1;
+    }
+
+    Ok(Scaffold::new(String::from(name), sequence))
+}
+
+/// Streams a FASTA file scaffold by scaffold, calling `f` on each of them.
+/// Only a single scaffold is held in memory at a time. Records are loaded
+/// per `options` as by `load_fasta_with_options`.
+///
+/// Processing stops at the first error returned by `f`, which is then
+/// returned. On success, the number of processed scaffolds is returned.
+pub fn process_fasta<F>(path: &Path, options: &FastaOptions, mut f: F) -> Result<usize>
+where
+    F: FnMut(Scaffold) -> Result<()>,
+{
+    let mut count = 0;
+    for scaffold in FastaReader::open(path)?.with_options(*options) {
+        f(scaffold?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Loads FASTA formatted data from any buffered reader, e.g. a network
+/// stream. Errors name the offending record and line.
+pub fn load_fasta_from_reader<R: BufRead>(reader: R) -> Result<Vec<Scaffold>> {
+    let scaffolds = FastaReader::new(reader).collect::<Result<Vec<Scaffold>>>()?;
+    check_unique_names(&scaffolds, "input")?;
+    Ok(scaffolds)
+}
+
+/// Parses FASTA formatted bytes, e.g. the whole content of a FASTA file,
+/// with the same options as `load_fasta_with_options`.
+///
+/// This never panics: invalid input of any kind, including invalid UTF-8,
+/// results in an error.
+pub fn parse_fasta_bytes(bytes: &[u8], options: &FastaOptions) -> Result<Vec<Scaffold>> {
+    let scaffolds = FastaReader::new(bytes)
+        .with_options(*options)
+        .collect::<Result<Vec<Scaffold>>>()?;
+    check_unique_names(&scaffolds, "input")?;
+    Ok(scaffolds)
+}
+
+/// Options of FASTA writing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FastaWriteOptions {
+    /// Number of symbols per sequence line, 0 disables wrapping.
+    pub line_width: usize,
+    /// Alphabet of the written sequences, e.g. `Alphabet::Rna` for
+    /// transcripts.
+    pub alphabet: Alphabet,
+}
+
+/// Writes scaffolds to a FASTA file, wrapping sequences at `line_width`
+/// symbols per line. A `line_width` of 0 disables wrapping. Descriptions
+/// are written after the name, separated by a space, circular scaffolds are
+/// marked with a `[topology=circular]` modifier and masked regions are
+/// written in lowercase.
+pub fn write_fasta(path: &Path, scaffolds: &[Scaffold], line_width: usize) -> Result<()> {
+    write_fasta_with_options(
+        path,
+        scaffolds,
+        &FastaWriteOptions {
+            line_width,
+            alphabet: Alphabet::Dna,
+        },
+    )
+}
+
+/// Same as `write_fasta`, with the alphabet configurable.
+pub fn write_fasta_with_options(
+    path: &Path,
+    scaffolds: &[Scaffold],
+    options: &FastaWriteOptions,
+) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create file {}.", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    for scaffold in scaffolds {
+        write_fasta_record(
+            &mut writer,
+            scaffold.name(),
+            scaffold,
+            options.line_width,
+            options.alphabet,
+        )
+        .with_context(|| format!("Failed to write file {}.", path.display()))?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write file {}.", path.display()))
+}
+
+/// Way of resolving two records with the same name but different sequences
+/// when merging FASTA files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollisionPolicy {
+    /// Fail before anything is written.
+    Error,
+    KeepFirst,
+    KeepLast,
+    /// Keep both, appending `.2`, `.3`, … to the name of the later record.
+    RenameWithSuffix,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Resolution {
+    /// Sequences are identical, the later record was dropped.
+    Deduplicated,
+    /// The later record was dropped.
+    KeptFirst,
+    /// The earlier record was dropped.
+    KeptLast,
+    /// The later record was written under the given name.
+    Renamed(String),
+}
+
+/// Record whose name was already taken by an earlier record. Inputs are
+/// identified by their index.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Collision {
+    pub name: String,
+    pub existing_input: usize,
+    pub incoming_input: usize,
+    pub resolution: Resolution,
+}
+
+impl Collision {
+    /// Returns true if the two records disagree on the sequence.
+    pub fn is_conflict(&self) -> bool {
+        self.resolution != Resolution::Deduplicated
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MergeReport {
+    records: Vec<usize>,
+    written: usize,
+    collisions: Vec<Collision>,
+}
+
+impl MergeReport {
+    /// Number of records read from each input.
+    pub fn records(&self) -> &[usize] {
+        &self.records
+    }
+
+    /// Number of records written to the output.
+    pub fn written(&self) -> usize {
+        self.written
+    }
 
-        if line.starts_with('>') {
-            if let Some(builder) = builder {
-                scaffolds.push(builder.build());
+    /// All name collisions in the order they were encountered.
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+
+    /// Collisions of records with the same name but different sequences.
+    pub fn conflicts(&self) -> Vec<&Collision> {
+        self.collisions.iter().filter(|c| c.is_conflict()).collect()
+    }
+}
+
+/// Occurrence of a record found during the first pass over the inputs.
+struct Occurrence {
+    input: usize,
+    name: String,
+    fingerprint: (usize, u64),
+}
+
+/// Merges FASTA files into a single FASTA output, keeping the input order.
+///
+/// Scaffold names are the first words of headers. A record whose name was
+/// already taken by an earlier record is dropped if both sequences are
+/// identical; otherwise it is resolved per `policy`. Written records keep
+/// their descriptions, soft-masked regions and circularity.
+///
+/// The inputs are read twice, once to resolve the collisions and once to
+/// write the output, so only a single record is held in memory at a time.
+/// Sequences are first compared by length and a 64-bit hash; records of the
+/// same name whose hashes match are read once more and held in memory to be
+/// compared in full.
+pub fn merge_fasta_files<W: Write>(
+    inputs: &[PathBuf],
+    policy: CollisionPolicy,
+    mut output: W,
+) -> Result<MergeReport> {
+    let options = FastaOptions {
+        split_header: true,
+        keep_mask: true,
+        ..FastaOptions::default()
+    };
+    let mut report = MergeReport::default();
+    let mut occurrences = Vec::new();
+
+    for (input, path) in inputs.iter().enumerate() {
+        let count = process_fasta(path, &options, |scaffold| {
+            occurrences.push(Occurrence {
+                input,
+                name: String::from(scaffold.name()),
+                fingerprint: fingerprint(&scaffold.sequence()),
+            });
+            Ok(())
+        })?;
+        report.records.push(count);
+    }
+
+    let mut fingerprints: HashMap<(&str, (usize, u64)), usize> = HashMap::new();
+    for occurrence in occurrences.iter() {
+        *fingerprints
+            .entry((&occurrence.name, occurrence.fingerprint))
+            .or_insert(0) += 1;
+    }
+    let suspects: HashSet<usize> = occurrences
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| fingerprints[&(o.name.as_str(), o.fingerprint)] > 1)
+        .map(|(i, _)| i)
+        .collect();
+    let mut sequences: HashMap<usize, Vec<Symbol>> = HashMap::with_capacity(suspects.len());
+    if !suspects.is_empty() {
+        let mut i = 0;
+        for path in inputs {
+            process_fasta(path, &options, |scaffold| {
+                if suspects.contains(&i) {
+                    sequences.insert(i, scaffold.sequence().into_owned());
+                }
+                i += 1;
+                Ok(())
+            })?;
+        }
+    }
+    let identical = |a: usize, b: usize| {
+        occurrences[a].fingerprint == occurrences[b].fingerprint && sequences[&a] == sequences[&b]
+    };
+
+    let mut taken: HashSet<String> = occurrences.iter().map(|o| o.name.clone()).collect();
+    // Output name of each occurrence, `None` for dropped ones.
+    let mut names: Vec<Option<String>> = vec![None; occurrences.len()];
+    // Index of the occurrence currently written under each name.
+    let mut holders: HashMap<&str, usize> = HashMap::new();
+
+    for (i, occurrence) in occurrences.iter().enumerate() {
+        let holder = match holders.get(occurrence.name.as_str()) {
+            Some(&holder) => holder,
+            None => {
+                holders.insert(&occurrence.name, i);
+                names[i] = Some(occurrence.name.clone());
+                continue;
             }
+        };
 
-            builder = Some(ScaffoldBuilder::new(String::from(&line[1..])));
+        let resolution = if identical(holder, i) {
+            Resolution::Deduplicated
         } else {
-            match builder {
-                Some(ref mut b) => b.extend_from_str(&line)?,
-                None => bail!("Ivalid FASTA file {}.", path.display()),
+            match policy {
+                CollisionPolicy::Error => bail!(
+                    "Record {} of input {} differs from the record of the same name in input {}.",
+                    occurrence.name,
+                    inputs[occurrence.input].display(),
+                    inputs[occurrences[holder].input].display()
+                ),
+                CollisionPolicy::KeepFirst => Resolution::KeptFirst,
+                CollisionPolicy::KeepLast => {
+                    names[holder] = None;
+                    names[i] = Some(occurrence.name.clone());
+                    holders.insert(&occurrence.name, i);
+                    Resolution::KeptLast
+                }
+                CollisionPolicy::RenameWithSuffix => {
+                    let name = (2..)
+                        .map(|suffix| format!("{}.{}", occurrence.name, suffix))
+                        .find(|name| !taken.contains(name))
+                        .unwrap();
+                    taken.insert(name.clone());
+                    names[i] = Some(name.clone());
+                    Resolution::Renamed(name)
+                }
             }
-        }
+        };
 
-        line.clear();
+        report.collisions.push(Collision {
+            name: occurrence.name.clone(),
+            existing_input: occurrences[holder].input,
+            incoming_input: occurrence.input,
+            resolution,
+        });
     }
 
-    match builder {
-        Some(builder) => scaffolds.push(builder.build()),
-        None => bail!("Empty FASTA file {}.", path.display()),
+    let mut names = names.into_iter();
+    for path in inputs {
+        process_fasta(path, &options, |scaffold| {
+            if let Some(name) = names.next().flatten() {
+                write_fasta_record(&mut output, &name, &scaffold, LINE_WIDTH, Alphabet::Dna)?;
+                report.written += 1;
+            }
+            Ok(())
+        })?;
     }
+    output.flush()?;
 
-    Ok(scaffolds)
+    Ok(report)
+}
+
+/// Returns sequence length and its FNV-1a hash.
+fn fingerprint(sequence: &[Symbol]) -> (usize, u64) {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let hash = sequence.iter().fold(OFFSET, |hash, &symbol| {
+        let byte: u8 = symbol.into();
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+    (sequence.len(), hash)
+}
+
+/// Writes a scaffold under `name`, see `write_fasta`.
+fn write_fasta_record<W: Write>(
+    writer: &mut W,
+    name: &str,
+    scaffold: &Scaffold,
+    line_width: usize,
+    alphabet: Alphabet,
+) -> Result<()> {
+    write!(writer, ">{}", name)?;
+    if let Some(description) = scaffold.description() {
+        write!(writer, " {}", description)?;
+    }
+    if scaffold.is_circular()
+        && !scaffold
+            .description()
+            .is_some_and(|d| d.contains(CIRCULAR_MODIFIER))
+    {
+        write!(writer, " {}", CIRCULAR_MODIFIER)?;
+    }
+    writeln!(writer)?;
+
+    let sequence = scaffold.sequence();
+    let masked = scaffold.masked_regions();
+    let line_width = if line_width == 0 {
+        sequence.len().max(1)
+    } else {
+        line_width
+    };
+    let mut next_mask = 0;
+    for (i, line) in sequence.chunks(line_width).enumerate() {
+        let (start, end) = (i * line_width, i * line_width + line.len());
+        let mut text = symbols_to_string(line, alphabet).into_bytes();
+        while next_mask < masked.len() && masked[next_mask].end <= start {
+            next_mask += 1;
+        }
+        for range in masked[next_mask..].iter().take_while(|r| r.start < end) {
+            text[range.start.max(start) - start..range.end.min(end) - start].make_ascii_lowercase();
+        }
+        writer.write_all(&text)?;
+        writeln!(writer)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::data::Symbol;
-    use std::path::Path;
+    use super::{
+        AmbiguityPolicy, Collision, CollisionPolicy, FastaOptions, FastaReader, FastaWriteOptions,
+        GapPolicy, Resolution,
+    };
+    use crate::data::{parse_sequence, Alphabet, Scaffold, Symbol};
+    use anyhow::Result;
+    use proptest::prelude::*;
+    use std::convert::TryFrom;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn test_load_fasta() {
@@ -112,7 +1094,7 @@ mod test {
         assert_eq!(first.sequence().len(), 280);
         assert_eq!(second.name(), "scaffold_2");
 
-        let expected_sequence = vec![
+        let expected_sequence = [
             Symbol::Thymine,
             Symbol::Thymine,
             Symbol::Cytosine,
@@ -123,4 +1105,807 @@ mod test {
         ];
         assert_eq!(second.sequence(), &expected_sequence[..]);
     }
+
+    #[test]
+    fn test_load_gzipped_fasta() {
+        let plain = super::load_fasta(Path::new("./tests/valid.fasta")).unwrap();
+        // Consists of two gzip members.
+        let gzipped = super::load_fasta(Path::new("./tests/valid.fasta.gz")).unwrap();
+        assert_eq!(plain.len(), gzipped.len());
+        for (a, b) in plain.iter().zip(gzipped.iter()) {
+            assert_eq!(a.name(), b.name());
+            assert_eq!(a.sequence(), b.sequence());
+        }
+
+        let error = match super::load_fasta(Path::new("./tests/truncated.fasta.gz")) {
+            Ok(_) => panic!("Truncated gzip stream was loaded."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Failed to read line 2 of FASTA file ./tests/truncated.fasta.gz."
+        );
+    }
+
+    #[test]
+    fn test_write_fasta() {
+        let directory = env::temp_dir().join(format!("ncrs-write-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let original = super::load_fasta(Path::new("./tests/valid.fasta")).unwrap();
+
+        for &line_width in &[0, 7, 60, 80] {
+            let path = directory.join(format!("{}.fasta", line_width));
+            super::write_fasta(&path, &original, line_width).unwrap();
+
+            let content = fs::read_to_string(&path).unwrap();
+            let longest = content.lines().map(|line| line.len()).max().unwrap();
+            let expected = if line_width == 0 { 280 } else { line_width };
+            assert_eq!(longest, expected.max(">scaffold_1".len()));
+
+            let reloaded = super::load_fasta(&path).unwrap();
+            assert_eq!(original.len(), reloaded.len());
+            for (a, b) in original.iter().zip(reloaded.iter()) {
+                assert_eq!(a.name(), b.name());
+                assert_eq!(a.sequence(), b.sequence());
+            }
+        }
+
+        let path = directory.join("rna.fasta");
+        let transcripts = vec![Scaffold::new(
+            String::from("t1"),
+            parse_sequence("ATGTTNA").unwrap(),
+        )];
+        super::write_fasta_with_options(
+            &path,
+            &transcripts,
+            &FastaWriteOptions {
+                line_width: 4,
+                alphabet: Alphabet::Rna,
+            },
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), ">t1\nAUGU\nUNA\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn parse_fasta_bytes_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..200)) {
+            let _ = super::parse_fasta_bytes(&bytes, &FastaOptions::default());
+        }
+
+        #[test]
+        fn parse_fasta_bytes_near_valid(text in "(>[a-z_ ]{0,5}\r?\n)?([ACGTNacgtnx]{0,10}\r?\n){0,5}") {
+            let _ = super::parse_fasta_bytes(text.as_bytes(), &FastaOptions::default());
+        }
+    }
+
+    #[test]
+    fn test_process_fasta() {
+        let fasta_path = Path::new("./tests/valid.fasta");
+
+        let mut lengths = Vec::new();
+        let count = super::process_fasta(fasta_path, &FastaOptions::default(), |scaffold| {
+            lengths.push(scaffold.sequence().len());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(lengths, vec![280, 7]);
+
+        let ambiguous = Path::new("./tests/ambiguous.fasta");
+        assert!(super::process_fasta(ambiguous, &FastaOptions::default(), |_| Ok(())).is_err());
+        let options = FastaOptions {
+            ambiguity: AmbiguityPolicy::Skip,
+            ..FastaOptions::default()
+        };
+        let mut lengths = Vec::new();
+        let count = super::process_fasta(ambiguous, &options, |scaffold| {
+            lengths.push(scaffold.len());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(lengths, vec![4, 4]);
+
+        let mut calls = 0;
+        let error = super::process_fasta(fasta_path, &FastaOptions::default(), |_| {
+            calls += 1;
+            bail!("Stop.")
+        })
+        .unwrap_err();
+        assert_eq!(format!("{}", error), "Stop.");
+        assert_eq!(calls, 1);
+    }
+
+    fn merge_inputs() -> Vec<PathBuf> {
+        let directory = env::temp_dir().join(format!("ncrs-merge-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        [
+            ">chr1\nACGT\n>chr2\nAAAA\n",
+            ">chr2\nAAAA\n>chr3\nCC\n",
+            ">chr1\nACGG\n>chr1.2\nT\n",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            let path = directory.join(format!("{}.fasta", i));
+            fs::write(&path, content).unwrap();
+            path
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_merge_fasta_files() {
+        let inputs = merge_inputs();
+        let merge = |policy| {
+            let mut output = Vec::new();
+            let report = super::merge_fasta_files(&inputs, policy, &mut output);
+            (report, String::from_utf8(output).unwrap())
+        };
+
+        let (report, output) = merge(CollisionPolicy::KeepFirst);
+        let report = report.unwrap();
+        assert_eq!(report.records(), &[2, 2, 2]);
+        assert_eq!(report.written(), 4);
+        assert_eq!(
+            report.collisions(),
+            &[
+                Collision {
+                    name: String::from("chr2"),
+                    existing_input: 0,
+                    incoming_input: 1,
+                    resolution: Resolution::Deduplicated,
+                },
+                Collision {
+                    name: String::from("chr1"),
+                    existing_input: 0,
+                    incoming_input: 2,
+                    resolution: Resolution::KeptFirst,
+                },
+            ]
+        );
+        assert_eq!(report.conflicts().len(), 1);
+        assert_eq!(output, ">chr1\nACGT\n>chr2\nAAAA\n>chr3\nCC\n>chr1.2\nT\n");
+
+        let (report, output) = merge(CollisionPolicy::KeepLast);
+        assert_eq!(
+            report.unwrap().collisions()[1].resolution,
+            Resolution::KeptLast
+        );
+        assert_eq!(output, ">chr2\nAAAA\n>chr3\nCC\n>chr1\nACGG\n>chr1.2\nT\n");
+
+        let (report, output) = merge(CollisionPolicy::RenameWithSuffix);
+        assert_eq!(
+            report.unwrap().collisions()[1].resolution,
+            Resolution::Renamed(String::from("chr1.3"))
+        );
+        assert_eq!(
+            output,
+            ">chr1\nACGT\n>chr2\nAAAA\n>chr3\nCC\n>chr1.3\nACGG\n>chr1.2\nT\n"
+        );
+
+        let (report, output) = merge(CollisionPolicy::Error);
+        assert!(report.is_err());
+        assert!(output.is_empty());
+
+        fs::remove_dir_all(inputs[0].parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_merge_fasta_metadata() {
+        let directory = env::temp_dir().join(format!("ncrs-merge-meta-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let inputs: Vec<PathBuf> = [
+            ">chr1 first chromosome\nACGT\n>chrM mitochondrion [topology=circular]\nGGCC\n",
+            ">chr1 duplicate\nACGT\n>chr2\nacGTTa\n",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            let path = directory.join(format!("input{}.fasta", i));
+            fs::write(&path, content).unwrap();
+            path
+        })
+        .collect();
+
+        let mut output = Vec::new();
+        let report =
+            super::merge_fasta_files(&inputs, CollisionPolicy::Error, &mut output).unwrap();
+        assert_eq!(report.collisions()[0].resolution, Resolution::Deduplicated);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">chr1 first chromosome\nACGT\n>chrM mitochondrion [topology=circular]\nGGCC\n\
+             >chr2\nacGTTa\n"
+        );
+
+        let merged = directory.join("merged.fasta");
+        super::merge_fasta_files(
+            &inputs,
+            CollisionPolicy::Error,
+            fs::File::create(&merged).unwrap(),
+        )
+        .unwrap();
+        let options = FastaOptions {
+            split_header: true,
+            keep_mask: true,
+            ..FastaOptions::default()
+        };
+        let scaffolds = super::load_fasta_with_options(&merged, &options).unwrap();
+        // Descriptions.
+        assert_eq!(scaffolds[0].description(), Some("first chromosome"));
+        assert_eq!(scaffolds[2].description(), None);
+        // Circularity.
+        assert!(!scaffolds[0].is_circular());
+        assert!(scaffolds[1].is_circular());
+        // Soft-masked regions.
+        assert_eq!(scaffolds[2].masked_regions(), &[0..2, 5..6]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_invalid_fasta() {
+        let path = Path::new("./tests/invalid.fasta");
+        let error = match super::load_fasta(path) {
+            Ok(_) => panic!("Loading did not fail."),
+            Err(error) => error,
+        };
+
+        assert_eq!(
+            format!("{}", error),
+            String::from("Invalid sequence of record scaffold_2 on line 6 of FASTA file ./tests/invalid.fasta.")
+        );
+        assert_eq!(
+            format!("{}", error.root_cause()),
+            String::from("Invalid symbol '@' at line 6, column 12 in record scaffold_2.")
+        );
+    }
+
+    #[test]
+    fn test_gap_policy() {
+        let input = ">gaps\n--*-\n*-\n>b\nA-C\n".as_bytes();
+        let load = |gap_policy| {
+            let options = FastaOptions {
+                gap_policy,
+                ..FastaOptions::default()
+            };
+            FastaReader::new(input)
+                .with_options(options)
+                .collect::<Result<Vec<Scaffold>>>()
+        };
+
+        let error = load(GapPolicy::Error).err().unwrap();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Invalid symbol '-' at line 2, column 1 in record gaps."
+        );
+
+        let mapped = load(GapPolicy::MapToOther).unwrap();
+        assert_eq!(mapped[0].sequence(), &[Symbol::Other; 6][..]);
+        assert_eq!(
+            mapped[1].sequence(),
+            &[Symbol::Adenine, Symbol::Other, Symbol::Cytosine][..]
+        );
+
+        // Skipped gaps shift all following positions.
+        let skipped = load(GapPolicy::Skip).unwrap();
+        assert!(skipped[0].is_empty());
+        assert_eq!(
+            skipped[1].sequence(),
+            &[Symbol::Adenine, Symbol::Cytosine][..]
+        );
+    }
+
+    #[test]
+    fn test_ambiguity_codes() {
+        let path = Path::new("./tests/ambiguous.fasta");
+        let error = match super::load_fasta(path) {
+            Ok(_) => panic!("Ambiguity codes were accepted by default."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid sequence of record ambiguous_1 on line 2 of FASTA file \
+             ./tests/ambiguous.fasta.: Invalid symbol 'R' at line 2, column 2 in record \
+             ambiguous_1."
+        );
+
+        let options = |ambiguity| FastaOptions {
+            ambiguity,
+            ..FastaOptions::default()
+        };
+        let mapped =
+            super::load_fasta_with_options(path, &options(AmbiguityPolicy::MapToOther)).unwrap();
+        assert_eq!(mapped[0].sequence().len(), 24);
+        assert_eq!(mapped[0].sequence()[0], Symbol::Adenine);
+        assert!(mapped[0].sequence()[1..21]
+            .iter()
+            .all(|&s| s == Symbol::Other));
+        assert_eq!(mapped[1].sequence().len(), 4);
+
+        let skipped =
+            super::load_fasta_with_options(path, &options(AmbiguityPolicy::Skip)).unwrap();
+        let expected = [
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Thymine,
+        ];
+        assert_eq!(skipped[0].sequence(), &expected[..]);
+        assert_eq!(skipped[1].sequence(), &expected[..]);
+
+        let invalid = ">a\nAXC\n".as_bytes();
+        let options = options(AmbiguityPolicy::Skip);
+        assert!(FastaReader::new(invalid)
+            .with_options(options)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_soft_mask() {
+        let input = ">a\nACgt\nnnTA\nc\n>b\nAC\n".as_bytes();
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input).collect::<Result<_, _>>().unwrap();
+        assert!(scaffolds[0].masked_regions().is_empty());
+
+        let options = FastaOptions {
+            keep_mask: true,
+            ..FastaOptions::default()
+        };
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input)
+            .with_options(options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(scaffolds[0].masked_regions(), &[2..6, 8..9]);
+        assert!(scaffolds[1].masked_regions().is_empty());
+
+        // Skipped ambiguity codes do not shift the mask.
+        let options = FastaOptions {
+            ambiguity: AmbiguityPolicy::Skip,
+            keep_mask: true,
+            ..FastaOptions::default()
+        };
+        let scaffolds: Vec<Scaffold> = FastaReader::new(">a\nArRc\ngAt\n".as_bytes())
+            .with_options(options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(scaffolds[0].sequence().len(), 5);
+        assert_eq!(scaffolds[0].masked_regions(), &[1..3, 4..5]);
+    }
+
+    #[test]
+    fn test_split_header() {
+        let input = ">s1\nA\n>s2 length=280 organism=X\nA\n>s3   two  spaces \nA\n\
+                     >s4\tcolumn\tseparated\nA\n"
+            .as_bytes();
+        let options = FastaOptions {
+            split_header: true,
+            ..FastaOptions::default()
+        };
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input)
+            .with_options(options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let headers: Vec<(&str, Option<&str>)> = scaffolds
+            .iter()
+            .map(|s| (s.name(), s.description()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("s1", None),
+                ("s2", Some("length=280 organism=X")),
+                ("s3", Some("two  spaces")),
+                ("s4", Some("column\tseparated")),
+            ]
+        );
+
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input).collect::<Result<_, _>>().unwrap();
+        assert_eq!(scaffolds[1].name(), "s2 length=280 organism=X");
+        assert_eq!(scaffolds[1].description(), None);
+    }
+
+    #[test]
+    fn test_fasta_reader() {
+        let input = ">a\nAC\n>empty\n>b\nGT\nTT";
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(scaffolds.len(), 3);
+        assert_eq!(scaffolds[1].name(), "empty");
+        assert!(scaffolds[1].sequence().is_empty());
+        assert_eq!(scaffolds[2].name(), "b");
+        assert_eq!(scaffolds[2].sequence().len(), 4);
+
+        // Scaffolds are yielded before later records are read.
+        let mut reader = FastaReader::new(">a\nAC\n>b\nAXC\n".as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().name(), "a");
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        let mut reader = FastaReader::new("".as_bytes());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn test_load_fasta_mmap() {
+        use crate::simulate::{simulate, SimulationOptions};
+
+        let assert_same = |path: &Path| {
+            let expected = super::load_fasta(path).unwrap();
+            let actual = super::load_fasta_mmap(path).unwrap();
+            assert_eq!(expected.len(), actual.len());
+            for (a, b) in expected.iter().zip(actual.iter()) {
+                assert_eq!(a.name(), b.name());
+                assert_eq!(a.sequence(), b.sequence());
+            }
+        };
+        assert_same(Path::new("./tests/valid.fasta"));
+        assert_same(Path::new("./tests/commented.fasta"));
+
+        assert_eq!(
+            super::load_fasta_mmap(Path::new("./tests/duplicate.fasta"))
+                .unwrap_err()
+                .to_string(),
+            "Duplicate scaffold scaffold_1 in FASTA file ./tests/duplicate.fasta, records 1 and 3."
+        );
+
+        let directory = env::temp_dir().join(format!("ncrs-mmap-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let genome = simulate(&SimulationOptions {
+            scaffolds: 20,
+            ..SimulationOptions::default()
+        });
+        let path = directory.join("simulated.fasta");
+        super::write_fasta(&path, genome.scaffolds(), 80).unwrap();
+        assert_same(&path);
+
+        let path = directory.join("edge_cases.fasta");
+        fs::write(&path, ">a x\r\nACgt\r\n\r\nNN\r\n>\n>b\n>c\nAC\nG").unwrap();
+        assert_same(&path);
+
+        for invalid in [
+            &b""[..],
+            b";a\n\n",
+            b"\nAC\n>a\nAC\n",
+            b">a\nAXC\n",
+            b">\xff\nAC\n",
+        ] {
+            fs::write(&path, invalid).unwrap();
+            assert!(super::load_fasta(&path).is_err());
+            assert!(super::load_fasta_mmap(&path).is_err());
+        }
+        assert!(super::load_fasta_mmap(Path::new("./tests/valid.fasta.gz")).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Compares symbol decoding via the lookup table with per character
+    /// conversion. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_symbol_decoding() {
+        use std::time::Instant;
+
+        let line: String = "ACGTNacgtn".repeat(8);
+        let options = FastaOptions::default();
+        let lines = 200_000;
+
+        let started = Instant::now();
+        let mut builder = super::ScaffoldBuilder::new(String::from("bench"));
+        for _ in 0..lines {
+            builder.extend_from_str(&line, 1, &options).unwrap();
+        }
+        let table = started.elapsed();
+
+        let started = Instant::now();
+        let mut sequence = Vec::new();
+        for _ in 0..lines {
+            let symbols = line
+                .chars()
+                .map(Symbol::try_from)
+                .collect::<anyhow::Result<Vec<Symbol>>>()
+                .unwrap();
+            sequence.extend(symbols);
+        }
+        let chars = started.elapsed();
+
+        assert_eq!(builder.build().sequence(), &sequence[..]);
+        println!("Lookup table: {:?}, per character: {:?}", table, chars);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_load_fasta_parallel() {
+        use crate::simulate::{simulate, SimulationOptions};
+
+        let directory = env::temp_dir().join(format!("ncrs-parallel-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let genome = simulate(&SimulationOptions {
+            scaffolds: 50,
+            ..SimulationOptions::default()
+        });
+        let simulated = directory.join("simulated.fasta");
+        super::write_fasta(&simulated, genome.scaffolds(), 70).unwrap();
+
+        for path in [
+            Path::new("./tests/valid.fasta"),
+            Path::new("./tests/commented.fasta"),
+            Path::new("./tests/valid.fasta.gz"),
+            &simulated,
+        ] {
+            let expected = super::load_fasta(path).unwrap();
+            let actual = super::load_fasta_parallel(path).unwrap();
+            assert_eq!(expected.len(), actual.len());
+            for (a, b) in expected.iter().zip(actual.iter()) {
+                assert_eq!(a.name(), b.name());
+                assert_eq!(a.sequence(), b.sequence());
+            }
+        }
+
+        let path = directory.join("invalid.fasta");
+        fs::write(&path, ">a\nACGT\n>b\r\nAC\r\nGTX\r\n>c\nA\n").unwrap();
+        let error = super::load_fasta_parallel(&path).err().unwrap();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Invalid sequence of record b on line 5, column 3, encountered invalid byte 0x58."
+        );
+        assert!(super::load_fasta_parallel(Path::new("./tests/duplicate.fasta")).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_fasta_with_progress() {
+        use crate::progress::{LoadOptions, Progress};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let reports: Rc<RefCell<Vec<Progress>>> = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&reports);
+        let load = LoadOptions {
+            progress: Some(Box::new(move |progress| sink.borrow_mut().push(progress))),
+            interval: 1,
+        };
+        let path = Path::new("./tests/valid.fasta.gz");
+        super::load_fasta_with_progress(path, &FastaOptions::default(), load).unwrap();
+
+        let reports = reports.borrow();
+        let size = fs::metadata(path).unwrap().len();
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                bytes_read: size,
+                total_bytes: Some(size),
+                records: 2,
+            })
+        );
+        assert!(reports.len() >= 2);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].bytes_read <= w[1].bytes_read));
+    }
+
+    #[test]
+    fn test_capacity_hints() {
+        let directory = env::temp_dir().join(format!("ncrs-capacity-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("genome.fasta");
+        fs::copy("./tests/valid.fasta", &path).unwrap();
+        let expected = super::load_fasta(&path).unwrap();
+
+        crate::fai::build_fai(&path).unwrap().save().unwrap();
+        let scaffolds = super::load_fasta(&path).unwrap();
+        for (a, b) in expected.iter().zip(scaffolds.iter()) {
+            assert_eq!(a.name(), b.name());
+            assert_eq!(a.sequence(), b.sequence());
+            assert_eq!(b.sequence_capacity(), b.sequence().len());
+        }
+
+        // Stale or invalid index does not change the result.
+        fs::write(
+            directory.join("genome.fasta.fai"),
+            "scaffold_1\t3\t12\t70\t71\n",
+        )
+        .unwrap();
+        let scaffolds = super::load_fasta(&path).unwrap();
+        assert_eq!(scaffolds[0].sequence(), expected[0].sequence());
+        fs::write(directory.join("genome.fasta.fai"), "invalid").unwrap();
+        let scaffolds = super::load_fasta(&path).unwrap();
+        assert_eq!(scaffolds[0].sequence(), expected[0].sequence());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_packed() {
+        let options = FastaOptions {
+            pack: true,
+            keep_mask: true,
+            ..FastaOptions::default()
+        };
+        for path in ["./tests/valid.fasta", "./tests/commented.fasta"] {
+            let expected = super::load_fasta(Path::new(path)).unwrap();
+            let packed = super::load_fasta_with_options(Path::new(path), &options).unwrap();
+            for (a, b) in expected.iter().zip(packed.iter()) {
+                let sequence = b.packed_sequence().unwrap();
+                assert_eq!(sequence.len(), a.sequence().len());
+                assert!(sequence.iter().eq(a.sequence().iter().cloned()));
+            }
+        }
+
+        let input = ">gaps\nNNNNN\n>odd\nACgTa\n".as_bytes();
+        let scaffolds: Vec<Scaffold> = FastaReader::new(input)
+            .with_options(options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(scaffolds[0]
+            .packed_sequence()
+            .unwrap()
+            .iter()
+            .all(|s| s == Symbol::Other));
+        assert_eq!(scaffolds[1].len(), 5);
+        assert_eq!(scaffolds[1].symbol(4), Symbol::Adenine);
+        assert_eq!(scaffolds[1].masked_regions(), &[2..3, 4..5]);
+    }
+
+    #[test]
+    fn test_load_fasta_files() {
+        let directory = env::temp_dir().join(format!("ncrs-files-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let paths: Vec<PathBuf> = ["chr1", "chr2", "chr3"]
+            .iter()
+            .map(|name| directory.join(format!("{}.fasta", name)))
+            .collect();
+        fs::write(&paths[0], ">chr1\nACGT\n").unwrap();
+        fs::write(&paths[1], ">chr2\nGG\n>chr2_random\nT\n").unwrap();
+        fs::write(&paths[2], ">chr2\nAA\n").unwrap();
+
+        let scaffolds = super::load_fasta_files(&paths[..2]).unwrap();
+        let names: Vec<&str> = scaffolds.iter().map(|s| s.name()).collect();
+        assert_eq!(names, vec!["chr1", "chr2", "chr2_random"]);
+
+        let error = super::load_fasta_files(&paths).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Scaffold chr2 is present in both file {} and file {}.",
+                paths[1].display(),
+                paths[2].display()
+            )
+        );
+
+        fs::write(&paths[2], ">chr3\nAXA\n").unwrap();
+        let error = super::load_fasta_files(&paths).err().unwrap();
+        assert!(error.to_string().contains(&paths[2].display().to_string()));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_reject_empty() {
+        let options = FastaOptions {
+            reject_empty: true,
+            ..FastaOptions::default()
+        };
+        let load = |input: &str| -> anyhow::Result<Vec<Scaffold>> {
+            FastaReader::new(input.as_bytes())
+                .with_options(options)
+                .collect()
+        };
+
+        let mid_file = ">a\nAC\n>b\n\n>c\nGT\n";
+        assert_eq!(
+            load(mid_file).err().unwrap().to_string(),
+            "Scaffold b with header on line 3 of FASTA input has an empty sequence."
+        );
+        assert_eq!(
+            load(">a\nAC\n>b").err().unwrap().to_string(),
+            "Scaffold b with header on line 3 of FASTA input has an empty sequence."
+        );
+        assert_eq!(load(">a\nAC\n>b\nG").unwrap().len(), 2);
+
+        let scaffolds = super::load_fasta_from_reader(mid_file.as_bytes()).unwrap();
+        assert_eq!(super::empty_scaffolds(&scaffolds), vec!["b"]);
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let path = Path::new("./tests/duplicate.fasta");
+        for error in [
+            super::load_fasta(path).err().unwrap(),
+            super::load_fasta_map(path).err().unwrap(),
+        ] {
+            assert_eq!(
+                error.to_string(),
+                "Duplicate scaffold scaffold_1 in FASTA file ./tests/duplicate.fasta, \
+                 records 1 and 3."
+            );
+        }
+
+        let scaffolds = super::load_fasta_map(Path::new("./tests/valid.fasta")).unwrap();
+        assert_eq!(scaffolds.len(), 2);
+        assert_eq!(scaffolds["scaffold_2"].sequence().len(), 7);
+    }
+
+    #[test]
+    fn test_blank_and_comment_lines() {
+        let expected = super::load_fasta(Path::new("./tests/valid.fasta")).unwrap();
+        let path = Path::new("./tests/commented.fasta");
+        let scaffolds = super::load_fasta(path).unwrap();
+        assert_eq!(expected.len(), scaffolds.len());
+        for (a, b) in expected.iter().zip(scaffolds.iter()) {
+            assert_eq!(a.name(), b.name());
+            assert_eq!(a.sequence(), b.sequence());
+        }
+
+        let error = super::load_fasta_from_reader(";a\n>a\nAC\n; b\n\nAXC\n".as_bytes())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid sequence of record a on line 6 of FASTA input."
+        );
+        assert!(super::load_fasta_from_reader(";a\n\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_load_fasta_from_reader() {
+        let input = ">a\nACGT\n>b\nAC\nAXC\n".as_bytes();
+        let error = match super::load_fasta_from_reader(input) {
+            Ok(_) => panic!("Invalid symbol was accepted."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Invalid sequence of record b on line 5 of FASTA input."
+        );
+
+        let error = match super::load_fasta_from_reader("\nACGT\n".as_bytes()) {
+            Ok(_) => panic!("Sequence without a header was accepted."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Invalid FASTA input, sequence on line 2 precedes the first header."
+        );
+
+        let file = fs::File::open("./tests/valid.fasta").unwrap();
+        let scaffolds = super::load_fasta_from_reader(std::io::BufReader::new(file)).unwrap();
+        assert_eq!(scaffolds.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_fasta_bytes() {
+        let parse = |bytes: &[u8]| super::parse_fasta_bytes(bytes, &FastaOptions::default());
+        let scaffolds = parse(b">a\r\nAC\r\ngt\n>b\n").unwrap();
+        assert_eq!(scaffolds.len(), 2);
+        assert_eq!(scaffolds[0].name(), "a");
+        assert_eq!(scaffolds[0].sequence().len(), 4);
+        assert!(scaffolds[0].masked_regions().is_empty());
+        assert!(scaffolds[1].sequence().is_empty());
+
+        assert!(parse(b"").is_err());
+        assert!(parse(b"ACGT\n").is_err());
+        assert!(parse(b">a\nAXGT\n").is_err());
+        assert!(parse(b">a\nARGT\n").is_err());
+        assert!(parse(b">\xff\nACGT\n").is_err());
+        assert!(parse(b">a\nA\n>a\nC\n").is_err());
+
+        let options = FastaOptions {
+            ambiguity: AmbiguityPolicy::MapToOther,
+            keep_mask: true,
+            ..FastaOptions::default()
+        };
+        let scaffolds = super::parse_fasta_bytes(b">a\nARgtAc\n", &options).unwrap();
+        assert_eq!(scaffolds[0].sequence()[1], Symbol::Other);
+        assert_eq!(scaffolds[0].masked_regions(), &[2..4, 5..6]);
+    }
 }
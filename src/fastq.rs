@@ -0,0 +1,278 @@
+use crate::data::Symbol;
+use crate::fasta::open_maybe_gzip;
+use anyhow::{Context, Result};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Offset of Phred quality characters in FASTQ files.
+const PHRED_OFFSET: u8 = 33;
+
+/// Sequencing read with per-base Phred quality scores.
+pub struct Read {
+    name: String,
+    sequence: Vec<Symbol>,
+    qualities: Vec<u8>,
+}
+
+impl Read {
+    /// Creates a new read. Sequence and qualities must be of the same length.
+    pub fn new(name: String, sequence: Vec<Symbol>, qualities: Vec<u8>) -> Result<Self> {
+        ensure!(
+            sequence.len() == qualities.len(),
+            "Read {} has {} bases but {} quality scores.",
+            name,
+            sequence.len(),
+            qualities.len()
+        );
+        Ok(Self {
+            name,
+            sequence,
+            qualities,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn sequence(&self) -> &[Symbol] {
+        &self.sequence
+    }
+
+    /// Decoded Phred quality scores, one per base.
+    pub fn qualities(&self) -> &[u8] {
+        &self.qualities
+    }
+}
+
+/// Streaming FASTQ reader yielding reads one at a time.
+///
+/// Records consist of exactly four lines: `@name`, the sequence, a `+`
+/// separator optionally repeating the name, and Phred+33 encoded qualities.
+/// Blank lines between records are ignored. After the first error the
+/// iterator is exhausted.
+pub struct FastqReader<R: BufRead> {
+    reader: R,
+    source: String,
+    line: String,
+    line_number: usize,
+    done: bool,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_source(reader, String::from("input"))
+    }
+
+    fn with_source(reader: R, source: String) -> Self {
+        Self {
+            reader,
+            source,
+            line: String::new(),
+            line_number: 0,
+            done: false,
+        }
+    }
+
+    /// Reads the next line without its line ending, returns `None` at the
+    /// end of input.
+    fn next_line(&mut self) -> Result<Option<&str>> {
+        self.line.clear();
+        let num_bytes = self.reader.read_line(&mut self.line).with_context(|| {
+            format!(
+                "Failed to read line {} of FASTQ {}.",
+                self.line_number + 1,
+                self.source
+            )
+        })?;
+        if num_bytes == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+
+        let line = self.line.strip_suffix('\n').unwrap_or(&self.line);
+        Ok(Some(line.strip_suffix('\r').unwrap_or(line)))
+    }
+
+    /// Reads a line of a record, failing at the end of input.
+    fn record_line(&mut self, name: &str) -> Result<String> {
+        match self.next_line()? {
+            Some(line) => Ok(String::from(line)),
+            None => bail!(
+                "Truncated record {} at the end of FASTQ {}.",
+                name,
+                self.source
+            ),
+        }
+    }
+
+    fn next_read(&mut self) -> Result<Option<Read>> {
+        let header = loop {
+            match self.next_line()? {
+                None => return Ok(None),
+                Some("") => continue,
+                Some(line) => break String::from(line),
+            }
+        };
+        let name = match header.strip_prefix('@') {
+            Some(name) => String::from(name),
+            None => bail!(
+                "Expected a record header starting with @ on line {} of FASTQ {}.",
+                self.line_number,
+                self.source
+            ),
+        };
+
+        let sequence = self.record_line(&name)?;
+        let sequence = sequence
+            .chars()
+            .map(Symbol::try_from)
+            .collect::<Result<Vec<Symbol>>>()
+            .with_context(|| {
+                format!(
+                    "Invalid sequence of record {} on line {} of FASTQ {}.",
+                    name, self.line_number, self.source
+                )
+            })?;
+
+        let separator = self.record_line(&name)?;
+        match separator.strip_prefix('+') {
+            Some("") => (),
+            Some(repeated) if repeated == name => (),
+            _ => bail!(
+                "Invalid separator line of record {} on line {} of FASTQ {}.",
+                name,
+                self.line_number,
+                self.source
+            ),
+        }
+
+        let qualities = self.record_line(&name)?;
+        let qualities = qualities
+            .bytes()
+            .map(|byte| match byte {
+                33..=126 => Ok(byte - PHRED_OFFSET),
+                _ => Err(anyhow!("Encountered invalid quality byte 0x{:02x}.", byte)),
+            })
+            .collect::<Result<Vec<u8>>>()
+            .with_context(|| {
+                format!(
+                    "Invalid qualities of record {} on line {} of FASTQ {}.",
+                    name, self.line_number, self.source
+                )
+            })?;
+
+        let line_number = self.line_number;
+        let read = Read::new(name, sequence, qualities).with_context(|| {
+            format!(
+                "Invalid record ending on line {} of FASTQ {}.",
+                line_number, self.source
+            )
+        })?;
+        Ok(Some(read))
+    }
+}
+
+impl FastqReader<BufReader<Box<dyn io::Read>>> {
+    /// Opens a FASTQ file. Gzip compressed files are transparently
+    /// decompressed.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::with_source(
+            open_maybe_gzip(path)?,
+            format!("file {}", path.display()),
+        ))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<Read>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_read();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+/// Load FASTQ file.
+pub fn load_fastq(path: &Path) -> Result<Vec<Read>> {
+    FastqReader::open(path)?.collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::FastqReader;
+    use crate::data::Symbol;
+    use std::path::Path;
+
+    #[test]
+    fn test_load_fastq() {
+        let reads = super::load_fastq(Path::new("./tests/valid.fastq")).unwrap();
+        assert_eq!(reads.len(), 3);
+
+        assert_eq!(reads[0].name(), "read_1");
+        assert_eq!(
+            reads[0].sequence(),
+            &[
+                Symbol::Adenine,
+                Symbol::Cytosine,
+                Symbol::Guanine,
+                Symbol::Thymine,
+                Symbol::Other
+            ][..]
+        );
+        assert_eq!(reads[0].qualities(), &[0, 9, 40, 41, 93][..]);
+        assert_eq!(reads[1].name(), "read_2 lane=1");
+        assert_eq!(reads[2].sequence().len(), 0);
+
+        let error = match super::load_fastq(Path::new("./tests/invalid.fastq")) {
+            Ok(_) => panic!("Invalid FASTQ was loaded."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid record ending on line 8 of FASTQ file ./tests/invalid.fastq.: \
+             Read read_2 has 4 bases but 3 quality scores."
+        );
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let error = |input: &str| {
+            let mut reader = FastqReader::new(input.as_bytes());
+            let error = reader.next().unwrap().err().unwrap();
+            assert!(reader.next().is_none());
+            error.to_string()
+        };
+
+        assert_eq!(
+            error("@a\nACGT\n+\n"),
+            "Truncated record a at the end of FASTQ input."
+        );
+        assert_eq!(
+            error("@a\nACGT\n+b\nIIII\n"),
+            "Invalid separator line of record a on line 3 of FASTQ input."
+        );
+        assert_eq!(
+            error(">a\nACGT\n+\nIIII\n"),
+            "Expected a record header starting with @ on line 1 of FASTQ input."
+        );
+        assert_eq!(
+            error("@a\nACGT\n+\nII I\n"),
+            "Invalid qualities of record a on line 4 of FASTQ input."
+        );
+        assert_eq!(
+            error("@a\nAXGT\n+\nIIII\n"),
+            "Invalid sequence of record a on line 2 of FASTQ input."
+        );
+        assert!(FastqReader::new("".as_bytes()).next().is_none());
+    }
+}
@@ -0,0 +1,374 @@
+use crate::data::{Annotation, Feature, Scaffold, Strand};
+use std::collections::{HashMap, HashSet};
+
+/// Summary of scaffolds and annotations removed by a filter.
+#[derive(Debug, Default)]
+pub struct FilterReport {
+    removed_scaffolds: usize,
+    removed_bases: usize,
+    removed_annotations: HashMap<Feature, usize>,
+}
+
+impl FilterReport {
+    /// Number of removed scaffolds.
+    pub fn removed_scaffolds(&self) -> usize {
+        self.removed_scaffolds
+    }
+
+    /// Total length of all removed scaffolds.
+    pub fn removed_bases(&self) -> usize {
+        self.removed_bases
+    }
+
+    /// Total number of removed annotations.
+    pub fn removed_annotations(&self) -> usize {
+        self.removed_annotations.values().sum()
+    }
+
+    /// Number of removed annotations of a given feature type.
+    pub fn removed_annotations_of(&self, feature: Feature) -> usize {
+        self.removed_annotations.get(&feature).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn add_scaffold(&mut self, scaffold: &Scaffold) {
+        self.removed_scaffolds += 1;
+        self.removed_bases += scaffold.len();
+    }
+
+    pub(crate) fn add_annotation(&mut self, annotation: &Annotation) {
+        *self
+            .removed_annotations
+            .entry(annotation.feature().clone())
+            .or_insert(0) += 1;
+    }
+}
+
+/// Removes scaffolds shorter than `min_len` together with all annotations
+/// referencing them.
+///
+/// Annotations referencing scaffolds which are not present in `scaffolds` are
+/// kept. See `Genome::filter_min_length` for filtering a `Genome` in place.
+pub fn filter_min_length(
+    scaffolds: Vec<Scaffold>,
+    annotations: Vec<Annotation>,
+    min_len: usize,
+) -> (Vec<Scaffold>, Vec<Annotation>, FilterReport) {
+    let mut report = FilterReport::default();
+
+    let (kept, removed): (Vec<Scaffold>, Vec<Scaffold>) = scaffolds
+        .into_iter()
+        .partition(|scaffold| scaffold.len() >= min_len);
+
+    removed
+        .iter()
+        .for_each(|scaffold| report.add_scaffold(scaffold));
+
+    let removed_names: HashSet<&str> = removed.iter().map(|s| s.name()).collect();
+    let annotations = annotations
+        .into_iter()
+        .filter(|annotation| {
+            if removed_names.contains(annotation.scaffold()) {
+                report.add_annotation(annotation);
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+
+    (kept, annotations, report)
+}
+
+/// Matching of annotations by `AnnotationFilter::region`. Regions and
+/// annotations are 0-based half-open.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RegionMode {
+    /// Annotations sharing at least one position with the region. Merely
+    /// touching annotations do not overlap it, empty annotations and empty
+    /// regions overlap nothing.
+    #[default]
+    Overlap,
+    /// Annotations lying fully within the region.
+    Contained,
+}
+
+/// Handling of annotations without a score by `AnnotationFilter::min_score`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MissingScore {
+    #[default]
+    Drop,
+    Keep,
+}
+
+/// Composable filter of loaded annotations, see `GffFilter` for filtering
+/// during parsing. All configured criteria must be met, an unconfigured
+/// criterion accepts everything.
+#[derive(Clone, Debug, Default)]
+pub struct AnnotationFilter {
+    feature: Option<Feature>,
+    scaffold: Option<String>,
+    source: Option<String>,
+    min_score: Option<f64>,
+    missing_score: MissingScore,
+    strand: Option<Strand>,
+    region: Option<(usize, usize)>,
+    region_mode: RegionMode,
+}
+
+impl AnnotationFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feature(mut self, feature: Feature) -> Self {
+        self.feature = Some(feature);
+        self
+    }
+
+    pub fn scaffold(mut self, scaffold: &str) -> Self {
+        self.scaffold = Some(String::from(scaffold));
+        self
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(String::from(source));
+        self
+    }
+
+    /// Keeps annotations with a score greater or equal to `min_score`.
+    /// Annotations without a score are handled per `missing_score`.
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Sets handling of annotations without a score by `min_score`,
+    /// they are dropped by default.
+    pub fn missing_score(mut self, missing_score: MissingScore) -> Self {
+        self.missing_score = missing_score;
+        self
+    }
+
+    pub fn strand(mut self, strand: Strand) -> Self {
+        self.strand = Some(strand);
+        self
+    }
+
+    /// Keeps annotations matching region `[start, end)` per `region_mode`,
+    /// by default annotations overlapping it. Combine with `scaffold` to
+    /// select a region of a single scaffold.
+    pub fn region(mut self, start: usize, end: usize) -> Self {
+        self.region = Some((start, end));
+        self
+    }
+
+    pub fn region_mode(mut self, region_mode: RegionMode) -> Self {
+        self.region_mode = region_mode;
+        self
+    }
+
+    /// Checks whether an annotation meets all criteria.
+    pub fn matches(&self, annotation: &Annotation) -> bool {
+        if let Some(feature) = self.feature.as_ref() {
+            if annotation.feature() != feature {
+                return false;
+            }
+        }
+        if let Some(scaffold) = self.scaffold.as_ref() {
+            if annotation.scaffold() != scaffold {
+                return false;
+            }
+        }
+        if let Some(source) = self.source.as_ref() {
+            if annotation.source() != source {
+                return false;
+            }
+        }
+        if let Some(min_score) = self.min_score {
+            let kept = match annotation.score() {
+                Some(score) => score >= min_score,
+                None => self.missing_score == MissingScore::Keep,
+            };
+            if !kept {
+                return false;
+            }
+        }
+        if let Some(strand) = self.strand {
+            if annotation.strand() != strand {
+                return false;
+            }
+        }
+        if let Some((start, end)) = self.region {
+            let (a_start, a_end) = (annotation.start(), annotation.end());
+            let kept = match self.region_mode {
+                RegionMode::Overlap => a_start.max(start) < a_end.min(end),
+                RegionMode::Contained => start <= a_start && a_end <= end,
+            };
+            if !kept {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the filter as a predicate, e.g. for `Vec::retain`.
+    pub fn predicate(&self) -> impl Fn(&Annotation) -> bool + '_ {
+        move |annotation| self.matches(annotation)
+    }
+
+    /// Returns matching annotations without cloning them.
+    pub fn apply<'a>(&self, annotations: &'a [Annotation]) -> Vec<&'a Annotation> {
+        annotations.iter().filter(|a| self.matches(a)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{AnnotationFilter, MissingScore, RegionMode};
+    use crate::data::{Annotation, Feature, Scaffold, Strand, Symbol};
+    use crate::testing;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_filter_min_length() {
+        let scaffolds = vec![
+            Scaffold::new(String::from("long"), vec![Symbol::Adenine; 10]),
+            Scaffold::new(String::from("short"), vec![Symbol::Adenine; 3]),
+            Scaffold::new(String::from("tiny"), vec![Symbol::Adenine; 1]),
+        ];
+        let annotations = vec![
+            testing::annotation("long", Feature::Exon, Strand::Positive, 0, 1),
+            testing::annotation("short", Feature::Exon, Strand::Positive, 0, 1),
+            testing::annotation("short", Feature::CDS, Strand::Positive, 0, 1),
+            testing::annotation("tiny", Feature::CDS, Strand::Positive, 0, 1),
+            testing::annotation("unknown", Feature::CDS, Strand::Positive, 0, 1),
+        ];
+
+        let (scaffolds, annotations, report) = super::filter_min_length(scaffolds, annotations, 5);
+
+        assert_eq!(scaffolds.len(), 1);
+        assert_eq!(scaffolds[0].name(), "long");
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[0].scaffold(), "long");
+        assert_eq!(annotations[1].scaffold(), "unknown");
+
+        assert_eq!(report.removed_scaffolds(), 2);
+        assert_eq!(report.removed_bases(), 4);
+        assert_eq!(report.removed_annotations(), 3);
+        assert_eq!(report.removed_annotations_of(Feature::Exon), 1);
+        assert_eq!(report.removed_annotations_of(Feature::CDS), 2);
+        assert_eq!(report.removed_annotations_of(Feature::StartCodon), 0);
+    }
+
+    fn arbitrary_annotation() -> impl Strategy<Value = Annotation> {
+        (
+            0usize..2,
+            0usize..2,
+            0usize..3,
+            prop::option::of(-5.0f64..5.0),
+            0usize..2,
+            0usize..30,
+            0usize..10,
+        )
+            .prop_map(|(scaffold, source, feature, score, strand, start, len)| {
+                Annotation::new(
+                    String::from(["s", "t"][scaffold]),
+                    String::from(["a", "b"][source]),
+                    [Feature::Exon, Feature::CDS, Feature::Gene][feature].clone(),
+                    score,
+                    [Strand::Positive, Strand::Negative][strand],
+                    None,
+                    start,
+                    start + len,
+                    String::new(),
+                )
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn annotation_filter_matches_closures(
+            annotations in proptest::collection::vec(arbitrary_annotation(), 0..40),
+            min_score in -5.0f64..5.0,
+            start in 0usize..30,
+            len in 0usize..15,
+        ) {
+            let end = start + len;
+
+            let filter = AnnotationFilter::new()
+                .feature(Feature::Exon)
+                .scaffold("s")
+                .source("a")
+                .strand(Strand::Negative);
+            let expected: Vec<&Annotation> = annotations
+                .iter()
+                .filter(|a| {
+                    *a.feature() == Feature::Exon
+                        && a.scaffold() == "s"
+                        && a.source() == "a"
+                        && a.strand() == Strand::Negative
+                })
+                .collect();
+            prop_assert_eq!(filter.apply(&annotations), expected);
+
+            for (missing, keep) in [(MissingScore::Drop, false), (MissingScore::Keep, true)] {
+                let filter = AnnotationFilter::new().min_score(min_score).missing_score(missing);
+                let expected: Vec<&Annotation> = annotations
+                    .iter()
+                    .filter(|a| a.score().map_or(keep, |score| score >= min_score))
+                    .collect();
+                prop_assert_eq!(filter.apply(&annotations), expected);
+            }
+
+            let overlap = AnnotationFilter::new().region(start, end);
+            let contained = overlap.clone().region_mode(RegionMode::Contained);
+            for a in annotations.iter() {
+                let positions = |s: usize, e: usize| (s..e).collect::<std::collections::HashSet<usize>>();
+                let inside = positions(a.start(), a.end());
+                let region = positions(start, end);
+                prop_assert_eq!(overlap.matches(a), !inside.is_disjoint(&region));
+                prop_assert_eq!(
+                    contained.matches(a),
+                    start <= a.start() && a.end() <= end
+                );
+            }
+
+            let mut retained = annotations.clone();
+            retained.retain(overlap.predicate());
+            let applied: Vec<Annotation> = overlap.apply(&annotations).into_iter().cloned().collect();
+            prop_assert_eq!(retained, applied);
+        }
+    }
+
+    #[test]
+    fn test_annotation_filter() {
+        let annotation = |start, end, score: Option<f64>| {
+            let builder = testing::builder("s", Feature::Exon, start, end);
+            match score {
+                Some(score) => builder.score(score),
+                None => builder,
+            }
+            .build()
+            .unwrap()
+        };
+        let annotations = vec![
+            annotation(0, 10, Some(1.)),
+            annotation(10, 20, None),
+            annotation(5, 15, Some(0.5)),
+        ];
+
+        let filter = AnnotationFilter::new().region(10, 15);
+        assert_eq!(filter.apply(&annotations).len(), 2);
+        let filter = filter.region_mode(RegionMode::Contained);
+        assert!(filter.apply(&annotations).is_empty());
+
+        let filter = AnnotationFilter::new().min_score(0.75);
+        assert_eq!(filter.apply(&annotations), [&annotations[0]]);
+        let filter = filter.missing_score(MissingScore::Keep);
+        assert_eq!(
+            filter.apply(&annotations),
+            [&annotations[0], &annotations[1]]
+        );
+    }
+}
@@ -0,0 +1,601 @@
+use crate::bounds::{Bounded, BoundsPolicy};
+use crate::codon::{self, CodonUsage};
+use crate::data::{sort_annotations, Annotation, Feature, Scaffold};
+use crate::fasta::load_fasta;
+use crate::filter::FilterReport;
+use crate::gff::load_gff_file;
+use crate::model::{build_gene_models, Gene};
+use crate::overlap::Track;
+use crate::region::Region;
+use crate::splice::{self, SpliceReport};
+use crate::trim::{trim_genome_n_ends, OffsetMap, TrimPolicy, TrimReport};
+use crate::validate::ValidationError;
+use crate::view::{region_view, OutOfRange, RegionView};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+/// Handling of annotations referencing scaffolds missing from a genome.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MissingScaffoldPolicy {
+    #[default]
+    Error,
+    /// Such annotations are dropped and reported by `Genome::warnings`.
+    Warn,
+}
+
+/// Options of `Genome::with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GenomeOptions {
+    pub missing_scaffolds: MissingScaffoldPolicy,
+    /// Handling of annotations extending past the end of their scaffold.
+    /// Clamped and dropped annotations are reported by `Genome::warnings`.
+    pub bounds: BoundsPolicy,
+}
+
+/// Annotations of a single scaffold sorted in the canonical order, see
+/// `sort_annotations`, with an overlap index.
+struct ScaffoldAnnotations {
+    annotations: Vec<Annotation>,
+    track: Track,
+}
+
+/// Scaffolds together with their annotations grouped by scaffold.
+pub struct Genome {
+    scaffolds: Vec<Scaffold>,
+    by_name: HashMap<String, usize>,
+    /// Annotations of each scaffold, in the order of `scaffolds`.
+    annotations: Vec<ScaffoldAnnotations>,
+    warnings: Vec<ValidationError>,
+}
+
+impl Genome {
+    /// Creates a genome failing on duplicate scaffold names, on annotations
+    /// referencing unknown scaffolds and on annotations extending past the
+    /// end of their scaffold.
+    pub fn new(scaffolds: Vec<Scaffold>, annotations: Vec<Annotation>) -> Result<Self> {
+        Self::with_options(scaffolds, annotations, GenomeOptions::default())
+    }
+
+    pub fn with_options(
+        scaffolds: Vec<Scaffold>,
+        annotations: Vec<Annotation>,
+        options: GenomeOptions,
+    ) -> Result<Self> {
+        let mut by_name = HashMap::with_capacity(scaffolds.len());
+        for (i, scaffold) in scaffolds.iter().enumerate() {
+            if let Some(first) = by_name.insert(String::from(scaffold.name()), i) {
+                bail!(
+                    "Duplicate scaffold {}, scaffolds {} and {}.",
+                    scaffold.name(),
+                    first,
+                    i
+                );
+            }
+        }
+
+        let mut grouped: Vec<Vec<Annotation>> = vec![Vec::new(); scaffolds.len()];
+        let mut warnings = Vec::new();
+        for (i, annotation) in annotations.into_iter().enumerate() {
+            match by_name.get(annotation.scaffold()) {
+                Some(&scaffold) => {
+                    let bounded = options
+                        .bounds
+                        .apply(&annotation, &scaffolds[scaffold])
+                        .with_context(|| format!("Invalid annotation {}.", i))?;
+                    if bounded != Bounded::Within {
+                        warnings.push(ValidationError::OutOfBounds {
+                            annotation: i,
+                            line: annotation.line(),
+                            scaffold: String::from(annotation.scaffold()),
+                            start: annotation.start(),
+                            end: annotation.end(),
+                            scaffold_len: scaffolds[scaffold].len(),
+                        });
+                    }
+                    match bounded {
+                        Bounded::Within => grouped[scaffold].push(annotation),
+                        Bounded::Clamped(start, end) => {
+                            grouped[scaffold].push(annotation.with_range(start, end))
+                        }
+                        Bounded::Dropped => (),
+                    }
+                }
+                None => {
+                    let warning = ValidationError::UnknownScaffold {
+                        annotation: i,
+                        line: annotation.line(),
+                        scaffold: String::from(annotation.scaffold()),
+                    };
+                    match options.missing_scaffolds {
+                        MissingScaffoldPolicy::Error => bail!("{}", warning),
+                        MissingScaffoldPolicy::Warn => warnings.push(warning),
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            scaffolds,
+            by_name,
+            annotations: index_groups(grouped),
+            warnings,
+        })
+    }
+
+    /// Loads a genome from a FASTA file and a GFF file, see `load_fasta`
+    /// and `load_gff_file`.
+    pub fn load(fasta_path: &Path, gff_path: &Path) -> Result<Self> {
+        let scaffolds = load_fasta(fasta_path)?;
+        let annotations = load_gff_file(gff_path)?;
+        Self::new(scaffolds, annotations).with_context(|| {
+            format!(
+                "Annotations of file {} do not match file {}.",
+                gff_path.display(),
+                fasta_path.display()
+            )
+        })
+    }
+
+    /// Scaffolds in their original order.
+    pub fn scaffolds(&self) -> &[Scaffold] {
+        &self.scaffolds
+    }
+
+    pub fn scaffold(&self, name: &str) -> Option<&Scaffold> {
+        self.by_name.get(name).map(|&i| &self.scaffolds[i])
+    }
+
+    /// Annotations of a scaffold sorted in the canonical order, see
+    /// `sort_annotations`. Unknown scaffolds have no annotations.
+    pub fn annotations_on(&self, scaffold: &str) -> &[Annotation] {
+        match self.by_name.get(scaffold) {
+            Some(&i) => &self.annotations[i].annotations,
+            None => &[],
+        }
+    }
+
+    /// Annotations of a scaffold overlapping 0-based half-open `range`
+    /// sorted by start and end, see `AnnotationIndex::query`.
+    pub fn annotations_overlapping(
+        &self,
+        scaffold: &str,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = &Annotation> + '_ {
+        let (annotations, indices) = match self.by_name.get(scaffold) {
+            Some(&i) => {
+                let group = &self.annotations[i];
+                let indices = group
+                    .track
+                    .query(&group.annotations, range.start, range.end);
+                (&group.annotations[..], indices)
+            }
+            None => (&[][..], Vec::new()),
+        };
+        indices.into_iter().map(move |i| &annotations[i])
+    }
+
+    /// Iterates over scaffolds, in their original order, together with
+    /// their annotations, see `annotations_on`.
+    pub fn iter(&self) -> impl Iterator<Item = (&Scaffold, &[Annotation])> + '_ {
+        self.scaffolds
+            .iter()
+            .zip(self.annotations.iter())
+            .map(|(scaffold, group)| (scaffold, &group.annotations[..]))
+    }
+
+    /// Returns the sequence of a region together with all annotations
+    /// overlapping it, looked up via the interval index, see `query_region`.
+    pub fn query(&self, region: &Region, out_of_range: OutOfRange) -> Result<RegionView<'_>> {
+        let scaffold = match self.scaffold(region.scaffold()) {
+            Some(scaffold) => scaffold,
+            None => bail!("Unknown scaffold {}.", region.scaffold()),
+        };
+        region_view(scaffold, region, out_of_range, |region| {
+            self.annotations_overlapping(region.scaffold(), region.start()..region.end())
+                .collect()
+        })
+    }
+
+    /// Builds gene models of each scaffold, in the order of scaffolds, see
+    /// `build_gene_models`.
+    pub fn gene_models(&self) -> Result<Vec<Gene>> {
+        let mut genes = Vec::new();
+        for (scaffold, annotations) in self.iter() {
+            genes.extend(build_gene_models(annotations).with_context(|| {
+                format!("Invalid gene models on scaffold {}.", scaffold.name())
+            })?);
+        }
+        Ok(genes)
+    }
+
+    /// Features of a given type of each transcript having at least one
+    /// such feature, in the order of `gene_models`.
+    fn transcript_features(&self, feature: &Feature) -> Result<Vec<Vec<Annotation>>> {
+        Ok(self
+            .gene_models()?
+            .iter()
+            .flat_map(|gene| gene.transcripts())
+            .map(|transcript| transcript.features_of(feature).cloned().collect::<Vec<_>>())
+            .filter(|features| !features.is_empty())
+            .collect())
+    }
+
+    /// Computes codon usage over all coding transcripts of `gene_models`,
+    /// see `codon::codon_usage`. `CodonUsage::skipped` indexes transcripts
+    /// with at least one CDS, in the order of `gene_models`.
+    pub fn codon_usage(&self) -> Result<CodonUsage> {
+        let transcripts = self.transcript_features(&Feature::CDS)?;
+        Ok(codon::codon_usage(&self.scaffolds, &transcripts))
+    }
+
+    /// Checks splice junctions of all transcripts of `gene_models`, see
+    /// `splice::check_splice_sites`. The report indexes transcripts with at
+    /// least one exon, in the order of `gene_models`.
+    pub fn check_splice_sites(&self) -> Result<SpliceReport> {
+        let transcripts = self.transcript_features(&Feature::Exon)?;
+        Ok(splice::check_splice_sites(&self.scaffolds, &transcripts))
+    }
+
+    /// Removes scaffolds shorter than `min_len` together with all their
+    /// annotations, see `filter_min_length`.
+    pub fn filter_min_length(&mut self, min_len: usize) -> FilterReport {
+        let mut report = FilterReport::default();
+        let scaffolds = mem::take(&mut self.scaffolds);
+        let groups = mem::take(&mut self.annotations);
+        for (scaffold, group) in scaffolds.into_iter().zip(groups) {
+            if scaffold.len() >= min_len {
+                self.scaffolds.push(scaffold);
+                self.annotations.push(group);
+            } else {
+                report.add_scaffold(&scaffold);
+                group
+                    .annotations
+                    .iter()
+                    .for_each(|annotation| report.add_annotation(annotation));
+            }
+        }
+
+        self.by_name = self
+            .scaffolds
+            .iter()
+            .enumerate()
+            .map(|(i, scaffold)| (String::from(scaffold.name()), i))
+            .collect();
+        report
+    }
+
+    /// Trims N runs from both ends of all scaffolds and shifts their
+    /// annotations accordingly, see `trim_genome_n_ends`.
+    pub fn trim_n_ends(&mut self, min_run: usize, policy: TrimPolicy) -> (OffsetMap, TrimReport) {
+        let annotations: Vec<Annotation> = mem::take(&mut self.annotations)
+            .into_iter()
+            .flat_map(|group| group.annotations)
+            .collect();
+        let (scaffolds, annotations, offsets, report) =
+            trim_genome_n_ends(&self.scaffolds, &annotations, min_run, policy);
+
+        let mut grouped: Vec<Vec<Annotation>> = vec![Vec::new(); scaffolds.len()];
+        for annotation in annotations {
+            grouped[self.by_name[annotation.scaffold()]].push(annotation);
+        }
+        self.scaffolds = scaffolds;
+        self.annotations = index_groups(grouped);
+        (offsets, report)
+    }
+
+    /// Annotations dropped for referencing unknown scaffolds, see
+    /// `MissingScaffoldPolicy::Warn`, and annotations clamped or dropped
+    /// per `GenomeOptions::bounds`.
+    pub fn warnings(&self) -> &[ValidationError] {
+        &self.warnings
+    }
+}
+
+/// Sorts annotations of each scaffold and builds their overlap index.
+fn index_groups(grouped: Vec<Vec<Annotation>>) -> Vec<ScaffoldAnnotations> {
+    grouped
+        .into_iter()
+        .map(|mut annotations| {
+            sort_annotations(&mut annotations);
+            let track = Track::new(&annotations, (0..annotations.len()).collect());
+            ScaffoldAnnotations { annotations, track }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Genome, GenomeOptions, MissingScaffoldPolicy};
+    use crate::bounds::BoundsPolicy;
+    use crate::data::{parse_sequence, Annotation, Feature, Phase, Scaffold, Strand, Symbol};
+    use crate::region::Region;
+    use crate::testing;
+    use crate::trim::TrimPolicy;
+    use crate::validate::ValidationError;
+    use crate::view::{query_region, OutOfRange};
+    use std::path::Path;
+
+    fn scaffold(name: &str, len: usize) -> Scaffold {
+        Scaffold::new(String::from(name), vec![Symbol::Adenine; len])
+    }
+
+    fn ranges<'a>(annotations: impl IntoIterator<Item = &'a Annotation>) -> Vec<(usize, usize)> {
+        annotations
+            .into_iter()
+            .map(|a| (a.start(), a.end()))
+            .collect()
+    }
+
+    #[test]
+    fn test_genome() {
+        let scaffolds = vec![scaffold("b", 100), scaffold("a", 50), scaffold("c", 10)];
+        let annotations = vec![
+            testing::annotation("a", Feature::Exon, Strand::Positive, 30, 40),
+            testing::annotation("b", Feature::Exon, Strand::Positive, 20, 60),
+            testing::annotation("a", Feature::Exon, Strand::Positive, 10, 20),
+            testing::annotation("b", Feature::Exon, Strand::Positive, 0, 10),
+            testing::annotation("a", Feature::Exon, Strand::Positive, 15, 35),
+        ];
+        let genome = Genome::new(scaffolds, annotations.clone()).unwrap();
+
+        assert_eq!(genome.scaffold("a").unwrap().len(), 50);
+        assert!(genome.scaffold("x").is_none());
+        assert_eq!(
+            ranges(genome.annotations_on("a")),
+            [(10, 20), (15, 35), (30, 40)]
+        );
+        assert_eq!(ranges(genome.annotations_on("c")), []);
+        assert_eq!(ranges(genome.annotations_on("x")), []);
+
+        assert_eq!(
+            ranges(genome.annotations_overlapping("a", 19..31)),
+            [(10, 20), (15, 35), (30, 40)]
+        );
+        assert_eq!(
+            ranges(genome.annotations_overlapping("a", 20..30)),
+            [(15, 35)]
+        );
+        assert_eq!(ranges(genome.annotations_overlapping("b", 10..20)), []);
+        assert_eq!(ranges(genome.annotations_overlapping("x", 0..100)), []);
+
+        let names: Vec<(&str, usize)> = genome
+            .iter()
+            .map(|(scaffold, annotations)| (scaffold.name(), annotations.len()))
+            .collect();
+        assert_eq!(names, [("b", 2), ("a", 3), ("c", 0)]);
+        assert!(genome.warnings().is_empty());
+
+        let mut unknown = annotations;
+        unknown.insert(
+            1,
+            testing::annotation("x", Feature::Exon, Strand::Positive, 0, 1),
+        );
+        let scaffolds = vec![scaffold("a", 50), scaffold("b", 100)];
+        let error = Genome::new(scaffolds.clone(), unknown.clone())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotation 1 references unknown scaffold x."
+        );
+
+        let options = GenomeOptions {
+            missing_scaffolds: MissingScaffoldPolicy::Warn,
+            ..GenomeOptions::default()
+        };
+        let genome = Genome::with_options(scaffolds, unknown, options).unwrap();
+        assert_eq!(
+            genome.warnings(),
+            [ValidationError::UnknownScaffold {
+                annotation: 1,
+                line: None,
+                scaffold: String::from("x"),
+            }]
+        );
+        assert_eq!(genome.iter().map(|(_, a)| a.len()).sum::<usize>(), 5);
+
+        let duplicate = vec![scaffold("a", 1), scaffold("b", 1), scaffold("a", 2)];
+        let error = Genome::new(duplicate, Vec::new()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Duplicate scaffold a, scaffolds 0 and 2."
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let scaffolds = vec![scaffold("a", 50), scaffold("b", 20).with_circular(true)];
+        let annotations = vec![
+            testing::annotation("a", Feature::Exon, Strand::Positive, 10, 20),
+            testing::annotation("a", Feature::Exon, Strand::Positive, 40, 60),
+            testing::annotation("b", Feature::Exon, Strand::Positive, 15, 25),
+            testing::annotation("a", Feature::Exon, Strand::Positive, 55, 60),
+        ];
+
+        let error = Genome::new(scaffolds.clone(), annotations.clone())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Invalid annotation 1.");
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Annotation 40..60 extends past the end of scaffold a of length 50."
+        );
+
+        for &(bounds, expected) in &[
+            (BoundsPolicy::Clamp, &[(10, 20), (40, 50)][..]),
+            (BoundsPolicy::Drop, &[(10, 20)][..]),
+        ] {
+            let options = GenomeOptions {
+                bounds,
+                ..GenomeOptions::default()
+            };
+            let genome =
+                Genome::with_options(scaffolds.clone(), annotations.clone(), options).unwrap();
+            assert_eq!(ranges(genome.annotations_on("a")), expected);
+            // Wrap-around annotations of circular scaffolds are kept.
+            assert_eq!(ranges(genome.annotations_on("b")), [(15, 25)]);
+            let warned: Vec<usize> = genome
+                .warnings()
+                .iter()
+                .map(|warning| match warning {
+                    ValidationError::OutOfBounds { annotation, .. } => *annotation,
+                    _ => panic!("Unexpected warning {}.", warning),
+                })
+                .collect();
+            assert_eq!(warned, [1, 3]);
+        }
+    }
+
+    /// A two exon gene with canonical splice sites coding `ATG AAA TAA`
+    /// and a scaffold of Ns on both sides of a single annotation.
+    fn model_genome() -> Genome {
+        let gene = Scaffold::new(
+            String::from("a"),
+            parse_sequence("CCCATGAAGTCCAGATAACCCCCC").unwrap(),
+        );
+        let padded = Scaffold::new(String::from("b"), parse_sequence("NNNACGTNN").unwrap());
+        let linked = |feature, start, end, attributes: &str| {
+            testing::builder("a", feature, start, end)
+                .attributes(attributes)
+                .build()
+                .unwrap()
+        };
+        let annotations = vec![
+            linked(Feature::Gene, 0, 24, "ID=g"),
+            linked(Feature::MRNA, 0, 24, "ID=t;Parent=g"),
+            linked(Feature::Exon, 0, 8, "Parent=t"),
+            linked(Feature::Exon, 14, 24, "Parent=t"),
+            testing::builder("a", Feature::CDS, 3, 8)
+                .phase(Phase::Zero)
+                .attributes("Parent=t")
+                .build()
+                .unwrap(),
+            testing::builder("a", Feature::CDS, 14, 18)
+                .phase(Phase::One)
+                .attributes("Parent=t")
+                .build()
+                .unwrap(),
+            testing::annotation("b", Feature::Exon, Strand::Positive, 1, 5),
+        ];
+        Genome::new(vec![gene, padded], annotations).unwrap()
+    }
+
+    #[test]
+    fn test_models() {
+        let genome = model_genome();
+        assert_eq!(genome.gene_models().unwrap().len(), 1);
+
+        let usage = genome.codon_usage().unwrap();
+        assert_eq!(usage.total(), 3);
+        assert!(usage.skipped().is_empty());
+        for codon in ["ATG", "AAA", "TAA"].iter() {
+            let codon = parse_sequence(codon).unwrap();
+            assert_eq!(usage.count([codon[0], codon[1], codon[2]]), Some(1));
+        }
+
+        let report = genome.check_splice_sites().unwrap();
+        assert_eq!(report.transcripts().len(), 1);
+        assert_eq!(report.overall().canonical, 1);
+        assert_eq!(report.overall().total(), 1);
+    }
+
+    #[test]
+    fn test_query() {
+        let genome = model_genome();
+        let region = Region::new("a", 6, 16);
+        let view = genome.query(&region, OutOfRange::Error).unwrap();
+        let indexed: Vec<(usize, usize)> = view
+            .annotations()
+            .iter()
+            .map(|(a, _)| (a.start(), a.end()))
+            .collect();
+        assert_eq!(
+            indexed,
+            [(0, 8), (0, 24), (0, 24), (3, 8), (14, 18), (14, 24)]
+        );
+
+        let annotations: Vec<Annotation> = genome
+            .iter()
+            .flat_map(|(_, annotations)| annotations.iter().cloned())
+            .collect();
+        let scanned =
+            query_region(genome.scaffolds(), &annotations, &region, OutOfRange::Error).unwrap();
+        assert_eq!(view.sequence(), scanned.sequence());
+        assert_eq!(view.annotations().len(), scanned.annotations().len());
+        for ((a, a_range), (b, b_range)) in view.annotations().iter().zip(scanned.annotations()) {
+            assert_eq!((a.start(), a.end()), (b.start(), b.end()));
+            assert_eq!(a_range, b_range);
+        }
+
+        let view = genome
+            .query(&Region::new("b", 5, 20), OutOfRange::Clamp)
+            .unwrap();
+        assert!(view.clamped());
+        assert_eq!(view.sequence().len(), 4);
+        assert!(genome
+            .query(&Region::new("b", 5, 20), OutOfRange::Error)
+            .is_err());
+        assert_eq!(
+            genome
+                .query(&Region::new("x", 0, 1), OutOfRange::Error)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Unknown scaffold x."
+        );
+    }
+
+    #[test]
+    fn test_filter_min_length() {
+        let mut genome = model_genome();
+        let report = genome.filter_min_length(10);
+        assert_eq!(report.removed_scaffolds(), 1);
+        assert_eq!(report.removed_bases(), 9);
+        assert_eq!(report.removed_annotations(), 1);
+        assert_eq!(report.removed_annotations_of(Feature::Exon), 1);
+        assert!(genome.scaffold("b").is_none());
+        assert!(genome.annotations_on("b").is_empty());
+        assert_eq!(genome.scaffolds().len(), 1);
+        assert_eq!(genome.annotations_on("a").len(), 6);
+    }
+
+    #[test]
+    fn test_trim_n_ends() {
+        let mut genome = model_genome();
+        let (offsets, report) = genome.trim_n_ends(2, TrimPolicy::Truncate);
+        assert_eq!(offsets.leading("b"), Some(3));
+        assert_eq!(offsets.leading("a"), Some(0));
+        assert_eq!(report.truncated.len(), 1);
+        assert!(report.dropped.is_empty());
+        assert_eq!(genome.scaffold("b").unwrap().len(), 4);
+        assert_eq!(ranges(genome.annotations_on("b")), [(0, 2)]);
+        assert_eq!(ranges(genome.annotations_overlapping("b", 1..2)), [(0, 2)]);
+        assert_eq!(genome.annotations_on("a").len(), 6);
+    }
+
+    #[test]
+    fn test_load() {
+        let fasta = Path::new("./tests/valid.fasta");
+        let genome = Genome::load(fasta, Path::new("./tests/embedded.gff3")).unwrap();
+        assert_eq!(genome.scaffolds().len(), 2);
+        assert_eq!(genome.annotations_on("scaffold_1").len(), 3);
+        assert_eq!(genome.annotations_on("scaffold_2").len(), 1);
+
+        let error = Genome::load(fasta, Path::new("./tests/valid.gff"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotations of file ./tests/valid.gff do not match file ./tests/valid.fasta."
+        );
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Annotation 773..1123 (line 1) extends past the end of scaffold scaffold_1 of length \
+             280."
+        );
+    }
+}
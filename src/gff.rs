@@ -1,78 +1,699 @@
-use crate::data::{Annotation, Feature, Phase, Strand};
+use crate::data::{Annotation, Attributes, Feature, Phase, Scaffold, Strand};
+use crate::fasta::{check_unique_names, decompress, is_gzip, open_maybe_gzip, FastaReader};
+use crate::ids::find_duplicate_ids;
+use crate::model::build_gene_models;
+use crate::names::NameNormalizer;
+use crate::progress::{CountingReader, LoadOptions, ProgressTracker};
 use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::io::{prelude::*, BufReader, BufWriter};
 use std::path::Path;
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
 const GFF_NUM_COLUMNS: usize = 9;
 
+/// Dialect of annotation files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GffFormat {
+    #[default]
+    Gff,
+    /// GTF (GFF version 2 as used by Ensembl). Attributes are strictly
+    /// checked with `Attributes::parse_gtf` and GTF feature types such as
+    /// `5UTR` are recognized.
+    Gtf,
+}
+
+/// Options of GFF loading.
+#[derive(Clone, Debug, Default)]
+pub struct GffOptions {
+    pub format: GffFormat,
+    /// Fail on feature types without a dedicated `Feature` variant instead
+    /// of loading them as `Feature::Other`.
+    pub strict_features: bool,
+    /// Records to load, see `GffFilter`.
+    pub filter: GffFilter,
+    /// Skip malformed records instead of failing on them. The skipped
+    /// records are not reported, see `load_gff_file_lossy` to collect them.
+    pub skip_malformed: bool,
+}
+
+/// Selection of GFF records applied while parsing so that skipped records
+/// are never turned into `Annotation`s. All configured criteria must be
+/// met, an unconfigured criterion accepts everything.
+///
+/// Filtering happens right after a line is split into its nine columns:
+/// scaffold, source and feature type are compared first and the score
+/// column is parsed only if a minimum score is set. No other column of a
+/// skipped line is validated, e.g. malformed coordinates, strands or GTF
+/// attributes do not cause an error.
+#[derive(Clone, Debug, Default)]
+pub struct GffFilter {
+    features: Option<HashSet<Feature>>,
+    scaffolds: Option<HashSet<String>>,
+    sources: Option<HashSet<String>>,
+    min_score: Option<f64>,
+}
+
+impl GffFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps only records of the given feature types.
+    pub fn features<I>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = Feature>,
+    {
+        self.features = Some(features.into_iter().collect());
+        self
+    }
+
+    /// Keeps only records on the given scaffolds.
+    pub fn scaffolds<I>(mut self, scaffolds: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.scaffolds = Some(scaffolds.into_iter().collect());
+        self
+    }
+
+    /// Keeps only records with the given sources.
+    pub fn sources<I>(mut self, sources: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.sources = Some(sources.into_iter().collect());
+        self
+    }
+
+    /// Keeps only records with a score greater or equal to `min_score`.
+    /// Records without a score are skipped.
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Checks the raw columns of a record. Errors only if the score column
+    /// has to be parsed and is invalid.
+    fn accepts(
+        &self,
+        scaffold: &str,
+        source: &str,
+        feature: &str,
+        score: &str,
+        format: GffFormat,
+    ) -> Result<bool> {
+        if let Some(scaffolds) = self.scaffolds.as_ref() {
+            if !scaffolds.contains(scaffold) {
+                return Ok(false);
+            }
+        }
+        if let Some(sources) = self.sources.as_ref() {
+            if !sources.contains(source) {
+                return Ok(false);
+            }
+        }
+        if let Some(features) = self.features.as_ref() {
+            if !features.contains(&map_feature(feature, format)) {
+                return Ok(false);
+            }
+        }
+        if let Some(min_score) = self.min_score {
+            match parse_score(score)? {
+                Some(score) if score >= min_score => (),
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+}
+
 /// Load scaffold annotations from a general feature format (GFF) file.
+///
+/// Comment and directive lines starting with `#` as well as blank lines are
+/// skipped. Parsing stops at the `##FASTA` directive. Gzip compressed files
+/// are transparently decompressed.
 pub fn load_gff_file(path: &Path) -> Result<Vec<Annotation>> {
-    let reader = {
-        let file =
-            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
-        BufReader::new(file)
+    load_gff_file_with_options(path, &GffOptions::default())
+}
+
+/// Load scaffold annotations from a GFF file, see `load_gff_file`.
+pub fn load_gff_file_with_options(path: &Path, options: &GffOptions) -> Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    process_gff_with_progress(path, options, LoadOptions::default(), |annotation| {
+        annotations.push(annotation);
+        Ok(())
+    })?;
+    Ok(annotations)
+}
+
+/// Load only GFF records selected by `filter`, see `GffFilter` and
+/// `load_gff_file`.
+pub fn load_gff_file_filtered(path: &Path, filter: &GffFilter) -> Result<Vec<Annotation>> {
+    let options = GffOptions {
+        filter: filter.clone(),
+        ..GffOptions::default()
     };
+    load_gff_file_with_options(path, &options)
+}
+
+/// Loads a GFF file by parsing its records on the Rayon thread pool. The
+/// result, including errors, is identical to `load_gff_file`. The whole
+/// (decompressed) file is held in memory during loading.
+#[cfg(feature = "rayon")]
+pub fn load_gff_file_parallel(path: &Path) -> Result<Vec<Annotation>> {
+    let mut data = String::new();
+    open_maybe_gzip(path)?
+        .read_to_string(&mut data)
+        .with_context(|| format!("Could not read file {}.", path.display()))?;
+
+    // Directives are parsed only to report the same errors as the serial
+    // loader.
+    let mut metadata = GffMetadata::default();
+    let mut records: Vec<(usize, &str)> = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with("##FASTA") {
+            break;
+        } else if !is_ignored_line(line) {
+            records.push((i, line));
+        } else {
+            metadata.parse_line(line).with_context(|| {
+                format!("Failed to parse line {} of file {}.", i + 1, path.display())
+            })?;
+        }
+    }
+
+    let options = GffOptions::default();
+    let parsed: Vec<Result<Option<Annotation>>> = records
+        .par_iter()
+        .map(|&(i, line)| {
+            parse_gff_line(line, &options)
+                .map(|annotation| annotation.map(|a| a.with_line(Some(i + 1))))
+                .with_context(|| {
+                    format!("Failed to parse line {} of file {}.", i + 1, path.display())
+                })
+        })
+        .collect();
+
+    let mut annotations = Vec::with_capacity(parsed.len());
+    for annotation in parsed {
+        annotations.extend(annotation?);
+    }
+    Ok(annotations)
+}
+
+/// GFF record which failed to parse, see `load_gff_file_lossy`.
+#[derive(Debug)]
+pub struct LineError {
+    line_number: usize,
+    line: String,
+    error: anyhow::Error,
+}
+
+impl LineError {
+    /// 1-based number of the line.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Text of the line without the line break.
+    pub fn line(&self) -> &str {
+        self.line.as_str()
+    }
+
+    pub fn error(&self) -> &anyhow::Error {
+        &self.error
+    }
+}
 
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {:#}", self.line_number, self.error)
+    }
+}
+
+/// Load scaffold annotations from a GFF file, collecting malformed records
+/// instead of failing on them. Errors reading the file are still returned
+/// as errors. See `load_gff_file_lossy_with_limit` to give up on files with
+/// too many malformed records.
+pub fn load_gff_file_lossy(path: &Path) -> Result<(Vec<Annotation>, Vec<LineError>)> {
+    load_gff_file_lossy_with_limit(path, usize::MAX)
+}
+
+/// Load scaffold annotations from a GFF file like `load_gff_file_lossy` but
+/// fail once more than `max_errors` records are malformed.
+pub fn load_gff_file_lossy_with_limit(
+    path: &Path,
+    max_errors: usize,
+) -> Result<(Vec<Annotation>, Vec<LineError>)> {
+    let mut reader = GffReader::open(path)?;
     let mut annotations = Vec::new();
+    let mut errors: Vec<LineError> = Vec::new();
 
-    for (i, line) in reader.lines().enumerate() {
-        let line = line.with_context(|| format!("Could not read file {}.", path.display()))?;
-        let annotation = parse_gff_line(line).with_context(|| {
-            format!("Failed to parse line {} of file {}.", i + 1, path.display())
-        })?;
-        annotations.push(annotation);
+    while reader.next_record()? {
+        match parse_gff_line(&reader.line, &reader.options) {
+            Ok(Some(annotation)) => {
+                annotations.push(annotation.with_line(Some(reader.line_number)))
+            }
+            Ok(None) => (),
+            Err(error) => {
+                errors.push(LineError {
+                    line_number: reader.line_number,
+                    line: reader.line.clone(),
+                    error,
+                });
+                ensure!(
+                    errors.len() <= max_errors,
+                    "More than {} malformed records in file {}, the first on line {}.",
+                    max_errors,
+                    path.display(),
+                    errors[0].line_number
+                );
+            }
+        }
+    }
+
+    Ok((annotations, errors))
+}
+
+/// Load scaffold annotations together with metadata given by directives of
+/// a GFF file, see `load_gff_file` and `GffMetadata`.
+pub fn load_gff_file_with_metadata(path: &Path) -> Result<(Vec<Annotation>, GffMetadata)> {
+    let mut reader = GffReader::open(path)?;
+    let annotations = reader.by_ref().collect::<Result<Vec<Annotation>>>()?;
+    Ok((annotations, reader.into_metadata()))
+}
+
+/// Load annotations and sequences of a GFF3 file embedding them after the
+/// `##FASTA` directive. Files without the directive yield no scaffolds.
+/// Errors in the sequences name lines of the whole file. Scaffolds of
+/// circular regions are marked circular, see `GffMetadata::is_circular`.
+pub fn load_gff3_with_sequences(path: &Path) -> Result<(Vec<Annotation>, Vec<Scaffold>)> {
+    let mut reader = GffReader::open(path)?;
+    let annotations = reader.by_ref().collect::<Result<Vec<Annotation>>>()?;
+    if !reader.at_fasta {
+        return Ok((annotations, Vec::new()));
     }
 
+    let source = format!("file {}", path.display());
+    let metadata = &reader.metadata;
+    let scaffolds = FastaReader::with_source(reader.reader, source.clone())
+        .with_line_offset(reader.line_number)
+        .map(|scaffold| {
+            scaffold.map(|s| {
+                let circular = metadata.is_circular(s.name());
+                s.with_circular(circular)
+            })
+        })
+        .collect::<Result<Vec<Scaffold>>>()?;
+    check_unique_names(&scaffolds, &source)?;
+    Ok((annotations, scaffolds))
+}
+
+/// Load scaffold annotations from a GTF file.
+pub fn load_gtf_file(path: &Path) -> Result<Vec<Annotation>> {
+    let options = GffOptions {
+        format: GffFormat::Gtf,
+        ..GffOptions::default()
+    };
+    load_gff_file_with_options(path, &options)
+}
+
+/// Load annotations from a GFF file with scaffold names rewritten by
+/// `normalizer`.
+pub fn load_gff_file_normalized(
+    path: &Path,
+    normalizer: &NameNormalizer,
+) -> Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    process_gff(path, &GffOptions::default(), |annotation| {
+        annotations.push(annotation.with_scaffold(normalizer.normalize(annotation.scaffold())));
+        Ok(())
+    })?;
+    Ok(annotations)
+}
+
+/// Streams a GFF file annotation by annotation, calling `f` on each of them.
+/// The line buffer is reused so memory use does not grow with file size.
+/// Records are loaded per `options` as by `load_gff_file_with_options`,
+/// records skipped by the filter or as malformed are neither passed to `f`
+/// nor counted.
+///
+/// Processing stops at the first error returned by `f`, which is then
+/// returned. On success, the number of processed annotations is returned.
+pub fn process_gff<F>(path: &Path, options: &GffOptions, f: F) -> Result<usize>
+where
+    F: FnMut(Annotation) -> Result<()>,
+{
+    process_gff_with_progress(path, options, LoadOptions::default(), f)
+}
+
+/// Load scaffold annotations from a GFF file, reporting progress per `load`.
+pub fn load_gff_file_with_progress(path: &Path, load: LoadOptions) -> Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    process_gff_with_progress(path, &GffOptions::default(), load, |annotation| {
+        annotations.push(annotation);
+        Ok(())
+    })?;
     Ok(annotations)
 }
 
-fn parse_gff_line(line: String) -> Result<Annotation> {
-    let mut tokens: Vec<String> = line.split('\t').take(9).map(String::from).collect();
+fn process_gff_with_progress<F>(
+    path: &Path,
+    options: &GffOptions,
+    load: LoadOptions,
+    mut f: F,
+) -> Result<usize>
+where
+    F: FnMut(Annotation) -> Result<()>,
+{
+    let mut file =
+        File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+    let gzip = is_gzip(&mut file, path)?;
+    let (mut tracker, bytes_read) = ProgressTracker::new(load, &file);
+    let reader = GffReader::with_source(
+        decompress(CountingReader::new(file, bytes_read), gzip),
+        format!("file {}", path.display()),
+    )
+    .with_options(options.clone());
 
-    let num_columns = tokens.len();
+    let mut count = 0;
+    for annotation in reader {
+        f(annotation?)?;
+        count += 1;
+        tracker.record();
+    }
+    tracker.finish();
 
-    ensure!(
-        num_columns == GFF_NUM_COLUMNS,
-        "Not enough tab separated tokens. Expected {} got {}.",
-        GFF_NUM_COLUMNS,
-        num_columns
-    );
+    Ok(count)
+}
+
+/// Metadata of a GFF file given by its `##` directives and `#!` pragmas.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GffMetadata {
+    version: Option<String>,
+    sequence_lengths: HashMap<String, usize>,
+    properties: Vec<(String, String)>,
+    other: Vec<String>,
+    circular: HashSet<String>,
+}
+
+impl GffMetadata {
+    /// Version given by the `##gff-version` directive.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Scaffold lengths declared by `##sequence-region` directives, i.e.
+    /// the region ends.
+    pub fn sequence_lengths(&self) -> &HashMap<String, usize> {
+        &self.sequence_lengths
+    }
+
+    /// Values of `##species` and `##genome-build` directives and of `#!`
+    /// pragmas, e.g. `#!genome-version`, in the order of their appearance.
+    pub fn properties(&self) -> &[(String, String)] {
+        &self.properties
+    }
+
+    /// First value of a property, see `properties`.
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns true if the scaffold is circular, i.e. it is described by a
+    /// `region` record with attribute `Is_circular=true`.
+    pub fn is_circular(&self, scaffold: &str) -> bool {
+        self.circular.contains(scaffold)
+    }
+
+    /// Remaining `##` directives verbatim, including the leading `##`.
+    pub fn other_directives(&self) -> &[String] {
+        &self.other
+    }
+
+    /// Records a comment or directive line, lines which are neither `##`
+    /// directives nor `#!` pragmas are ignored.
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let (directive, pragma) = if let Some(pragma) = line.strip_prefix("#!") {
+            (pragma, true)
+        } else {
+            match line.strip_prefix("##") {
+                Some(directive) if !directive.starts_with('#') => (directive, false),
+                _ => return Ok(()),
+            }
+        };
+
+        let (key, value) = match directive.split_once(char::is_whitespace) {
+            Some((key, value)) => (key, value.trim()),
+            None => (directive.trim(), ""),
+        };
+        match key {
+            "gff-version" if !pragma => self.version = Some(String::from(value)),
+            "sequence-region" if !pragma => {
+                let tokens: Vec<&str> = value.split_whitespace().collect();
+                ensure!(
+                    tokens.len() == 3,
+                    "Expected scaffold, start and end in sequence-region directive, got: {}",
+                    value
+                );
+                let end = tokens[2].parse::<usize>().with_context(|| {
+                    format!(
+                        "Sequence region end has to be a positive integer. Got: {}",
+                        tokens[2]
+                    )
+                })?;
+                self.sequence_lengths.insert(String::from(tokens[0]), end);
+            }
+            _ if pragma || key == "species" || key == "genome-build" => {
+                self.properties
+                    .push((String::from(key), String::from(value)));
+            }
+            _ => self.other.push(String::from(line)),
+        }
+        Ok(())
+    }
+}
+
+/// Streaming GFF reader yielding annotations one at a time.
+///
+/// Comment and directive lines starting with `#` as well as blank lines are
+/// skipped, reading stops at the `##FASTA` directive. Directives are
+/// collected into `GffMetadata`, see `metadata`. Errors name the line of
+/// the offending record or directive. After the first error the iterator
+/// is exhausted.
+pub struct GffReader<R: BufRead> {
+    reader: R,
+    source: String,
+    options: GffOptions,
+    metadata: GffMetadata,
+    /// Whether reading stopped at the `##FASTA` directive.
+    at_fasta: bool,
+    line: String,
+    /// Number of lines read so far.
+    line_number: usize,
+    done: bool,
+}
+
+impl<R: BufRead> GffReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_source(reader, String::from("input"))
+    }
+
+    pub fn with_options(mut self, options: GffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Skips records not selected by `filter`, see `GffFilter`. This
+    /// replaces `GffOptions::filter`.
+    pub fn with_filter(mut self, filter: GffFilter) -> Self {
+        self.options.filter = filter;
+        self
+    }
+
+    fn with_source(reader: R, source: String) -> Self {
+        Self {
+            reader,
+            source,
+            options: GffOptions::default(),
+            metadata: GffMetadata::default(),
+            at_fasta: false,
+            line: String::new(),
+            line_number: 0,
+            done: false,
+        }
+    }
+
+    /// Reads lines until the next record and keeps it without the line
+    /// break in `self.line`. Returns false at the end of the records.
+    fn next_record(&mut self) -> Result<bool> {
+        loop {
+            self.line.clear();
+            let num_bytes = self
+                .reader
+                .read_line(&mut self.line)
+                .with_context(|| format!("Could not read {}.", self.source))?;
+            if num_bytes == 0 {
+                return Ok(false);
+            }
+            self.line_number += 1;
 
-    let attributes = tokens.pop().unwrap();
-    let phase = tokens.pop().unwrap();
-    let strand = tokens.pop().unwrap();
-    let score = tokens.pop().unwrap();
-    let end = tokens.pop().unwrap();
-    let start = tokens.pop().unwrap();
-    let feature = tokens.pop().unwrap();
-    let source = tokens.pop().unwrap();
-    let scaffold = tokens.pop().unwrap();
-    assert_eq!(tokens.len(), 0);
-
-    let phase = match phase.as_str() {
+            let len = self.line.trim_end_matches(['\n', '\r']).len();
+            self.line.truncate(len);
+            if self.line.starts_with("##FASTA") {
+                // Embedded sequences follow until the end of the file.
+                self.at_fasta = true;
+                return Ok(false);
+            } else if !is_ignored_line(&self.line) {
+                return Ok(true);
+            }
+
+            let (source, line_number) = (&self.source, self.line_number);
+            self.metadata
+                .parse_line(&self.line)
+                .with_context(|| format!("Failed to parse line {} of {}.", line_number, source))?;
+        }
+    }
+
+    /// Metadata of directives read so far. Directives may appear anywhere
+    /// in the file, the metadata is complete once the reader is exhausted.
+    pub fn metadata(&self) -> &GffMetadata {
+        &self.metadata
+    }
+
+    pub fn into_metadata(self) -> GffMetadata {
+        self.metadata
+    }
+
+    fn next_annotation(&mut self) -> Result<Option<Annotation>> {
+        while self.next_record()? {
+            let (source, line_number) = (&self.source, self.line_number);
+            let annotation = match parse_gff_line(&self.line, &self.options) {
+                Err(_) if self.options.skip_malformed => continue,
+                annotation => annotation.with_context(|| {
+                    format!("Failed to parse line {} of {}.", line_number, source)
+                })?,
+            };
+            if let Some(annotation) = annotation {
+                if annotation.feature() == &Feature::Other(String::from("region"))
+                    && Attributes::parse(annotation.attributes()).get("Is_circular") == Some("true")
+                {
+                    self.metadata
+                        .circular
+                        .insert(String::from(annotation.scaffold()));
+                }
+                return Ok(Some(annotation.with_line(Some(line_number))));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Checks whether a line is a comment, a directive, a `###` separator or
+/// blank.
+fn is_ignored_line(line: &str) -> bool {
+    line.starts_with('#') || line.trim().is_empty()
+}
+
+impl GffReader<BufReader<Box<dyn Read>>> {
+    /// Opens a GFF file. Gzip compressed files, recognized by their magic
+    /// bytes, are transparently decompressed.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::with_source(
+            open_maybe_gzip(path)?,
+            format!("file {}", path.display()),
+        ))
+    }
+}
+
+impl<R: BufRead> Iterator for GffReader<R> {
+    type Item = Result<Annotation>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_annotation() {
+            Ok(Some(annotation)) => Some(Ok(annotation)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Parses a single GFF record from raw bytes, e.g. a line read from a file
+/// with or without the trailing line break.
+///
+/// This never panics: invalid input of any kind, including invalid UTF-8,
+/// results in an error.
+pub fn parse_gff_record(bytes: &[u8]) -> Result<Annotation> {
+    let line = std::str::from_utf8(bytes).context("GFF record is not valid UTF-8.")?;
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    parse_gff_line(line, &GffOptions::default()).map(Option::unwrap)
+}
+
+/// Parses a GFF line, returns `None` if the record is skipped by the filter
+/// of `options`.
+fn parse_gff_line(line: &str, options: &GffOptions) -> Result<Option<Annotation>> {
+    let tokens: Vec<&str> = line.split('\t').take(GFF_NUM_COLUMNS).collect();
+
+    let (scaffold, source, feature, start, end, score, strand, phase, attributes) = match tokens[..]
+    {
+        [scaffold, source, feature, start, end, score, strand, phase, attributes] => (
+            scaffold, source, feature, start, end, score, strand, phase, attributes,
+        ),
+        _ => bail!(
+            "Not enough tab separated tokens. Expected {} got {}.",
+            GFF_NUM_COLUMNS,
+            tokens.len()
+        ),
+    };
+
+    if !options
+        .filter
+        .accepts(scaffold, source, feature, score, options.format)?
+    {
+        return Ok(None);
+    }
+
+    let phase = match phase {
         "0" => Some(Phase::Zero),
         "1" => Some(Phase::One),
         "2" => Some(Phase::Two),
         _ => None,
     };
 
-    let strand = match strand.as_str() {
+    let strand = match strand {
         "+" => Strand::Positive,
         "-" => Strand::Negative,
+        "." => Strand::Unstranded,
+        "?" => Strand::Unknown,
         unrecognized => {
-            bail!("Invalid strand, only +, - are valid. Got: {}", unrecognized);
+            bail!(
+                "Invalid strand, only +, -, . and ? are valid. Got: {}",
+                unrecognized
+            );
         }
     };
 
-    let score = match score.as_str() {
-        "." => None,
-        score => Some(
-            score
-                .parse::<u32>()
-                .context("Score is not a positive integer.")?,
-        ),
-    };
+    let score = parse_score(score)?;
 
     // GFF end is 1-based inclusive, we want 0-based exclusive which is the same number.
     let end = end
@@ -102,25 +723,409 @@ fn parse_gff_line(line: String) -> Result<Annotation> {
         end
     );
 
-    let feature = match feature.as_str() {
-        "start_codon" => Feature::StartCodon,
-        "stop_codon" => Feature::StopCodon,
-        "CDS" => Feature::CDS,
-        "exon" => Feature::Exon,
-        unrecognized => {
-            bail!("Unrecognized feature: {}", unrecognized);
+    if options.format == GffFormat::Gtf {
+        Attributes::parse_gtf(attributes)?;
+    }
+
+    let feature = parse_feature(feature, options)?;
+
+    Ok(Some(Annotation::new(
+        String::from(scaffold),
+        String::from(source),
+        feature,
+        score,
+        strand,
+        phase,
+        start,
+        end,
+        String::from(attributes),
+    )))
+}
+
+fn parse_score(score: &str) -> Result<Option<f64>> {
+    match score {
+        "." => Ok(None),
+        score => {
+            let value = score
+                .parse::<f64>()
+                .with_context(|| format!("Score is not a number. Got: {}", score))?;
+            ensure!(value.is_finite(), "Score is not finite. Got: {}", score);
+            Ok(Some(value))
+        }
+    }
+}
+
+fn parse_feature(feature: &str, options: &GffOptions) -> Result<Feature> {
+    let feature = map_feature(feature, options.format);
+    if let Feature::Other(unrecognized) = &feature {
+        ensure!(
+            !options.strict_features,
+            "Unrecognized feature: {}",
+            unrecognized
+        );
+    }
+    Ok(feature)
+}
+
+/// Maps a GFF feature type to a `Feature`, unrecognized types are mapped to
+/// `Feature::Other`.
+fn map_feature(feature: &str, format: GffFormat) -> Feature {
+    match (feature, format) {
+        ("start_codon", _) => Feature::StartCodon,
+        ("stop_codon", _) => Feature::StopCodon,
+        ("CDS", _) => Feature::CDS,
+        ("exon", _) => Feature::Exon,
+        ("repeat_region", _) => Feature::Repeat,
+        ("gene", _) => Feature::Gene,
+        ("mRNA", _) => Feature::MRNA,
+        ("transcript", _) => Feature::Transcript,
+        ("intron", _) => Feature::Intron,
+        ("five_prime_UTR", _) => Feature::FivePrimeUTR,
+        ("three_prime_UTR", _) => Feature::ThreePrimeUTR,
+        ("5UTR", GffFormat::Gtf) | ("five_prime_utr", GffFormat::Gtf) => Feature::FivePrimeUTR,
+        ("3UTR", GffFormat::Gtf) | ("three_prime_utr", GffFormat::Gtf) => Feature::ThreePrimeUTR,
+        (unrecognized, _) => Feature::Other(String::from(unrecognized)),
+    }
+}
+
+/// Writes annotations to a GFF3 file, see `GffWriter` for more control
+/// over the output.
+pub fn write_gff(path: &Path, annotations: &[Annotation]) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create file {}.", path.display()))?;
+    GffWriter::new(BufWriter::new(file))
+        .write(annotations)
+        .with_context(|| format!("Failed to write file {}.", path.display()))
+}
+
+/// Writer of GFF3 files.
+///
+/// The output starts with a `##gff-version 3` directive followed by one
+/// `##sequence-region` directive per scaffold configured via
+/// `with_regions`. Attribute columns are re-serialized through `Attributes`,
+/// i.e. percent encoded in GFF3 style even for records loaded from GTF.
+pub struct GffWriter<W: Write> {
+    writer: W,
+    regions: Vec<(String, usize)>,
+    sorted: bool,
+    unique_ids: bool,
+}
+
+impl<W: Write> GffWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            regions: Vec::new(),
+            sorted: false,
+            unique_ids: false,
+        }
+    }
+
+    /// Sets scaffold names and lengths written as `##sequence-region`
+    /// directives. The order of the regions is kept.
+    pub fn with_regions<I>(mut self, regions: I) -> Self
+    where
+        I: IntoIterator<Item = (String, usize)>,
+    {
+        self.regions = regions.into_iter().collect();
+        self
+    }
+
+    /// If enabled, annotations are written sorted genomically: by scaffold
+    /// (in the order of configured regions, followed by remaining scaffolds
+    /// sorted by name), by start and longer annotations first.
+    ///
+    /// Each gene graph, see `build_gene_models`, is kept together with
+    /// parents before children (genes, transcripts, their features and
+    /// deeper descendants, each sorted by coordinates) and is followed by a
+    /// `###` directive. Genes sharing records form a single graph. If gene
+    /// models cannot be built, e.g. due to a `Parent` referencing a missing
+    /// record, all records are written as single records in genomic order.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// If enabled, `write` fails before writing anything when two
+    /// annotations share the same GFF3 `ID` attribute.
+    pub fn unique_ids(mut self, unique_ids: bool) -> Self {
+        self.unique_ids = unique_ids;
+        self
+    }
+
+    /// Writes all annotations and flushes the underlying writer.
+    pub fn write(mut self, annotations: &[Annotation]) -> Result<()> {
+        if self.unique_ids {
+            let duplicates = find_duplicate_ids(annotations);
+            if !duplicates.is_empty() {
+                let listing: Vec<String> = duplicates
+                    .iter()
+                    .map(|d| format!("{} (annotations {} and {})", d.id, d.first, d.second))
+                    .collect();
+                bail!("Duplicate annotation IDs: {}.", listing.join(", "));
+            }
+        }
+
+        writeln!(self.writer, "##gff-version 3")?;
+        for (name, length) in self.regions.iter() {
+            writeln!(self.writer, "##sequence-region {} 1 {}", name, length)?;
+        }
+
+        if self.sorted {
+            let order: HashMap<&str, usize> = self
+                .regions
+                .iter()
+                .enumerate()
+                .map(|(i, (name, _))| (name.as_str(), i))
+                .collect();
+            let mut graphs = gene_graphs(annotations);
+            graphs.sort_by(|a, b| {
+                let (a, b) = (&annotations[a.members[0].1], &annotations[b.members[0].1]);
+                let a_order = order.get(a.scaffold()).copied().unwrap_or(usize::MAX);
+                let b_order = order.get(b.scaffold()).copied().unwrap_or(usize::MAX);
+                a_order
+                    .cmp(&b_order)
+                    .then_with(|| a.scaffold().cmp(b.scaffold()))
+            });
+            for graph in graphs {
+                for &(_, i) in graph.members.iter() {
+                    write_gff_line(&mut self.writer, &annotations[i])?;
+                }
+                if graph.gene {
+                    writeln!(self.writer, "###")?;
+                }
+            }
+        } else {
+            for annotation in annotations {
+                write_gff_line(&mut self.writer, annotation)?;
+            }
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Records written together by a sorted `GffWriter`: a gene graph or a
+/// single record outside of any gene.
+struct Graph {
+    /// Whether this is a gene graph followed by a `###` directive.
+    gene: bool,
+    /// Hierarchy levels and indices of the records in the order of writing.
+    members: Vec<(usize, usize)>,
+}
+
+/// Hierarchy levels of records within a gene graph.
+const GENE_LEVEL: usize = 0;
+const TRANSCRIPT_LEVEL: usize = 1;
+const FEATURE_LEVEL: usize = 2;
+const DESCENDANT_LEVEL: usize = 3;
+
+/// Scaffold, coordinates, type and attributes of a record.
+type RecordKey<'a> = (&'a str, usize, usize, &'a Feature, &'a str);
+
+fn record_key(annotation: &Annotation) -> RecordKey<'_> {
+    (
+        annotation.scaffold(),
+        annotation.start(),
+        annotation.end(),
+        annotation.feature(),
+        annotation.attributes(),
+    )
+}
+
+/// Groups annotations into gene graphs per `build_gene_models` and single
+/// records, each sorted by levels and coordinates. Graphs are sorted by the
+/// start and the end of their first record, scaffolds are left unordered.
+/// All annotations are single records if gene models cannot be built.
+fn gene_graphs(annotations: &[Annotation]) -> Vec<Graph> {
+    let genes = build_gene_models(annotations).unwrap_or_default();
+
+    // Gene models hold copies of the records, they are mapped back to their
+    // indices by coordinates, type and attributes.
+    let mut indices: HashMap<RecordKey, Vec<usize>> = HashMap::new();
+    for (i, annotation) in annotations.iter().enumerate() {
+        indices.entry(record_key(annotation)).or_default().push(i);
+    }
+
+    let mut owners: Vec<Option<usize>> = vec![None; annotations.len()];
+    let mut graphs: Vec<Graph> = Vec::new();
+    for gene in genes.iter() {
+        let current = graphs.len();
+        let records = gene
+            .annotation()
+            .map(|a| (GENE_LEVEL, a))
+            .into_iter()
+            .chain(gene.transcripts().iter().flat_map(|transcript| {
+                transcript
+                    .annotation()
+                    .map(|a| (TRANSCRIPT_LEVEL, a))
+                    .into_iter()
+                    .chain(transcript.features().iter().map(|a| (FEATURE_LEVEL, a)))
+            }));
+
+        let mut members = Vec::new();
+        let mut shared: Option<usize> = None;
+        for (level, record) in records {
+            let candidates = &indices[&record_key(record)];
+            match candidates.iter().find(|&&i| owners[i].is_none()) {
+                Some(&i) => {
+                    owners[i] = Some(current);
+                    members.push((level, i));
+                }
+                None => {
+                    if let Some(owner) = candidates
+                        .iter()
+                        .filter_map(|&i| owners[i])
+                        .find(|&owner| owner != current)
+                    {
+                        shared.get_or_insert(owner);
+                    }
+                }
+            }
+        }
+
+        match shared {
+            Some(owner) => {
+                for &(_, i) in members.iter() {
+                    owners[i] = Some(owner);
+                }
+                graphs[owner].members.extend(members);
+            }
+            None => graphs.push(Graph {
+                gene: true,
+                members,
+            }),
+        }
+    }
+
+    // Records below transcript features are not part of gene models, they
+    // join the graph of their parent.
+    let attributes: Vec<Attributes> = annotations.iter().map(|a| a.parsed_attributes()).collect();
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    loop {
+        for (owner, attributes) in owners.iter().zip(attributes.iter()) {
+            if let (&Some(owner), Some(id)) = (owner, attributes.get("ID")) {
+                ids.entry(id).or_insert(owner);
+            }
+        }
+
+        let mut changed = false;
+        for (i, attributes) in attributes.iter().enumerate() {
+            if owners[i].is_some() {
+                continue;
+            }
+            if let Some(&owner) = attributes
+                .get_all("Parent")
+                .iter()
+                .find_map(|parent| ids.get(parent.as_str()))
+            {
+                owners[i] = Some(owner);
+                graphs[owner].members.push((DESCENDANT_LEVEL, i));
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
         }
+    }
+
+    for (i, owner) in owners.iter().enumerate() {
+        if owner.is_none() {
+            graphs.push(Graph {
+                gene: false,
+                members: vec![(GENE_LEVEL, i)],
+            });
+        }
+    }
+
+    for graph in graphs.iter_mut() {
+        graph.members.sort_by(|&(a_level, a), &(b_level, b)| {
+            let (a, b) = (&annotations[a], &annotations[b]);
+            a_level
+                .cmp(&b_level)
+                .then_with(|| a.start().cmp(&b.start()))
+                .then_with(|| b.end().cmp(&a.end()))
+        });
+    }
+    graphs.sort_by(|a, b| {
+        let (a, b) = (&annotations[a.members[0].1], &annotations[b.members[0].1]);
+        a.start()
+            .cmp(&b.start())
+            .then_with(|| b.end().cmp(&a.end()))
+    });
+
+    graphs
+}
+
+/// Returns the GFF type string of a feature.
+pub(crate) fn feature_to_str(feature: &Feature) -> &str {
+    match feature {
+        Feature::StartCodon => "start_codon",
+        Feature::StopCodon => "stop_codon",
+        Feature::CDS => "CDS",
+        Feature::Exon => "exon",
+        Feature::Repeat => "repeat_region",
+        Feature::Gene => "gene",
+        Feature::MRNA => "mRNA",
+        Feature::Transcript => "transcript",
+        Feature::Intron => "intron",
+        Feature::FivePrimeUTR => "five_prime_UTR",
+        Feature::ThreePrimeUTR => "three_prime_UTR",
+        Feature::Other(feature) => feature,
+    }
+}
+
+fn write_gff_line<W: Write>(writer: &mut W, annotation: &Annotation) -> Result<()> {
+    let feature = feature_to_str(annotation.feature());
+
+    let score = match annotation.score() {
+        Some(score) => score.to_string(),
+        None => String::from("."),
     };
 
-    Ok(Annotation::new(
-        scaffold, source, feature, score, strand, phase, start, end, attributes,
-    ))
+    let strand = match annotation.strand() {
+        Strand::Positive => "+",
+        Strand::Negative => "-",
+        Strand::Unstranded => ".",
+        Strand::Unknown => "?",
+    };
+
+    let phase = match annotation.phase() {
+        Some(Phase::Zero) => "0",
+        Some(Phase::One) => "1",
+        Some(Phase::Two) => "2",
+        None => ".",
+    };
+
+    let attributes = annotation.parsed_attributes();
+
+    // GFF start is 1-based inclusive while ours is 0-based inclusive. End is
+    // the same number in both representations.
+    writeln!(
+        writer,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        annotation.scaffold(),
+        annotation.source(),
+        feature,
+        annotation.start() + 1,
+        annotation.end(),
+        score,
+        strand,
+        phase,
+        attributes
+    )?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::data::{Feature, Phase, Strand};
+    use super::{GffFilter, GffFormat, GffOptions, GffReader, GffWriter};
+    use crate::data::{Annotation, Feature, Phase, Strand};
+    use proptest::prelude::*;
+    use std::env;
+    use std::fs;
     use std::path::Path;
 
     #[test]
@@ -135,17 +1140,17 @@ mod test {
         let one = annotations.pop().unwrap();
 
         assert_eq!(one.scaffold(), "scaffold_1");
-        assert_eq!(one.feature(), Feature::Exon);
+        assert_eq!(one.feature(), &Feature::Exon);
 
         assert_eq!(two.scaffold(), "scaffold_2");
-        assert_eq!(two.feature(), Feature::CDS);
+        assert_eq!(two.feature(), &Feature::CDS);
 
         assert_eq!(three.scaffold(), "scaffold_3");
-        assert_eq!(three.feature(), Feature::StartCodon);
+        assert_eq!(three.feature(), &Feature::StartCodon);
 
         assert_eq!(four.scaffold(), "scaffold_4");
         assert_eq!(four.source(), "JGI");
-        assert_eq!(four.feature(), Feature::StopCodon);
+        assert_eq!(four.feature(), &Feature::StopCodon);
         assert_eq!(four.start(), 2183);
         assert_eq!(four.end(), 2186);
         assert_eq!(four.score(), None);
@@ -158,10 +1163,478 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_process_gff() {
+        let gff_path = Path::new("./tests/valid.gff");
+
+        let mut features = Vec::new();
+        let count = super::process_gff(gff_path, &GffOptions::default(), |annotation| {
+            features.push(annotation.feature().clone());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(features[3], Feature::StopCodon);
+
+        let options = GffOptions {
+            filter: GffFilter::new().features(vec![Feature::StopCodon]),
+            ..GffOptions::default()
+        };
+        let mut features = Vec::new();
+        let count = super::process_gff(gff_path, &options, |annotation| {
+            features.push(annotation.feature().clone());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(features, vec![Feature::StopCodon]);
+
+        let malformed = Path::new("./tests/malformed.gff3");
+        assert!(super::process_gff(malformed, &GffOptions::default(), |_| Ok(())).is_err());
+        let options = GffOptions {
+            skip_malformed: true,
+            ..GffOptions::default()
+        };
+        let count = super::process_gff(malformed, &options, |_| Ok(())).unwrap();
+        assert_eq!(count, 3);
+
+        let mut calls = 0;
+        let error = super::process_gff(gff_path, &GffOptions::default(), |annotation| {
+            calls += 1;
+            ensure!(*annotation.feature() == Feature::Exon, "Not an exon.");
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(format!("{}", error), "Not an exon.");
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_load_gff_file_with_progress() {
+        use crate::progress::{LoadOptions, Progress};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let reports: Rc<RefCell<Vec<Progress>>> = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&reports);
+        let load = LoadOptions {
+            progress: Some(Box::new(move |progress| sink.borrow_mut().push(progress))),
+            ..LoadOptions::default()
+        };
+        let gff_path = Path::new("./tests/valid.gff");
+        let annotations = super::load_gff_file_with_progress(gff_path, load).unwrap();
+        assert_eq!(annotations.len(), 4);
+
+        // The file is smaller than the interval so only the final report is
+        // made.
+        let size = std::fs::metadata(gff_path).unwrap().len();
+        assert_eq!(
+            *reports.borrow(),
+            vec![Progress {
+                bytes_read: size,
+                total_bytes: Some(size),
+                records: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_load_gff3_with_directives() {
+        let expected = super::load_gff_file(Path::new("./tests/valid.gff")).unwrap();
+        let annotations = super::load_gff_file(Path::new("./tests/directives.gff3")).unwrap();
+        assert_eq!(annotations.len(), expected.len());
+        for (a, b) in annotations.iter().zip(expected.iter()) {
+            assert_eq!(a.scaffold(), b.scaffold());
+            assert_eq!(a.feature(), b.feature());
+            assert_eq!((a.start(), a.end()), (b.start(), b.end()));
+            assert_eq!(a.attributes(), b.attributes());
+        }
+
+        let error = match super::load_gff_file(Path::new("./tests/invalid_directives.gff3")) {
+            Ok(_) => panic!("Loading did not fail."),
+            Err(error) => error,
+        };
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse line 5 of file ./tests/invalid_directives.gff3."
+        );
+    }
+
+    #[test]
+    fn test_load_scores() {
+        let annotations = super::load_gff_file(Path::new("./tests/scores.gff")).unwrap();
+        let scores: Vec<Option<f64>> = annotations.iter().map(|a| a.score()).collect();
+        assert_eq!(
+            scores,
+            [Some(0.87), Some(-1.2), Some(1e-5), Some(12.), None]
+        );
+
+        assert!(super::parse_gff_record(b"s\tt\texon\t1\t2\tx\t+\t.\t").is_err());
+        assert!(super::parse_gff_record(b"s\tt\texon\t1\t2\tNaN\t+\t.\t").is_err());
+    }
+
+    #[test]
+    fn test_load_strands() {
+        let annotations = super::load_gff_file(Path::new("./tests/strands.gff")).unwrap();
+        let strands: Vec<Strand> = annotations.iter().map(|a| a.strand()).collect();
+        assert_eq!(
+            strands,
+            [Strand::Unstranded, Strand::Unknown, Strand::Negative]
+        );
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output).write(&annotations).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\t10\t50\t.\t.\t.\tName=L1\n"));
+        assert!(output.contains("\t60\t80\t.\t?\t.\tName=AluY\n"));
+
+        assert!(super::parse_gff_record(b"s\tt\texon\t1\t2\t.\tx\t.\t").is_err());
+    }
+
+    #[test]
+    fn test_write_gff_round_trip() {
+        let gff_path = Path::new("./tests/valid.gff");
+        let annotations = super::load_gff_file(gff_path).unwrap();
+
+        let directory = env::temp_dir().join(format!("ncrs-gff-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let output = directory.join("round_trip.gff3");
+        super::write_gff(&output, &annotations).unwrap();
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .starts_with("##gff-version 3\n"));
+
+        let loaded = super::load_gff_file(&output).unwrap();
+        assert_eq!(loaded.len(), annotations.len());
+        for (a, b) in annotations.iter().zip(loaded.iter()) {
+            assert_eq!(a.scaffold(), b.scaffold());
+            assert_eq!(a.source(), b.source());
+            assert_eq!(a.feature(), b.feature());
+            assert_eq!(a.score(), b.score());
+            assert_eq!(a.strand(), b.strand());
+            assert_eq!(a.phase(), b.phase());
+            assert_eq!(a.start(), b.start());
+            assert_eq!(a.end(), b.end());
+            // GTF attributes are written in GFF3 style.
+            assert_eq!(a.parsed_attributes(), b.parsed_attributes());
+        }
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_write_percent_encoded_attributes() {
+        let raw = "ID=g1;Name=ABC%3B1;Note=50%25 GC%09x,%G1";
+        let annotation =
+            super::parse_gff_record(format!("s\tt\tgene\t1\t10\t.\t+\t.\t{}", raw).as_bytes())
+                .unwrap();
+        let rebuilt = annotation.with_attributes(annotation.parsed_attributes().to_string());
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .write(&[annotation.clone(), rebuilt])
+            .unwrap();
+        let loaded: Vec<Annotation> = GffReader::new(&output[..]).map(Result::unwrap).collect();
+        // Attributes are re-encoded including the malformed escape, line
+        // numbers are not written.
+        assert_eq!(loaded[1].line(), Some(3));
+        for loaded in loaded.iter() {
+            assert_eq!(
+                loaded.attributes(),
+                "ID=g1;Name=ABC%3B1;Note=50%25 GC%09x,%25G1"
+            );
+        }
+        for loaded in loaded.iter() {
+            assert_eq!(loaded.parsed_attributes(), annotation.parsed_attributes());
+        }
+    }
+
+    #[test]
+    fn test_gff_reader() {
+        let reader = GffReader::open(Path::new("./tests/valid.gff")).unwrap();
+        let cds: Vec<Annotation> = reader
+            .map(Result::unwrap)
+            .filter(|a| *a.feature() == Feature::CDS)
+            .collect();
+        assert_eq!(cds.len(), 1);
+        assert_eq!(cds[0].scaffold(), "scaffold_2");
+
+        let input = "##gff-version 3\ns\tt\texon\t1\t10\t.\t+\t.\t.\ns\tt\texon\t5\n";
+        let mut reader = GffReader::new(input.as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().line(), Some(2));
+        let error = reader.next().unwrap().err().unwrap();
+        assert_eq!(error.to_string(), "Failed to parse line 3 of input.");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_load_gff_file_filtered() {
+        let path = Path::new("./tests/scores.gff");
+        let filter = GffFilter::new().features(vec![Feature::Exon, Feature::StartCodon]);
+        let annotations = super::load_gff_file_filtered(path, &filter).unwrap();
+        assert_eq!(annotations.len(), 3);
+
+        let filter = filter
+            .scaffolds(vec![String::from("scaffold_2")])
+            .sources(vec![String::from("AUGUSTUS")]);
+        let annotations = super::load_gff_file_filtered(path, &filter).unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].attributes(), "ID=e2");
+
+        // Records without a score are skipped.
+        let filter = GffFilter::new().min_score(0.5);
+        let annotations = super::load_gff_file_filtered(path, &filter).unwrap();
+        let ids: Vec<&str> = annotations.iter().map(|a| a.attributes()).collect();
+        assert_eq!(ids, ["ID=c1", "ID=s2"]);
+
+        // The invalid GTF attributes of line 3 are never parsed.
+        let options = GffOptions {
+            format: GffFormat::Gtf,
+            ..GffOptions::default()
+        };
+        let filter = GffFilter::new().features(vec![Feature::Gene, Feature::Transcript]);
+        let file = fs::File::open("./tests/invalid.gtf").unwrap();
+        let reader = GffReader::new(std::io::BufReader::new(file))
+            .with_options(options)
+            .with_filter(filter);
+        let annotations: Vec<Annotation> = reader.map(Result::unwrap).collect();
+        assert_eq!(annotations.len(), 2);
+
+        // Column count is checked on all lines.
+        let input = "s\tt\texon\t1\t10\t.\t+\t.\t.\ns\tt\tgene\t5\n";
+        let mut reader = GffReader::new(input.as_bytes())
+            .with_filter(GffFilter::new().features(vec![Feature::Exon]));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_load_gff_file_lossy() {
+        let path = Path::new("./tests/malformed.gff3");
+        assert!(super::load_gff_file(path).is_err());
+
+        let (annotations, errors) = super::load_gff_file_lossy(path).unwrap();
+        assert_eq!(annotations.len(), 3);
+        let lines: Vec<usize> = errors.iter().map(|e| e.line_number()).collect();
+        assert_eq!(lines, [3, 5, 6, 8, 9]);
+        assert_eq!(errors[0].line(), "scaffold_1\tt\texon\t100");
+        assert_eq!(
+            errors[1].to_string(),
+            "Line 5: Invalid strand, only +, -, . and ? are valid. Got: x"
+        );
+        assert_eq!(
+            errors[2].error().to_string(),
+            "Feature start index is greater or equal to end index. 199 >= 150"
+        );
+        assert_eq!(
+            errors[3].to_string(),
+            "Line 8: Score is not a number. Got: high: invalid float literal"
+        );
+
+        assert!(super::load_gff_file_lossy_with_limit(path, 5).is_ok());
+        let error = super::load_gff_file_lossy_with_limit(path, 4)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "More than 4 malformed records in file ./tests/malformed.gff3, the first on line 3."
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_load_gff_file_parallel() {
+        for path in [
+            "./tests/valid.gff",
+            "./tests/directives.gff3",
+            "./tests/genes.gff3",
+            "./tests/scores.gff",
+            "./tests/strands.gff",
+            "./tests/valid.gff.gz",
+        ] {
+            let expected = super::load_gff_file(Path::new(path)).unwrap();
+            let actual = super::load_gff_file_parallel(Path::new(path)).unwrap();
+            assert_eq!(expected, actual);
+        }
+
+        for path in ["./tests/invalid_directives.gff3", "./tests/malformed.gff3"] {
+            let expected = super::load_gff_file(Path::new(path)).err().unwrap();
+            let actual = super::load_gff_file_parallel(Path::new(path))
+                .err()
+                .unwrap();
+            assert_eq!(format!("{:#}", expected), format!("{:#}", actual));
+        }
+    }
+
+    /// Compares serial and parallel GFF loading of a generated file. Run
+    /// with `cargo test --release --all-features -- --ignored --nocapture`.
+    #[cfg(feature = "rayon")]
+    #[test]
+    #[ignore]
+    fn bench_load_gff_file_parallel() {
+        use std::fmt::Write;
+        use std::time::Instant;
+
+        let mut data = String::from("##gff-version 3\n");
+        for i in 0..1_000_000 {
+            writeln!(
+                data,
+                "scaffold_{}\tbench\texon\t{}\t{}\t0.5\t+\t.\tID=e{};Parent=t{}",
+                i % 20,
+                i + 1,
+                i + 100,
+                i,
+                i / 10
+            )
+            .unwrap();
+        }
+        let path = env::temp_dir().join(format!("ncrs-bench-{}.gff3", std::process::id()));
+        fs::write(&path, data).unwrap();
+
+        let started = Instant::now();
+        let serial = super::load_gff_file(&path).unwrap();
+        let serial_elapsed = started.elapsed();
+
+        let started = Instant::now();
+        let parallel = super::load_gff_file_parallel(&path).unwrap();
+        let parallel_elapsed = started.elapsed();
+
+        assert_eq!(serial, parallel);
+        println!(
+            "Serial: {:?}, parallel on {} threads: {:?}",
+            serial_elapsed,
+            rayon::current_num_threads(),
+            parallel_elapsed
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_gff_file_with_metadata() {
+        let (annotations, metadata) =
+            super::load_gff_file_with_metadata(Path::new("./tests/metadata.gff3")).unwrap();
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(metadata.version(), Some("3.1.26"));
+        assert_eq!(metadata.sequence_lengths().len(), 2);
+        assert_eq!(metadata.sequence_lengths()["scaffold_1"], 280);
+        assert_eq!(metadata.sequence_lengths()["scaffold_2"], 7);
+        assert_eq!(
+            metadata.property("species"),
+            Some("https://www.ncbi.nlm.nih.gov/Taxonomy/Browser/wwwtax.cgi?id=9606")
+        );
+        assert_eq!(metadata.property("genome-build"), Some("JGI v1.0"));
+        assert_eq!(metadata.property("genome-version"), Some("GRCh38"));
+        assert_eq!(metadata.properties().len(), 3);
+        assert_eq!(metadata.other_directives(), ["##feature-ontology so.obo"]);
+
+        let input = "##sequence-region scaffold_1 1\ns\tt\texon\t1\t10\t.\t+\t.\t.\n";
+        let mut reader = GffReader::new(input.as_bytes());
+        let error = reader.next().unwrap().err().unwrap();
+        assert_eq!(error.to_string(), "Failed to parse line 1 of input.");
+
+        let (_, metadata) =
+            super::load_gff_file_with_metadata(Path::new("./tests/valid.gff")).unwrap();
+        assert_eq!(metadata, super::GffMetadata::default());
+    }
+
+    #[test]
+    fn test_load_gff3_with_sequences() {
+        let (annotations, scaffolds) =
+            super::load_gff3_with_sequences(Path::new("./tests/embedded.gff3")).unwrap();
+        assert_eq!(annotations.len(), 4);
+        let names: Vec<&str> = scaffolds.iter().map(|s| s.name()).collect();
+        assert_eq!(names, ["scaffold_1", "scaffold_2"]);
+        assert_eq!(scaffolds[0].len(), 24);
+        for annotation in annotations.iter() {
+            let scaffold = scaffolds
+                .iter()
+                .find(|s| s.name() == annotation.scaffold())
+                .unwrap();
+            assert!(annotation.end() <= scaffold.len());
+        }
+
+        let (annotations, scaffolds) =
+            super::load_gff3_with_sequences(Path::new("./tests/valid.gff")).unwrap();
+        assert_eq!(annotations.len(), 4);
+        assert!(scaffolds.is_empty());
+
+        let directory = env::temp_dir().join(format!("ncrs-embedded-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("invalid.gff3");
+        fs::write(&path, "s\tt\texon\t1\t2\t.\t+\t.\t.\n##FASTA\n>s\nAC\nA@\n").unwrap();
+        let error = super::load_gff3_with_sequences(&path).err().unwrap();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Invalid symbol '@' at line 5, column 2 in record s."
+        );
+        fs::remove_dir_all(directory).unwrap();
+
+        let (_, scaffolds) =
+            super::load_gff3_with_sequences(Path::new("./tests/circular.gff3")).unwrap();
+        assert!(scaffolds[0].is_circular());
+        assert!(!scaffolds[1].is_circular());
+    }
+
+    #[test]
+    fn test_load_gtf() {
+        let annotations = super::load_gtf_file(Path::new("./tests/valid.gtf")).unwrap();
+        assert_eq!(annotations.len(), 6);
+        assert_eq!(annotations[0].feature(), &Feature::Gene);
+        assert_eq!(annotations[1].feature(), &Feature::Transcript);
+        assert_eq!(annotations[3].feature(), &Feature::FivePrimeUTR);
+        assert_eq!(annotations[5].feature(), &Feature::ThreePrimeUTR);
+        let attributes = annotations[2].parsed_attributes();
+        assert_eq!(attributes.get("gene_id"), Some("ENSG00000223972"));
+        assert_eq!(attributes.get("transcript_id"), Some("ENST00000456328"));
+        assert_eq!(attributes.get("exon_number"), Some("1"));
+
+        let error = super::load_gtf_file(Path::new("./tests/invalid.gtf"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse line 3 of file ./tests/invalid.gtf."
+        );
+        assert_eq!(
+            format!("{}", error.root_cause()),
+            "Attribute ID=t1 is not in GTF style."
+        );
+    }
+
+    #[test]
+    fn test_load_gzipped_gff() {
+        let expected = super::load_gff_file(Path::new("./tests/valid.gff")).unwrap();
+        let annotations = super::load_gff_file(Path::new("./tests/valid.gff.gz")).unwrap();
+        assert_eq!(annotations.len(), expected.len());
+        for (a, b) in annotations.iter().zip(expected.iter()) {
+            assert_eq!(a.scaffold(), b.scaffold());
+            assert_eq!((a.start(), a.end()), (b.start(), b.end()));
+        }
+        let reader = GffReader::open(Path::new("./tests/valid.gff.gz")).unwrap();
+        assert_eq!(reader.count(), expected.len());
+
+        let options = GffOptions {
+            strict_features: true,
+            ..GffOptions::default()
+        };
+        let path = Path::new("./tests/invalid.gff.gz");
+        let error = super::load_gff_file_with_options(path, &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse line 2 of file ./tests/invalid.gff.gz."
+        );
+    }
+
     #[test]
     fn test_load_invalid_gff() {
         let gff_path = Path::new("./tests/invalid.gff");
-        let error = match super::load_gff_file(gff_path) {
+        let options = GffOptions {
+            strict_features: true,
+            ..GffOptions::default()
+        };
+        let error = match super::load_gff_file_with_options(gff_path, &options) {
             Ok(_) => panic!("Loading did not fail."),
             Err(error) => error,
         };
@@ -175,5 +1648,173 @@ mod test {
             format!("{}", error.root_cause()),
             String::from("Unrecognized feature: XXX")
         );
+
+        let annotations = super::load_gff_file(gff_path).unwrap();
+        assert_eq!(
+            annotations[1].feature(),
+            &Feature::Other(String::from("XXX"))
+        );
+    }
+
+    #[test]
+    fn test_structural_features() {
+        let mut output = Vec::new();
+        for feature in [
+            "gene",
+            "mRNA",
+            "transcript",
+            "intron",
+            "five_prime_UTR",
+            "three_prime_UTR",
+            "tRNA",
+        ] {
+            let record = format!("s\tt\t{}\t1\t10\t.\t+\t.\tID=x", feature);
+            let annotation = super::parse_gff_record(record.as_bytes()).unwrap();
+            assert_eq!(super::feature_to_str(annotation.feature()), feature);
+            output.push(annotation.feature().clone());
+        }
+        assert_eq!(output[0], Feature::Gene);
+        assert_eq!(output[1], Feature::MRNA);
+        assert_eq!(output[4], Feature::FivePrimeUTR);
+        assert_eq!(output[6], Feature::Other(String::from("tRNA")));
+    }
+
+    #[test]
+    fn test_write_sorted_gff() {
+        let gff_path = Path::new("./tests/valid.gff");
+        let mut annotations = super::load_gff_file(gff_path).unwrap();
+        annotations.reverse();
+
+        let regions = vec![
+            (String::from("scaffold_2"), 5000),
+            (String::from("scaffold_1"), 4000),
+        ];
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .with_regions(regions)
+            .sorted(true)
+            .write(&annotations)
+            .unwrap();
+
+        let expected = fs::read_to_string("./tests/valid.sorted.gff3").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_sorted_gene_graphs() {
+        let mut annotations = super::load_gff_file(Path::new("./tests/genes.gff3")).unwrap();
+        annotations.reverse();
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .with_regions(vec![(String::from("scaffold_1"), 10000)])
+            .sorted(true)
+            .write(&annotations)
+            .unwrap();
+
+        let expected = fs::read_to_string("./tests/genes.sorted.gff3").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_shared_gene_graphs() {
+        // Two genes sharing an exon form a single graph, a record below an
+        // exon joins the graph of the exon.
+        let input = "\
+            s\tt\tmotif\t12\t14\t.\t+\t.\tParent=e1\n\
+            s\tt\texon\t10\t20\t.\t+\t.\tID=e1;Parent=t1,t2\n\
+            s\tt\tmRNA\t10\t40\t.\t+\t.\tID=t2;Parent=g2\n\
+            s\tt\tgene\t10\t40\t.\t+\t.\tID=g2\n\
+            s\tt\tmRNA\t5\t30\t.\t+\t.\tID=t1;Parent=g1\n\
+            s\tt\tgene\t5\t30\t.\t+\t.\tID=g1\n";
+        let annotations: Vec<Annotation> = GffReader::new(input.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .sorted(true)
+            .write(&annotations)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let ids: Vec<&str> = output
+            .lines()
+            .skip(1)
+            .map(|line| line.rsplit('\t').next().unwrap())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "ID=g1",
+                "ID=g2",
+                "ID=t1;Parent=g1",
+                "ID=t2;Parent=g2",
+                "ID=e1;Parent=t1,t2",
+                "Parent=e1",
+                "###",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_sorted_orphan() {
+        // The exon references a missing transcript, so no gene models can be
+        // built.
+        let input = "\
+            s\tt\texon\t30\t40\t.\t+\t.\tParent=t2\n\
+            s\tt\tmRNA\t10\t40\t.\t+\t.\tID=t1;Parent=g1\n\
+            s\tt\tgene\t5\t40\t.\t+\t.\tID=g1\n";
+        let annotations: Vec<Annotation> = GffReader::new(input.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .sorted(true)
+            .write(&annotations)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "##gff-version 3\n\
+             s\tt\tgene\t5\t40\t.\t+\t.\tID=g1\n\
+             s\tt\tmRNA\t10\t40\t.\t+\t.\tID=t1;Parent=g1\n\
+             s\tt\texon\t30\t40\t.\t+\t.\tParent=t2\n"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn parse_gff_record_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..200)) {
+            let _ = super::parse_gff_record(&bytes);
+        }
+
+        #[test]
+        fn parse_gff_record_near_valid(
+            tokens in proptest::collection::vec("[0-9+.\\-a-zA-Z_=;]{0,12}", 0..11),
+        ) {
+            let _ = super::parse_gff_record(tokens.join("\t").as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_parse_gff_record() {
+        let annotation =
+            super::parse_gff_record(b"s1\tsrc\tCDS\t1\t18446744073709551615\t.\t-\t2\tID=x\r\n")
+                .unwrap();
+        assert_eq!(annotation.start(), 0);
+        assert_eq!(annotation.end(), usize::MAX);
+        assert_eq!(annotation.phase(), Some(Phase::Two));
+        assert_eq!(annotation.attributes(), "ID=x");
+
+        for record in [
+            &b"s1\tsrc\tCDS\t1\t18446744073709551616\t.\t-\t2\t."[..],
+            b"s1\tsrc\tCDS\t0\t10\t.\t-\t2\t.",
+            b"s1\tsrc\tCDS\t5\t4\t.\t-\t2\t.",
+            b"s1\tsrc\tCDS\t1\t10\tabc\t-\t2\t.",
+            b"s1\tsrc\tCDS\t1\t10",
+            b"s1\tsrc\tCDS\t1\t10\t.\t-\t\xff\t.",
+        ] {
+            assert!(super::parse_gff_record(record).is_err());
+        }
     }
 }
@@ -0,0 +1,281 @@
+use crate::data::{Annotation, Feature, Strand};
+use crate::gff::feature_to_str;
+use std::collections::{HashMap, HashSet};
+
+/// Way of generating annotation IDs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IdStrategy {
+    /// `<prefix>.<feature>.<n>` with `n` counting from 1 separately for each
+    /// feature type in the order of generation.
+    Sequential,
+    /// `<prefix>.<feature>.<hash>` where `hash` is a hex encoded FNV-1a hash
+    /// of scaffold, coordinates, strand and feature. The ID does not depend
+    /// on the order of generation.
+    ContentHash,
+}
+
+/// Generator of GFF3 `ID` attributes unique among all IDs it generated and
+/// all IDs registered via `with_existing`.
+///
+/// Collisions are resolved deterministically: sequential IDs skip taken
+/// numbers and content hash IDs get a `.2`, `.3`, … suffix.
+pub struct IdGenerator {
+    prefix: String,
+    strategy: IdStrategy,
+    counters: HashMap<Feature, usize>,
+    used: HashSet<String>,
+}
+
+impl IdGenerator {
+    pub fn new(prefix: &str, strategy: IdStrategy) -> Self {
+        Self {
+            prefix: String::from(prefix),
+            strategy,
+            counters: HashMap::new(),
+            used: HashSet::new(),
+        }
+    }
+
+    /// Registers IDs of existing annotations so that they are never
+    /// generated.
+    pub fn with_existing(mut self, annotations: &[Annotation]) -> Self {
+        self.used
+            .extend(annotations.iter().filter_map(annotation_id));
+        self
+    }
+
+    /// Generates a new ID for an annotation.
+    pub fn generate(&mut self, annotation: &Annotation) -> String {
+        let base = format!("{}.{}", self.prefix, feature_to_str(annotation.feature()));
+
+        let id = match self.strategy {
+            IdStrategy::Sequential => {
+                let counter = self
+                    .counters
+                    .entry(annotation.feature().clone())
+                    .or_insert(0);
+                loop {
+                    *counter += 1;
+                    let id = format!("{}.{}", base, counter);
+                    if !self.used.contains(&id) {
+                        break id;
+                    }
+                }
+            }
+            IdStrategy::ContentHash => {
+                let id = format!("{}.{:016x}", base, content_hash(annotation));
+                let mut candidate = id.clone();
+                let mut suffix = 1;
+                while self.used.contains(&candidate) {
+                    suffix += 1;
+                    candidate = format!("{}.{}", id, suffix);
+                }
+                candidate
+            }
+        };
+
+        self.used.insert(id.clone());
+        id
+    }
+
+    /// Prepends a newly generated `ID` attribute to annotations which do not
+    /// have one.
+    pub fn assign(&mut self, annotations: Vec<Annotation>) -> Vec<Annotation> {
+        annotations
+            .into_iter()
+            .map(|annotation| {
+                if annotation_id(&annotation).is_some() {
+                    return annotation;
+                }
+
+                let id = self.generate(&annotation);
+                with_id(annotation, &id)
+            })
+            .collect()
+    }
+}
+
+/// Assigns IDs to annotations if a generator is given, see
+/// `IdGenerator::assign`.
+pub(crate) fn assign_ids(
+    ids: Option<&mut IdGenerator>,
+    annotations: Vec<Annotation>,
+) -> Vec<Annotation> {
+    match ids {
+        Some(ids) => ids.assign(annotations),
+        None => annotations,
+    }
+}
+
+/// Prepends an `ID` attribute to an annotation. An empty attribute column
+/// (`.` in GFF) is replaced.
+pub(crate) fn with_id(annotation: Annotation, id: &str) -> Annotation {
+    let attributes = match annotation.attributes() {
+        "" | "." => format!("ID={}", id),
+        attributes => format!("ID={};{}", id, attributes),
+    };
+    annotation.with_attributes(attributes)
+}
+
+fn content_hash(annotation: &Annotation) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let strand = match annotation.strand() {
+        Strand::Positive => "+",
+        Strand::Negative => "-",
+        Strand::Unstranded => ".",
+        Strand::Unknown => "?",
+    };
+    let key = format!(
+        "{}\t{}\t{}\t{}\t{}",
+        annotation.scaffold(),
+        annotation.start(),
+        annotation.end(),
+        strand,
+        feature_to_str(annotation.feature())
+    );
+
+    key.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Returns value of the GFF3 `ID` attribute of an annotation.
+pub fn annotation_id(annotation: &Annotation) -> Option<String> {
+    annotation.parsed_attributes().get("ID").map(String::from)
+}
+
+/// ID shared by two annotations, which are identified by their indices.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DuplicateId {
+    pub id: String,
+    pub first: usize,
+    pub second: usize,
+}
+
+/// Finds annotations with the same GFF3 `ID` attribute. Each repeated
+/// occurrence is reported together with the first occurrence.
+pub fn find_duplicate_ids(annotations: &[Annotation]) -> Vec<DuplicateId> {
+    let mut first_occurrences: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for (i, annotation) in annotations.iter().enumerate() {
+        if let Some(id) = annotation_id(annotation) {
+            match first_occurrences.get(&id) {
+                Some(&first) => duplicates.push(DuplicateId {
+                    id,
+                    first,
+                    second: i,
+                }),
+                None => {
+                    first_occurrences.insert(id, i);
+                }
+            }
+        }
+    }
+
+    duplicates
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{DuplicateId, IdGenerator, IdStrategy};
+    use crate::data::{Annotation, Feature};
+    use crate::gff::GffWriter;
+    use crate::testing;
+
+    fn annotation(feature: Feature, start: usize, attributes: &str) -> Annotation {
+        testing::builder("s", feature, start, start + 10)
+            .attributes(attributes)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sequential() {
+        let existing = vec![annotation(Feature::Exon, 0, "ID=gen.exon.2;Parent=t1")];
+        let mut generator =
+            IdGenerator::new("gen", IdStrategy::Sequential).with_existing(&existing);
+
+        assert_eq!(
+            generator.generate(&annotation(Feature::Exon, 0, "")),
+            "gen.exon.1"
+        );
+        assert_eq!(
+            generator.generate(&annotation(Feature::CDS, 0, "")),
+            "gen.CDS.1"
+        );
+        assert_eq!(
+            generator.generate(&annotation(Feature::Exon, 5, "")),
+            "gen.exon.3"
+        );
+
+        let assigned = generator.assign(vec![
+            annotation(Feature::CDS, 0, "note=x"),
+            annotation(Feature::CDS, 0, ""),
+            annotation(Feature::CDS, 0, "ID=keep"),
+            annotation(Feature::CDS, 0, "."),
+        ]);
+        assert_eq!(assigned[0].attributes(), "ID=gen.CDS.2;note=x");
+        assert_eq!(assigned[1].attributes(), "ID=gen.CDS.3");
+        assert_eq!(assigned[2].attributes(), "ID=keep");
+        assert_eq!(assigned[3].attributes(), "ID=gen.CDS.4");
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let a = annotation(Feature::Exon, 0, "");
+        let b = annotation(Feature::Exon, 20, "");
+
+        let mut first = IdGenerator::new("gen", IdStrategy::ContentHash);
+        let a_id = first.generate(&a);
+        let b_id = first.generate(&b);
+        assert!(a_id.starts_with("gen.exon."));
+        assert_ne!(a_id, b_id);
+
+        // Stable regardless of order and across generators.
+        let mut second = IdGenerator::new("gen", IdStrategy::ContentHash);
+        assert_eq!(second.generate(&b), b_id);
+        assert_eq!(second.generate(&a), a_id);
+
+        // Identical content gets a deterministic suffix.
+        assert_eq!(second.generate(&a), format!("{}.2", a_id));
+        assert_eq!(second.generate(&a), format!("{}.3", a_id));
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let annotations = vec![
+            annotation(Feature::Exon, 0, "ID=a"),
+            annotation(Feature::Exon, 0, "ID=b"),
+            annotation(Feature::Exon, 0, "Parent=a"),
+            annotation(Feature::Exon, 0, "Name=x; ID=a"),
+        ];
+        assert_eq!(
+            super::find_duplicate_ids(&annotations),
+            vec![DuplicateId {
+                id: String::from("a"),
+                first: 0,
+                second: 3,
+            }]
+        );
+
+        let mut output = Vec::new();
+        let error = GffWriter::new(&mut output)
+            .unique_ids(true)
+            .write(&annotations)
+            .unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Duplicate annotation IDs: a (annotations 0 and 3)."
+        );
+        assert!(output.is_empty());
+
+        GffWriter::new(&mut output)
+            .unique_ids(true)
+            .write(&annotations[..3])
+            .unwrap();
+    }
+}
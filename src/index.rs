@@ -0,0 +1,612 @@
+use crate::data::{Annotation, Symbol};
+use crate::fai::{read_range, scan_fasta, FaiEntry};
+use crate::gff::parse_gff_record;
+use crate::region::Region;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader, BufWriter, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"NCIX";
+const VERSION: u32 = 2;
+
+/// Content fingerprint of a file: its length and FNV-1a hash of its whole
+/// content, so that any edit, including same-length edits of coordinates,
+/// is detected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Fingerprint {
+    len: u64,
+    hash: u64,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Result<Self> {
+        const OFFSET: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+
+        let file =
+            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut len = 0;
+        let mut hash = OFFSET;
+        loop {
+            let buffer = reader
+                .fill_buf()
+                .with_context(|| format!("Could not read file {}.", path.display()))?;
+            if buffer.is_empty() {
+                break;
+            }
+            hash = buffer
+                .iter()
+                .fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME));
+            let consumed = buffer.len();
+            len += consumed as u64;
+            reader.consume(consumed);
+        }
+        Ok(Self { len, hash })
+    }
+}
+
+/// Location of a GFF record together with its 0-based half-open range.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct AnnotationEntry {
+    start: u64,
+    end: u64,
+    offset: u64,
+    len: u64,
+}
+
+/// Records of a single scaffold sorted by start and end.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct AnnotationTrack {
+    scaffold: String,
+    max_len: u64,
+    entries: Vec<AnnotationEntry>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Index {
+    fasta: Fingerprint,
+    gff: Fingerprint,
+    sequences: Vec<FaiEntry>,
+    tracks: Vec<AnnotationTrack>,
+}
+
+impl Index {
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for fingerprint in [self.fasta, self.gff] {
+            write_u64(writer, fingerprint.len)?;
+            write_u64(writer, fingerprint.hash)?;
+        }
+
+        write_u64(writer, self.sequences.len() as u64)?;
+        for sequence in self.sequences.iter() {
+            write_str(writer, &sequence.name)?;
+            for value in [
+                sequence.length,
+                sequence.offset,
+                sequence.line_bases,
+                sequence.line_bytes,
+            ] {
+                write_u64(writer, value)?;
+            }
+        }
+
+        write_u64(writer, self.tracks.len() as u64)?;
+        for track in self.tracks.iter() {
+            write_str(writer, &track.scaffold)?;
+            write_u64(writer, track.max_len)?;
+            write_u64(writer, track.entries.len() as u64)?;
+            for entry in track.entries.iter() {
+                for value in [entry.start, entry.end, entry.offset, entry.len] {
+                    write_u64(writer, value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(bytes: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor { bytes, position: 0 };
+
+        ensure!(cursor.take(4)? == MAGIC, "Not an ncrs index file.");
+        let version = u32::from_le_bytes(cursor.take(4)?.try_into().unwrap());
+        ensure!(version == VERSION, "Unsupported index version {}.", version);
+
+        let fasta = Fingerprint {
+            len: cursor.u64()?,
+            hash: cursor.u64()?,
+        };
+        let gff = Fingerprint {
+            len: cursor.u64()?,
+            hash: cursor.u64()?,
+        };
+
+        let mut sequences = Vec::new();
+        for _ in 0..cursor.u64()? {
+            sequences.push(FaiEntry {
+                name: cursor.string()?,
+                length: cursor.u64()?,
+                offset: cursor.u64()?,
+                line_bases: cursor.u64()?,
+                line_bytes: cursor.u64()?,
+            });
+        }
+
+        let mut tracks = Vec::new();
+        for _ in 0..cursor.u64()? {
+            let scaffold = cursor.string()?;
+            let max_len = cursor.u64()?;
+            let mut entries = Vec::new();
+            for _ in 0..cursor.u64()? {
+                entries.push(AnnotationEntry {
+                    start: cursor.u64()?,
+                    end: cursor.u64()?,
+                    offset: cursor.u64()?,
+                    len: cursor.u64()?,
+                });
+            }
+            tracks.push(AnnotationTrack {
+                scaffold,
+                max_len,
+                entries,
+            });
+        }
+
+        ensure!(
+            cursor.position == bytes.len(),
+            "Trailing data in index file."
+        );
+        Ok(Self {
+            fasta,
+            gff,
+            sequences,
+            tracks,
+        })
+    }
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_str<W: Write>(writer: &mut W, value: &str) -> Result<()> {
+    write_u64(writer, value.len() as u64)?;
+    writer.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .context("Truncated index file.")?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u64()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).context("Invalid name in index file.")
+    }
+}
+
+fn scan_gff(path: &Path) -> Result<Vec<AnnotationTrack>> {
+    let mut reader = {
+        let file =
+            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+        BufReader::new(file)
+    };
+
+    let mut tracks: Vec<AnnotationTrack> = Vec::new();
+    let mut track_indices: HashMap<String, usize> = HashMap::new();
+    let mut position = 0;
+    let mut line = Vec::new();
+
+    for i in 1.. {
+        line.clear();
+        let num_bytes = reader
+            .read_until(b'\n', &mut line)
+            .with_context(|| format!("Could not read file {}.", path.display()))?;
+        if num_bytes == 0 {
+            break;
+        }
+        let offset = position;
+        position += num_bytes as u64;
+
+        if line.starts_with(b"##FASTA") {
+            // Embedded sequences follow until the end of the file.
+            break;
+        } else if line.trim_ascii().is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+
+        let annotation = parse_gff_record(&line)
+            .with_context(|| format!("Failed to parse line {} of file {}.", i, path.display()))?;
+        let track = *track_indices
+            .entry(String::from(annotation.scaffold()))
+            .or_insert_with(|| {
+                tracks.push(AnnotationTrack {
+                    scaffold: String::from(annotation.scaffold()),
+                    max_len: 0,
+                    entries: Vec::new(),
+                });
+                tracks.len() - 1
+            });
+        tracks[track].entries.push(AnnotationEntry {
+            start: annotation.start() as u64,
+            end: annotation.end() as u64,
+            offset,
+            len: num_bytes as u64,
+        });
+    }
+
+    for track in tracks.iter_mut() {
+        track.entries.sort_by_key(|e| (e.start, e.end));
+        track.max_len = track
+            .entries
+            .iter()
+            .map(|e| e.end - e.start)
+            .max()
+            .unwrap_or(0);
+    }
+
+    Ok(tracks)
+}
+
+/// Builds an index over a FASTA file and a GFF file and writes it to
+/// `out_path`.
+///
+/// The index file, all integers little endian `u64` unless stated
+/// otherwise, consists of:
+///
+/// * magic `NCIX` and `u32` format version 2,
+/// * fingerprints of the FASTA and the GFF file: file length and FNV-1a
+///   hash of the whole file,
+/// * number of scaffolds followed by, for each scaffold, its name (byte
+///   length and UTF-8 bytes), sequence length, byte offset of the first
+///   base, bases per line and bytes per line, i.e. the content of a `.fai`
+///   index,
+/// * number of annotated scaffolds followed by, for each of them, its name,
+///   maximum annotation length, number of records and, for each record
+///   sorted by coordinates, 0-based half-open start and end, byte offset of
+///   the GFF line and its length in bytes.
+///
+/// FASTA sequence lines of a scaffold must have the same length except the
+/// last one. Blank and `#` prefixed GFF lines are skipped.
+pub fn build_index(fasta: &Path, gff: &Path, out_path: &Path) -> Result<()> {
+    let index = Index {
+        fasta: Fingerprint::of(fasta)?,
+        gff: Fingerprint::of(gff)?,
+        sequences: scan_fasta(fasta)?,
+        tracks: scan_gff(gff)?,
+    };
+
+    let file = File::create(out_path)
+        .with_context(|| format!("Failed to create file {}.", out_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    index
+        .write(&mut writer)
+        .with_context(|| format!("Failed to write file {}.", out_path.display()))?;
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write file {}.", out_path.display()))
+}
+
+/// FASTA and GFF files accessed via an index built by `build_index`. Queries
+/// only seek into the original files.
+pub struct IndexedGenome {
+    index: Index,
+    sequences: HashMap<String, usize>,
+    tracks: HashMap<String, usize>,
+    fasta_path: PathBuf,
+    fasta: File,
+    gff: File,
+}
+
+impl IndexedGenome {
+    /// Opens indexed files. Fails if either file changed since the index was
+    /// built. Both files are read once to verify their fingerprints.
+    pub fn open(index: &Path, fasta: &Path, gff: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(index).with_context(|| format!("Failed to read file {}.", index.display()))?;
+        let index_data = Index::read(&bytes)
+            .with_context(|| format!("Failed to load index {}.", index.display()))?;
+
+        ensure!(
+            Fingerprint::of(fasta)? == index_data.fasta,
+            "Index {} is stale, file {} changed since the index was built.",
+            index.display(),
+            fasta.display()
+        );
+        ensure!(
+            Fingerprint::of(gff)? == index_data.gff,
+            "Index {} is stale, file {} changed since the index was built.",
+            index.display(),
+            gff.display()
+        );
+
+        let sequences = index_data
+            .sequences
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.name.clone(), i))
+            .collect();
+        let tracks = index_data
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.scaffold.clone(), i))
+            .collect();
+
+        Ok(Self {
+            index: index_data,
+            sequences,
+            tracks,
+            fasta_path: fasta.to_path_buf(),
+            fasta: File::open(fasta)
+                .with_context(|| format!("Failed to open file {}.", fasta.display()))?,
+            gff: File::open(gff)
+                .with_context(|| format!("Could not open file {}.", gff.display()))?,
+        })
+    }
+
+    /// Names and lengths of all scaffolds in FASTA file order.
+    pub fn scaffolds(&self) -> Vec<(&str, usize)> {
+        self.index
+            .sequences
+            .iter()
+            .map(|s| (s.name.as_str(), s.length as usize))
+            .collect()
+    }
+
+    /// Returns the sequence of a region and all annotations overlapping it
+    /// sorted by coordinates. The region must lie within its scaffold.
+    pub fn query(&mut self, region: &Region) -> Result<(Vec<Symbol>, Vec<Annotation>)> {
+        let Self {
+            index,
+            sequences,
+            tracks,
+            fasta_path,
+            fasta,
+            gff,
+        } = self;
+
+        let sequence = match sequences.get(region.scaffold()) {
+            Some(&i) => &index.sequences[i],
+            None => bail!("Unknown scaffold {}.", region.scaffold()),
+        };
+        ensure!(
+            region.start() <= region.end() && region.end() as u64 <= sequence.length,
+            "Region {} is out of bounds of scaffold {} of length {}.",
+            region,
+            sequence.name,
+            sequence.length
+        );
+
+        let symbols = read_range(
+            fasta,
+            fasta_path,
+            sequence,
+            region.start() as u64,
+            region.end() as u64,
+        )?;
+
+        let mut annotations = Vec::new();
+        if let Some(&i) = tracks.get(region.scaffold()) {
+            let track = &index.tracks[i];
+            let (start, end) = (region.start() as u64, region.end() as u64);
+            let first = track
+                .entries
+                .partition_point(|e| e.start + track.max_len <= start);
+            let last = track.entries.partition_point(|e| e.start < end);
+
+            for entry in track.entries[first..last.max(first)].iter() {
+                if entry.end <= start {
+                    continue;
+                }
+                let mut line = vec![0; entry.len as usize];
+                gff.seek(SeekFrom::Start(entry.offset))
+                    .and_then(|_| gff.read_exact(&mut line))
+                    .context("Failed to read indexed GFF record.")?;
+                annotations.push(parse_gff_record(&line)?);
+            }
+        }
+
+        Ok((symbols, annotations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::IndexedGenome;
+    use crate::fasta::{load_fasta, write_fasta};
+    use crate::gff::load_gff3_with_sequences;
+    use crate::region::Region;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("ncrs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_query() {
+        let directory = temp_dir("index");
+        let fasta = Path::new("./tests/valid.fasta");
+        let gff = directory.join("annotations.gff");
+        fs::write(
+            &gff,
+            "##gff-version 3\n\
+             scaffold_1\ttest\texon\t11\t20\t.\t+\t.\tID=a\n\
+             scaffold_1\ttest\texon\t1\t200\t.\t-\t.\tID=b\n\
+             \n\
+             scaffold_2\ttest\tCDS\t2\t4\t.\t+\t0\tID=c\n\
+             scaffold_1\ttest\texon\t150\t151\t.\t+\t.\tID=d\n",
+        )
+        .unwrap();
+        let index = directory.join("genome.idx");
+        super::build_index(fasta, &gff, &index).unwrap();
+
+        let mut genome = IndexedGenome::open(&index, fasta, &gff).unwrap();
+        assert_eq!(
+            genome.scaffolds(),
+            vec![("scaffold_1", 280), ("scaffold_2", 7)]
+        );
+
+        let scaffolds = load_fasta(fasta).unwrap();
+
+        for &(start, end) in &[
+            (0, 280),
+            (69, 71),
+            (70, 140),
+            (139, 141),
+            (279, 280),
+            (5, 5),
+        ] {
+            let (sequence, _) = genome
+                .query(&Region::new("scaffold_1", start, end))
+                .unwrap();
+            assert_eq!(sequence, &scaffolds[0].sequence()[start..end]);
+        }
+
+        let ids = |genome: &mut IndexedGenome, region: Region| -> Vec<String> {
+            let (_, annotations) = genome.query(&region).unwrap();
+            annotations
+                .iter()
+                .map(|a| String::from(a.attributes()))
+                .collect()
+        };
+        assert_eq!(
+            ids(&mut genome, Region::new("scaffold_1", 0, 10)),
+            vec!["ID=b"]
+        );
+        assert_eq!(
+            ids(&mut genome, Region::new("scaffold_1", 19, 150)),
+            vec!["ID=b", "ID=a", "ID=d"]
+        );
+        assert_eq!(
+            ids(&mut genome, Region::new("scaffold_1", 200, 280)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            ids(&mut genome, Region::new("scaffold_2", 0, 7)),
+            vec!["ID=c"]
+        );
+
+        assert!(genome.query(&Region::new("scaffold_2", 0, 8)).is_err());
+        assert!(genome.query(&Region::new("scaffold_3", 0, 1)).is_err());
+
+        fs::write(&gff, "scaffold_1\ttest\texon\t11\t20\t.\t+\t.\tID=a\n").unwrap();
+        let error = IndexedGenome::open(&index, fasta, &gff).err().unwrap();
+        assert!(format!("{}", error).contains("is stale"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_embedded_fasta() {
+        let directory = temp_dir("index-embedded");
+        let gff = Path::new("./tests/embedded.gff3");
+        let (_, scaffolds) = load_gff3_with_sequences(gff).unwrap();
+        let fasta = directory.join("genome.fasta");
+        write_fasta(&fasta, &scaffolds, 0).unwrap();
+        let index = directory.join("genome.idx");
+        super::build_index(&fasta, gff, &index).unwrap();
+
+        let mut genome = IndexedGenome::open(&index, &fasta, gff).unwrap();
+        let (sequence, annotations) = genome.query(&Region::new("scaffold_1", 0, 24)).unwrap();
+        assert_eq!(sequence, scaffolds[0].sequence().as_ref());
+        assert_eq!(annotations.len(), 3);
+        let (_, annotations) = genome.query(&Region::new("scaffold_2", 0, 7)).unwrap();
+        assert_eq!(annotations.len(), 1);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_stale_middle_edit() {
+        let directory = temp_dir("index-stale");
+        let fasta = Path::new("./tests/valid.fasta");
+        let gff = directory.join("annotations.gff");
+        let index = directory.join("genome.idx");
+
+        // Records well past the first MiB on both sides of the edit.
+        let record = |start: usize| {
+            format!(
+                "scaffold_1\ttest\texon\t{}\t{}\t.\t+\t.\tID=a\n",
+                start,
+                start + 10
+            )
+        };
+        let padding = "#".repeat(100) + "\n";
+        let mut content = padding.repeat(30_000);
+        let middle = content.len();
+        content += &record(1000);
+        content += &padding.repeat(30_000);
+        fs::write(&gff, &content).unwrap();
+        super::build_index(fasta, &gff, &index).unwrap();
+        assert!(IndexedGenome::open(&index, fasta, &gff).is_ok());
+
+        // Same length edit of a coordinate in the middle of the file.
+        content.replace_range(middle..middle + record(1000).len(), &record(2000));
+        fs::write(&gff, &content).unwrap();
+        let error = IndexedGenome::open(&index, fasta, &gff).err().unwrap();
+        assert!(format!("{}", error).contains("is stale"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_line_lengths() {
+        let directory = temp_dir("index-lines");
+        let fasta = directory.join("genome.fasta");
+        let gff = directory.join("annotations.gff");
+        let index = directory.join("genome.idx");
+        fs::write(&gff, "").unwrap();
+
+        fs::write(&fasta, ">a\r\nACG\r\nTTA\r\nC\r\n>b\r\nGG").unwrap();
+        super::build_index(&fasta, &gff, &index).unwrap();
+        let mut genome = IndexedGenome::open(&index, &fasta, &gff).unwrap();
+        assert_eq!(genome.scaffolds(), vec![("a", 7), ("b", 2)]);
+        let (sequence, _) = genome.query(&Region::new("a", 2, 7)).unwrap();
+        assert_eq!(sequence, &load_fasta(&fasta).unwrap()[0].sequence()[2..7]);
+
+        fs::write(&fasta, ">a\nACG\nTTA").unwrap();
+        super::build_index(&fasta, &gff, &index).unwrap();
+        let mut genome = IndexedGenome::open(&index, &fasta, &gff).unwrap();
+        let (sequence, _) = genome.query(&Region::new("a", 4, 6)).unwrap();
+        assert_eq!(sequence, &load_fasta(&fasta).unwrap()[0].sequence()[4..6]);
+
+        fs::write(&fasta, ">a\nACG\nT\nTTA\n").unwrap();
+        assert!(super::build_index(&fasta, &gff, &index).is_err());
+        fs::write(&fasta, ">a\nACG\nTTAA\n").unwrap();
+        assert!(super::build_index(&fasta, &gff, &index).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
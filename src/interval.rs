@@ -0,0 +1,595 @@
+use crate::data::{Annotation, Feature, Scaffold, Strand};
+use crate::ids::{assign_ids, IdGenerator};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// Set of positions on a single scaffold represented as a sorted list of
+/// non-overlapping, non-abutting, non-empty 0-based half-open ranges.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct IntervalSet {
+    ranges: Vec<Range<usize>>,
+}
+
+impl IntervalSet {
+    /// Creates a normalized set from arbitrary (possibly overlapping,
+    /// unsorted or empty) ranges.
+    pub fn new(mut ranges: Vec<Range<usize>>) -> Self {
+        ranges.retain(|range| range.start < range.end);
+        ranges.sort_unstable_by_key(|range| range.start);
+
+        let mut normalized: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match normalized.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => normalized.push(range),
+            }
+        }
+
+        Self { ranges: normalized }
+    }
+
+    /// Creates a set covered by annotations on scaffold `scaffold`.
+    pub fn from_annotations(annotations: &[Annotation], scaffold: &str) -> Self {
+        Self::new(
+            annotations
+                .iter()
+                .filter(|a| a.scaffold() == scaffold)
+                .map(|a| a.start()..a.end())
+                .collect(),
+        )
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub fn into_ranges(self) -> Vec<Range<usize>> {
+        self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Number of positions in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        let i = self.ranges.partition_point(|range| range.end <= pos);
+        self.ranges.get(i).is_some_and(|range| range.start <= pos)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut ranges = self.ranges.clone();
+        ranges.extend(other.ranges.iter().cloned());
+        Self::new(ranges)
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Positions of `self` which are not in `other`.
+    pub fn subtract(&self, other: &Self) -> Self {
+        let end = self.ranges.last().map_or(0, |range| range.end);
+        self.intersect(&other.complement(end))
+    }
+
+    /// Positions in `[0, len)` which are not in the set.
+    pub fn complement(&self, len: usize) -> Self {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        let mut start = 0;
+        for range in self.ranges.iter() {
+            if range.start >= len {
+                break;
+            }
+            if start < range.start {
+                ranges.push(start..range.start);
+            }
+            start = range.end;
+        }
+        if start < len {
+            ranges.push(start..len);
+        }
+        Self { ranges }
+    }
+
+    /// Number of positions contained in both sets.
+    pub fn overlap_len(&self, other: &Self) -> usize {
+        self.intersect(other).len()
+    }
+
+    /// Converts each range to an annotation with empty attributes, or only
+    /// a generated `ID` attribute if `ids` is given.
+    pub fn to_annotations(
+        &self,
+        scaffold: &str,
+        source: &str,
+        feature: Feature,
+        strand: Strand,
+        ids: Option<&mut IdGenerator>,
+    ) -> Vec<Annotation> {
+        let annotations = self
+            .ranges
+            .iter()
+            .map(|range| {
+                Annotation::new(
+                    String::from(scaffold),
+                    String::from(source),
+                    feature.clone(),
+                    None,
+                    strand,
+                    None,
+                    range.start,
+                    range.end,
+                    String::new(),
+                )
+            })
+            .collect();
+        assign_ids(ids, annotations)
+    }
+}
+
+/// Options of `merge_overlapping`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MergeOptions {
+    /// Merge intervals which only touch, i.e. one ends where the other
+    /// starts. Otherwise only intervals sharing at least one position are
+    /// merged.
+    pub merge_touching: bool,
+    /// Merge only intervals on the same strand.
+    pub strand_aware: bool,
+}
+
+/// Union of overlapping annotations, see `merge_overlapping`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MergedInterval {
+    scaffold: String,
+    strand: Option<Strand>,
+    start: usize,
+    end: usize,
+    count: usize,
+}
+
+impl MergedInterval {
+    pub fn scaffold(&self) -> &str {
+        self.scaffold.as_str()
+    }
+
+    /// Strand of the merged annotations. `None` unless merged with
+    /// `MergeOptions::strand_aware`.
+    pub fn strand(&self) -> Option<Strand> {
+        self.strand
+    }
+
+    /// 0-based inclusive start.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 0-based exclusive end.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Number of merged annotations.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Flattens annotations of a feature type, e.g. exons of all transcripts,
+/// into disjoint intervals. Intervals on different scaffolds (and strands
+/// if strand aware) are never merged. Empty annotations are ignored.
+///
+/// The result is sorted by scaffold name, strand and start.
+pub fn merge_overlapping(
+    annotations: &[Annotation],
+    feature: Feature,
+    options: MergeOptions,
+) -> Vec<MergedInterval> {
+    let mut groups: BTreeMap<(&str, Option<Strand>), Vec<Range<usize>>> = BTreeMap::new();
+    for annotation in annotations
+        .iter()
+        .filter(|a| *a.feature() == feature && a.start() < a.end())
+    {
+        let strand = if options.strand_aware {
+            Some(annotation.strand())
+        } else {
+            None
+        };
+        groups
+            .entry((annotation.scaffold(), strand))
+            .or_default()
+            .push(annotation.start()..annotation.end());
+    }
+
+    let mut merged = Vec::new();
+    for ((scaffold, strand), ranges) in groups {
+        let mut starts: Vec<usize> = ranges.iter().map(|range| range.start).collect();
+        starts.sort_unstable();
+
+        // Interval sets merge touching ranges. Unless these are to be
+        // merged, each range is shrunk by half a position at its end in
+        // doubled coordinates, which keeps overlaps but separates touching
+        // ranges.
+        let set = if options.merge_touching {
+            IntervalSet::new(ranges)
+        } else {
+            IntervalSet::new(
+                ranges
+                    .into_iter()
+                    .map(|range| 2 * range.start..2 * range.end - 1)
+                    .collect(),
+            )
+        };
+        for range in set.ranges() {
+            let (start, end) = if options.merge_touching {
+                (range.start, range.end)
+            } else {
+                (range.start / 2, range.end.div_ceil(2))
+            };
+            let count =
+                starts.partition_point(|&s| s < end) - starts.partition_point(|&s| s < start);
+            merged.push(MergedInterval {
+                scaffold: String::from(scaffold),
+                strand,
+                start,
+                end,
+                count,
+            });
+        }
+    }
+    merged
+}
+
+/// Returns 0-based half-open regions of scaffold `scaffold` of length
+/// `scaffold_len` not covered by any annotation of one of `features`, e.g.
+/// intergenic regions, sorted by start. Touching or overlapping annotations
+/// are merged so no empty regions are returned.
+pub fn complement_regions(
+    scaffold_len: usize,
+    scaffold: &str,
+    annotations: &[Annotation],
+    features: &[Feature],
+) -> Vec<(usize, usize)> {
+    let covered = annotations
+        .iter()
+        .filter(|a| a.scaffold() == scaffold && features.contains(a.feature()))
+        .map(|a| a.start()..a.end())
+        .collect();
+    gaps(scaffold_len, covered)
+}
+
+/// Computes `complement_regions` of all scaffolds, keyed by scaffold name.
+pub fn genome_complement_regions(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    features: &[Feature],
+) -> HashMap<String, Vec<(usize, usize)>> {
+    let mut covered: HashMap<&str, Vec<Range<usize>>> = HashMap::new();
+    for annotation in annotations {
+        if features.contains(annotation.feature()) {
+            covered
+                .entry(annotation.scaffold())
+                .or_default()
+                .push(annotation.start()..annotation.end());
+        }
+    }
+
+    scaffolds
+        .iter()
+        .map(|scaffold| {
+            let ranges = covered.remove(scaffold.name()).unwrap_or_default();
+            (String::from(scaffold.name()), gaps(scaffold.len(), ranges))
+        })
+        .collect()
+}
+
+/// Returns ranges of `[0, len)` not covered by any of `covered`.
+fn gaps(len: usize, covered: Vec<Range<usize>>) -> Vec<(usize, usize)> {
+    IntervalSet::new(covered)
+        .complement(len)
+        .ranges()
+        .iter()
+        .map(|range| (range.start, range.end))
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod test {
+
+    use super::{IntervalSet, MergeOptions};
+    use crate::data::{Annotation, Feature, Scaffold, Strand, Symbol};
+    use crate::ids::{IdGenerator, IdStrategy};
+    use crate::simulate::Random;
+    use crate::testing;
+
+    const LEN: usize = 60;
+
+    fn random_set(random: &mut Random) -> IntervalSet {
+        let count = random.below(6);
+        IntervalSet::new(
+            (0..count)
+                .map(|_| {
+                    let start = random.below(LEN);
+                    start..start + random.below(15)
+                })
+                .collect(),
+        )
+    }
+
+    fn brute_force(set: &IntervalSet) -> Vec<bool> {
+        (0..LEN + 20).map(|pos| set.contains(pos)).collect()
+    }
+
+    #[test]
+    fn test_normalization() {
+        let set = IntervalSet::new(vec![5..7, 0..2, 2..3, 6..10, 12..12, 1..2]);
+        assert_eq!(set.ranges(), &[0..3, 5..10]);
+        assert_eq!(set.len(), 8);
+        assert!(set.contains(0));
+        assert!(set.contains(2));
+        assert!(!set.contains(3));
+        assert!(set.contains(9));
+        assert!(!set.contains(10));
+        assert_eq!(IntervalSet::new(set.ranges().to_vec()), set);
+
+        assert_eq!(set.complement(12).ranges(), &[3..5, 10..12]);
+        assert_eq!(set.complement(7).ranges(), &[3..5]);
+        assert_eq!(IntervalSet::default().complement(4).ranges(), &[0..4]);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let annotations: Vec<Annotation> = [("s", 0, 4), ("t", 0, 10), ("s", 3, 6)]
+            .iter()
+            .map(|&(scaffold, start, end)| {
+                testing::annotation(scaffold, Feature::Exon, Strand::Positive, start, end)
+            })
+            .collect();
+        let set = IntervalSet::from_annotations(&annotations, "s");
+        assert_eq!(set.ranges(), &[0..6]);
+
+        let merged = set.to_annotations("s", "merged", Feature::Exon, Strand::Negative, None);
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].start(), merged[0].end()), (0, 6));
+        assert_eq!(merged[0].source(), "merged");
+        assert_eq!(merged[0].strand(), Strand::Negative);
+        assert_eq!(merged[0].attributes(), "");
+
+        let mut ids =
+            IdGenerator::new("merged", IdStrategy::Sequential).with_existing(&[testing::builder(
+                "s",
+                Feature::Exon,
+                0,
+                4,
+            )
+            .attributes("ID=merged.exon.1")
+            .build()
+            .unwrap()]);
+        let merged = set.to_annotations(
+            "s",
+            "merged",
+            Feature::Exon,
+            Strand::Negative,
+            Some(&mut ids),
+        );
+        assert_eq!(merged[0].attributes(), "ID=merged.exon.2");
+    }
+
+    #[test]
+    fn test_random_identities() {
+        let mut random = Random::new(42);
+        for _ in 0..500 {
+            let (a, b, c) = (
+                random_set(&mut random),
+                random_set(&mut random),
+                random_set(&mut random),
+            );
+            let universe = LEN + 20;
+
+            // Normalization is idempotent.
+            assert_eq!(IntervalSet::new(a.ranges().to_vec()), a);
+            for window in a.ranges().windows(2) {
+                assert!(window[0].end < window[1].start);
+            }
+
+            // Agreement with per-position semantics.
+            let (pa, pb) = (brute_force(&a), brute_force(&b));
+            let union: Vec<bool> = pa.iter().zip(pb.iter()).map(|(x, y)| *x || *y).collect();
+            let intersection: Vec<bool> = pa.iter().zip(pb.iter()).map(|(x, y)| *x && *y).collect();
+            let difference: Vec<bool> = pa.iter().zip(pb.iter()).map(|(x, y)| *x && !*y).collect();
+            assert_eq!(brute_force(&a.union(&b)), union);
+            assert_eq!(brute_force(&a.intersect(&b)), intersection);
+            assert_eq!(brute_force(&a.subtract(&b)), difference);
+            assert_eq!(
+                a.overlap_len(&b),
+                intersection.iter().filter(|&&x| x).count()
+            );
+
+            // De Morgan's laws.
+            assert_eq!(
+                a.union(&b).complement(universe),
+                a.complement(universe).intersect(&b.complement(universe))
+            );
+            assert_eq!(
+                a.intersect(&b).complement(universe),
+                a.complement(universe).union(&b.complement(universe))
+            );
+
+            // Double complement, commutativity and distributivity.
+            assert_eq!(a.complement(universe).complement(universe), a);
+            assert_eq!(a.union(&b), b.union(&a));
+            assert_eq!(a.intersect(&b), b.intersect(&a));
+            assert_eq!(
+                a.intersect(&b.union(&c)),
+                a.intersect(&b).union(&a.intersect(&c))
+            );
+            assert_eq!(a.subtract(&b), a.intersect(&b.complement(universe)));
+            assert_eq!(a.subtract(&b).overlap_len(&b), 0);
+        }
+    }
+
+    #[test]
+    fn test_merge_overlapping_random() {
+        let mut random = Random::new(7);
+        for _ in 0..200 {
+            let annotations: Vec<Annotation> = (0..random.below(8))
+                .map(|_| {
+                    let start = random.below(30);
+                    let end = start + 1 + random.below(8);
+                    testing::annotation("s", Feature::Exon, Strand::Positive, start, end)
+                })
+                .collect();
+
+            for &merge_touching in [false, true].iter() {
+                let options = MergeOptions {
+                    merge_touching,
+                    strand_aware: false,
+                };
+                let merged = super::merge_overlapping(&annotations, Feature::Exon, options);
+                assert_eq!(
+                    merged.iter().map(|m| m.count()).sum::<usize>(),
+                    annotations.len()
+                );
+                for pair in merged.windows(2) {
+                    if merge_touching {
+                        assert!(pair[0].end() < pair[1].start());
+                    } else {
+                        assert!(pair[0].end() <= pair[1].start());
+                    }
+                }
+                for m in merged.iter() {
+                    let within: Vec<&Annotation> = annotations
+                        .iter()
+                        .filter(|a| m.start() <= a.start() && a.end() <= m.end())
+                        .collect();
+                    assert_eq!(within.len(), m.count());
+                    assert_eq!(within.iter().map(|a| a.start()).min(), Some(m.start()));
+                    assert_eq!(within.iter().map(|a| a.end()).max(), Some(m.end()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_overlapping() {
+        let annotation = testing::annotation;
+        let annotations = vec![
+            annotation("s", Feature::Exon, Strand::Positive, 10, 20),
+            // Nested.
+            annotation("s", Feature::Exon, Strand::Negative, 12, 15),
+            // Identical duplicates.
+            annotation("s", Feature::Exon, Strand::Positive, 30, 40),
+            annotation("s", Feature::Exon, Strand::Positive, 30, 40),
+            // Touching.
+            annotation("s", Feature::Exon, Strand::Positive, 40, 45),
+            annotation("s", Feature::CDS, Strand::Positive, 0, 100),
+            annotation("s", Feature::Exon, Strand::Positive, 50, 50),
+            // Same coordinates on another scaffold.
+            annotation("t", Feature::Exon, Strand::Positive, 10, 20),
+        ];
+        let intervals = |options| {
+            super::merge_overlapping(&annotations, Feature::Exon, options)
+                .iter()
+                .map(|m| {
+                    (
+                        String::from(m.scaffold()),
+                        m.strand(),
+                        m.start(),
+                        m.end(),
+                        m.count(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let s = String::from("s");
+        let t = String::from("t");
+
+        assert_eq!(
+            intervals(MergeOptions::default()),
+            [
+                (s.clone(), None, 10, 20, 2),
+                (s.clone(), None, 30, 40, 2),
+                (s.clone(), None, 40, 45, 1),
+                (t.clone(), None, 10, 20, 1),
+            ]
+        );
+        assert_eq!(
+            intervals(MergeOptions {
+                merge_touching: true,
+                strand_aware: true,
+            }),
+            [
+                (s.clone(), Some(Strand::Positive), 10, 20, 1),
+                (s.clone(), Some(Strand::Positive), 30, 45, 3),
+                (s, Some(Strand::Negative), 12, 15, 1),
+                (t, Some(Strand::Positive), 10, 20, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complement_regions() {
+        let annotation = |scaffold, feature, start, end| {
+            testing::annotation(scaffold, feature, Strand::Positive, start, end)
+        };
+        let annotations = vec![
+            annotation("s", Feature::Exon, 10, 20),
+            // Adjacent, leaves a zero-length gap.
+            annotation("s", Feature::CDS, 20, 25),
+            annotation("s", Feature::Exon, 22, 30),
+            annotation("s", Feature::Gene, 0, 100),
+            annotation("s", Feature::Exon, 40, 50),
+            annotation("t", Feature::Exon, 0, 30),
+            annotation("u", Feature::Exon, 0, 50),
+        ];
+        let features = [Feature::Exon, Feature::CDS];
+
+        assert_eq!(
+            super::complement_regions(60, "s", &annotations, &features),
+            [(0, 10), (30, 40), (50, 60)]
+        );
+        assert_eq!(
+            super::complement_regions(60, "s", &annotations, &[Feature::Gene]),
+            []
+        );
+        assert_eq!(
+            super::complement_regions(30, "t", &annotations, &features),
+            []
+        );
+        assert_eq!(
+            super::complement_regions(45, "v", &annotations, &features),
+            [(0, 45)]
+        );
+
+        let scaffolds: Vec<Scaffold> = [("s", 60), ("t", 30), ("v", 5)]
+            .iter()
+            .map(|&(name, len)| Scaffold::new(String::from(name), vec![Symbol::Adenine; len]))
+            .collect();
+        let regions = super::genome_complement_regions(&scaffolds, &annotations, &features);
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions["s"], [(0, 10), (30, 40), (50, 60)]);
+        assert_eq!(regions["t"], []);
+        assert_eq!(regions["v"], [(0, 5)]);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::data::Symbol;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Maximum k-mer length encodable into a `u64`.
+pub const MAX_K: usize = 32;
+
+/// Handling of k-mers containing `Symbol::Other` when counting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OtherKmers {
+    /// K-mers containing `Other` are not counted, so counts of a sequence
+    /// with gaps sum to less than the number of windows.
+    #[default]
+    Skip,
+    /// K-mers containing `Other` are an error.
+    Error,
+}
+
+/// Options of `count_kmers_with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct KmerOptions {
+    /// Count each k-mer together with its reverse complement under the
+    /// smaller of the two codes, see `canonical_kmer`.
+    pub canonical: bool,
+    pub other: OtherKmers,
+}
+
+/// Iterates over all `seq.len() - k + 1` overlapping k-mers of a sequence.
+///
+/// # Panics
+///
+/// Panics if `k` is zero.
+pub fn kmers(seq: &[Symbol], k: usize) -> impl Iterator<Item = &[Symbol]> {
+    seq.windows(k)
+}
+
+/// Two bit code of a base, complementary bases sum up to 3. `Other` has no
+/// code.
+fn base_code(symbol: Symbol) -> Option<u64> {
+    match symbol {
+        Symbol::Adenine => Some(0),
+        Symbol::Cytosine => Some(1),
+        Symbol::Guanine => Some(2),
+        Symbol::Thymine => Some(3),
+        Symbol::Other => None,
+    }
+}
+
+fn check_k(k: usize) {
+    assert!(
+        k > 0 && k <= MAX_K,
+        "K-mer length has to be between 1 and {}, got {}.",
+        MAX_K,
+        k
+    );
+}
+
+/// Packs a k-mer into two bits per base with the first base in the most
+/// significant bits, i.e. codes of equally long k-mers sort
+/// lexicographically by A, C, G, T. Returns `None` if the k-mer contains
+/// `Symbol::Other`.
+///
+/// # Panics
+///
+/// Panics if the k-mer is empty or longer than `MAX_K`.
+pub fn encode_kmer(kmer: &[Symbol]) -> Option<u64> {
+    check_k(kmer.len());
+    kmer.iter()
+        .try_fold(0, |code, &symbol| Some((code << 2) | base_code(symbol)?))
+}
+
+/// Unpacks a k-mer packed by `encode_kmer`.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn decode_kmer(code: u64, k: usize) -> Vec<Symbol> {
+    check_k(k);
+    (0..k)
+        .rev()
+        .map(|i| match (code >> (2 * i)) & 3 {
+            0 => Symbol::Adenine,
+            1 => Symbol::Cytosine,
+            2 => Symbol::Guanine,
+            _ => Symbol::Thymine,
+        })
+        .collect()
+}
+
+/// Returns the code of the reverse complement of a k-mer packed by
+/// `encode_kmer`.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn reverse_complement_kmer(code: u64, k: usize) -> u64 {
+    check_k(k);
+    (0..k).fold(0, |reverse, i| {
+        (reverse << 2) | (3 - ((code >> (2 * i)) & 3))
+    })
+}
+
+/// Returns the smaller of the codes of a k-mer and of its reverse
+/// complement, so that both strands of a sequence yield the same codes.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn canonical_kmer(code: u64, k: usize) -> u64 {
+    code.min(reverse_complement_kmer(code, k))
+}
+
+/// Iterates over codes of all overlapping k-mers of a sequence, see
+/// `encode_kmer`, in time linear in the sequence length. K-mers containing
+/// `Symbol::Other` yield `None` so that positions of the codes are kept.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn encoded_kmers(
+    seq: &[Symbol],
+    k: usize,
+    canonical: bool,
+) -> impl Iterator<Item = Option<u64>> + '_ {
+    check_k(k);
+    let mask = if k == MAX_K {
+        u64::MAX
+    } else {
+        (1 << (2 * k)) - 1
+    };
+
+    let mut forward = 0u64;
+    let mut reverse = 0u64;
+    // Number of consecutive bases, i.e. symbols other than `Other`, ending
+    // at the current position.
+    let mut valid = 0;
+    seq.iter().enumerate().filter_map(move |(i, &symbol)| {
+        match base_code(symbol) {
+            Some(code) => {
+                forward = ((forward << 2) | code) & mask;
+                reverse = (reverse >> 2) | ((3 - code) << (2 * (k - 1)));
+                valid += 1;
+            }
+            None => valid = 0,
+        }
+        if i + 1 < k {
+            return None;
+        }
+        if valid < k {
+            return Some(None);
+        }
+        Some(Some(if canonical {
+            forward.min(reverse)
+        } else {
+            forward
+        }))
+    })
+}
+
+/// Counts k-mers of a sequence by their codes, see `encode_kmer`. K-mers
+/// containing `Symbol::Other` are skipped.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn count_kmers(seq: &[Symbol], k: usize) -> HashMap<u64, u64> {
+    count_kmers_with_options(seq, k, KmerOptions::default()).unwrap()
+}
+
+/// Counts k-mers of a sequence by their codes, see `encode_kmer`.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or larger than `MAX_K`.
+pub fn count_kmers_with_options(
+    seq: &[Symbol],
+    k: usize,
+    options: KmerOptions,
+) -> Result<HashMap<u64, u64>> {
+    let mut counts = HashMap::new();
+    for (i, code) in encoded_kmers(seq, k, options.canonical).enumerate() {
+        match (code, options.other) {
+            (Some(code), _) => *counts.entry(code).or_insert(0) += 1,
+            (None, OtherKmers::Skip) => (),
+            (None, OtherKmers::Error) => bail!("K-mer at position {} contains an unknown base.", i),
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{KmerOptions, OtherKmers};
+    use crate::data::{parse_sequence, reverse_complement, sequence_to_string};
+    use proptest::prelude::*;
+
+    fn code(kmer: &str) -> u64 {
+        super::encode_kmer(&parse_sequence(kmer).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_kmers() {
+        let seq = parse_sequence("ACGTN").unwrap();
+        let kmers: Vec<String> = super::kmers(&seq, 3).map(sequence_to_string).collect();
+        assert_eq!(kmers, ["ACG", "CGT", "GTN"]);
+        assert_eq!(super::kmers(&seq, 6).count(), 0);
+    }
+
+    #[test]
+    fn test_encode_kmer() {
+        assert_eq!(code("A"), 0);
+        assert_eq!(code("T"), 3);
+        assert_eq!(code("ACGT"), 0b00_01_10_11);
+        assert_eq!(super::encode_kmer(&parse_sequence("ANT").unwrap()), None);
+        assert_eq!(
+            sequence_to_string(&super::decode_kmer(code("GATTACA"), 7)),
+            "GATTACA"
+        );
+        let long = "ACGT".repeat(8);
+        assert_eq!(
+            sequence_to_string(&super::decode_kmer(code(&long), 32)),
+            long
+        );
+
+        assert_eq!(
+            super::reverse_complement_kmer(code("AACG"), 4),
+            code("CGTT")
+        );
+        assert_eq!(super::canonical_kmer(code("CGTT"), 4), code("AACG"));
+        assert_eq!(super::canonical_kmer(code("AACG"), 4), code("AACG"));
+        // Palindromes are their own reverse complement.
+        assert_eq!(
+            super::reverse_complement_kmer(code("ACGT"), 4),
+            code("ACGT")
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_encode_kmer_too_long() {
+        super::encode_kmer(&parse_sequence(&"A".repeat(33)).unwrap());
+    }
+
+    #[test]
+    fn test_count_kmers() {
+        let seq = parse_sequence("AACGNAACGT").unwrap();
+        let codes: Vec<Option<u64>> = super::encoded_kmers(&seq, 3, false).collect();
+        assert_eq!(
+            codes,
+            [
+                Some(code("AAC")),
+                Some(code("ACG")),
+                None,
+                None,
+                None,
+                Some(code("AAC")),
+                Some(code("ACG")),
+                Some(code("CGT")),
+            ]
+        );
+
+        // Windows containing N are skipped, 5 of 8 windows are counted.
+        let counts = super::count_kmers(&seq, 3);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&code("AAC")], 2);
+        assert_eq!(counts[&code("ACG")], 2);
+        assert_eq!(counts[&code("CGT")], 1);
+        assert_eq!(counts.values().sum::<u64>(), 5);
+
+        let options = KmerOptions {
+            canonical: true,
+            other: OtherKmers::Skip,
+        };
+        let counts = super::count_kmers_with_options(&seq, 3, options).unwrap();
+        assert_eq!(counts.len(), 2);
+        // ACG is the reverse complement of CGT.
+        assert_eq!(counts[&code("ACG")], 3);
+        assert_eq!(counts[&code("AAC")], 2);
+
+        let options = KmerOptions {
+            canonical: false,
+            other: OtherKmers::Error,
+        };
+        let error = super::count_kmers_with_options(&seq, 3, options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "K-mer at position 2 contains an unknown base."
+        );
+        assert!(super::count_kmers_with_options(&seq[5..], 3, options).is_ok());
+        assert!(super::count_kmers(&seq, 11).is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn encoded_kmers_match_encode_kmer(
+            text in "[ACGTN]{0,80}",
+            k in 1usize..=32,
+        ) {
+            let seq = parse_sequence(&text).unwrap();
+            let expected: Vec<Option<u64>> = super::kmers(&seq, k).map(super::encode_kmer).collect();
+            let actual: Vec<Option<u64>> = super::encoded_kmers(&seq, k, false).collect();
+            prop_assert_eq!(actual, expected);
+
+            let reversed = reverse_complement(&seq);
+            let mut forward: Vec<u64> = super::encoded_kmers(&seq, k, true).flatten().collect();
+            let mut backward: Vec<u64> = super::encoded_kmers(&reversed, k, true).flatten().collect();
+            forward.sort_unstable();
+            backward.sort_unstable();
+            prop_assert_eq!(forward, backward);
+        }
+    }
+}
@@ -0,0 +1,761 @@
+use crate::bounds::{Bounded, BoundsPolicy};
+use crate::data::{Annotation, Feature, Scaffold, Strand};
+use anyhow::{Context, Result};
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"NCLT";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 24;
+const BLOCK_LEN: usize = 4096;
+
+const BLOCK_PACKED: u8 = 0;
+const BLOCK_RUNS: u8 = 1;
+/// Run length encoded blocks store runs as a label byte followed by a
+/// little endian `u32` run length.
+const RUN_LEN: usize = 5;
+
+/// Compact per-position label array of a single scaffold.
+///
+/// The serialized bytes are held in `B`, which is a `Vec<u8>` for owned
+/// tracks but can be a borrowed slice or, with the `memmap2` feature, a
+/// memory mapped file (see `open`).
+///
+/// Labels are split into blocks of 4096 positions and each block is stored
+/// either bit-packed (1, 2, 4 or 8 bits per label depending on the number of
+/// classes) or run length encoded, whichever is smaller. The serialized
+/// form, which is exactly what `save` writes, is used directly for random
+/// access so that a track can be queried by position without unpacking it.
+///
+/// Version 1 layout, all integers little endian:
+///
+/// * bytes 0..4: magic `NCLT`,
+/// * bytes 4..6: format version,
+/// * bytes 6..8: number of classes,
+/// * bytes 8..12: block length,
+/// * bytes 12..16: reserved, zero,
+/// * bytes 16..24: number of labels,
+/// * one `u64` offset per block plus a final end offset, relative to the
+///   start of the block data,
+/// * block data: each block starts with its kind byte (0 packed, 1 run
+///   length encoded) followed by the payload.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LabelTrack<B = Vec<u8>> {
+    bytes: B,
+    len: usize,
+    n_classes: usize,
+    block_len: usize,
+}
+
+impl LabelTrack {
+    /// Packs labels, each of which has to be smaller than `n_classes`.
+    /// Up to 256 classes are supported.
+    pub fn pack(labels: &[u8], n_classes: usize) -> Result<Self> {
+        ensure!(
+            (1..=256).contains(&n_classes),
+            "Number of classes must be between 1 and 256, got {}.",
+            n_classes
+        );
+        if let Some(&label) = labels.iter().find(|&&l| l as usize >= n_classes) {
+            bail!("Label {} is out of range for {} classes.", label, n_classes);
+        }
+
+        let bits = bits_per_label(n_classes);
+        let n_blocks = labels.len().div_ceil(BLOCK_LEN);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * (n_blocks + 1));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(n_classes as u16).to_le_bytes());
+        bytes.extend_from_slice(&(BLOCK_LEN as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(labels.len() as u64).to_le_bytes());
+
+        let mut data = Vec::new();
+        let mut offsets = Vec::with_capacity(n_blocks + 1);
+        for block in labels.chunks(BLOCK_LEN) {
+            offsets.push(data.len() as u64);
+
+            let runs = runs(block);
+            if runs.len() * RUN_LEN < (block.len() * bits).div_ceil(8) {
+                data.push(BLOCK_RUNS);
+                for (label, length) in runs {
+                    data.push(label);
+                    data.extend_from_slice(&(length as u32).to_le_bytes());
+                }
+            } else {
+                data.push(BLOCK_PACKED);
+                let per_byte = 8 / bits;
+                for group in block.chunks(per_byte) {
+                    let byte = group
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (i, &label)| byte | (label << (i * bits)));
+                    data.push(byte);
+                }
+            }
+        }
+        offsets.push(data.len() as u64);
+
+        for offset in offsets {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        bytes.extend_from_slice(&data);
+
+        Ok(Self {
+            bytes,
+            len: labels.len(),
+            n_classes,
+            block_len: BLOCK_LEN,
+        })
+    }
+
+    /// Loads a track written by `save`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("Failed to read file {}.", path.display()))?;
+        Self::from_bytes(bytes)
+            .with_context(|| format!("Failed to load label track {}.", path.display()))
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl LabelTrack<memmap2::Mmap> {
+    /// Memory maps a track written by `save`. Labels are decoded from the
+    /// mapping on access so the file is never read as a whole.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = fs::File::open(path)
+            .with_context(|| format!("Failed to open file {}.", path.display()))?;
+        // Safety: the file is assumed not to be modified while mapped.
+        let bytes = unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("Failed to map file {}.", path.display()))?;
+        Self::from_bytes(bytes)
+            .with_context(|| format!("Failed to load label track {}.", path.display()))
+    }
+}
+
+impl<B: AsRef<[u8]>> LabelTrack<B> {
+    /// Validates and wraps a serialized track, e.g. an owned `Vec<u8>` or a
+    /// borrowed `&[u8]`.
+    pub fn from_bytes(bytes: B) -> Result<Self> {
+        let raw = bytes.as_ref();
+        ensure!(
+            raw.len() >= HEADER_LEN && &raw[..4] == MAGIC,
+            "Not a label track."
+        );
+        let version = u16::from_le_bytes(raw[4..6].try_into().unwrap());
+        ensure!(
+            version == VERSION,
+            "Unsupported label track version {}.",
+            version
+        );
+
+        let n_classes = u16::from_le_bytes(raw[6..8].try_into().unwrap()) as usize;
+        let block_len = u32::from_le_bytes(raw[8..12].try_into().unwrap()) as usize;
+        let len = u64::from_le_bytes(raw[16..24].try_into().unwrap());
+        ensure!(
+            (1..=256).contains(&n_classes),
+            "Invalid number of classes {}.",
+            n_classes
+        );
+        ensure!(block_len > 0, "Invalid block length 0.");
+        let len = usize::try_from(len).context("Label track is too long.")?;
+
+        let track = Self {
+            bytes,
+            len,
+            n_classes,
+            block_len,
+        };
+
+        let n_blocks = len.div_ceil(block_len);
+        let data_start = n_blocks
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(8))
+            .and_then(|n| n.checked_add(HEADER_LEN))
+            .filter(|&start| start <= track.as_bytes().len())
+            .context("Truncated label track.")?;
+        ensure!(
+            track.offset(n_blocks) == Some(track.as_bytes().len() - data_start),
+            "Truncated label track."
+        );
+
+        let bits = bits_per_label(n_classes);
+        for block in 0..n_blocks {
+            let (start, end) = match (track.offset(block), track.offset(block + 1)) {
+                (Some(start), Some(end))
+                    if start < end && end <= track.as_bytes().len() - data_start =>
+                {
+                    (data_start + start, data_start + end)
+                }
+                _ => bail!("Invalid offset of block {}.", block),
+            };
+            let block_len = block_len.min(len - block * block_len);
+            let payload = &track.as_bytes()[start + 1..end];
+
+            match track.as_bytes()[start] {
+                BLOCK_PACKED => {
+                    ensure!(
+                        payload.len() == (block_len * bits).div_ceil(8),
+                        "Invalid length of block {}.",
+                        block
+                    );
+                    let per_byte = 8 / bits;
+                    let mask = ((1u16 << bits) - 1) as u8;
+                    let out_of_range = payload
+                        .iter()
+                        .flat_map(|&byte| (0..per_byte).map(move |i| (byte >> (i * bits)) & mask))
+                        .take(block_len)
+                        .any(|label| label as usize >= n_classes);
+                    ensure!(!out_of_range, "Label out of range in block {}.", block);
+                }
+                BLOCK_RUNS => {
+                    ensure!(
+                        payload.len().is_multiple_of(RUN_LEN),
+                        "Invalid length of block {}.",
+                        block
+                    );
+                    let total = payload.chunks(RUN_LEN).try_fold(0usize, |total, run| {
+                        ensure!(
+                            (run[0] as usize) < n_classes,
+                            "Label {} is out of range in block {}.",
+                            run[0],
+                            block
+                        );
+                        Ok(total + u32::from_le_bytes(run[1..].try_into().unwrap()) as usize)
+                    })?;
+                    ensure!(
+                        total == block_len,
+                        "Runs of block {} do not cover the block.",
+                        block
+                    );
+                }
+                kind => bail!("Unknown kind {} of block {}.", kind, block),
+            }
+        }
+
+        Ok(track)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.as_bytes())
+            .with_context(|| format!("Failed to write file {}.", path.display()))
+    }
+
+    /// Serialized form of the track.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// Number of labels.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.n_classes
+    }
+
+    /// Returns label at a position, decoding only the block containing it.
+    pub fn get(&self, pos: usize) -> Option<u8> {
+        if pos >= self.len {
+            return None;
+        }
+
+        let block = pos / self.block_len;
+        let index = pos % self.block_len;
+        let (start, end) = self.block_range(block);
+        let payload = &self.as_bytes()[start + 1..end];
+
+        if self.as_bytes()[start] == BLOCK_RUNS {
+            let mut covered = 0;
+            for run in payload.chunks(RUN_LEN) {
+                covered += u32::from_le_bytes(run[1..].try_into().unwrap()) as usize;
+                if index < covered {
+                    return Some(run[0]);
+                }
+            }
+            unreachable!("Runs are validated to cover the block.");
+        }
+
+        let bits = bits_per_label(self.n_classes);
+        let per_byte = 8 / bits;
+        let byte = payload[index / per_byte];
+        let mask = ((1u16 << bits) - 1) as u8;
+        Some((byte >> ((index % per_byte) * bits)) & mask)
+    }
+
+    /// Decodes all labels.
+    pub fn unpack(&self) -> Vec<u8> {
+        let mut labels = Vec::with_capacity(self.len);
+        let bits = bits_per_label(self.n_classes);
+        let per_byte = 8 / bits;
+        let mask = ((1u16 << bits) - 1) as u8;
+
+        for block in 0..self.len.div_ceil(self.block_len) {
+            let block_len = self.block_len.min(self.len - block * self.block_len);
+            let (start, end) = self.block_range(block);
+            let payload = &self.as_bytes()[start + 1..end];
+
+            if self.as_bytes()[start] == BLOCK_RUNS {
+                for run in payload.chunks(RUN_LEN) {
+                    let length = u32::from_le_bytes(run[1..].try_into().unwrap()) as usize;
+                    labels.extend(std::iter::repeat_n(run[0], length));
+                }
+            } else {
+                labels.extend(
+                    payload
+                        .iter()
+                        .flat_map(|&byte| (0..per_byte).map(move |i| (byte >> (i * bits)) & mask))
+                        .take(block_len),
+                );
+            }
+        }
+
+        labels
+    }
+
+    /// Offset of a block relative to the block data start.
+    fn offset(&self, block: usize) -> Option<usize> {
+        let position = HEADER_LEN + 8 * block;
+        let bytes = self.as_bytes().get(position..position + 8)?;
+        usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap())).ok()
+    }
+
+    /// Absolute byte range of a block including its kind byte.
+    fn block_range(&self, block: usize) -> (usize, usize) {
+        let data_start = HEADER_LEN + 8 * (self.len.div_ceil(self.block_len) + 1);
+        (
+            data_start + self.offset(block).unwrap(),
+            data_start + self.offset(block + 1).unwrap(),
+        )
+    }
+}
+
+fn bits_per_label(n_classes: usize) -> usize {
+    match n_classes {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+/// Splits labels into runs of equal labels.
+fn runs(labels: &[u8]) -> Vec<(u8, usize)> {
+    let mut runs: Vec<(u8, usize)> = Vec::new();
+    for &label in labels {
+        match runs.last_mut() {
+            Some((last, length)) if *last == label => *length += 1,
+            _ => runs.push((label, 1)),
+        }
+    }
+    runs
+}
+
+/// Classes of per-position labels generated by `labels_for_scaffold`.
+/// Overlapping features resolve to the class with the highest label, i.e.
+/// the one listed last.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LabelScheme {
+    /// 0 outside of exons and 1 in exons.
+    Exon,
+    /// 0 intergenic, 1 intron, i.e. within a gene, mRNA or transcript but
+    /// outside of its exons, and 2 exon. CDS and UTR annotations count as
+    /// exons.
+    GeneStructure,
+    /// 0 intergenic, 1 intron, 2 UTR and 3 CDS. Exon positions not covered
+    /// by a CDS are UTR, including exons of non-coding transcripts.
+    Coding,
+}
+
+impl LabelScheme {
+    /// Number of distinct labels.
+    pub fn num_classes(self) -> usize {
+        match self {
+            Self::Exon => 2,
+            Self::GeneStructure => 3,
+            Self::Coding => 4,
+        }
+    }
+
+    /// Label of positions covered by a feature, `None` if the feature does
+    /// not affect labels.
+    fn label(self, feature: &Feature) -> Option<u8> {
+        match (self, feature) {
+            (Self::Exon, Feature::Exon) => Some(1),
+            (Self::Exon, _) => None,
+            (_, Feature::Gene) | (_, Feature::MRNA) | (_, Feature::Transcript) => Some(1),
+            (Self::GeneStructure, Feature::Exon)
+            | (Self::GeneStructure, Feature::CDS)
+            | (Self::GeneStructure, Feature::FivePrimeUTR)
+            | (Self::GeneStructure, Feature::ThreePrimeUTR) => Some(2),
+            (Self::Coding, Feature::Exon)
+            | (Self::Coding, Feature::FivePrimeUTR)
+            | (Self::Coding, Feature::ThreePrimeUTR) => Some(2),
+            (Self::Coding, Feature::CDS) => Some(3),
+            _ => None,
+        }
+    }
+}
+
+/// Options of `labels_for_scaffold_with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LabelOptions {
+    /// Handling of annotations extending past the end of the scaffold.
+    pub bounds: BoundsPolicy,
+}
+
+/// Labels each position of `scaffold` per `scheme`. Only annotations on the
+/// scaffold are considered, others are ignored, and annotations extending
+/// past its end are an error. Annotations wrapping around the origin of
+/// circular scaffolds label positions modulo the scaffold length. If
+/// `strand` is given, only annotations on that strand are considered, e.g.
+/// to label each strand separately.
+pub fn labels_for_scaffold(
+    scaffold: &Scaffold,
+    annotations: &[Annotation],
+    scheme: LabelScheme,
+    strand: Option<Strand>,
+) -> Result<Vec<u8>> {
+    labels_for_scaffold_with_options(
+        scaffold,
+        annotations,
+        scheme,
+        strand,
+        LabelOptions::default(),
+    )
+}
+
+/// Same as `labels_for_scaffold` with further options, e.g. the handling of
+/// out of bounds annotations.
+pub fn labels_for_scaffold_with_options(
+    scaffold: &Scaffold,
+    annotations: &[Annotation],
+    scheme: LabelScheme,
+    strand: Option<Strand>,
+    options: LabelOptions,
+) -> Result<Vec<u8>> {
+    let len = scaffold.len();
+    let mut labels = vec![0; len];
+    for annotation in annotations {
+        if annotation.scaffold() != scaffold.name()
+            || strand.is_some_and(|strand| annotation.strand() != strand)
+        {
+            continue;
+        }
+        let label = match scheme.label(annotation.feature()) {
+            Some(label) => label,
+            None => continue,
+        };
+        let (start, end) = match options.bounds.apply(annotation, scaffold)? {
+            Bounded::Within => (annotation.start(), annotation.end()),
+            Bounded::Clamped(start, end) => (start, end),
+            Bounded::Dropped => continue,
+        };
+
+        // Positions reach past the end only in wrap-around ranges of
+        // circular scaffolds.
+        for position in start..end {
+            let position = &mut labels[position % len];
+            *position = (*position).max(label);
+        }
+    }
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        LabelOptions, LabelScheme, LabelTrack, BLOCK_LEN, BLOCK_PACKED, BLOCK_RUNS, HEADER_LEN,
+    };
+    use crate::bounds::BoundsPolicy;
+    use crate::data::{AnnotationBuilder, Feature, Scaffold, Strand, Symbol};
+    use crate::simulate::Random;
+    use std::env;
+
+    fn block_kinds(track: &LabelTrack) -> Vec<u8> {
+        (0..track.len().div_ceil(BLOCK_LEN))
+            .map(|block| track.as_bytes()[track.block_range(block).0])
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut random = Random::new(3);
+        for &(n_classes, len) in &[
+            (1, 10),
+            (2, BLOCK_LEN * 3),
+            (3, BLOCK_LEN + 1),
+            (5, BLOCK_LEN - 1),
+            (17, 2 * BLOCK_LEN + 7),
+            (256, 300),
+        ] {
+            let labels: Vec<u8> = (0..len).map(|_| random.below(n_classes) as u8).collect();
+            let track = LabelTrack::pack(&labels, n_classes).unwrap();
+            assert_eq!(track.len(), len);
+            assert_eq!(track.n_classes(), n_classes);
+            assert_eq!(track.unpack(), labels);
+            for (pos, &label) in labels.iter().enumerate() {
+                assert_eq!(track.get(pos), Some(label));
+            }
+            assert_eq!(track.get(len), None);
+
+            let copy = LabelTrack::from_bytes(track.as_bytes().to_vec()).unwrap();
+            assert_eq!(copy, track);
+
+            let borrowed = LabelTrack::from_bytes(track.as_bytes()).unwrap();
+            assert_eq!(borrowed.len(), len);
+            assert_eq!(borrowed.unpack(), labels);
+            if len > 0 {
+                assert_eq!(borrowed.get(len - 1), labels.last().copied());
+            }
+        }
+
+        let empty = LabelTrack::pack(&[], 2).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.unpack().is_empty());
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        // Long runs crossing block boundaries in the first two blocks and
+        // noise in the third one.
+        let mut labels = vec![0u8; BLOCK_LEN - 3];
+        labels.extend(vec![1u8; BLOCK_LEN + 6]);
+        labels.extend((0..BLOCK_LEN).map(|i| (i % 4) as u8));
+        labels.push(2);
+
+        let track = LabelTrack::pack(&labels, 4).unwrap();
+        assert_eq!(
+            block_kinds(&track),
+            vec![BLOCK_RUNS, BLOCK_RUNS, BLOCK_PACKED, BLOCK_PACKED]
+        );
+        // Binary labels of the first half would need 1024 bytes bit-packed.
+        assert!(track.as_bytes().len() < HEADER_LEN + 5 * 8 + 1200);
+
+        for pos in [
+            0,
+            BLOCK_LEN - 4,
+            BLOCK_LEN - 3,
+            BLOCK_LEN - 1,
+            BLOCK_LEN,
+            2 * BLOCK_LEN - 1,
+            2 * BLOCK_LEN + 2,
+            2 * BLOCK_LEN + 3,
+            3 * BLOCK_LEN + 2,
+            3 * BLOCK_LEN + 3,
+        ] {
+            assert_eq!(track.get(pos), Some(labels[pos]), "position {}", pos);
+        }
+        assert_eq!(track.unpack(), labels);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(LabelTrack::pack(&[0, 1, 2], 2).is_err());
+        assert!(LabelTrack::pack(&[0], 0).is_err());
+        assert!(LabelTrack::pack(&[0], 257).is_err());
+
+        let track = LabelTrack::pack(&[0, 1, 1, 0, 1], 2).unwrap();
+        let bytes = track.as_bytes();
+        assert!(LabelTrack::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(LabelTrack::from_bytes(bytes[..10].to_vec()).is_err());
+
+        let mut corrupted = bytes.to_vec();
+        corrupted[4] = 2;
+        assert!(LabelTrack::from_bytes(corrupted).is_err());
+
+        let mut corrupted = bytes.to_vec();
+        corrupted[HEADER_LEN + 16] = 7;
+        assert!(LabelTrack::from_bytes(corrupted).is_err());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let labels: Vec<u8> = (0..10_000).map(|i| (i / 100 % 3) as u8).collect();
+        let track = LabelTrack::pack(&labels, 3).unwrap();
+
+        let path = env::temp_dir().join(format!("ncrs-labels-{}.bin", std::process::id()));
+        track.save(&path).unwrap();
+        let loaded = LabelTrack::load(&path).unwrap();
+        #[cfg(feature = "memmap2")]
+        {
+            let mapped = LabelTrack::open(&path).unwrap();
+            assert_eq!(mapped.as_bytes(), track.as_bytes());
+            assert_eq!(mapped.get(250), Some(2));
+            assert_eq!(mapped.unpack(), labels);
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unpack(), labels);
+    }
+
+    #[test]
+    fn test_labels_for_scaffold() {
+        let annotation = |feature, strand, start, end| {
+            AnnotationBuilder::new("s", feature)
+                .strand(strand)
+                .start(start)
+                .end(end)
+                .build()
+                .unwrap()
+        };
+        // A two exon gene on the positive strand and a single exon gene on
+        // the negative strand.
+        let annotations = vec![
+            annotation(Feature::Gene, Strand::Positive, 2, 28),
+            annotation(Feature::MRNA, Strand::Positive, 2, 28),
+            annotation(Feature::Exon, Strand::Positive, 2, 10),
+            annotation(Feature::FivePrimeUTR, Strand::Positive, 2, 5),
+            annotation(Feature::CDS, Strand::Positive, 5, 10),
+            annotation(Feature::Exon, Strand::Positive, 15, 28),
+            annotation(Feature::CDS, Strand::Positive, 15, 24),
+            annotation(Feature::ThreePrimeUTR, Strand::Positive, 24, 28),
+            annotation(Feature::Gene, Strand::Negative, 29, 32),
+            annotation(Feature::Exon, Strand::Negative, 30, 32),
+            annotation(Feature::Repeat, Strand::Positive, 0, 30),
+        ];
+        let scaffold = Scaffold::new(String::from("s"), vec![Symbol::Other; 32]);
+        let render =
+            |labels: Vec<u8>| -> String { labels.iter().map(|&l| char::from(b'0' + l)).collect() };
+
+        assert_eq!(
+            render(
+                super::labels_for_scaffold(&scaffold, &annotations, LabelScheme::Exon, None)
+                    .unwrap()
+            ),
+            "00111111110000011111111111110011"
+        );
+        assert_eq!(
+            render(
+                super::labels_for_scaffold(
+                    &scaffold,
+                    &annotations,
+                    LabelScheme::GeneStructure,
+                    None
+                )
+                .unwrap()
+            ),
+            "00222222221111122222222222220122"
+        );
+        assert_eq!(
+            render(
+                super::labels_for_scaffold(&scaffold, &annotations, LabelScheme::Coding, None)
+                    .unwrap()
+            ),
+            "00222333331111133333333322220122"
+        );
+        assert_eq!(
+            render(
+                super::labels_for_scaffold(
+                    &scaffold,
+                    &annotations,
+                    LabelScheme::Coding,
+                    Some(Strand::Positive)
+                )
+                .unwrap()
+            ),
+            "00222333331111133333333322220000"
+        );
+        assert_eq!(
+            render(
+                super::labels_for_scaffold(
+                    &scaffold,
+                    &annotations,
+                    LabelScheme::Coding,
+                    Some(Strand::Negative)
+                )
+                .unwrap()
+            ),
+            "00000000000000000000000000000122"
+        );
+        let empty = Scaffold::new(String::from("s"), Vec::new());
+        assert!(
+            super::labels_for_scaffold(&empty, &[], LabelScheme::Exon, None)
+                .unwrap()
+                .is_empty()
+        );
+
+        // The negative strand gene and its exon extend past the end of a
+        // shorter scaffold.
+        let short = Scaffold::new(String::from("s"), vec![Symbol::Other; 31]);
+        let error = super::labels_for_scaffold(&short, &annotations, LabelScheme::Exon, None)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotation 30..32 extends past the end of scaffold s of length 31."
+        );
+        for &(bounds, expected) in &[
+            (BoundsPolicy::Clamp, "0022222222111112222222222222012"),
+            (BoundsPolicy::Drop, "0022222222111112222222222222000"),
+        ] {
+            let labels = super::labels_for_scaffold_with_options(
+                &short,
+                &annotations,
+                LabelScheme::GeneStructure,
+                None,
+                LabelOptions { bounds },
+            )
+            .unwrap();
+            assert_eq!(render(labels), expected);
+        }
+    }
+
+    #[test]
+    fn test_labels_circular() {
+        let scaffold =
+            Scaffold::new(String::from("c"), vec![Symbol::Other; 10]).with_circular(true);
+        let annotations = vec![AnnotationBuilder::new("c", Feature::Exon)
+            .start(7)
+            .end(13)
+            .build()
+            .unwrap()];
+        let labels =
+            super::labels_for_scaffold(&scaffold, &annotations, LabelScheme::Exon, None).unwrap();
+        assert_eq!(labels, [1, 1, 1, 0, 0, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_labels_scaffolds() {
+        let annotation = |scaffold, start, end| {
+            AnnotationBuilder::new(scaffold, Feature::Exon)
+                .start(start)
+                .end(end)
+                .build()
+                .unwrap()
+        };
+        let annotations = vec![
+            annotation("a", 0, 2),
+            annotation("b", 3, 8),
+            annotation("a", 4, 5),
+        ];
+        let a = Scaffold::new(String::from("a"), vec![Symbol::Other; 6]);
+        let b = Scaffold::new(String::from("b"), vec![Symbol::Other; 8]);
+
+        let labels = super::labels_for_scaffold(&a, &annotations, LabelScheme::Exon, None).unwrap();
+        assert_eq!(labels, [1, 1, 0, 0, 1, 0]);
+        let labels = super::labels_for_scaffold(&b, &annotations, LabelScheme::Exon, None).unwrap();
+        assert_eq!(labels, [0, 0, 0, 1, 1, 1, 1, 1]);
+
+        // The annotation of the longer scaffold is not clamped into the
+        // shorter one.
+        let labels = super::labels_for_scaffold_with_options(
+            &a,
+            &annotations,
+            LabelScheme::Exon,
+            None,
+            LabelOptions {
+                bounds: BoundsPolicy::Clamp,
+            },
+        )
+        .unwrap();
+        assert_eq!(labels, [1, 1, 0, 0, 1, 0]);
+    }
+}
@@ -1,6 +1,48 @@
 #[macro_use]
 extern crate anyhow;
 
+pub mod assembly;
+pub mod augment;
+pub mod bed;
+pub mod bedgraph;
+pub mod bgzf;
+pub mod bounds;
+pub mod cds;
+pub mod checksum;
+pub mod codon;
+pub mod composition;
+pub mod coverage;
 pub mod data;
+pub mod dedup;
+pub mod edit;
+pub mod encode;
+pub mod extract;
+pub mod fai;
 pub mod fasta;
+pub mod fastq;
+pub mod filter;
+pub mod genome;
 pub mod gff;
+pub mod ids;
+pub mod index;
+pub mod interval;
+pub mod kmer;
+pub mod labels;
+pub mod matching;
+pub mod model;
+pub mod names;
+pub mod overlap;
+pub mod prediction;
+pub mod progress;
+pub mod region;
+pub mod repeats;
+pub mod simulate;
+pub mod splice;
+pub mod split;
+pub mod trim;
+pub mod validate;
+pub mod view;
+pub mod window;
+
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,309 @@
+use crate::data::{Annotation, Feature, Strand};
+use std::collections::BTreeMap;
+
+/// Classification of a predicted annotation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PredictionMatch {
+    /// Matched to the reference annotation with the given index and both
+    /// boundaries are within tolerance.
+    Exact(usize),
+    /// Matched to the reference annotation with the given index but at least
+    /// one boundary is off by more than the tolerance.
+    BoundaryOff(usize),
+    /// Not matched to any reference annotation.
+    Unmatched,
+}
+
+/// One-to-one matching of predicted annotations to reference annotations.
+#[derive(Clone, Debug)]
+pub struct MatchResult {
+    predictions: Vec<PredictionMatch>,
+    references: Vec<Option<usize>>,
+}
+
+impl MatchResult {
+    /// Classification of each predicted annotation, in input order.
+    pub fn predictions(&self) -> &[PredictionMatch] {
+        &self.predictions
+    }
+
+    /// Index of the predicted annotation matched to each reference
+    /// annotation, in input order. `None` marks a missed reference.
+    pub fn references(&self) -> &[Option<usize>] {
+        &self.references
+    }
+
+    /// Number of references matched by a prediction.
+    pub fn found(&self) -> usize {
+        self.references.iter().filter(|r| r.is_some()).count()
+    }
+
+    /// Number of references not matched by any prediction.
+    pub fn missed(&self) -> usize {
+        self.references.len() - self.found()
+    }
+}
+
+/// Matches predicted annotations to reference annotations one-to-one.
+///
+/// A prediction and a reference are candidates if they lie on the same
+/// scaffold and strand, have the same feature and their overlap is at least
+/// `min_overlap_frac` of the longer of the two. Candidate pairs are matched
+/// greedily by decreasing overlap length; ties are broken by smaller total
+/// boundary distance and then by lower reference and prediction indices, so
+/// the result does not depend on anything but input order. Consequently, if
+/// several predictions overlap a single reference, only the best one is
+/// matched and the others are unmatched.
+///
+/// A matched prediction is classified as exact if both its start and its end
+/// are within `tolerance` positions of the reference boundaries.
+pub fn match_annotations(
+    reference: &[Annotation],
+    predicted: &[Annotation],
+    tolerance: usize,
+    min_overlap_frac: f64,
+) -> MatchResult {
+    let mut groups: BTreeMap<GroupKey, (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for (r, annotation) in reference.iter().enumerate() {
+        groups.entry(group_key(annotation)).or_default().0.push(r);
+    }
+    for (p, annotation) in predicted.iter().enumerate() {
+        groups.entry(group_key(annotation)).or_default().1.push(p);
+    }
+
+    // (overlap, boundary distance, reference index, prediction index)
+    let mut candidates: Vec<(usize, usize, usize, usize)> = Vec::new();
+    for (references, predictions) in groups.values() {
+        for (r, p) in overlapping_pairs(reference, references, predicted, predictions) {
+            let (ref_annotation, pred_annotation) = (&reference[r], &predicted[p]);
+            let overlap = ref_annotation
+                .end()
+                .min(pred_annotation.end())
+                .saturating_sub(ref_annotation.start().max(pred_annotation.start()));
+            let longer = ref_annotation.len().max(pred_annotation.len());
+            if overlap == 0 || (overlap as f64) < min_overlap_frac * longer as f64 {
+                continue;
+            }
+
+            let distance = ref_annotation.start().abs_diff(pred_annotation.start())
+                + ref_annotation.end().abs_diff(pred_annotation.end());
+            candidates.push((overlap, distance, r, p));
+        }
+    }
+
+    candidates.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(a.1.cmp(&b.1))
+            .then(a.2.cmp(&b.2))
+            .then(a.3.cmp(&b.3))
+    });
+
+    let mut predictions = vec![PredictionMatch::Unmatched; predicted.len()];
+    let mut references = vec![None; reference.len()];
+    for (_, _, r, p) in candidates {
+        if references[r].is_some() || predictions[p] != PredictionMatch::Unmatched {
+            continue;
+        }
+
+        references[r] = Some(p);
+        let (ref_annotation, pred_annotation) = (&reference[r], &predicted[p]);
+        predictions[p] = if ref_annotation.start().abs_diff(pred_annotation.start()) <= tolerance
+            && ref_annotation.end().abs_diff(pred_annotation.end()) <= tolerance
+        {
+            PredictionMatch::Exact(r)
+        } else {
+            PredictionMatch::BoundaryOff(r)
+        };
+    }
+
+    MatchResult {
+        predictions,
+        references,
+    }
+}
+
+/// Scaffold, strand and feature; only annotations with equal keys are matched.
+type GroupKey<'a> = (&'a str, Strand, &'a Feature);
+
+fn group_key(annotation: &Annotation) -> GroupKey<'_> {
+    (
+        annotation.scaffold(),
+        annotation.strand(),
+        annotation.feature(),
+    )
+}
+
+/// Returns all pairs of reference and prediction indices whose annotations
+/// overlap. Both index lists are swept in the order of annotation starts;
+/// each annotation is paired with the still active annotations of the other
+/// list, so every overlapping pair is reported exactly once. Empty
+/// annotations overlap nothing and are skipped.
+fn overlapping_pairs(
+    reference: &[Annotation],
+    references: &[usize],
+    predicted: &[Annotation],
+    predictions: &[usize],
+) -> Vec<(usize, usize)> {
+    let mut events: Vec<(usize, bool, usize)> = references
+        .iter()
+        .filter(|&&r| !reference[r].is_empty())
+        .map(|&r| (reference[r].start(), false, r))
+        .chain(
+            predictions
+                .iter()
+                .filter(|&&p| !predicted[p].is_empty())
+                .map(|&p| (predicted[p].start(), true, p)),
+        )
+        .collect();
+    events.sort_unstable();
+
+    let mut pairs = Vec::new();
+    let mut active_references: Vec<usize> = Vec::new();
+    let mut active_predictions: Vec<usize> = Vec::new();
+    for (start, is_prediction, index) in events {
+        active_references.retain(|&r| reference[r].end() > start);
+        active_predictions.retain(|&p| predicted[p].end() > start);
+
+        if is_prediction {
+            pairs.extend(active_references.iter().map(|&r| (r, index)));
+            active_predictions.push(index);
+        } else {
+            pairs.extend(active_predictions.iter().map(|&p| (index, p)));
+            active_references.push(index);
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::PredictionMatch;
+    use crate::data::{Annotation, Feature, Strand};
+    use crate::simulate::Random;
+    use crate::testing;
+
+    #[test]
+    fn test_match_annotations() {
+        let reference = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 100, 200),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 300, 400),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 500, 600),
+            testing::annotation("t", Feature::Exon, Strand::Positive, 100, 200),
+        ];
+        let predicted = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 98, 203),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 330, 400),
+            testing::annotation("s", Feature::Exon, Strand::Negative, 500, 600),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 700, 800),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 590, 610),
+        ];
+
+        let result = super::match_annotations(&reference, &predicted, 3, 0.5);
+        assert_eq!(
+            result.predictions(),
+            &[
+                PredictionMatch::Exact(0),
+                PredictionMatch::BoundaryOff(1),
+                PredictionMatch::Unmatched,
+                PredictionMatch::Unmatched,
+                // Overlap of 10 is below half of the longer annotation.
+                PredictionMatch::Unmatched,
+            ]
+        );
+        assert_eq!(result.references(), &[Some(0), Some(1), None, None]);
+        assert_eq!(result.found(), 2);
+        assert_eq!(result.missed(), 2);
+
+        let result = super::match_annotations(&reference, &predicted, 3, 0.);
+        assert_eq!(result.predictions()[4], PredictionMatch::BoundaryOff(2));
+    }
+
+    #[test]
+    fn test_many_to_one() {
+        let reference = vec![testing::annotation(
+            "s",
+            Feature::Exon,
+            Strand::Positive,
+            100,
+            200,
+        )];
+        let predicted = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 100, 150),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 90, 199),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 101, 200),
+        ];
+
+        // The second and the third prediction tie on overlap, the third one
+        // is closer to the reference boundaries.
+        let result = super::match_annotations(&reference, &predicted, 1, 0.);
+        assert_eq!(
+            result.predictions(),
+            &[
+                PredictionMatch::Unmatched,
+                PredictionMatch::Unmatched,
+                PredictionMatch::Exact(0),
+            ]
+        );
+
+        // Full tie is resolved by input order.
+        let predicted = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 90, 190),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 110, 210),
+        ];
+        let result = super::match_annotations(&reference, &predicted, 0, 0.);
+        assert_eq!(
+            result.predictions(),
+            &[PredictionMatch::BoundaryOff(0), PredictionMatch::Unmatched]
+        );
+
+        // One prediction spanning two references is matched only once.
+        let reference = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 0, 10),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 10, 30),
+        ];
+        let predicted = vec![testing::annotation(
+            "s",
+            Feature::Exon,
+            Strand::Positive,
+            0,
+            30,
+        )];
+        let result = super::match_annotations(&reference, &predicted, 0, 0.);
+        assert_eq!(result.predictions(), &[PredictionMatch::BoundaryOff(1)]);
+        assert_eq!(result.references(), &[None, Some(0)]);
+    }
+
+    #[test]
+    fn test_overlapping_pairs_random() {
+        let mut random = Random::new(11);
+        let generate = |random: &mut Random| -> Vec<Annotation> {
+            (0..random.below(10))
+                .map(|_| {
+                    let start = random.below(40);
+                    let end = start + random.below(10);
+                    testing::annotation("s", Feature::Exon, Strand::Positive, start, end)
+                })
+                .collect()
+        };
+        for _ in 0..200 {
+            let reference = generate(&mut random);
+            let predicted = generate(&mut random);
+            let references: Vec<usize> = (0..reference.len()).collect();
+            let predictions: Vec<usize> = (0..predicted.len()).collect();
+
+            let mut pairs =
+                super::overlapping_pairs(&reference, &references, &predicted, &predictions);
+            pairs.sort_unstable();
+            let mut expected = Vec::new();
+            for (r, a) in reference.iter().enumerate() {
+                for (p, b) in predicted.iter().enumerate() {
+                    if a.start().max(b.start()) < a.end().min(b.end()) {
+                        expected.push((r, p));
+                    }
+                }
+            }
+            assert_eq!(pairs, expected);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use crate::data::{Annotation, Attributes, Feature};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Transcript with its child features (exons, CDS, UTRs...) sorted by
+/// coordinates.
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    id: String,
+    annotation: Option<Annotation>,
+    features: Vec<Annotation>,
+}
+
+impl Transcript {
+    fn new(id: &str) -> Self {
+        Self {
+            id: String::from(id),
+            annotation: None,
+            features: Vec::new(),
+        }
+    }
+
+    /// GFF3 `ID` or GTF `transcript_id` of the transcript.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Record of the transcript itself. `None` for transcripts only implied
+    /// by `transcript_id` attributes of GTF features.
+    pub fn annotation(&self) -> Option<&Annotation> {
+        self.annotation.as_ref()
+    }
+
+    /// Child features sorted by start and end.
+    pub fn features(&self) -> &[Annotation] {
+        &self.features
+    }
+
+    /// Child features of a given type, e.g. exons.
+    pub fn features_of<'a>(&'a self, feature: &'a Feature) -> impl Iterator<Item = &'a Annotation> {
+        self.features.iter().filter(move |a| a.feature() == feature)
+    }
+}
+
+/// Gene with its transcripts in the order of their appearance.
+#[derive(Clone, Debug)]
+pub struct Gene {
+    id: String,
+    annotation: Option<Annotation>,
+    transcripts: Vec<Transcript>,
+}
+
+impl Gene {
+    fn new(id: &str) -> Self {
+        Self {
+            id: String::from(id),
+            annotation: None,
+            transcripts: Vec::new(),
+        }
+    }
+
+    /// GFF3 `ID` or GTF `gene_id` of the gene.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Record of the gene itself. `None` for genes only implied by
+    /// `gene_id` attributes of GTF records.
+    pub fn annotation(&self) -> Option<&Annotation> {
+        self.annotation.as_ref()
+    }
+
+    pub fn transcripts(&self) -> &[Transcript] {
+        &self.transcripts
+    }
+}
+
+/// Collection of genes under construction.
+#[derive(Default)]
+struct Models {
+    genes: Vec<Gene>,
+    gene_index: HashMap<String, usize>,
+    /// Positions of transcripts keyed by their IDs. A transcript with
+    /// multiple parent genes is present in each of them.
+    transcript_index: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl Models {
+    fn gene(&mut self, id: &str) -> &mut Gene {
+        let position = match self.gene_index.get(id) {
+            Some(&position) => position,
+            None => {
+                self.genes.push(Gene::new(id));
+                self.gene_index
+                    .insert(String::from(id), self.genes.len() - 1);
+                self.genes.len() - 1
+            }
+        };
+        &mut self.genes[position]
+    }
+
+    fn transcript(&mut self, gene: &str, id: &str) -> &mut Transcript {
+        self.gene(gene);
+        let g = self.gene_index[gene];
+        let positions = self.transcript_index.entry(String::from(id)).or_default();
+        let position = match positions.iter().find(|&&(other, _)| other == g) {
+            Some(&(_, position)) => position,
+            None => {
+                self.genes[g].transcripts.push(Transcript::new(id));
+                let position = self.genes[g].transcripts.len() - 1;
+                positions.push((g, position));
+                position
+            }
+        };
+        &mut self.genes[g].transcripts[position]
+    }
+}
+
+/// How a record links into the gene hierarchy.
+enum Link<'a> {
+    /// GFF3 `ID` and `Parent` attributes.
+    Gff3 {
+        id: Option<&'a str>,
+        parents: &'a [String],
+    },
+    /// GTF `gene_id` and `transcript_id` attributes.
+    Gtf {
+        gene: &'a str,
+        transcript: Option<&'a str>,
+    },
+    Unlinked,
+}
+
+fn link(attributes: &Attributes) -> Link<'_> {
+    let parents = attributes.get_all("Parent");
+    if attributes.get("ID").is_some() || !parents.is_empty() {
+        Link::Gff3 {
+            id: attributes.get("ID"),
+            parents,
+        }
+    } else if let Some(gene) = attributes.get("gene_id") {
+        Link::Gtf {
+            gene,
+            transcript: attributes.get("transcript_id"),
+        }
+    } else {
+        Link::Unlinked
+    }
+}
+
+fn is_transcript(feature: &Feature) -> bool {
+    matches!(feature, Feature::MRNA | Feature::Transcript)
+}
+
+/// Builds gene models from GFF3 `ID` and `Parent` attributes or GTF
+/// `gene_id` and `transcript_id` attributes. The order of records does not
+/// matter, parents may be defined after their children.
+///
+/// In GFF3, genes are parentless records with an `ID` which are either of
+/// the `gene` type or referenced as a parent. Their direct children are
+/// transcripts and children of transcripts are features. Deeper records
+/// are ignored, as are records without any links (e.g. repeats). A record
+/// with multiple parents is added to each of them. A `Parent` referencing
+/// a missing `ID` is an error.
+///
+/// In GTF, genes and transcripts without records of their own are implied
+/// by the attributes of their features.
+pub fn build_gene_models(annotations: &[Annotation]) -> Result<Vec<Gene>> {
+    let attributes: Vec<Attributes> = annotations.iter().map(|a| a.parsed_attributes()).collect();
+    let links: Vec<Link> = attributes.iter().map(link).collect();
+
+    let mut ids: HashSet<&str> = HashSet::new();
+    let mut referenced: HashSet<&str> = HashSet::new();
+    for link in links.iter() {
+        if let Link::Gff3 { id, parents } = link {
+            ids.extend(id.iter());
+            referenced.extend(parents.iter().map(String::as_str));
+        }
+    }
+    for (i, link) in links.iter().enumerate() {
+        if let Link::Gff3 { parents, .. } = link {
+            if let Some(parent) = parents.iter().find(|p| !ids.contains(p.as_str())) {
+                bail!(
+                    "Annotation {}{} at {}:{}-{} references missing parent {}.",
+                    i,
+                    annotations[i].line_note(),
+                    annotations[i].scaffold(),
+                    annotations[i].start() + 1,
+                    annotations[i].end(),
+                    parent
+                );
+            }
+        }
+    }
+
+    let mut models = Models::default();
+
+    // Genes first, so that their order follows the input.
+    for (annotation, link) in annotations.iter().zip(links.iter()) {
+        match *link {
+            Link::Gff3 {
+                id: Some(id),
+                parents: [],
+            } if *annotation.feature() == Feature::Gene || referenced.contains(id) => {
+                models.gene(id).annotation = Some(annotation.clone());
+            }
+            Link::Gtf { gene, .. } => {
+                let gene = models.gene(gene);
+                if *annotation.feature() == Feature::Gene {
+                    gene.annotation = Some(annotation.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for (i, (annotation, link)) in annotations.iter().zip(links.iter()).enumerate() {
+        match *link {
+            Link::Gff3 {
+                id: Some(id),
+                parents,
+            } => {
+                for parent in parents.iter() {
+                    if models.gene_index.contains_key(parent.as_str()) {
+                        models.transcript(parent, id).annotation = Some(annotation.clone());
+                    }
+                }
+            }
+            Link::Gtf { gene, transcript } if is_transcript(annotation.feature()) => {
+                let transcript = match transcript {
+                    Some(transcript) => transcript,
+                    None => bail!(
+                        "Transcript annotation {}{} at {}:{}-{} has no transcript_id.",
+                        i,
+                        annotation.line_note(),
+                        annotation.scaffold(),
+                        annotation.start() + 1,
+                        annotation.end()
+                    ),
+                };
+                models.transcript(gene, transcript).annotation = Some(annotation.clone());
+            }
+            _ => (),
+        }
+    }
+
+    for (annotation, link) in annotations.iter().zip(links.iter()) {
+        match *link {
+            Link::Gff3 { parents, .. } => {
+                for parent in parents.iter() {
+                    let positions = match models.transcript_index.get(parent.as_str()) {
+                        Some(positions) => positions.clone(),
+                        None => continue,
+                    };
+                    for (g, t) in positions {
+                        models.genes[g].transcripts[t]
+                            .features
+                            .push(annotation.clone());
+                    }
+                }
+            }
+            Link::Gtf {
+                gene,
+                transcript: Some(transcript),
+            } if !is_transcript(annotation.feature()) && *annotation.feature() != Feature::Gene => {
+                models
+                    .transcript(gene, transcript)
+                    .features
+                    .push(annotation.clone());
+            }
+            _ => (),
+        }
+    }
+
+    let mut genes = models.genes;
+    for transcript in genes.iter_mut().flat_map(|g| g.transcripts.iter_mut()) {
+        transcript.features.sort_by_key(|a| (a.start(), a.end()));
+    }
+    Ok(genes)
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::data::Feature;
+    use crate::gff::{load_gff_file, load_gtf_file, parse_gff_record};
+    use std::path::Path;
+
+    #[test]
+    fn test_build_gene_models() {
+        let annotations = load_gff_file(Path::new("./tests/genes.gff3")).unwrap();
+        let genes = super::build_gene_models(&annotations).unwrap();
+
+        let ids: Vec<&str> = genes.iter().map(|g| g.id()).collect();
+        assert_eq!(ids, ["gene1", "gene2", "gene3"]);
+
+        let exons = |gene: usize, transcript: usize| {
+            genes[gene].transcripts()[transcript]
+                .features_of(&Feature::Exon)
+                .count()
+        };
+        let transcripts: Vec<&str> = genes[0].transcripts().iter().map(|t| t.id()).collect();
+        assert_eq!(transcripts, ["mrna1a", "mrna1b"]);
+        assert_eq!(exons(0, 0), 3);
+        // The shared exon is present in both transcripts.
+        assert_eq!(exons(0, 1), 2);
+        assert_eq!(genes[0].transcripts()[0].features().len(), 5);
+
+        // Gene and transcript records follow their children.
+        assert!(genes[1].annotation().is_some());
+        assert_eq!(genes[1].transcripts()[0].id(), "mrna2");
+        assert_eq!(exons(1, 0), 2);
+        let starts: Vec<usize> = genes[1].transcripts()[0]
+            .features()
+            .iter()
+            .map(|a| a.start())
+            .collect();
+        assert_eq!(starts, [2999, 3499]);
+
+        assert_eq!(genes[2].transcripts().len(), 1);
+        assert_eq!(exons(2, 0), 1);
+
+        let mut dangling = annotations.clone();
+        dangling.push(
+            parse_gff_record(b"scaffold_1\tt\texon\t1\t10\t.\t+\t.\tParent=missing").unwrap(),
+        );
+        let error = super::build_gene_models(&dangling).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotation 19 at scaffold_1:1-10 references missing parent missing."
+        );
+        let dangling = &dangling[1..];
+        let error = super::build_gene_models(dangling).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Annotation 0 (line 4) at scaffold_1:1000-2000 references missing parent gene1."
+        );
+    }
+
+    #[test]
+    fn test_build_gene_models_gtf() {
+        let annotations = load_gtf_file(Path::new("./tests/valid.gtf")).unwrap();
+        let genes = super::build_gene_models(&annotations).unwrap();
+        assert_eq!(genes.len(), 1);
+        assert_eq!(genes[0].id(), "ENSG00000223972");
+        let transcript = &genes[0].transcripts()[0];
+        assert_eq!(transcript.id(), "ENST00000456328");
+        assert!(transcript.annotation().is_some());
+        assert_eq!(transcript.features().len(), 4);
+
+        // Without gene and transcript records both are implied.
+        let implied = &annotations[2..];
+        let genes = super::build_gene_models(implied).unwrap();
+        assert!(genes[0].annotation().is_none());
+        assert!(genes[0].transcripts()[0].annotation().is_none());
+        assert_eq!(genes[0].transcripts()[0].features().len(), 4);
+    }
+}
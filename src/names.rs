@@ -0,0 +1,296 @@
+use crate::data::{Annotation, Scaffold};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+/// Scaffold name rewrite applied to both FASTA and GFF input so that names
+/// of the two agree, see `load_fasta_normalized` and
+/// `load_gff_file_normalized`.
+pub enum NameNormalizer {
+    /// Strip the UCSC style `chr` prefix, e.g. `chr1` → `1`.
+    StripChr,
+    /// Convert ASCII letters to lowercase, e.g. `ChrX` → `chrx`.
+    Lowercase,
+    /// User supplied rewrite.
+    Custom(Box<dyn Fn(&str) -> String>),
+}
+
+impl NameNormalizer {
+    pub fn normalize(&self, name: &str) -> String {
+        match self {
+            Self::StripChr => String::from(name.strip_prefix("chr").unwrap_or(name)),
+            Self::Lowercase => name.to_ascii_lowercase(),
+            Self::Custom(f) => f(name),
+        }
+    }
+}
+
+impl fmt::Debug for NameNormalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::StripChr => write!(f, "StripChr"),
+            Self::Lowercase => write!(f, "Lowercase"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Scaffold naming convention rewrite which may resolve a name mismatch
+/// between a FASTA and an annotation file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rename {
+    /// Add or strip the UCSC style `chr` prefix, e.g. `chr1` ↔ `1`.
+    ChrPrefix,
+    /// Compare names case insensitively, e.g. `ChrX` ↔ `chrx`.
+    CaseFold,
+    /// Compare only the part of the name before the first whitespace or `.`,
+    /// e.g. `NC_000001.11` ↔ `NC_000001`.
+    FirstToken,
+}
+
+impl Rename {
+    const ALL: [Rename; 3] = [Rename::ChrPrefix, Rename::CaseFold, Rename::FirstToken];
+
+    fn apply<'a>(&self, name: &'a str) -> &'a str {
+        match self {
+            Self::ChrPrefix => name.strip_prefix("chr").unwrap_or(name),
+            Self::CaseFold => name,
+            Self::FirstToken => name
+                .split(|c: char| c.is_whitespace() || c == '.')
+                .next()
+                .unwrap_or(name),
+        }
+    }
+
+    fn matches(&self, a: &str, b: &str) -> bool {
+        let (a, b) = (self.apply(a), self.apply(b));
+        match self {
+            Self::CaseFold => a.eq_ignore_ascii_case(b),
+            _ => a == b,
+        }
+    }
+}
+
+/// A scaffold which would match an orphan name after a rename.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Suggestion {
+    rule: Rename,
+    candidate: String,
+}
+
+impl Suggestion {
+    pub fn rule(&self) -> Rename {
+        self.rule
+    }
+
+    /// Name of the scaffold the orphan would match.
+    pub fn candidate(&self) -> &str {
+        self.candidate.as_str()
+    }
+}
+
+/// Scaffold name referenced by annotations but missing among scaffolds.
+#[derive(Clone, Debug)]
+pub struct Orphan {
+    name: String,
+    annotations: usize,
+    suggestions: Vec<Suggestion>,
+}
+
+impl Orphan {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Number of annotations referencing the missing scaffold.
+    pub fn annotations(&self) -> usize {
+        self.annotations
+    }
+
+    /// Renames which would resolve the orphan, each leading to exactly one
+    /// scaffold. A rule matching multiple scaffolds is not suggested.
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OrphanReport {
+    orphans: Vec<Orphan>,
+}
+
+impl OrphanReport {
+    /// Orphans sorted by name.
+    pub fn orphans(&self) -> &[Orphan] {
+        &self.orphans
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+}
+
+/// Finds annotation scaffold names which do not appear among scaffolds and
+/// suggests naming convention rewrites which would resolve them.
+pub fn find_orphans(scaffolds: &[Scaffold], annotations: &[Annotation]) -> OrphanReport {
+    let names: HashSet<&str> = scaffolds.iter().map(|s| s.name()).collect();
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for annotation in annotations {
+        if !names.contains(annotation.scaffold()) {
+            *counts.entry(annotation.scaffold()).or_insert(0) += 1;
+        }
+    }
+
+    let orphans = counts
+        .into_iter()
+        .map(|(name, annotations)| Orphan {
+            name: String::from(name),
+            annotations,
+            suggestions: suggest(name, scaffolds),
+        })
+        .collect();
+
+    OrphanReport { orphans }
+}
+
+/// Returns sorted, deduplicated scaffold names referenced by annotations but
+/// missing among scaffolds. See `find_orphans` for suggested fixes.
+pub fn check_name_consistency(scaffolds: &[Scaffold], annotations: &[Annotation]) -> Vec<String> {
+    let names: HashSet<&str> = scaffolds.iter().map(|s| s.name()).collect();
+    let missing: BTreeSet<&str> = annotations
+        .iter()
+        .map(|a| a.scaffold())
+        .filter(|name| !names.contains(name))
+        .collect();
+    missing.into_iter().map(String::from).collect()
+}
+
+fn suggest(orphan: &str, scaffolds: &[Scaffold]) -> Vec<Suggestion> {
+    Rename::ALL
+        .iter()
+        .filter_map(|&rule| {
+            let mut candidates = scaffolds
+                .iter()
+                .filter(|scaffold| rule.matches(orphan, scaffold.name()));
+            match (candidates.next(), candidates.next()) {
+                (Some(candidate), None) => Some(Suggestion {
+                    rule,
+                    candidate: String::from(candidate.name()),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{NameNormalizer, Rename};
+    use crate::data::{Annotation, Feature, Scaffold, Strand};
+    use crate::fasta::load_fasta_normalized;
+    use crate::gff::load_gff_file_normalized;
+    use crate::testing;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_find_orphans() {
+        // Ensembl style FASTA with UCSC style annotations.
+        let scaffolds: Vec<Scaffold> = ["1", "X", "NC_000002", "scaffold_7"]
+            .iter()
+            .map(|&name| Scaffold::new(String::from(name), Vec::new()))
+            .collect();
+        let annotations: Vec<Annotation> = [
+            "chr1",
+            "chr1",
+            "chrX",
+            "x",
+            "NC_000002.12",
+            "Scaffold_7",
+            "scaffold_7",
+            "chrUn",
+        ]
+        .iter()
+        .map(|&name| testing::annotation(name, Feature::Exon, Strand::Positive, 0, 1))
+        .collect();
+
+        let report = super::find_orphans(&scaffolds, &annotations);
+        let orphans = report.orphans();
+        assert_eq!(orphans.len(), 6);
+
+        assert_eq!(orphans[0].name(), "NC_000002.12");
+        assert_eq!(orphans[0].suggestions().len(), 1);
+        assert_eq!(orphans[0].suggestions()[0].rule(), Rename::FirstToken);
+        assert_eq!(orphans[0].suggestions()[0].candidate(), "NC_000002");
+
+        assert_eq!(orphans[1].name(), "Scaffold_7");
+        assert_eq!(orphans[1].suggestions()[0].rule(), Rename::CaseFold);
+        assert_eq!(orphans[1].suggestions()[0].candidate(), "scaffold_7");
+
+        assert_eq!(orphans[2].name(), "chr1");
+        assert_eq!(orphans[2].annotations(), 2);
+        assert_eq!(orphans[2].suggestions()[0].rule(), Rename::ChrPrefix);
+        assert_eq!(orphans[2].suggestions()[0].candidate(), "1");
+
+        assert_eq!(orphans[3].name(), "chrUn");
+        assert!(orphans[3].suggestions().is_empty());
+
+        assert_eq!(orphans[4].name(), "chrX");
+        assert_eq!(orphans[4].suggestions()[0].candidate(), "X");
+
+        assert_eq!(orphans[5].name(), "x");
+        assert_eq!(orphans[5].suggestions()[0].rule(), Rename::CaseFold);
+        assert_eq!(orphans[5].suggestions()[0].candidate(), "X");
+    }
+
+    #[test]
+    fn test_find_orphans_reverse_prefix() {
+        // UCSC style FASTA with Ensembl style annotations.
+        let scaffolds = vec![Scaffold::new(String::from("chr2"), Vec::new())];
+        let report = super::find_orphans(
+            &scaffolds,
+            &[
+                testing::annotation("2", Feature::Exon, Strand::Positive, 0, 1),
+                testing::annotation("chr2", Feature::Exon, Strand::Positive, 0, 1),
+            ],
+        );
+        assert_eq!(report.orphans().len(), 1);
+        assert_eq!(report.orphans()[0].name(), "2");
+        assert_eq!(report.orphans()[0].suggestions()[0].candidate(), "chr2");
+    }
+
+    #[test]
+    fn test_normalized_loading() {
+        let directory = env::temp_dir().join(format!("ncrs-names-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let fasta = directory.join("genome.fasta");
+        fs::write(&fasta, ">chr1\nACGT\n>chrM\nA\n").unwrap();
+        let gff = directory.join("genes.gff");
+        fs::write(&gff, "1\ttest\texon\t1\t4\t.\t+\t.\tID=a\n").unwrap();
+
+        let scaffolds = crate::fasta::load_fasta(&fasta).unwrap();
+        let annotations = crate::gff::load_gff_file(&gff).unwrap();
+        assert_eq!(
+            super::check_name_consistency(&scaffolds, &annotations),
+            vec![String::from("1")]
+        );
+
+        let normalizer = NameNormalizer::StripChr;
+        let scaffolds = load_fasta_normalized(&fasta, &normalizer).unwrap();
+        let annotations = load_gff_file_normalized(&gff, &normalizer).unwrap();
+        assert_eq!(scaffolds[0].name(), "1");
+        assert_eq!(scaffolds[1].name(), "M");
+        assert!(super::check_name_consistency(&scaffolds, &annotations).is_empty());
+
+        let normalizer = NameNormalizer::Custom(Box::new(|name| format!("{}_v2", name)));
+        let annotations = load_gff_file_normalized(&gff, &normalizer).unwrap();
+        assert_eq!(annotations[0].scaffold(), "1_v2");
+
+        // Names colliding after normalization are rejected.
+        fs::write(&fasta, ">chr1\nACGT\n>1\nA\n").unwrap();
+        assert!(load_fasta_normalized(&fasta, &NameNormalizer::StripChr).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use crate::data::Annotation;
+use std::collections::HashMap;
+
+/// Annotations of a single scaffold sorted by start and end and organized
+/// as an implicit balanced binary search tree: the middle element of each
+/// range is the root of the range. Each node is augmented with the maximum
+/// end of its subtree.
+pub(crate) struct Track {
+    /// Indices of annotations sorted by start and end.
+    order: Vec<usize>,
+    /// Maximum annotation end of the subtree rooted at each position.
+    max_end: Vec<usize>,
+}
+
+impl Track {
+    /// Creates a track of annotations with indices `order`, which all have
+    /// to be on a single scaffold.
+    pub(crate) fn new(annotations: &[Annotation], mut order: Vec<usize>) -> Self {
+        order.sort_by_key(|&i| (annotations[i].start(), annotations[i].end()));
+        let mut track = Self {
+            max_end: vec![0; order.len()],
+            order,
+        };
+        track.build(annotations, 0, track.order.len());
+        track
+    }
+
+    /// Computes maximum ends of the subtree of range `lo..hi` and returns
+    /// it.
+    fn build(&mut self, annotations: &[Annotation], lo: usize, hi: usize) -> usize {
+        if lo >= hi {
+            return 0;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.build(annotations, lo, mid);
+        let right = self.build(annotations, mid + 1, hi);
+        self.max_end[mid] = annotations[self.order[mid]].end().max(left).max(right);
+        self.max_end[mid]
+    }
+
+    /// Returns indices of annotations overlapping non-empty `start..end` in
+    /// the order of their starts and ends, see `AnnotationIndex::query`.
+    pub(crate) fn query(&self, annotations: &[Annotation], start: usize, end: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        if start < end {
+            self.collect(
+                annotations,
+                (0, self.order.len()),
+                (start, end),
+                &mut indices,
+            );
+        }
+        indices
+    }
+
+    /// Collects indices of annotations from range `lo..hi` overlapping
+    /// `start..end` in the order of their starts.
+    fn collect(
+        &self,
+        annotations: &[Annotation],
+        (lo, hi): (usize, usize),
+        (start, end): (usize, usize),
+        result: &mut Vec<usize>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_end[mid] <= start {
+            return;
+        }
+
+        self.collect(annotations, (lo, mid), (start, end), result);
+        let annotation = &annotations[self.order[mid]];
+        if annotation.start() >= end {
+            return;
+        }
+        if annotation.end() > start && annotation.start() < annotation.end() {
+            result.push(self.order[mid]);
+        }
+        self.collect(annotations, (mid + 1, hi), (start, end), result);
+    }
+}
+
+/// Index of annotations answering overlap queries in time logarithmic in
+/// the number of annotations plus the number of reported annotations.
+/// Construction takes O(n log n).
+pub struct AnnotationIndex<'a> {
+    annotations: &'a [Annotation],
+    tracks: HashMap<&'a str, Track>,
+}
+
+impl<'a> AnnotationIndex<'a> {
+    pub fn new(annotations: &'a [Annotation]) -> Self {
+        let mut by_scaffold: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, annotation) in annotations.iter().enumerate() {
+            by_scaffold
+                .entry(annotation.scaffold())
+                .or_default()
+                .push(i);
+        }
+
+        let tracks = by_scaffold
+            .into_iter()
+            .map(|(scaffold, order)| (scaffold, Track::new(annotations, order)))
+            .collect();
+        Self {
+            annotations,
+            tracks,
+        }
+    }
+
+    /// Returns all annotations on scaffold `scaffold` overlapping 0-based
+    /// half-open range `start..end` sorted by start and end. Annotations
+    /// merely touching the range, i.e. ending at `start` or starting at
+    /// `end`, do not overlap it. Neither do empty annotations or ranges.
+    pub fn query(
+        &self,
+        scaffold: &str,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = &'a Annotation> + '_ {
+        let indices = match self.tracks.get(scaffold) {
+            Some(track) => track.query(self.annotations, start, end),
+            None => Vec::new(),
+        };
+        indices.into_iter().map(move |i| &self.annotations[i])
+    }
+
+    /// Returns all annotations on scaffold `scaffold` covering 0-based
+    /// position `pos` sorted by start and end.
+    pub fn query_point(
+        &self,
+        scaffold: &str,
+        pos: usize,
+    ) -> impl Iterator<Item = &'a Annotation> + '_ {
+        self.query(scaffold, pos, pos + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::AnnotationIndex;
+    use crate::data::{Annotation, Feature, Strand};
+    use crate::testing;
+    use proptest::prelude::*;
+
+    fn ranges<'a>(annotations: impl Iterator<Item = &'a Annotation>) -> Vec<(usize, usize)> {
+        annotations.map(|a| (a.start(), a.end())).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn query_matches_brute_force(
+            intervals in proptest::collection::vec((0usize..2, 0usize..100, 0usize..30), 0..60),
+            queries in proptest::collection::vec((0usize..2, 0usize..120, 0usize..30), 1..20),
+        ) {
+            let annotations: Vec<Annotation> = intervals
+                .iter()
+                .map(|&(s, start, len)| testing::annotation(["a", "b"][s], Feature::Exon, Strand::Positive, start, start + len))
+                .collect();
+            let index = AnnotationIndex::new(&annotations);
+
+            for &(s, start, len) in queries.iter() {
+                let scaffold = ["a", "b"][s];
+                let end = start + len;
+                let mut expected: Vec<(usize, usize)> = annotations
+                    .iter()
+                    .filter(|a| a.scaffold() == scaffold && a.start() < a.end())
+                    .filter(|a| start < end && a.start() < end && a.end() > start)
+                    .map(|a| (a.start(), a.end()))
+                    .collect();
+                expected.sort_unstable();
+                prop_assert_eq!(ranges(index.query(scaffold, start, end)), expected);
+
+                let mut expected: Vec<(usize, usize)> = annotations
+                    .iter()
+                    .filter(|a| a.scaffold() == scaffold && a.start() <= start && a.end() > start)
+                    .map(|a| (a.start(), a.end()))
+                    .collect();
+                expected.sort_unstable();
+                prop_assert_eq!(ranges(index.query_point(scaffold, start)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_boundaries() {
+        let annotations = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 0, 10),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 10, 20),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 15, 15),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 5, 25),
+            testing::annotation("t", Feature::Exon, Strand::Positive, 0, 100),
+        ];
+        let index = AnnotationIndex::new(&annotations);
+
+        assert_eq!(ranges(index.query("s", 10, 15)), [(5, 25), (10, 20)]);
+        // Annotations ending at the query start or starting at its end do
+        // not overlap it.
+        assert_eq!(ranges(index.query("s", 20, 30)), [(5, 25)]);
+        assert_eq!(ranges(index.query("s", 0, 5)), [(0, 10)]);
+        assert_eq!(ranges(index.query("s", 25, 30)), []);
+        assert_eq!(ranges(index.query("s", 12, 12)), []);
+        assert_eq!(ranges(index.query("s", 14, 16)), [(5, 25), (10, 20)]);
+        assert_eq!(ranges(index.query("u", 0, 100)), []);
+
+        assert_eq!(ranges(index.query_point("s", 9)), [(0, 10), (5, 25)]);
+        assert_eq!(ranges(index.query_point("s", 10)), [(5, 25), (10, 20)]);
+        assert_eq!(ranges(index.query_point("s", 15)), [(5, 25), (10, 20)]);
+        assert_eq!(ranges(index.query_point("s", 25)), []);
+        assert_eq!(ranges(index.query_point("t", 99)), [(0, 100)]);
+    }
+}
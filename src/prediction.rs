@@ -0,0 +1,451 @@
+use crate::data::{Annotation, Feature, Strand};
+use crate::ids::{assign_ids, IdGenerator};
+use anyhow::Result;
+use std::ops::Range;
+
+/// Resolution of overlapping per-base predictions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MergeRule {
+    /// Arithmetic mean of all overlapping predictions.
+    Average,
+    /// Maximum of all overlapping predictions.
+    Max,
+    /// Prediction of the chunk whose centre is closest to the position. Ties
+    /// are resolved in favour of the chunk given first.
+    KeepCentre,
+}
+
+/// Splits a scaffold of length `scaffold_len` into chunks of length `chunk`
+/// where consecutive chunks overlap by at least `overlap` symbols.
+///
+/// All chunks have length `chunk` except when the scaffold is shorter, in
+/// which case a single chunk covers the whole scaffold. The last chunk is
+/// aligned to the scaffold end and may therefore overlap its predecessor by
+/// more than `overlap`.
+pub fn chunk_plan(scaffold_len: usize, chunk: usize, overlap: usize) -> Result<Vec<Range<usize>>> {
+    ensure!(chunk > 0, "Chunk length must be positive.");
+    ensure!(
+        overlap < chunk,
+        "Overlap {} must be smaller than chunk length {}.",
+        overlap,
+        chunk
+    );
+
+    let mut plan = Vec::new();
+    if scaffold_len == 0 {
+        return Ok(plan);
+    }
+    if scaffold_len <= chunk {
+        plan.push(0..scaffold_len);
+        return Ok(plan);
+    }
+
+    let step = chunk - overlap;
+    let mut start = 0;
+    while start + chunk < scaffold_len {
+        plan.push(start..start + chunk);
+        start += step;
+    }
+    plan.push(scaffold_len - chunk..scaffold_len);
+
+    Ok(plan)
+}
+
+/// Merges per-base predictions of (possibly overlapping) chunks into a
+/// single per-base vector of length `scaffold_len`.
+///
+/// Every chunk must lie within the scaffold, have exactly one prediction per
+/// position and the chunks together must cover every position of the
+/// scaffold.
+pub fn stitch_predictions(
+    chunks: &[(Range<usize>, Vec<f32>)],
+    scaffold_len: usize,
+    merge: MergeRule,
+) -> Result<Vec<f32>> {
+    let mut merged = vec![0f32; scaffold_len];
+    let mut counts = vec![0u32; scaffold_len];
+    // Doubled distance of the position to the centre of the chunk currently
+    // providing the prediction.
+    let mut distances = vec![usize::MAX; scaffold_len];
+
+    for (i, (range, values)) in chunks.iter().enumerate() {
+        ensure!(
+            range.start < range.end && range.end <= scaffold_len,
+            "Chunk {} with range {}..{} does not fit on scaffold of length {}.",
+            i,
+            range.start,
+            range.end,
+            scaffold_len
+        );
+        ensure!(
+            values.len() == range.len(),
+            "Chunk {} with range {}..{} has {} predictions, expected {}.",
+            i,
+            range.start,
+            range.end,
+            values.len(),
+            range.len()
+        );
+
+        for (position, &value) in range.clone().zip(values.iter()) {
+            let count = counts[position];
+            counts[position] += 1;
+
+            merged[position] = match merge {
+                MergeRule::Average => merged[position] + value,
+                MergeRule::Max if count > 0 => merged[position].max(value),
+                MergeRule::Max => value,
+                MergeRule::KeepCentre => {
+                    let distance = (2 * position + 1).abs_diff(range.start + range.end);
+                    if distance < distances[position] {
+                        distances[position] = distance;
+                        value
+                    } else {
+                        merged[position]
+                    }
+                }
+            };
+        }
+    }
+
+    if let Some(gap) = counts.iter().position(|&count| count == 0) {
+        bail!("Position {} is not covered by any chunk.", gap);
+    }
+
+    if merge == MergeRule::Average {
+        for (value, &count) in merged.iter_mut().zip(counts.iter()) {
+            *value /= count as f32;
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Parameters of conversion of per-base probabilities to segments.
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentOptions {
+    /// A segment starts at a position with probability above this threshold.
+    pub high: f32,
+    /// A segment ends before the first position with probability below this
+    /// threshold.
+    pub low: f32,
+    /// Segments shorter than this (after gap joining) are discarded.
+    pub min_length: usize,
+    /// Segments separated by at most this many positions are joined.
+    pub max_gap: usize,
+}
+
+impl Default for SegmentOptions {
+    fn default() -> Self {
+        Self {
+            high: 0.5,
+            low: 0.5,
+            min_length: 1,
+            max_gap: 0,
+        }
+    }
+}
+
+/// Converts per-base probabilities to 0-based half-open segments
+/// `(start, end, mean probability)` using thresholding with hysteresis.
+///
+/// A segment is entered at a position with probability strictly above
+/// `high` and continues while probabilities are at least `low`. NaN values
+/// are treated as below both thresholds. Segments separated by at most
+/// `max_gap` positions are joined and afterwards segments shorter than
+/// `min_length` are dropped. The mean is computed over all non-NaN positions
+/// of the final segment, including joined gaps.
+pub fn segments_from_probabilities(
+    probs: &[f32],
+    opts: &SegmentOptions,
+) -> Vec<(usize, usize, f32)> {
+    let mut raw: Vec<(usize, usize)> = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, &p) in probs.iter().enumerate() {
+        start = match start {
+            Some(s) if p.is_nan() || p < opts.low => {
+                raw.push((s, i));
+                None
+            }
+            // NaN fails the comparison.
+            None if p > opts.high => Some(i),
+            other => other,
+        };
+    }
+    if let Some(s) = start {
+        raw.push((s, probs.len()));
+    }
+
+    let mut joined: Vec<(usize, usize)> = Vec::with_capacity(raw.len());
+    for (start, end) in raw {
+        match joined.last_mut() {
+            Some(last) if start - last.1 <= opts.max_gap => last.1 = end,
+            _ => joined.push((start, end)),
+        }
+    }
+
+    joined
+        .into_iter()
+        .filter(|(start, end)| end - start >= opts.min_length)
+        .map(|(start, end)| {
+            let (sum, count) = probs[start..end]
+                .iter()
+                .filter(|p| !p.is_nan())
+                .fold((0f64, 0usize), |(sum, count), &p| {
+                    (sum + p as f64, count + 1)
+                });
+            (start, end, (sum / count as f64) as f32)
+        })
+        .collect()
+}
+
+/// Converts per-base probabilities of a scaffold to annotations, see
+/// `segments_from_probabilities`. The mean probability of each segment is
+/// stored as the annotation score. If `ids` is given, each annotation gets a
+/// generated `ID` attribute.
+pub fn annotations_from_probabilities(
+    probs: &[f32],
+    opts: &SegmentOptions,
+    scaffold: &str,
+    source: &str,
+    feature: Feature,
+    strand: Strand,
+    ids: Option<&mut IdGenerator>,
+) -> Vec<Annotation> {
+    let annotations = segments_from_probabilities(probs, opts)
+        .into_iter()
+        .map(|(start, end, mean)| {
+            Annotation::new(
+                String::from(scaffold),
+                String::from(source),
+                feature.clone(),
+                Some(mean as f64),
+                strand,
+                None,
+                start,
+                end,
+                String::new(),
+            )
+        })
+        .collect();
+    assign_ids(ids, annotations)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{MergeRule, SegmentOptions};
+    use crate::data::{Feature, Strand};
+    use crate::ids::{IdGenerator, IdStrategy};
+    use std::ops::Range;
+
+    #[test]
+    fn test_chunk_plan() {
+        assert_eq!(super::chunk_plan(0, 4, 1).unwrap(), vec![]);
+        assert_eq!(super::chunk_plan(3, 4, 1).unwrap(), vec![0..3]);
+        assert_eq!(super::chunk_plan(4, 4, 1).unwrap(), vec![0..4]);
+        assert_eq!(super::chunk_plan(5, 4, 1).unwrap(), vec![0..4, 1..5]);
+        assert_eq!(super::chunk_plan(7, 4, 1).unwrap(), vec![0..4, 3..7]);
+        assert_eq!(super::chunk_plan(8, 4, 1).unwrap(), vec![0..4, 3..7, 4..8]);
+        assert_eq!(super::chunk_plan(8, 4, 0).unwrap(), vec![0..4, 4..8]);
+        assert_eq!(
+            super::chunk_plan(10, 4, 2).unwrap(),
+            vec![0..4, 2..6, 4..8, 6..10]
+        );
+
+        assert!(super::chunk_plan(10, 0, 0).is_err());
+        assert!(super::chunk_plan(10, 4, 4).is_err());
+
+        for length in 1..40 {
+            for chunk in 1..10 {
+                for overlap in 0..chunk {
+                    let plan = super::chunk_plan(length, chunk, overlap).unwrap();
+                    assert_eq!(plan.first().unwrap().start, 0);
+                    assert_eq!(plan.last().unwrap().end, length);
+                    for window in plan.windows(2) {
+                        assert!(window[1].start <= window[0].end - overlap);
+                        assert!(window[0].start < window[1].start);
+                    }
+                    for range in plan.iter() {
+                        assert_eq!(range.len(), chunk.min(length));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stitch_predictions() {
+        let chunks = vec![
+            (0..4, vec![1., 1., 1., 1.]),
+            (2..6, vec![3., 3., 3., 3.]),
+            (4..6, vec![0., 8.]),
+        ];
+
+        assert_eq!(
+            super::stitch_predictions(&chunks, 6, MergeRule::Average).unwrap(),
+            vec![1., 1., 2., 2., 1.5, 5.5]
+        );
+        assert_eq!(
+            super::stitch_predictions(&chunks, 6, MergeRule::Max).unwrap(),
+            vec![1., 1., 3., 3., 3., 8.]
+        );
+        // Position 4 is equally distant from centres of the last two chunks.
+        assert_eq!(
+            super::stitch_predictions(&chunks, 6, MergeRule::KeepCentre).unwrap(),
+            vec![1., 1., 1., 3., 3., 8.]
+        );
+    }
+
+    #[test]
+    fn test_stitch_single_chunk() {
+        let chunks = vec![(0..3, vec![0.1, 0.2, 0.3])];
+        for &rule in &[MergeRule::Average, MergeRule::Max, MergeRule::KeepCentre] {
+            assert_eq!(
+                super::stitch_predictions(&chunks, 3, rule).unwrap(),
+                vec![0.1, 0.2, 0.3]
+            );
+        }
+    }
+
+    #[test]
+    fn test_stitch_plan_round_trip() {
+        let truth: Vec<f32> = (0..23).map(|i| i as f32).collect();
+        for &(chunk, overlap) in &[(5, 0), (5, 2), (7, 3), (30, 4)] {
+            let chunks: Vec<(Range<usize>, Vec<f32>)> = super::chunk_plan(23, chunk, overlap)
+                .unwrap()
+                .into_iter()
+                .map(|range| (range.clone(), truth[range].to_vec()))
+                .collect();
+            for &rule in &[MergeRule::Average, MergeRule::Max, MergeRule::KeepCentre] {
+                assert_eq!(super::stitch_predictions(&chunks, 23, rule).unwrap(), truth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stitch_errors() {
+        let gap = vec![(0..2, vec![1., 1.]), (3..5, vec![1., 1.])];
+        assert_eq!(
+            format!(
+                "{}",
+                super::stitch_predictions(&gap, 5, MergeRule::Average).unwrap_err()
+            ),
+            "Position 2 is not covered by any chunk."
+        );
+
+        let uncovered_end = vec![(0..4, vec![1.; 4])];
+        assert!(super::stitch_predictions(&uncovered_end, 5, MergeRule::Max).is_err());
+
+        let mismatch = vec![(0..5, vec![1.; 4])];
+        assert_eq!(
+            format!(
+                "{}",
+                super::stitch_predictions(&mismatch, 5, MergeRule::Max).unwrap_err()
+            ),
+            "Chunk 0 with range 0..5 has 4 predictions, expected 5."
+        );
+
+        let outside = vec![(0..6, vec![1.; 6])];
+        assert!(super::stitch_predictions(&outside, 5, MergeRule::Max).is_err());
+    }
+
+    const TRACE: [f32; 16] = [
+        0.1, 0.7, 0.9, 0.5, 0.35, 0.2, 0.6, 0.8, 0.1, 0.9, 0.4, 0.45, 0.1, 0.3, 0.95, 0.9,
+    ];
+
+    #[test]
+    fn test_segments_from_probabilities() {
+        let cases = vec![
+            // Plain thresholding.
+            (0.5, 0.5, 1, 0, vec![(1, 4), (6, 8), (9, 10), (14, 16)]),
+            // Hysteresis keeps segments open above the low threshold.
+            (0.5, 0.3, 1, 0, vec![(1, 5), (6, 8), (9, 12), (14, 16)]),
+            // The low threshold does not open segments.
+            (0.85, 0.3, 1, 0, vec![(2, 5), (9, 12), (14, 16)]),
+            // Minimum length.
+            (0.5, 0.5, 2, 0, vec![(1, 4), (6, 8), (14, 16)]),
+            // Gap joining happens before the minimum length filter.
+            (0.5, 0.5, 4, 1, vec![(6, 10)]),
+            (0.5, 0.5, 1, 2, vec![(1, 10), (14, 16)]),
+            // Nothing above threshold.
+            (0.96, 0.5, 1, 0, vec![]),
+        ];
+
+        for (high, low, min_length, max_gap, expected) in cases {
+            let opts = SegmentOptions {
+                high,
+                low,
+                min_length,
+                max_gap,
+            };
+            let segments: Vec<(usize, usize)> = super::segments_from_probabilities(&TRACE, &opts)
+                .into_iter()
+                .map(|(start, end, _)| (start, end))
+                .collect();
+            assert_eq!(
+                segments, expected,
+                "high {} low {} min length {} max gap {}",
+                high, low, min_length, max_gap
+            );
+        }
+    }
+
+    #[test]
+    fn test_segment_scores_and_nan() {
+        let probs = [0.6, 0.8, f32::NAN, 0.9, 0.7, 0.1];
+        let opts = SegmentOptions::default();
+        let segments = super::segments_from_probabilities(&probs, &opts);
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[0].0, segments[0].1), (0, 2));
+        assert!((segments[0].2 - 0.7).abs() < 1e-6);
+        assert_eq!((segments[1].0, segments[1].1), (3, 5));
+
+        let opts = SegmentOptions {
+            max_gap: 1,
+            ..SegmentOptions::default()
+        };
+        let segments = super::segments_from_probabilities(&probs, &opts);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].0, segments[0].1), (0, 5));
+        assert!((segments[0].2 - 0.75).abs() < 1e-6);
+
+        assert!(super::segments_from_probabilities(&[], &opts).is_empty());
+        assert!(super::segments_from_probabilities(&[f32::NAN; 3], &opts).is_empty());
+    }
+
+    #[test]
+    fn test_annotations_from_probabilities() {
+        let annotations = super::annotations_from_probabilities(
+            &[0.1, 0.9, 0.8, 0.2],
+            &SegmentOptions::default(),
+            "scaffold_1",
+            "model",
+            Feature::Exon,
+            Strand::Negative,
+            None,
+        );
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].scaffold(), "scaffold_1");
+        assert_eq!(annotations[0].source(), "model");
+        assert_eq!(annotations[0].strand(), Strand::Negative);
+        assert_eq!((annotations[0].start(), annotations[0].end()), (1, 3));
+        assert!((annotations[0].score().unwrap() - 0.85).abs() < 1e-6);
+        assert_eq!(annotations[0].attributes(), "");
+
+        let mut ids = IdGenerator::new("pred", IdStrategy::Sequential);
+        let annotations = super::annotations_from_probabilities(
+            &[0.9, 0.1, 0.9, 0.9],
+            &SegmentOptions::default(),
+            "scaffold_1",
+            "model",
+            Feature::Exon,
+            Strand::Negative,
+            Some(&mut ids),
+        );
+        assert_eq!(annotations[0].attributes(), "ID=pred.exon.1");
+        assert_eq!(annotations[1].attributes(), "ID=pred.exon.2");
+        assert!((annotations[1].score().unwrap() - 0.9).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,105 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+/// Default minimum number of bytes read between two progress reports.
+pub const DEFAULT_PROGRESS_INTERVAL: u64 = 1 << 20;
+
+/// Progress of loading a file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress {
+    /// Number of bytes read from the file so far. For compressed files
+    /// these are compressed bytes.
+    pub bytes_read: u64,
+    /// File size, if known.
+    pub total_bytes: Option<u64>,
+    /// Number of records parsed so far.
+    pub records: usize,
+}
+
+/// Options of file loaders reporting their progress.
+pub struct LoadOptions {
+    /// Called whenever at least `interval` bytes were read since the last
+    /// call and once more after the whole file was loaded.
+    pub progress: Option<Box<dyn FnMut(Progress)>>,
+    pub interval: u64,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            progress: None,
+            interval: DEFAULT_PROGRESS_INTERVAL,
+        }
+    }
+}
+
+/// Reader counting bytes read through it.
+pub(crate) struct CountingReader<R: Read> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub(crate) fn new(inner: R, count: Rc<Cell<u64>>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let num_bytes = self.inner.read(buf)?;
+        self.count.set(self.count.get() + num_bytes as u64);
+        Ok(num_bytes)
+    }
+}
+
+/// Reports progress of a loader at a bounded frequency.
+pub(crate) struct ProgressTracker {
+    options: LoadOptions,
+    total_bytes: Option<u64>,
+    bytes_read: Rc<Cell<u64>>,
+    records: usize,
+    reported: u64,
+}
+
+impl ProgressTracker {
+    /// Creates a tracker of reading `file`. The returned counter has to be
+    /// updated with the number of bytes read, e.g. via `CountingReader`.
+    pub(crate) fn new(options: LoadOptions, file: &File) -> (Self, Rc<Cell<u64>>) {
+        let bytes_read = Rc::new(Cell::new(0));
+        let tracker = Self {
+            options,
+            total_bytes: file.metadata().ok().map(|m| m.len()),
+            bytes_read: Rc::clone(&bytes_read),
+            records: 0,
+            reported: 0,
+        };
+        (tracker, bytes_read)
+    }
+
+    /// Registers a parsed record.
+    pub(crate) fn record(&mut self) {
+        self.records += 1;
+        if self.bytes_read.get() - self.reported >= self.options.interval {
+            self.report();
+        }
+    }
+
+    /// Reports the final progress.
+    pub(crate) fn finish(&mut self) {
+        self.report();
+    }
+
+    fn report(&mut self) {
+        self.reported = self.bytes_read.get();
+        if let Some(ref mut callback) = self.options.progress {
+            callback(Progress {
+                bytes_read: self.reported,
+                total_bytes: self.total_bytes,
+                records: self.records,
+            });
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Range of positions on a scaffold.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Region {
+    scaffold: String,
+    start: usize,
+    end: usize,
+}
+
+impl Region {
+    /// Creates a region spanning 0-based half-open `[start, end)`.
+    pub fn new(scaffold: &str, start: usize, end: usize) -> Self {
+        Self {
+            scaffold: String::from(scaffold),
+            start,
+            end,
+        }
+    }
+
+    pub fn scaffold(&self) -> &str {
+        self.scaffold.as_str()
+    }
+
+    /// Inclusive 0-based start.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Exclusive 0-based end.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Parses regions written as `scaffold:start-end` with 1-based inclusive
+/// coordinates, e.g. `scaffold_1:2,000-2,300`. Thousands separators are
+/// ignored.
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (scaffold, range) = text
+            .rsplit_once(':')
+            .with_context(|| format!("Region {} is not in scaffold:start-end format.", text))?;
+        let (start, end) = range
+            .split_once('-')
+            .with_context(|| format!("Region {} is not in scaffold:start-end format.", text))?;
+        ensure!(
+            !scaffold.is_empty(),
+            "Region {} has empty scaffold name.",
+            text
+        );
+
+        let parse = |value: &str| {
+            value
+                .replace(',', "")
+                .parse::<usize>()
+                .with_context(|| format!("Invalid position {} in region {}.", value, text))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        ensure!(
+            start > 0 && start <= end,
+            "Invalid range {}-{} in region {}.",
+            start,
+            end,
+            text
+        );
+
+        Ok(Self::new(scaffold, start - 1, end))
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.scaffold, self.start + 1, self.end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Region;
+
+    #[test]
+    fn test_parse_region() {
+        let region: Region = "scaffold_1:2,000-2,300".parse().unwrap();
+        assert_eq!(region, Region::new("scaffold_1", 1999, 2300));
+        assert_eq!(region.len(), 301);
+        assert_eq!(region.to_string(), "scaffold_1:2000-2300");
+
+        let region: Region = "HLA:A:5-5".parse().unwrap();
+        assert_eq!(region.scaffold(), "HLA:A");
+        assert_eq!((region.start(), region.end()), (4, 5));
+
+        for invalid in ["scaffold_1", "s:1", ":1-2", "s:0-5", "s:5-4", "s:a-5"] {
+            assert!(invalid.parse::<Region>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::data::{Annotation, Feature, Strand};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+use std::path::Path;
+
+/// Minimum number of whitespace separated columns of a RepeatMasker hit.
+const RM_NUM_COLUMNS: usize = 15;
+
+/// Load repeat annotations from a RepeatMasker `.out` file.
+///
+/// Each hit is converted to a `Feature::Repeat` annotation with source
+/// `RepeatMasker`, the Smith-Waterman score as score and the repeat name,
+/// class, family (if any) and divergence in `Name`, `repeat_class`,
+/// `repeat_family` and `divergence` attributes. Columns may be aligned by
+/// any amount of white space; header lines, blank lines and the trailing
+/// `*` marking hits overlapping a higher scoring one are accepted.
+pub fn load_repeatmasker_out(path: &Path) -> Result<Vec<Annotation>> {
+    let reader = {
+        let file =
+            File::open(path).with_context(|| format!("Could not open file {}.", path.display()))?;
+        BufReader::new(file)
+    };
+
+    let mut annotations = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("Could not read file {}.", path.display()))?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            None | Some(&"SW") | Some(&"score") => continue,
+            _ => (),
+        }
+
+        let annotation = parse_repeatmasker_line(&tokens).with_context(|| {
+            format!("Failed to parse line {} of file {}.", i + 1, path.display())
+        })?;
+        annotations.push(annotation.with_line(Some(i + 1)));
+    }
+
+    Ok(annotations)
+}
+
+fn parse_repeatmasker_line(tokens: &[&str]) -> Result<Annotation> {
+    ensure!(
+        tokens.len() >= RM_NUM_COLUMNS,
+        "Not enough columns. Expected at least {} got {}.",
+        RM_NUM_COLUMNS,
+        tokens.len()
+    );
+
+    let score = tokens[0]
+        .parse::<f64>()
+        .with_context(|| format!("Score has to be a number. Got: {}", tokens[0]))?;
+    tokens[1]
+        .parse::<f64>()
+        .with_context(|| format!("Divergence has to be a number. Got: {}", tokens[1]))?;
+
+    let start = tokens[5]
+        .parse::<usize>()
+        .with_context(|| format!("Hit begin has to be a positive integer. Got: {}", tokens[5]))?;
+    let end = tokens[6]
+        .parse::<usize>()
+        .with_context(|| format!("Hit end has to be a positive integer. Got: {}", tokens[6]))?;
+    ensure!(
+        start > 0,
+        "Hit begin is 0 but must be bigger or equal to 1."
+    );
+    // RepeatMasker coordinates are 1-based inclusive.
+    let start = start - 1;
+    ensure!(
+        start < end,
+        "Hit begin is greater than hit end. {} > {}",
+        start + 1,
+        end
+    );
+
+    let strand = match tokens[8] {
+        "+" => Strand::Positive,
+        "C" => Strand::Negative,
+        unrecognized => bail!(
+            "Invalid strand, only + and C are valid. Got: {}",
+            unrecognized
+        ),
+    };
+
+    let mut attributes = format!("Name={}", tokens[9]);
+    let (class, family) = match tokens[10].split_once('/') {
+        Some((class, family)) => (class, Some(family)),
+        None => (tokens[10], None),
+    };
+    attributes.push_str(&format!(";repeat_class={}", class));
+    if let Some(family) = family {
+        attributes.push_str(&format!(";repeat_family={}", family));
+    }
+    attributes.push_str(&format!(";divergence={}", tokens[1]));
+
+    Ok(Annotation::new(
+        String::from(tokens[4]),
+        String::from("RepeatMasker"),
+        Feature::Repeat,
+        Some(score),
+        strand,
+        None,
+        start,
+        end,
+        attributes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::data::{Feature, Strand};
+    use crate::interval::IntervalSet;
+    use std::path::Path;
+
+    #[test]
+    fn test_load_repeatmasker_out() {
+        let path = Path::new("./tests/repeatmasker.out");
+        let annotations = super::load_repeatmasker_out(path).unwrap();
+        assert_eq!(annotations.len(), 4);
+
+        let first = &annotations[0];
+        assert_eq!(first.scaffold(), "scaffold_1");
+        assert_eq!(first.source(), "RepeatMasker");
+        assert_eq!(first.feature(), &Feature::Repeat);
+        assert_eq!(first.score(), Some(463.));
+        assert_eq!(first.strand(), Strand::Positive);
+        assert_eq!((first.start(), first.end()), (10, 478));
+        assert_eq!(
+            first.attributes(),
+            "Name=(CCCTAA)n;repeat_class=Simple_repeat;divergence=1.3"
+        );
+
+        let second = &annotations[1];
+        assert_eq!(second.strand(), Strand::Negative);
+        assert_eq!((second.start(), second.end()), (468, 1447));
+        assert_eq!(
+            second.attributes(),
+            "Name=TAR1;repeat_class=Satellite;repeat_family=telo;divergence=11.4"
+        );
+
+        let mask = IntervalSet::from_annotations(&annotations, "scaffold_1");
+        assert_eq!(mask.ranges().len(), 1);
+        assert_eq!(mask.ranges()[0], 10..1447);
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        let tokens: Vec<&str> = "463 1.3 0.6 1.7 s 11 478 (1022) - X Simple_repeat 1 463 (0) 1"
+            .split(' ')
+            .collect();
+        let error = super::parse_repeatmasker_line(&tokens).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "Invalid strand, only + and C are valid. Got: -"
+        );
+        assert!(super::parse_repeatmasker_line(&tokens[..10]).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+use crate::cds::{synthesize_codon_annotations, usize_to_phase};
+use crate::data::{Annotation, Feature, Scaffold, Strand, Symbol};
+use crate::ids::{IdGenerator, IdStrategy};
+
+/// Parameters of a simulated genome. All lengths are in bases and all ranges
+/// are inclusive.
+#[derive(Clone, Debug)]
+pub struct SimulationOptions {
+    /// Seed of the random number generator. The same options always produce
+    /// the same genome.
+    pub seed: u64,
+    pub scaffolds: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Probability of `G` or `C` at each non-gap position.
+    pub gc_content: f64,
+    /// Probability of an N gap starting at each position.
+    pub gap_rate: f64,
+    pub max_gap_length: usize,
+    /// Expected number of genes per 10 kb.
+    pub gene_density: f64,
+    pub max_exons: usize,
+    pub min_exon_length: usize,
+    pub max_exon_length: usize,
+    pub min_intron_length: usize,
+    pub max_intron_length: usize,
+    /// Strategy of generating gene and codon IDs, which are prefixed with
+    /// `sim`.
+    pub ids: IdStrategy,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scaffolds: 3,
+            min_length: 5_000,
+            max_length: 20_000,
+            gc_content: 0.4,
+            gap_rate: 0.000_5,
+            max_gap_length: 50,
+            gene_density: 2.,
+            max_exons: 5,
+            min_exon_length: 30,
+            max_exon_length: 300,
+            min_intron_length: 40,
+            max_intron_length: 400,
+            ids: IdStrategy::Sequential,
+        }
+    }
+}
+
+/// A planted single transcript gene.
+#[derive(Clone, Debug)]
+pub struct SimulatedGene {
+    exons: Vec<Annotation>,
+    cds: Vec<Annotation>,
+}
+
+impl SimulatedGene {
+    /// Exons in transcription order.
+    pub fn exons(&self) -> &[Annotation] {
+        &self.exons
+    }
+
+    /// CDS segments in transcription order. The CDS spans all exons and
+    /// includes the stop codon.
+    pub fn cds(&self) -> &[Annotation] {
+        &self.cds
+    }
+}
+
+pub struct SimulatedGenome {
+    scaffolds: Vec<Scaffold>,
+    genes: Vec<SimulatedGene>,
+    codons: Vec<Annotation>,
+}
+
+impl SimulatedGenome {
+    pub fn scaffolds(&self) -> &[Scaffold] {
+        &self.scaffolds
+    }
+
+    pub fn genes(&self) -> &[SimulatedGene] {
+        &self.genes
+    }
+
+    /// All annotations: exons, CDS segments and start and stop codons.
+    pub fn annotations(&self) -> Vec<Annotation> {
+        self.genes
+            .iter()
+            .flat_map(|gene| gene.exons.iter().chain(gene.cds.iter()))
+            .chain(self.codons.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// SplitMix64 generator, small and good enough for synthetic data.
+pub(crate) struct Random(u64);
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[0, bound)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Uniform integer in `[min, max]`.
+    pub(crate) fn range(&mut self, min: usize, max: usize) -> usize {
+        if max <= min {
+            return min;
+        }
+        min + self.below(max - min + 1)
+    }
+
+    fn base(&mut self, gc_content: f64) -> Symbol {
+        let gc = self.next_f64() < gc_content;
+        match (gc, self.next_u64() & 1 == 0) {
+            (true, true) => Symbol::Guanine,
+            (true, false) => Symbol::Cytosine,
+            (false, true) => Symbol::Adenine,
+            (false, false) => Symbol::Thymine,
+        }
+    }
+
+    fn sense_codon(&mut self, gc_content: f64) -> [Symbol; 3] {
+        loop {
+            let codon = [
+                self.base(gc_content),
+                self.base(gc_content),
+                self.base(gc_content),
+            ];
+            if !is_stop(codon) {
+                break codon;
+            }
+        }
+    }
+}
+
+fn is_stop(codon: [Symbol; 3]) -> bool {
+    matches!(
+        codon,
+        [Symbol::Thymine, Symbol::Adenine, Symbol::Adenine]
+            | [Symbol::Thymine, Symbol::Adenine, Symbol::Guanine]
+            | [Symbol::Thymine, Symbol::Guanine, Symbol::Adenine]
+    )
+}
+
+/// Generates a random genome with planted multi-exon genes.
+///
+/// Scaffolds are named `sim1`, `sim2`, … and contain random sequence of the
+/// configured GC content interrupted by N gaps. Genes are planted over the
+/// background (and over any gaps) on a random strand with spacing drawn so
+/// that the expected density is met; a gene not fitting before the scaffold
+/// end is not planted. Each gene is a coding transcript without UTRs: it
+/// starts with `ATG`, ends with a stop codon, has no in-frame stop codon and
+/// all its introns are GT-AG. Exons and CDS segments carry a `Parent`
+/// attribute with the generated gene ID (e.g. `sim.gene.1`), start and stop
+/// codon annotations are derived from the CDS and get generated IDs too.
+pub fn simulate(options: &SimulationOptions) -> SimulatedGenome {
+    let mut random = Random::new(options.seed);
+    let mut ids = IdGenerator::new("sim", options.ids);
+    let mut scaffolds = Vec::with_capacity(options.scaffolds);
+    let mut genes = Vec::new();
+
+    for i in 0..options.scaffolds {
+        let name = format!("sim{}", i + 1);
+        let len = random.range(options.min_length, options.max_length);
+
+        let mut sequence = Vec::with_capacity(len);
+        while sequence.len() < len {
+            if random.next_f64() < options.gap_rate {
+                let gap = random.range(1, options.max_gap_length);
+                sequence.extend((0..gap.min(len - sequence.len())).map(|_| Symbol::Other));
+            } else {
+                sequence.push(random.base(options.gc_content));
+            }
+        }
+
+        if options.gene_density > 0. {
+            let mean_spacing = (10_000. / options.gene_density) as usize;
+            let mut position = 0;
+            loop {
+                position += random.range(0, 2 * mean_spacing);
+                match plant_gene(
+                    &mut random,
+                    options,
+                    &name,
+                    &mut ids,
+                    &mut sequence,
+                    position,
+                ) {
+                    Some((gene, end)) => {
+                        genes.push(gene);
+                        position = end;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        scaffolds.push(Scaffold::new(name, sequence));
+    }
+
+    let transcripts: Vec<Vec<Annotation>> = genes.iter().map(|gene| gene.cds.clone()).collect();
+    let codons =
+        synthesize_codon_annotations(&scaffolds, &transcripts, &[], Some(&mut ids)).annotations;
+
+    SimulatedGenome {
+        scaffolds,
+        genes,
+        codons,
+    }
+}
+
+/// Plants a gene starting at `position` and returns it together with its end
+/// position, or `None` if it does not fit.
+fn plant_gene(
+    random: &mut Random,
+    options: &SimulationOptions,
+    scaffold: &str,
+    ids: &mut IdGenerator,
+    sequence: &mut [Symbol],
+    position: usize,
+) -> Option<(SimulatedGene, usize)> {
+    let exon_count = random.range(1, options.max_exons.max(1));
+    let mut exon_lengths: Vec<usize> = (0..exon_count)
+        .map(|_| random.range(options.min_exon_length, options.max_exon_length))
+        .collect();
+    let coding_length: usize = exon_lengths.iter().sum();
+    // Room for at least a start and a stop codon and a whole number of
+    // codons.
+    let padded = coding_length.max(6).div_ceil(3) * 3;
+    *exon_lengths.last_mut().unwrap() += padded - coding_length;
+    let coding_length = padded;
+
+    let mut coding = vec![Symbol::Adenine, Symbol::Thymine, Symbol::Guanine];
+    for _ in 0..coding_length / 3 - 2 {
+        coding.extend_from_slice(&random.sense_codon(options.gc_content));
+    }
+    coding.extend_from_slice(match random.range(0, 2) {
+        0 => &[Symbol::Thymine, Symbol::Adenine, Symbol::Adenine],
+        1 => &[Symbol::Thymine, Symbol::Adenine, Symbol::Guanine],
+        _ => &[Symbol::Thymine, Symbol::Guanine, Symbol::Adenine],
+    });
+
+    // Gene sequence in transcription order and exon offsets within it.
+    let mut gene = Vec::new();
+    let mut offsets = Vec::with_capacity(exon_count);
+    let mut coded = 0;
+    for (i, &exon_length) in exon_lengths.iter().enumerate() {
+        if i > 0 {
+            let intron_length = random.range(
+                options.min_intron_length.max(4),
+                options.max_intron_length.max(4),
+            );
+            gene.extend_from_slice(&[Symbol::Guanine, Symbol::Thymine]);
+            gene.extend((0..intron_length - 4).map(|_| random.base(options.gc_content)));
+            gene.extend_from_slice(&[Symbol::Adenine, Symbol::Guanine]);
+        }
+        offsets.push((gene.len(), gene.len() + exon_length, coded));
+        gene.extend_from_slice(&coding[coded..coded + exon_length]);
+        coded += exon_length;
+    }
+
+    let end = position + gene.len();
+    if end > sequence.len() {
+        return None;
+    }
+
+    let strand = if random.next_u64() & 1 == 0 {
+        Strand::Positive
+    } else {
+        Strand::Negative
+    };
+    match strand {
+        Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+            sequence[position..end].copy_from_slice(&gene)
+        }
+        Strand::Negative => {
+            for (target, &symbol) in sequence[position..end].iter_mut().zip(gene.iter().rev()) {
+                *target = symbol.complement();
+            }
+        }
+    }
+
+    let annotation = |feature, phase, start, end, attributes| {
+        let (start, end) = match strand {
+            Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+                (position + start, position + end)
+            }
+            Strand::Negative => (position + gene.len() - end, position + gene.len() - start),
+        };
+        Annotation::new(
+            String::from(scaffold),
+            String::from("simulation"),
+            feature,
+            None,
+            strand,
+            phase,
+            start,
+            end,
+            attributes,
+        )
+    };
+
+    let gene_id = ids.generate(&annotation(
+        Feature::Gene,
+        None,
+        0,
+        gene.len(),
+        String::new(),
+    ));
+    let parent = format!("Parent={}", gene_id);
+
+    let mut exons = Vec::with_capacity(exon_count);
+    let mut cds = Vec::with_capacity(exon_count);
+    for &(start, end, coded) in offsets.iter() {
+        exons.push(annotation(Feature::Exon, None, start, end, parent.clone()));
+        let phase = usize_to_phase((3 - coded % 3) % 3);
+        cds.push(annotation(
+            Feature::CDS,
+            Some(phase),
+            start,
+            end,
+            parent.clone(),
+        ));
+    }
+
+    Some((SimulatedGene { exons, cds }, end))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::SimulationOptions;
+    use crate::cds::validate_cds_segments;
+    use crate::data::{Feature, Symbol};
+    use crate::gff::GffWriter;
+    use crate::splice::check_splice_sites;
+
+    #[test]
+    fn test_simulate() {
+        let options = SimulationOptions {
+            seed: 7,
+            ..SimulationOptions::default()
+        };
+        let genome = super::simulate(&options);
+
+        assert_eq!(genome.scaffolds().len(), 3);
+        assert_eq!(genome.scaffolds()[1].name(), "sim2");
+        for scaffold in genome.scaffolds() {
+            let len = scaffold.sequence().len();
+            assert!((options.min_length..=options.max_length).contains(&len));
+        }
+        assert!(genome.genes().len() > 3);
+
+        let bases: Vec<Symbol> = genome
+            .scaffolds()
+            .iter()
+            .flat_map(|s| s.sequence().into_owned())
+            .collect();
+        let gc = bases
+            .iter()
+            .filter(|&&s| s == Symbol::Guanine || s == Symbol::Cytosine)
+            .count();
+        let gc_content = gc as f64 / bases.len() as f64;
+        assert!((0.35..0.45).contains(&gc_content));
+        assert!(bases.contains(&Symbol::Other));
+
+        for gene in genome.genes() {
+            assert!(validate_cds_segments(gene.cds(), gene.exons()).is_empty());
+        }
+
+        let transcripts: Vec<_> = genome.genes().iter().map(|g| g.exons().to_vec()).collect();
+        let report = check_splice_sites(genome.scaffolds(), &transcripts);
+        assert!(report.skipped().is_empty());
+        assert_eq!(report.overall().total(), report.overall().canonical);
+
+        let annotations = genome.annotations();
+        let starts = annotations
+            .iter()
+            .filter(|a| *a.feature() == Feature::StartCodon)
+            .count();
+        assert!(starts >= genome.genes().len());
+        assert_eq!(
+            genome.genes()[0].exons()[0].attributes(),
+            "Parent=sim.gene.1"
+        );
+        let codon = annotations
+            .iter()
+            .find(|a| *a.feature() == Feature::StopCodon)
+            .unwrap();
+        assert!(codon
+            .attributes()
+            .starts_with("ID=sim.stop_codon.1;Parent=sim.gene."));
+
+        let mut output = Vec::new();
+        GffWriter::new(&mut output)
+            .with_regions(
+                genome
+                    .scaffolds()
+                    .iter()
+                    .map(|s| (String::from(s.name()), s.sequence().len())),
+            )
+            .write(&annotations)
+            .unwrap();
+
+        // Reproducible.
+        let again = super::simulate(&options);
+        for (a, b) in again.scaffolds().iter().zip(genome.scaffolds()) {
+            assert_eq!(a.sequence(), b.sequence());
+        }
+        assert_eq!(again.annotations().len(), annotations.len());
+    }
+}
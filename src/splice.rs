@@ -0,0 +1,454 @@
+use crate::data::{reverse_complement_in_place, Annotation, Scaffold, Strand, Symbol};
+use std::collections::HashMap;
+
+/// Classification of a splice junction by the dinucleotides at the intron
+/// ends, read in the direction of transcription.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JunctionClass {
+    /// GT…AG
+    Canonical,
+    /// GC…AG or AT…AC
+    Minor,
+    NonCanonical,
+}
+
+/// Junction counts of a single transcript or of a whole annotation set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct JunctionCounts {
+    pub canonical: usize,
+    pub minor: usize,
+    pub non_canonical: usize,
+}
+
+impl JunctionCounts {
+    fn add(&mut self, class: JunctionClass) {
+        match class {
+            JunctionClass::Canonical => self.canonical += 1,
+            JunctionClass::Minor => self.minor += 1,
+            JunctionClass::NonCanonical => self.non_canonical += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.canonical + self.minor + self.non_canonical
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SpliceReport {
+    transcripts: Vec<JunctionCounts>,
+    skipped: Vec<usize>,
+}
+
+impl SpliceReport {
+    /// Junction counts of each transcript, in input order. Skipped
+    /// transcripts have all counts zero.
+    pub fn transcripts(&self) -> &[JunctionCounts] {
+        &self.transcripts
+    }
+
+    /// Indices of transcripts referencing a missing scaffold or extending
+    /// past the scaffold end.
+    pub fn skipped(&self) -> &[usize] {
+        &self.skipped
+    }
+
+    /// Junction counts over all transcripts.
+    pub fn overall(&self) -> JunctionCounts {
+        self.transcripts
+            .iter()
+            .fold(JunctionCounts::default(), |acc, counts| JunctionCounts {
+                canonical: acc.canonical + counts.canonical,
+                minor: acc.minor + counts.minor,
+                non_canonical: acc.non_canonical + counts.non_canonical,
+            })
+    }
+
+    /// Up to `n` transcripts with the most non-canonical junctions as pairs
+    /// of transcript index and non-canonical junction count. Transcripts
+    /// without non-canonical junctions are never listed.
+    pub fn worst_offenders(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut offenders: Vec<(usize, usize)> = self
+            .transcripts
+            .iter()
+            .enumerate()
+            .filter(|(_, counts)| counts.non_canonical > 0)
+            .map(|(i, counts)| (i, counts.non_canonical))
+            .collect();
+        offenders.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        offenders.truncate(n);
+        offenders
+    }
+}
+
+/// Classifies a junction from the first two and the last two bases of the
+/// intron in transcription direction.
+pub fn classify_junction(donor: [Symbol; 2], acceptor: [Symbol; 2]) -> JunctionClass {
+    use Symbol::{Adenine as A, Cytosine as C, Guanine as G, Thymine as T};
+
+    match (donor, acceptor) {
+        ([G, T], [A, G]) => JunctionClass::Canonical,
+        ([G, C], [A, G]) | ([A, T], [A, C]) => JunctionClass::Minor,
+        _ => JunctionClass::NonCanonical,
+    }
+}
+
+/// Checks splice junctions of transcripts, each given as the list of its
+/// exons.
+///
+/// Introns are the gaps between consecutive exons sorted by coordinates;
+/// abutting or overlapping exons do not form an intron. Introns shorter
+/// than four bases are non-canonical. The strand of a transcript is taken
+/// from its first exon. See `Genome::check_splice_sites` for transcripts
+/// taken from gene models.
+pub fn check_splice_sites(scaffolds: &[Scaffold], transcripts: &[Vec<Annotation>]) -> SpliceReport {
+    let scaffolds: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+    let mut report = SpliceReport::default();
+
+    for (i, exons) in transcripts.iter().enumerate() {
+        let mut counts = JunctionCounts::default();
+
+        let scaffold = exons.first().and_then(|a| scaffolds.get(a.scaffold()));
+        let scaffold = match scaffold {
+            Some(scaffold) if exons.iter().all(|a| a.end() <= scaffold.len()) => scaffold,
+            _ => {
+                report.transcripts.push(counts);
+                report.skipped.push(i);
+                continue;
+            }
+        };
+
+        let strand = exons[0].strand();
+        let mut sorted: Vec<&Annotation> = exons.iter().collect();
+        sorted.sort_by_key(|a| (a.start(), a.end()));
+
+        for pair in sorted.windows(2) {
+            let (start, end) = (pair[0].end(), pair[1].start());
+            if start >= end {
+                continue;
+            }
+            if end - start < 4 {
+                counts.add(JunctionClass::NonCanonical);
+                continue;
+            }
+
+            let (donor, acceptor) = match strand {
+                Strand::Positive | Strand::Unstranded | Strand::Unknown => (
+                    [scaffold.symbol(start), scaffold.symbol(start + 1)],
+                    [scaffold.symbol(end - 2), scaffold.symbol(end - 1)],
+                ),
+                Strand::Negative => (
+                    [
+                        scaffold.symbol(end - 1).complement(),
+                        scaffold.symbol(end - 2).complement(),
+                    ],
+                    [
+                        scaffold.symbol(start + 1).complement(),
+                        scaffold.symbol(start).complement(),
+                    ],
+                ),
+            };
+            counts.add(classify_junction(donor, acceptor));
+        }
+
+        report.transcripts.push(counts);
+    }
+
+    report
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpliceSiteKind {
+    /// Exon to intron boundary in transcription direction, i.e. the 5' end
+    /// of an intron.
+    Donor,
+    /// Intron to exon boundary in transcription direction, i.e. the 3' end
+    /// of an intron.
+    Acceptor,
+}
+
+/// Exon and intron boundary of a transcript.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpliceSite {
+    scaffold: String,
+    position: usize,
+    strand: Strand,
+    kind: SpliceSiteKind,
+}
+
+impl SpliceSite {
+    pub fn scaffold(&self) -> &str {
+        self.scaffold.as_str()
+    }
+
+    /// 0-based scaffold position of the base right of the boundary, i.e.
+    /// the boundary lies between `position - 1` and `position`.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    pub fn kind(&self) -> SpliceSiteKind {
+        self.kind
+    }
+}
+
+/// Returns splice sites of a transcript given as the list of its exons,
+/// sorted by position. Introns are determined as in `check_splice_sites`,
+/// so single-exon transcripts have no sites.
+///
+/// On the positive strand, donors lie at exon ends and acceptors at exon
+/// starts. On the negative strand it is the other way round. Unstranded
+/// transcripts are read on the positive strand.
+pub fn splice_sites(transcript_exons: &[Annotation]) -> Vec<SpliceSite> {
+    let strand = match transcript_exons.first() {
+        Some(exon) => exon.strand(),
+        None => return Vec::new(),
+    };
+    let mut sorted: Vec<&Annotation> = transcript_exons.iter().collect();
+    sorted.sort_by_key(|a| (a.start(), a.end()));
+
+    let (left, right) = match strand {
+        Strand::Negative => (SpliceSiteKind::Acceptor, SpliceSiteKind::Donor),
+        _ => (SpliceSiteKind::Donor, SpliceSiteKind::Acceptor),
+    };
+    let mut sites = Vec::new();
+    for pair in sorted.windows(2) {
+        let (start, end) = (pair[0].end(), pair[1].start());
+        if start >= end {
+            continue;
+        }
+        for &(position, kind) in [(start, left), (end, right)].iter() {
+            sites.push(SpliceSite {
+                scaffold: String::from(pair[0].scaffold()),
+                position,
+                strand,
+                kind,
+            });
+        }
+    }
+    sites
+}
+
+/// Extracts windows of `2 * flank` symbols centered on splice sites of
+/// scaffold `scaffold`, oriented in the direction of transcription, i.e.
+/// reverse complemented for negative strand sites. The first `flank`
+/// symbols precede the boundary, so canonical donors have `GT` at
+/// positions `flank` and `flank + 1` and canonical acceptors have `AG` at
+/// positions `flank - 2` and `flank - 1`. Positions outside of the
+/// scaffold are `Symbol::Other`.
+///
+/// All sites are assumed to be on the scaffold.
+pub fn splice_site_windows(
+    scaffold: &Scaffold,
+    sites: &[SpliceSite],
+    flank: usize,
+) -> Vec<(Vec<Symbol>, SpliceSiteKind)> {
+    sites
+        .iter()
+        .map(|site| {
+            let mut window: Vec<Symbol> = (0..2 * flank)
+                .map(|i| {
+                    (site.position + i)
+                        .checked_sub(flank)
+                        .filter(|&position| position < scaffold.len())
+                        .map_or(Symbol::Other, |position| scaffold.symbol(position))
+                })
+                .collect();
+            if site.strand == Strand::Negative {
+                reverse_complement_in_place(&mut window);
+            }
+            (window, site.kind)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{JunctionClass, JunctionCounts, SpliceSiteKind};
+    use crate::data::{
+        parse_sequence, reverse_complement, sequence_to_string, Feature, Scaffold, Strand, Symbol,
+    };
+    use crate::testing;
+
+    fn scaffold(name: &str, sequence: &str) -> Scaffold {
+        Scaffold::new(String::from(name), parse_sequence(sequence).unwrap())
+    }
+
+    #[test]
+    fn test_classify_junction() {
+        use Symbol::{Adenine as A, Cytosine as C, Guanine as G, Thymine as T};
+
+        assert_eq!(
+            super::classify_junction([G, T], [A, G]),
+            JunctionClass::Canonical
+        );
+        assert_eq!(
+            super::classify_junction([G, C], [A, G]),
+            JunctionClass::Minor
+        );
+        assert_eq!(
+            super::classify_junction([A, T], [A, C]),
+            JunctionClass::Minor
+        );
+        assert_eq!(
+            super::classify_junction([G, T], [A, C]),
+            JunctionClass::NonCanonical
+        );
+    }
+
+    #[test]
+    fn test_check_splice_sites() {
+        let positive = Strand::Positive;
+        let negative = Strand::Negative;
+        let scaffolds = vec![
+            // Exons AAA, CCC and TTT separated by GTxxAG and ATxxAC introns.
+            scaffold("p", "AAAGTCCAGCCCATGGACTTT"),
+            // Reverse complement of AAA GTCCAG CCC GCAAAG TTT.
+            scaffold("m", "AAACTTTGCGGGCTGGACTTT"),
+        ];
+        let transcripts = vec![
+            vec![
+                testing::annotation("p", Feature::Exon, positive, 0, 3),
+                testing::annotation("p", Feature::Exon, positive, 9, 12),
+                testing::annotation("p", Feature::Exon, positive, 18, 21),
+            ],
+            vec![
+                testing::annotation("m", Feature::Exon, negative, 18, 21),
+                testing::annotation("m", Feature::Exon, negative, 0, 3),
+                testing::annotation("m", Feature::Exon, negative, 9, 12),
+            ],
+            // Off by one coordinates flip the dinucleotides.
+            vec![
+                testing::annotation("p", Feature::Exon, positive, 0, 4),
+                testing::annotation("p", Feature::Exon, positive, 10, 12),
+            ],
+            // The same coordinates read on the wrong strand.
+            vec![
+                testing::annotation("p", Feature::Exon, negative, 0, 3),
+                testing::annotation("p", Feature::Exon, negative, 9, 12),
+            ],
+            vec![testing::annotation("x", Feature::Exon, positive, 0, 3)],
+            vec![testing::annotation("p", Feature::Exon, positive, 0, 30)],
+        ];
+
+        let report = super::check_splice_sites(&scaffolds, &transcripts);
+        assert_eq!(report.skipped(), &[4, 5]);
+        assert_eq!(
+            report.transcripts()[0],
+            JunctionCounts {
+                canonical: 1,
+                minor: 1,
+                non_canonical: 0
+            }
+        );
+        assert_eq!(
+            report.transcripts()[1],
+            JunctionCounts {
+                canonical: 1,
+                minor: 1,
+                non_canonical: 0
+            }
+        );
+        assert_eq!(report.transcripts()[2].non_canonical, 1);
+        assert_eq!(report.transcripts()[3].non_canonical, 1);
+        assert_eq!(
+            report.overall(),
+            JunctionCounts {
+                canonical: 2,
+                minor: 2,
+                non_canonical: 2
+            }
+        );
+        assert_eq!(report.worst_offenders(1), vec![(2, 1)]);
+        assert_eq!(report.worst_offenders(5), vec![(2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_splice_site_windows() {
+        // Transcript CCC GTAAAAAG TTT.
+        let transcript = parse_sequence("CCCGTAAAAAGTTT").unwrap();
+        let positive = Scaffold::new(String::from("p"), transcript.clone());
+        let negative = Scaffold::new(String::from("m"), reverse_complement(&transcript));
+
+        let positive_exons = vec![
+            testing::annotation("p", Feature::Exon, Strand::Positive, 11, 14),
+            testing::annotation("p", Feature::Exon, Strand::Positive, 0, 3),
+        ];
+        let negative_exons = vec![
+            testing::annotation("m", Feature::Exon, Strand::Negative, 11, 14),
+            testing::annotation("m", Feature::Exon, Strand::Negative, 0, 3),
+        ];
+
+        let sites = super::splice_sites(&positive_exons);
+        assert_eq!(
+            sites
+                .iter()
+                .map(|s| (s.scaffold(), s.position(), s.strand(), s.kind()))
+                .collect::<Vec<_>>(),
+            [
+                ("p", 3, Strand::Positive, SpliceSiteKind::Donor),
+                ("p", 11, Strand::Positive, SpliceSiteKind::Acceptor),
+            ]
+        );
+        let negative_sites = super::splice_sites(&negative_exons);
+        assert_eq!(
+            negative_sites
+                .iter()
+                .map(|s| (s.position(), s.kind()))
+                .collect::<Vec<_>>(),
+            [(3, SpliceSiteKind::Acceptor), (11, SpliceSiteKind::Donor)]
+        );
+
+        let render =
+            |windows: Vec<(Vec<Symbol>, SpliceSiteKind)>| -> Vec<(String, SpliceSiteKind)> {
+                windows
+                    .into_iter()
+                    .map(|(window, kind)| (sequence_to_string(&window), kind))
+                    .collect()
+            };
+        // Both strands yield the same transcript oriented windows, padded
+        // past the scaffold ends.
+        assert_eq!(
+            render(super::splice_site_windows(&positive, &sites, 4)),
+            [
+                (String::from("NCCCGTAA"), SpliceSiteKind::Donor),
+                (String::from("AAAGTTTN"), SpliceSiteKind::Acceptor),
+            ]
+        );
+        assert_eq!(
+            render(super::splice_site_windows(&negative, &negative_sites, 4)),
+            [
+                (String::from("AAAGTTTN"), SpliceSiteKind::Acceptor),
+                (String::from("NCCCGTAA"), SpliceSiteKind::Donor),
+            ]
+        );
+
+        let windows = super::splice_site_windows(&negative, &negative_sites, 2);
+        assert_eq!(sequence_to_string(&windows[0].0), "AGTT");
+        assert_eq!(sequence_to_string(&windows[1].0), "CCGT");
+        assert!(super::splice_site_windows(&positive, &sites, 0)[0]
+            .0
+            .is_empty());
+
+        assert!(super::splice_sites(&[testing::annotation(
+            "p",
+            Feature::Exon,
+            Strand::Positive,
+            0,
+            3
+        )])
+        .is_empty());
+        assert!(super::splice_sites(&[]).is_empty());
+        // Abutting exons do not form an intron.
+        assert!(super::splice_sites(&[
+            testing::annotation("p", Feature::Exon, Strand::Positive, 0, 3),
+            testing::annotation("p", Feature::Exon, Strand::Positive, 3, 6)
+        ])
+        .is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+use crate::data::{Annotation, Scaffold};
+use crate::simulate::Random;
+use anyhow::{Context, Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{prelude::*, BufReader, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Tolerance of the sum of split fractions.
+const FRACTION_TOLERANCE: f64 = 1e-6;
+
+/// Data set a scaffold is assigned to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Subset {
+    Train,
+    Validation,
+    Test,
+}
+
+impl Subset {
+    const ALL: [Subset; 3] = [Subset::Train, Subset::Validation, Subset::Test];
+}
+
+impl fmt::Display for Subset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Train => "train",
+            Self::Validation => "validation",
+            Self::Test => "test",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Subset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "train" => Ok(Self::Train),
+            "validation" => Ok(Self::Validation),
+            "test" => Ok(Self::Test),
+            _ => bail!("Unknown subset {}.", s),
+        }
+    }
+}
+
+/// Values split into train, validation and test subsets.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Partition<T> {
+    pub train: Vec<T>,
+    pub validation: Vec<T>,
+    pub test: Vec<T>,
+}
+
+impl<T> Partition<T> {
+    fn new() -> Self {
+        Self {
+            train: Vec::new(),
+            validation: Vec::new(),
+            test: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, subset: Subset) -> &[T] {
+        match subset {
+            Subset::Train => &self.train,
+            Subset::Validation => &self.validation,
+            Subset::Test => &self.test,
+        }
+    }
+
+    fn get_mut(&mut self, subset: Subset) -> &mut Vec<T> {
+        match subset {
+            Subset::Train => &mut self.train,
+            Subset::Validation => &mut self.validation,
+            Subset::Test => &mut self.test,
+        }
+    }
+}
+
+/// Assignment of whole scaffolds, identified by name, to subsets.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitAssignment {
+    subsets: HashMap<String, Subset>,
+}
+
+impl SplitAssignment {
+    /// Subset of scaffold `name`, `None` if it is not assigned.
+    pub fn get(&self, name: &str) -> Option<Subset> {
+        self.subsets.get(name).copied()
+    }
+
+    /// Names of scaffolds assigned to `subset` in arbitrary order.
+    pub fn scaffolds(&self, subset: Subset) -> impl Iterator<Item = &str> {
+        self.subsets
+            .iter()
+            .filter(move |(_, &s)| s == subset)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Map of scaffold names to their subsets.
+    pub fn as_map(&self) -> &HashMap<String, Subset> {
+        &self.subsets
+    }
+
+    pub fn into_map(self) -> HashMap<String, Subset> {
+        self.subsets
+    }
+
+    /// Splits annotations by the subsets of their scaffolds, keeping their
+    /// order. Fails if a scaffold is not assigned.
+    pub fn partition_annotations(
+        &self,
+        annotations: Vec<Annotation>,
+    ) -> Result<Partition<Annotation>> {
+        let mut partition = Partition::new();
+        for annotation in annotations {
+            let subset = self.get(annotation.scaffold()).with_context(|| {
+                format!(
+                    "Scaffold {} is not assigned to any subset.",
+                    annotation.scaffold()
+                )
+            })?;
+            partition.get_mut(subset).push(annotation);
+        }
+        Ok(partition)
+    }
+
+    /// Writes the assignment as tab separated lines of scaffold names and
+    /// subsets sorted by name.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut subsets: Vec<(&String, &Subset)> = self.subsets.iter().collect();
+        subsets.sort_unstable_by_key(|&(name, _)| name);
+        for (name, subset) in subsets {
+            writeln!(writer, "{}\t{}", name, subset)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads an assignment written by `write`.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut subsets = HashMap::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let (name, subset) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(subset), None) => (name, subset),
+                _ => bail!("Line {} does not have exactly two fields.", i + 1),
+            };
+            let subset: Subset = subset
+                .parse()
+                .with_context(|| format!("Invalid subset on line {}.", i + 1))?;
+            ensure!(
+                subsets.insert(String::from(name), subset).is_none(),
+                "Duplicate scaffold {} on line {}.",
+                name,
+                i + 1
+            );
+        }
+        Ok(Self { subsets })
+    }
+
+    /// Writes the assignment to file `path`, see `write`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create file {}.", path.display()))?;
+        self.write(BufWriter::new(file))
+            .with_context(|| format!("Failed to write file {}.", path.display()))
+    }
+
+    /// Loads an assignment from file `path`, see `read`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open file {}.", path.display()))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("Failed to read file {}.", path.display()))
+    }
+}
+
+impl From<HashMap<String, Subset>> for SplitAssignment {
+    fn from(subsets: HashMap<String, Subset>) -> Self {
+        Self { subsets }
+    }
+}
+
+/// Assigns whole scaffolds to train, validation and test subsets so that
+/// their total lengths approximately follow `fractions`, which must be
+/// non-negative and sum to 1.
+///
+/// Scaffolds are shuffled with `seed`, so the assignment is reproducible,
+/// and each is greedily assigned to the subset furthest below its target
+/// length. Scaffolds are placed roughly from the longest, with random order
+/// among scaffolds of lengths within a factor of two, so that long
+/// scaffolds cannot overshoot the fractions late. The achieved fractions
+/// thus differ from the requested ones by about the length of the longest
+/// scaffold relative to the total length.
+pub fn split_scaffolds(
+    scaffolds: &[Scaffold],
+    fractions: (f64, f64, f64),
+    seed: u64,
+) -> Result<SplitAssignment> {
+    let fractions = [fractions.0, fractions.1, fractions.2];
+    ensure!(
+        fractions.iter().all(|f| f.is_finite() && *f >= 0.),
+        "Split fractions must be non-negative, got {:?}.",
+        fractions
+    );
+    let sum: f64 = fractions.iter().sum();
+    ensure!(
+        (sum - 1.).abs() <= FRACTION_TOLERANCE,
+        "Split fractions must sum to 1, got {}.",
+        sum
+    );
+
+    let mut order: Vec<usize> = (0..scaffolds.len()).collect();
+    let mut random = Random::new(seed);
+    for i in (1..order.len()).rev() {
+        order.swap(i, random.range(0, i));
+    }
+    // Stable, so the shuffled order is kept within each length class.
+    order.sort_by_key(|&i| std::cmp::Reverse(usize::BITS - scaffolds[i].len().leading_zeros()));
+
+    let total: usize = scaffolds.iter().map(|s| s.len()).sum();
+    let mut assigned = [0usize; 3];
+    let mut subsets = HashMap::with_capacity(scaffolds.len());
+    for i in order {
+        let scaffold = &scaffolds[i];
+        let (index, _) = fractions
+            .iter()
+            .zip(assigned.iter())
+            .enumerate()
+            .filter(|(_, (&fraction, _))| fraction > 0.)
+            .map(|(index, (&fraction, &assigned))| {
+                (index, fraction * total as f64 - assigned as f64)
+            })
+            .fold((0, f64::NEG_INFINITY), |best, current| {
+                if current.1 > best.1 {
+                    current
+                } else {
+                    best
+                }
+            });
+        assigned[index] += scaffold.len();
+        ensure!(
+            subsets
+                .insert(String::from(scaffold.name()), Subset::ALL[index])
+                .is_none(),
+            "Duplicate scaffold {}.",
+            scaffold.name()
+        );
+    }
+    Ok(SplitAssignment { subsets })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{split_scaffolds, Partition, SplitAssignment, Subset};
+    use crate::data::{AnnotationBuilder, Feature, Scaffold, Symbol};
+    use std::collections::HashMap;
+
+    fn scaffolds(lengths: &[usize]) -> Vec<Scaffold> {
+        lengths
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| Scaffold::new(format!("s{}", i), vec![Symbol::Adenine; len]))
+            .collect()
+    }
+
+    fn subset_len(scaffolds: &[Scaffold], assignment: &SplitAssignment, subset: Subset) -> usize {
+        scaffolds
+            .iter()
+            .filter(|s| assignment.get(s.name()) == Some(subset))
+            .map(|s| s.len())
+            .sum()
+    }
+
+    #[test]
+    fn test_split_fractions() {
+        let scaffolds = scaffolds(&[10, 20]);
+        for fractions in [(0.8, 0.1, 0.), (0.8, 0.3, -0.1), (0.5, 0.5, f64::NAN)] {
+            assert!(split_scaffolds(&scaffolds, fractions, 0).is_err());
+        }
+        assert!(split_scaffolds(&scaffolds, (0.7, 0.2, 0.1), 0).is_ok());
+        assert!(split_scaffolds(&scaffolds, (1., 0., 0.), 0).is_ok());
+
+        let duplicates = vec![scaffolds[0].clone(), scaffolds[0].clone()];
+        assert_eq!(
+            split_scaffolds(&duplicates, (1., 0., 0.), 0)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Duplicate scaffold s0."
+        );
+    }
+
+    #[test]
+    fn test_split_deterministic() {
+        let scaffolds = scaffolds(&[100; 50]);
+        let first = split_scaffolds(&scaffolds, (0.6, 0.2, 0.2), 42).unwrap();
+        assert_eq!(
+            first,
+            split_scaffolds(&scaffolds, (0.6, 0.2, 0.2), 42).unwrap()
+        );
+        assert_ne!(
+            first,
+            split_scaffolds(&scaffolds, (0.6, 0.2, 0.2), 43).unwrap()
+        );
+        assert_eq!(subset_len(&scaffolds, &first, Subset::Train), 3000);
+        assert_eq!(subset_len(&scaffolds, &first, Subset::Validation), 1000);
+        assert_eq!(subset_len(&scaffolds, &first, Subset::Test), 1000);
+        assert_eq!(first.as_map().len(), 50);
+    }
+
+    #[test]
+    fn test_split_length_weighted() {
+        // Two scaffolds hold 80 % of the bases.
+        let mut lengths = vec![8000, 8000];
+        lengths.extend([20; 200].iter());
+        let scaffolds = scaffolds(&lengths);
+
+        for seed in 0..10 {
+            let assignment = split_scaffolds(&scaffolds, (0.8, 0.1, 0.1), seed).unwrap();
+            assert_eq!(assignment.get("s0"), Some(Subset::Train));
+            assert_eq!(assignment.get("s1"), Some(Subset::Train));
+            assert_eq!(assignment.scaffolds(Subset::Train).count(), 2);
+            assert_eq!(subset_len(&scaffolds, &assignment, Subset::Train), 16000);
+            assert_eq!(
+                subset_len(&scaffolds, &assignment, Subset::Validation),
+                2000
+            );
+            assert_eq!(subset_len(&scaffolds, &assignment, Subset::Test), 2000);
+        }
+
+        let assignment = split_scaffolds(&scaffolds, (0.5, 0.5, 0.), 3).unwrap();
+        assert_eq!(assignment.scaffolds(Subset::Test).count(), 0);
+        assert_eq!(subset_len(&scaffolds, &assignment, Subset::Train), 10000);
+        assert_eq!(
+            subset_len(&scaffolds, &assignment, Subset::Validation),
+            10000
+        );
+    }
+
+    #[test]
+    fn test_assignment_io() {
+        let scaffolds = scaffolds(&[5, 7, 3, 9]);
+        let assignment = split_scaffolds(&scaffolds, (0.5, 0.25, 0.25), 1).unwrap();
+
+        let mut buffer = Vec::new();
+        assignment.write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.starts_with("s0\t"));
+        assert_eq!(SplitAssignment::read(text.as_bytes()).unwrap(), assignment);
+
+        assert_eq!(
+            SplitAssignment::read(&b"s0\ttrain\ns1\tholdout\n"[..])
+                .err()
+                .unwrap()
+                .to_string(),
+            "Invalid subset on line 2."
+        );
+        assert_eq!(
+            SplitAssignment::read(&b"s0\ttrain\ns0\ttest\n"[..])
+                .err()
+                .unwrap()
+                .to_string(),
+            "Duplicate scaffold s0 on line 2."
+        );
+
+        let mut map = HashMap::new();
+        map.insert(String::from("chr1"), Subset::Test);
+        let assignment = SplitAssignment::from(map.clone());
+        assert_eq!(assignment.get("chr1"), Some(Subset::Test));
+        assert_eq!(assignment.into_map(), map);
+    }
+
+    #[test]
+    fn test_partition_annotations() {
+        let mut map = HashMap::new();
+        map.insert(String::from("a"), Subset::Train);
+        map.insert(String::from("b"), Subset::Test);
+        let assignment = SplitAssignment::from(map);
+
+        let annotation = |scaffold, start| {
+            AnnotationBuilder::new(scaffold, Feature::Exon)
+                .start(start)
+                .end(start + 1)
+                .build()
+                .unwrap()
+        };
+        let annotations = vec![annotation("a", 0), annotation("b", 1), annotation("a", 2)];
+        let partition: Partition<_> = assignment
+            .partition_annotations(annotations.clone())
+            .unwrap();
+        assert_eq!(
+            partition.train,
+            [annotations[0].clone(), annotations[2].clone()]
+        );
+        assert!(partition.get(Subset::Validation).is_empty());
+        assert_eq!(partition.get(Subset::Test), [annotations[1].clone()]);
+
+        assert_eq!(
+            assignment
+                .partition_annotations(vec![annotation("c", 4)])
+                .err()
+                .unwrap()
+                .to_string(),
+            "Scaffold c is not assigned to any subset."
+        );
+    }
+}
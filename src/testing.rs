@@ -0,0 +1,41 @@
+//! Fixtures shared by unit tests.
+
+use crate::data::{Annotation, AnnotationBuilder, Feature, Strand};
+
+/// Annotation with source `test` and without score, phase and attributes.
+/// Unlike `AnnotationBuilder`, the coordinates are not checked, so invalid
+/// annotations can be made too.
+pub(crate) fn annotation(
+    scaffold: &str,
+    feature: Feature,
+    strand: Strand,
+    start: usize,
+    end: usize,
+) -> Annotation {
+    Annotation::new(
+        String::from(scaffold),
+        String::from("test"),
+        feature,
+        None,
+        strand,
+        None,
+        start,
+        end,
+        String::new(),
+    )
+}
+
+/// Builder of a positive strand annotation with source `test`, for tests
+/// setting further columns.
+pub(crate) fn builder(
+    scaffold: &str,
+    feature: Feature,
+    start: usize,
+    end: usize,
+) -> AnnotationBuilder {
+    AnnotationBuilder::new(scaffold, feature)
+        .source("test")
+        .strand(Strand::Positive)
+        .start(start)
+        .end(end)
+}
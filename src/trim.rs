@@ -0,0 +1,265 @@
+use crate::data::{Annotation, PackedSequence, Scaffold, Symbol};
+use std::collections::HashMap;
+
+/// Determines what happens to annotations which (partially) fall into a
+/// trimmed region.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrimPolicy {
+    /// Drop every annotation touching a trimmed region.
+    Drop,
+    /// Truncate annotations to the retained part of the scaffold. Annotations
+    /// lying fully inside a trimmed region are dropped.
+    Truncate,
+}
+
+/// Coordinate mapping from original scaffolds to trimmed scaffolds.
+#[derive(Clone, Debug, Default)]
+pub struct OffsetMap {
+    offsets: HashMap<String, (usize, usize)>,
+}
+
+impl OffsetMap {
+    fn insert(&mut self, scaffold: &str, leading: usize, length: usize) {
+        self.offsets
+            .insert(String::from(scaffold), (leading, length));
+    }
+
+    /// Number of symbols removed from the beginning of a scaffold or `None`
+    /// if the scaffold is not known.
+    pub fn leading(&self, scaffold: &str) -> Option<usize> {
+        self.offsets.get(scaffold).map(|&(leading, _)| leading)
+    }
+
+    /// Maps 0-based half-open interval `[start, end)` on an original scaffold
+    /// to the trimmed scaffold. The interval is clipped to the retained
+    /// region and `None` is returned if nothing remains. Intervals on unknown
+    /// scaffolds are returned unchanged.
+    pub fn lift(&self, scaffold: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+        let (leading, length) = match self.offsets.get(scaffold) {
+            Some(&offset) => offset,
+            None => return Some((start, end)),
+        };
+
+        let start = start.saturating_sub(leading).min(length);
+        let end = end.saturating_sub(leading).min(length);
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+}
+
+/// Annotations affected by trimming.
+#[derive(Debug, Default)]
+pub struct TrimReport {
+    /// Original annotations which were removed.
+    pub dropped: Vec<Annotation>,
+    /// Original annotations which were truncated.
+    pub truncated: Vec<Annotation>,
+}
+
+/// Removes leading and trailing runs of `Symbol::Other` which are at least
+/// `min_run` symbols long. Returns the trimmed scaffold and the number of
+/// symbols removed from its beginning and its end respectively.
+///
+/// The trimmed scaffold keeps the name, the description, the circular flag
+/// and the storage (packed or not) of the original. Soft-masked regions are
+/// shifted and clipped to the retained sequence.
+///
+/// A scaffold consisting only of `Symbol::Other` is trimmed to an empty
+/// sequence, all of it being accounted to the beginning.
+pub fn trim_n_ends(scaffold: &Scaffold, min_run: usize) -> (Scaffold, usize, usize) {
+    let sequence = scaffold.sequence();
+
+    let mut leading = sequence
+        .iter()
+        .take_while(|&&symbol| symbol == Symbol::Other)
+        .count();
+    if leading < min_run {
+        leading = 0;
+    }
+
+    let mut trailing = sequence[leading..]
+        .iter()
+        .rev()
+        .take_while(|&&symbol| symbol == Symbol::Other)
+        .count();
+    if trailing < min_run {
+        trailing = 0;
+    }
+
+    let name = String::from(scaffold.name());
+    let retained = sequence[leading..sequence.len() - trailing].to_vec();
+    let trimmed = match scaffold.packed_sequence() {
+        Some(_) => Scaffold::new_packed(name, PackedSequence::from(retained)),
+        None => Scaffold::new(name, retained),
+    };
+    // Masked regions are clamped to the trimmed sequence and dropped if
+    // empty by `with_masked_regions`.
+    let masked = scaffold
+        .masked_regions()
+        .iter()
+        .map(|range| range.start.saturating_sub(leading)..range.end.saturating_sub(leading))
+        .collect();
+    let trimmed = trimmed
+        .with_description(scaffold.description().map(String::from))
+        .with_masked_regions(masked)
+        .with_circular(scaffold.is_circular());
+    (trimmed, leading, trailing)
+}
+
+/// Trims N runs from all scaffolds (see `trim_n_ends`) and shifts the
+/// annotations accordingly.
+///
+/// Annotations referencing scaffolds which are not present are kept
+/// unchanged. See `Genome::trim_n_ends` for trimming a `Genome` in place.
+pub fn trim_genome_n_ends(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    min_run: usize,
+    policy: TrimPolicy,
+) -> (Vec<Scaffold>, Vec<Annotation>, OffsetMap, TrimReport) {
+    let mut offsets = OffsetMap::default();
+    let trimmed_scaffolds = scaffolds
+        .iter()
+        .map(|scaffold| {
+            let (trimmed, leading, _) = trim_n_ends(scaffold, min_run);
+            offsets.insert(scaffold.name(), leading, trimmed.len());
+            trimmed
+        })
+        .collect();
+
+    let mut report = TrimReport::default();
+    let mut lifted = Vec::with_capacity(annotations.len());
+
+    for annotation in annotations {
+        let (start, end) = (annotation.start(), annotation.end());
+        let leading = match offsets.leading(annotation.scaffold()) {
+            Some(leading) => leading,
+            None => {
+                lifted.push(annotation.clone());
+                continue;
+            }
+        };
+
+        match offsets.lift(annotation.scaffold(), start, end) {
+            Some((new_start, new_end))
+                if new_start + leading == start && new_end + leading == end =>
+            {
+                lifted.push(annotation.with_range(new_start, new_end));
+            }
+            Some((new_start, new_end)) if policy == TrimPolicy::Truncate => {
+                lifted.push(annotation.with_range(new_start, new_end));
+                report.truncated.push(annotation.clone());
+            }
+            _ => report.dropped.push(annotation.clone()),
+        }
+    }
+
+    (trimmed_scaffolds, lifted, offsets, report)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::TrimPolicy;
+    use crate::data::{parse_sequence, Feature, PackedSequence, Scaffold, Strand, Symbol};
+    use crate::testing;
+
+    #[test]
+    fn test_trim_n_ends() {
+        let sequence = vec![
+            Symbol::Other,
+            Symbol::Other,
+            Symbol::Adenine,
+            Symbol::Other,
+            Symbol::Guanine,
+            Symbol::Other,
+        ];
+        let scaffold = Scaffold::new(String::from("s"), sequence);
+
+        let (trimmed, leading, trailing) = super::trim_n_ends(&scaffold, 2);
+        assert_eq!(leading, 2);
+        assert_eq!(trailing, 0);
+        assert_eq!(trimmed.sequence().len(), 4);
+
+        let (trimmed, leading, trailing) = super::trim_n_ends(&scaffold, 1);
+        assert_eq!(leading, 2);
+        assert_eq!(trailing, 1);
+        assert_eq!(
+            trimmed.sequence(),
+            &[Symbol::Adenine, Symbol::Other, Symbol::Guanine][..]
+        );
+
+        let gap = Scaffold::new(String::from("gap"), vec![Symbol::Other; 3]);
+        let (trimmed, leading, trailing) = super::trim_n_ends(&gap, 1);
+        assert_eq!((trimmed.sequence().len(), leading, trailing), (0, 3, 0));
+    }
+
+    #[test]
+    fn test_trim_n_ends_metadata() {
+        let sequence = parse_sequence("NNACGTACNNN").unwrap();
+        let scaffold = Scaffold::new(String::from("s"), sequence.clone())
+            .with_description(Some(String::from("chromosome 1")))
+            .with_masked_regions(vec![0..3, 5..7, 7..10]);
+
+        let (trimmed, leading, trailing) = super::trim_n_ends(&scaffold, 2);
+        assert_eq!((leading, trailing), (2, 3));
+        assert_eq!(trimmed.name(), "s");
+        assert_eq!(trimmed.description(), Some("chromosome 1"));
+        assert_eq!(trimmed.masked_regions(), &[0..1, 3..6]);
+        assert!(!trimmed.is_circular());
+        assert!(trimmed.packed_sequence().is_none());
+
+        let packed = Scaffold::new_packed(String::from("p"), PackedSequence::from(sequence))
+            .with_circular(true);
+        let (trimmed, _, _) = super::trim_n_ends(&packed, 2);
+        assert!(trimmed.is_circular());
+        assert_eq!(trimmed.packed_sequence().map(PackedSequence::len), Some(6));
+    }
+
+    #[test]
+    fn test_trim_genome_n_ends() {
+        let mut sequence = vec![Symbol::Other; 3];
+        sequence.extend(vec![Symbol::Adenine; 5]);
+        sequence.extend(vec![Symbol::Other; 2]);
+        let scaffolds = vec![Scaffold::new(String::from("s"), sequence)];
+        let annotations = vec![
+            testing::annotation("s", Feature::Exon, Strand::Positive, 0, 2),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 2, 5),
+            testing::annotation("s", Feature::Exon, Strand::Positive, 4, 6),
+        ];
+
+        let (scaffolds, lifted, offsets, report) =
+            super::trim_genome_n_ends(&scaffolds, &annotations, 1, TrimPolicy::Truncate);
+        assert_eq!(scaffolds[0].sequence().len(), 5);
+        assert_eq!(offsets.leading("s"), Some(3));
+        assert_eq!(offsets.lift("s", 9, 10), None);
+        assert_eq!(offsets.lift("unknown", 9, 10), Some((9, 10)));
+
+        assert_eq!(lifted.len(), 2);
+        assert_eq!((lifted[0].start(), lifted[0].end()), (0, 2));
+        assert_eq!((lifted[1].start(), lifted[1].end()), (1, 3));
+        assert_eq!(report.truncated.len(), 1);
+        assert_eq!(report.truncated[0].start(), 2);
+        assert_eq!(report.dropped.len(), 1);
+        assert_eq!(report.dropped[0].start(), 0);
+
+        let (_, lifted, _, report) = super::trim_genome_n_ends(
+            &[Scaffold::new(
+                String::from("s"),
+                vec![Symbol::Other, Symbol::Adenine, Symbol::Adenine],
+            )],
+            &[
+                testing::annotation("s", Feature::Exon, Strand::Positive, 0, 2),
+                testing::annotation("s", Feature::Exon, Strand::Positive, 1, 3),
+            ],
+            1,
+            TrimPolicy::Drop,
+        );
+        assert_eq!(lifted.len(), 1);
+        assert_eq!((lifted[0].start(), lifted[0].end()), (0, 2));
+        assert_eq!(report.dropped.len(), 1);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::data::{
+    line_note, symbols_to_string, Alphabet, Annotation, Feature, Scaffold, Strand, Symbol,
+};
+use crate::gff::GffMetadata;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Problem of an annotation found by `validate_annotations`. Annotations are
+/// identified by their index and source line, if known, and coordinates are
+/// 0-based half-open.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ValidationError {
+    /// The annotated scaffold is not among the scaffolds.
+    UnknownScaffold {
+        annotation: usize,
+        line: Option<usize>,
+        scaffold: String,
+    },
+    /// The annotation extends past the scaffold end.
+    OutOfBounds {
+        annotation: usize,
+        line: Option<usize>,
+        scaffold: String,
+        start: usize,
+        end: usize,
+        scaffold_len: usize,
+    },
+    /// A start codon annotation does not cover `ATG`.
+    NotStartCodon {
+        annotation: usize,
+        line: Option<usize>,
+        start: usize,
+        end: usize,
+        codon: [Symbol; 3],
+    },
+    /// A stop codon annotation does not cover `TAA`, `TAG` or `TGA`.
+    NotStopCodon {
+        annotation: usize,
+        line: Option<usize>,
+        start: usize,
+        end: usize,
+        codon: [Symbol; 3],
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownScaffold {
+                annotation,
+                line,
+                scaffold,
+            } => write!(
+                f,
+                "Annotation {}{} references unknown scaffold {}.",
+                annotation,
+                line_note(*line),
+                scaffold
+            ),
+            Self::OutOfBounds {
+                annotation,
+                line,
+                scaffold,
+                start,
+                end,
+                scaffold_len,
+            } => write!(
+                f,
+                "Annotation {}{} at {}..{} exceeds scaffold {} of length {}.",
+                annotation,
+                line_note(*line),
+                start,
+                end,
+                scaffold,
+                scaffold_len
+            ),
+            Self::NotStartCodon {
+                annotation,
+                line,
+                start,
+                end,
+                codon,
+            } => write!(
+                f,
+                "Start codon annotation {}{} at {}..{} covers {}.",
+                annotation,
+                line_note(*line),
+                start,
+                end,
+                symbols_to_string(codon, Alphabet::Dna)
+            ),
+            Self::NotStopCodon {
+                annotation,
+                line,
+                start,
+                end,
+                codon,
+            } => write!(
+                f,
+                "Stop codon annotation {}{} at {}..{} covers {}.",
+                annotation,
+                line_note(*line),
+                start,
+                end,
+                symbols_to_string(codon, Alphabet::Dna)
+            ),
+        }
+    }
+}
+
+/// Options of `validate_annotations_with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ValidationOptions {
+    /// Check that three bases long start and stop codon annotations cover a
+    /// start or a stop codon on their strand. Shorter parts of codons split
+    /// by an intron are not checked.
+    pub check_codons: bool,
+}
+
+/// Reports all annotations referencing unknown scaffolds or extending past
+/// the scaffold end.
+pub fn validate_annotations(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+) -> Vec<ValidationError> {
+    validate_annotations_with_options(scaffolds, annotations, ValidationOptions::default())
+}
+
+/// Reports all problems of annotations, see `ValidationError`.
+pub fn validate_annotations_with_options(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    options: ValidationOptions,
+) -> Vec<ValidationError> {
+    let scaffolds: HashMap<&str, &Scaffold> = scaffolds.iter().map(|s| (s.name(), s)).collect();
+    let mut errors = Vec::new();
+
+    for (i, annotation) in annotations.iter().enumerate() {
+        let (start, end) = (annotation.start(), annotation.end());
+        let scaffold = match scaffolds.get(annotation.scaffold()) {
+            Some(scaffold) => scaffold,
+            None => {
+                errors.push(ValidationError::UnknownScaffold {
+                    annotation: i,
+                    line: annotation.line(),
+                    scaffold: String::from(annotation.scaffold()),
+                });
+                continue;
+            }
+        };
+        if !scaffold.contains_range(&(start..end)) {
+            errors.push(ValidationError::OutOfBounds {
+                annotation: i,
+                line: annotation.line(),
+                scaffold: String::from(annotation.scaffold()),
+                start,
+                end,
+                scaffold_len: scaffold.len(),
+            });
+            continue;
+        }
+
+        let is_start = match annotation.feature() {
+            Feature::StartCodon => true,
+            Feature::StopCodon => false,
+            _ => continue,
+        };
+        if !options.check_codons || end - start != 3 {
+            continue;
+        }
+
+        let mut codon = [Symbol::Other; 3];
+        for (k, symbol) in codon.iter_mut().enumerate() {
+            *symbol = match annotation.strand() {
+                Strand::Negative => scaffold.symbol((end - 1 - k) % scaffold.len()).complement(),
+                Strand::Positive | Strand::Unstranded | Strand::Unknown => {
+                    scaffold.symbol((start + k) % scaffold.len())
+                }
+            };
+        }
+
+        use Symbol::{Adenine as A, Guanine as G, Thymine as T};
+        if is_start && codon != [A, T, G] {
+            errors.push(ValidationError::NotStartCodon {
+                annotation: i,
+                line: annotation.line(),
+                start,
+                end,
+                codon,
+            });
+        } else if !is_start && ![[T, A, A], [T, A, G], [T, G, A]].contains(&codon) {
+            errors.push(ValidationError::NotStopCodon {
+                annotation: i,
+                line: annotation.line(),
+                start,
+                end,
+                codon,
+            });
+        }
+    }
+
+    errors
+}
+
+/// Reports annotations extending past scaffold lengths declared by GFF3
+/// `##sequence-region` directives. This needs no sequences, annotations of
+/// undeclared scaffolds are not checked. Annotations of circular scaffolds
+/// may wrap around the origin.
+pub fn validate_annotations_with_metadata(
+    annotations: &[Annotation],
+    metadata: &GffMetadata,
+) -> Vec<ValidationError> {
+    let lengths = metadata.sequence_lengths();
+    annotations
+        .iter()
+        .enumerate()
+        .filter_map(|(i, annotation)| {
+            let scaffold_len = *lengths.get(annotation.scaffold())?;
+            let wraps = metadata.is_circular(annotation.scaffold())
+                && annotation.start() < scaffold_len
+                && annotation.len() <= scaffold_len;
+            if annotation.end() <= scaffold_len || wraps {
+                return None;
+            }
+            Some(ValidationError::OutOfBounds {
+                annotation: i,
+                line: annotation.line(),
+                scaffold: String::from(annotation.scaffold()),
+                start: annotation.start(),
+                end: annotation.end(),
+                scaffold_len,
+            })
+        })
+        .collect()
+}
+
+/// Strict version of `validate_annotations_with_options` failing with all
+/// problems listed if there are any.
+pub fn check_annotations(
+    scaffolds: &[Scaffold],
+    annotations: &[Annotation],
+    options: ValidationOptions,
+) -> Result<()> {
+    let errors = validate_annotations_with_options(scaffolds, annotations, options);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let listing: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    bail!(
+        "Found {} invalid annotations: {}",
+        errors.len(),
+        listing.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{ValidationError, ValidationOptions};
+    use crate::data::{Feature, Scaffold, Strand, Symbol};
+    use crate::gff::load_gff_file_with_metadata;
+    use crate::testing;
+    use std::path::Path;
+
+    #[test]
+    fn test_validate_annotations() {
+        use Symbol::{Adenine as A, Cytosine as C, Guanine as G, Thymine as T};
+        // ATG CCC TAA, the reverse complement of CAT GGG TTA.
+        let scaffolds = vec![
+            Scaffold::new(String::from("p"), vec![A, T, G, C, C, C, T, A, A]),
+            Scaffold::new(String::from("m"), vec![T, T, A, G, G, G, C, A, T]),
+        ];
+        let annotations = vec![
+            testing::annotation("p", Feature::StartCodon, Strand::Positive, 0, 3),
+            testing::annotation("p", Feature::StopCodon, Strand::Positive, 6, 9),
+            testing::annotation("m", Feature::StartCodon, Strand::Negative, 6, 9),
+            testing::annotation("m", Feature::StopCodon, Strand::Negative, 0, 3),
+            testing::annotation("p", Feature::StartCodon, Strand::Positive, 3, 6),
+            testing::annotation("p", Feature::StopCodon, Strand::Positive, 0, 2),
+            testing::annotation("x", Feature::Exon, Strand::Positive, 0, 3),
+            testing::annotation("m", Feature::Exon, Strand::Positive, 5, 10),
+        ];
+
+        let errors = super::validate_annotations(&scaffolds, &annotations);
+        assert_eq!(
+            errors,
+            [
+                ValidationError::UnknownScaffold {
+                    annotation: 6,
+                    line: None,
+                    scaffold: String::from("x")
+                },
+                ValidationError::OutOfBounds {
+                    annotation: 7,
+                    line: None,
+                    scaffold: String::from("m"),
+                    start: 5,
+                    end: 10,
+                    scaffold_len: 9
+                },
+            ]
+        );
+
+        let options = ValidationOptions { check_codons: true };
+        let errors = super::validate_annotations_with_options(&scaffolds, &annotations, options);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0],
+            ValidationError::NotStartCodon {
+                annotation: 4,
+                line: None,
+                start: 3,
+                end: 6,
+                codon: [C, C, C]
+            }
+        );
+
+        let error = super::check_annotations(&scaffolds, &annotations, options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Found 3 invalid annotations: Start codon annotation 4 at 3..6 covers CCC. \
+             Annotation 6 references unknown scaffold x. \
+             Annotation 7 at 5..10 exceeds scaffold m of length 9."
+        );
+        assert!(super::check_annotations(&scaffolds, &annotations[..4], options).is_ok());
+    }
+
+    #[test]
+    fn test_validate_annotations_with_metadata() {
+        let (annotations, metadata) =
+            load_gff_file_with_metadata(Path::new("./tests/metadata.gff3")).unwrap();
+        let errors = super::validate_annotations_with_metadata(&annotations, &metadata);
+        assert_eq!(
+            errors[0].to_string(),
+            "Annotation 1 (line 10) at 9..290 exceeds scaffold scaffold_1 of length 280."
+        );
+        assert_eq!(
+            errors,
+            [ValidationError::OutOfBounds {
+                annotation: 1,
+                line: Some(10),
+                scaffold: String::from("scaffold_1"),
+                start: 9,
+                end: 290,
+                scaffold_len: 280
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_circular() {
+        let (annotations, metadata) =
+            load_gff_file_with_metadata(Path::new("./tests/circular.gff3")).unwrap();
+        assert!(metadata.is_circular("chrM"));
+        assert!(!metadata.is_circular("chr1"));
+        let errors = super::validate_annotations_with_metadata(&annotations, &metadata);
+        assert_eq!(
+            errors,
+            [ValidationError::OutOfBounds {
+                annotation: 3,
+                line: Some(7),
+                scaffold: String::from("chr1"),
+                start: 15,
+                end: 25,
+                scaffold_len: 20
+            }]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::data::{symbols_to_string, Alphabet, Annotation, Scaffold, Strand, Symbol};
+use crate::gff::feature_to_str;
+use crate::region::Region;
+use anyhow::Result;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Number of bases per line of the text rendering.
+const TEXT_WIDTH: usize = 60;
+
+/// Handling of regions extending past the scaffold end.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OutOfRange {
+    #[default]
+    Error,
+    /// Clamp the region to the scaffold; `RegionView::clamped` is set.
+    Clamp,
+}
+
+/// Sequence and annotations of a region of a scaffold.
+#[derive(Clone, Debug)]
+pub struct RegionView<'a> {
+    region: Region,
+    clamped: bool,
+    sequence: Cow<'a, [Symbol]>,
+    annotations: Vec<(&'a Annotation, Range<usize>)>,
+}
+
+impl<'a> RegionView<'a> {
+    /// The viewed region, after clamping.
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Whether the requested region was clamped to the scaffold.
+    pub fn clamped(&self) -> bool {
+        self.clamped
+    }
+
+    pub fn sequence(&self) -> &[Symbol] {
+        &self.sequence
+    }
+
+    /// Annotations overlapping the region sorted by start and end, each with
+    /// its range clipped to the region, relative to the region start.
+    pub fn annotations(&self) -> &[(&'a Annotation, Range<usize>)] {
+        &self.annotations
+    }
+
+    /// Renders the sequence in lines of 60 bases prefixed with 1-based
+    /// positions. Each line is followed by one track line per annotation
+    /// overlapping it, marking the annotation with `>` (positive strand), `<`
+    /// (negative strand) or `=` (unstranded or unknown strand) and naming its
+    /// feature type.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}", self.region);
+        if self.clamped {
+            text.push_str(" (clamped)");
+        }
+        text.push('\n');
+
+        for (i, line) in self.sequence.chunks(TEXT_WIDTH).enumerate() {
+            let line_start = i * TEXT_WIDTH;
+            let line_end = line_start + line.len();
+            writeln!(
+                text,
+                "{:>10} {}",
+                self.region.start() + line_start + 1,
+                symbols_to_string(line, Alphabet::Dna)
+            )
+            .unwrap();
+
+            for (annotation, range) in self.annotations.iter() {
+                if range.end <= line_start || range.start >= line_end {
+                    continue;
+                }
+                let marker = match annotation.strand() {
+                    Strand::Positive => '>',
+                    Strand::Negative => '<',
+                    Strand::Unstranded | Strand::Unknown => '=',
+                };
+                let track: String = (line_start..line_end)
+                    .map(|position| {
+                        if range.contains(&position) {
+                            marker
+                        } else {
+                            ' '
+                        }
+                    })
+                    .collect();
+                writeln!(
+                    text,
+                    "{:>10} {} {}",
+                    "",
+                    track,
+                    feature_to_str(annotation.feature())
+                )
+                .unwrap();
+            }
+        }
+
+        text
+    }
+
+    /// Renders the view as a JSON object. All coordinates are 0-based
+    /// half-open; `start` and `end` of annotations are scaffold coordinates
+    /// while `window_start` and `window_end` are clipped and relative to the
+    /// region start.
+    pub fn to_json(&self) -> String {
+        let annotations: Vec<String> = self
+            .annotations
+            .iter()
+            .map(|(annotation, range)| {
+                let strand = match annotation.strand() {
+                    Strand::Positive => "+",
+                    Strand::Negative => "-",
+                    Strand::Unstranded => ".",
+                    Strand::Unknown => "?",
+                };
+                format!(
+                    "{{\"feature\":{},\"source\":{},\"strand\":\"{}\",\"start\":{},\"end\":{},\
+                     \"window_start\":{},\"window_end\":{},\"attributes\":{}}}",
+                    json_string(feature_to_str(annotation.feature())),
+                    json_string(annotation.source()),
+                    strand,
+                    annotation.start(),
+                    annotation.end(),
+                    range.start,
+                    range.end,
+                    json_string(annotation.attributes())
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"scaffold\":{},\"start\":{},\"end\":{},\"clamped\":{},\"sequence\":\"{}\",\
+             \"annotations\":[{}]}}",
+            json_string(self.region.scaffold()),
+            self.region.start(),
+            self.region.end(),
+            self.clamped,
+            symbols_to_string(&self.sequence, Alphabet::Dna),
+            annotations.join(",")
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Returns the sequence of a region together with all annotations
+/// overlapping it. Annotations are scanned linearly, see `Genome::query`
+/// for an indexed lookup.
+///
+/// Annotations merely touching the region, empty annotations and empty
+/// regions do not overlap, as in `AnnotationIndex::query`.
+pub fn query_region<'a>(
+    scaffolds: &'a [Scaffold],
+    annotations: &'a [Annotation],
+    region: &Region,
+    out_of_range: OutOfRange,
+) -> Result<RegionView<'a>> {
+    let scaffold = match scaffolds.iter().find(|s| s.name() == region.scaffold()) {
+        Some(scaffold) => scaffold,
+        None => bail!("Unknown scaffold {}.", region.scaffold()),
+    };
+    region_view(scaffold, region, out_of_range, |region| {
+        annotations
+            .iter()
+            .filter(|a| {
+                a.scaffold() == region.scaffold()
+                    && a.start().max(region.start()) < a.end().min(region.end())
+            })
+            .collect()
+    })
+}
+
+/// Builds the view of `region` on `scaffold`. `overlapping` returns the
+/// annotations overlapping the region after clamping.
+pub(crate) fn region_view<'a>(
+    scaffold: &'a Scaffold,
+    region: &Region,
+    out_of_range: OutOfRange,
+    overlapping: impl FnOnce(&Region) -> Vec<&'a Annotation>,
+) -> Result<RegionView<'a>> {
+    let len = scaffold.len();
+    ensure!(
+        region.start() <= region.end(),
+        "Region {} has start after end.",
+        region
+    );
+
+    let clamped = region.end() > len;
+    if clamped && out_of_range == OutOfRange::Error {
+        bail!(
+            "Region {} is out of bounds of scaffold {} of length {}.",
+            region,
+            scaffold.name(),
+            len
+        );
+    }
+    let region = Region::new(
+        region.scaffold(),
+        region.start().min(len),
+        region.end().min(len),
+    );
+
+    let mut overlapping: Vec<(&Annotation, Range<usize>)> = overlapping(&region)
+        .into_iter()
+        .map(|a| {
+            let start = a.start().max(region.start()) - region.start();
+            let end = a.end().min(region.end()) - region.start();
+            (a, start..end)
+        })
+        .collect();
+    overlapping.sort_by_key(|(a, _)| (a.start(), a.end()));
+
+    Ok(RegionView {
+        sequence: scaffold
+            .subsequence(region.start()..region.end())
+            .expect("Region was clamped to the scaffold."),
+        region,
+        clamped,
+        annotations: overlapping,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::OutOfRange;
+    use crate::data::{Annotation, Feature, Scaffold, Strand, Symbol};
+    use crate::region::Region;
+    use crate::testing;
+
+    fn annotation(strand: Strand, start: usize, end: usize, attributes: &str) -> Annotation {
+        testing::builder("s", Feature::Exon, start, end)
+            .strand(strand)
+            .attributes(attributes)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_query_region() {
+        let sequence = [
+            Symbol::Adenine,
+            Symbol::Cytosine,
+            Symbol::Guanine,
+            Symbol::Thymine,
+        ]
+        .iter()
+        .cycle()
+        .take(100)
+        .cloned()
+        .collect();
+        let scaffolds = vec![Scaffold::new(String::from("s"), sequence)];
+        let annotations = vec![
+            annotation(Strand::Negative, 8, 12, "ID=\"b\""),
+            annotation(Strand::Positive, 0, 3, "ID=a"),
+            annotation(Strand::Positive, 20, 30, "ID=c"),
+        ];
+
+        let region = "s:3-10".parse().unwrap();
+        let view =
+            super::query_region(&scaffolds, &annotations, &region, OutOfRange::Error).unwrap();
+        assert!(!view.clamped());
+        assert_eq!(view.sequence().len(), 8);
+        assert_eq!(view.annotations().len(), 2);
+        assert_eq!(view.annotations()[0].0.attributes(), "ID=a");
+        assert_eq!(view.annotations()[0].1, 0..1);
+        assert_eq!(view.annotations()[1].1, 6..8);
+
+        assert_eq!(
+            view.to_text(),
+            "s:3-10\n         3 GTACGTAC\n           >        exon\n                 << exon\n"
+        );
+        assert_eq!(
+            view.to_json(),
+            "{\"scaffold\":\"s\",\"start\":2,\"end\":10,\"clamped\":false,\"sequence\":\"GTACGTAC\",\
+             \"annotations\":[\
+             {\"feature\":\"exon\",\"source\":\"test\",\"strand\":\"+\",\"start\":0,\"end\":3,\
+             \"window_start\":0,\"window_end\":1,\"attributes\":\"ID=a\"},\
+             {\"feature\":\"exon\",\"source\":\"test\",\"strand\":\"-\",\"start\":8,\"end\":12,\
+             \"window_start\":6,\"window_end\":8,\"attributes\":\"ID=\\\"b\\\"\"}]}"
+        );
+
+        let region = Region::new("s", 90, 120);
+        assert!(super::query_region(&scaffolds, &annotations, &region, OutOfRange::Error).is_err());
+        let view =
+            super::query_region(&scaffolds, &annotations, &region, OutOfRange::Clamp).unwrap();
+        assert!(view.clamped());
+        assert_eq!(view.region(), &Region::new("s", 90, 100));
+        assert_eq!(view.sequence().len(), 10);
+        assert!(view.annotations().is_empty());
+
+        let region = Region::new("t", 0, 1);
+        assert!(super::query_region(&scaffolds, &annotations, &region, OutOfRange::Clamp).is_err());
+    }
+}
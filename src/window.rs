@@ -0,0 +1,347 @@
+use crate::data::{hard_mask, Scaffold, Symbol};
+
+/// Handling of windows reaching past the scaffold end.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Padding {
+    /// Skip windows which do not fit in the scaffold.
+    #[default]
+    Drop,
+    /// Fill the part of the window past the scaffold end with
+    /// `Symbol::Other`. Only windows starting within the scaffold are
+    /// yielded.
+    Pad,
+}
+
+/// Options of `windows_with_options` and `labeled_windows_with_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct WindowOptions {
+    pub padding: Padding,
+    /// Replace soft-masked positions of each window by `Symbol::Other` as
+    /// in `Scaffold::hard_masked`, without a hard-masked copy of the
+    /// scaffold.
+    pub hard_mask: bool,
+}
+
+impl From<Padding> for WindowOptions {
+    fn from(padding: Padding) -> Self {
+        Self {
+            padding,
+            ..Self::default()
+        }
+    }
+}
+
+/// Fixed length window of a scaffold sequence.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Window {
+    scaffold: String,
+    start: usize,
+    padded: usize,
+    sequence: Vec<Symbol>,
+}
+
+impl Window {
+    pub fn scaffold(&self) -> &str {
+        self.scaffold.as_str()
+    }
+
+    /// 0-based start of the window on the scaffold.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Number of padding symbols at the end of the window.
+    pub fn padded(&self) -> usize {
+        self.padded
+    }
+
+    /// Window sequence including padding.
+    pub fn sequence(&self) -> &[Symbol] {
+        self.sequence.as_slice()
+    }
+
+    pub fn into_sequence(self) -> Vec<Symbol> {
+        self.sequence
+    }
+}
+
+/// Lazy iterator over window ranges, see `windows`.
+struct Starts {
+    len: usize,
+    size: usize,
+    stride: usize,
+    padding: Padding,
+    next: usize,
+}
+
+impl Starts {
+    fn new(len: usize, size: usize, stride: usize, padding: Padding) -> Self {
+        assert!(size > 0, "Window size must be positive.");
+        assert!(stride > 0, "Window stride must be positive.");
+        Self {
+            len,
+            size,
+            stride,
+            padding,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for Starts {
+    /// Window start and end of its part within the scaffold.
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.next;
+        let fits = match self.padding {
+            Padding::Drop => self.len >= self.size && start <= self.len - self.size,
+            Padding::Pad => start < self.len,
+        };
+        if !fits {
+            return None;
+        }
+        self.next = start.saturating_add(self.stride);
+        Some((start, self.len.min(start + self.size)))
+    }
+}
+
+/// Returns windows of length `size` starting every `stride` positions from
+/// the scaffold start. Windows are created lazily, one at a time. With
+/// `stride` larger than `size`, positions between windows are skipped.
+///
+/// # Panics
+///
+/// Panics if `size` or `stride` is zero.
+pub fn windows(
+    scaffold: &Scaffold,
+    size: usize,
+    stride: usize,
+    padding: Padding,
+) -> impl Iterator<Item = Window> + '_ {
+    windows_with_options(scaffold, size, stride, WindowOptions::from(padding))
+}
+
+/// Same as `windows` with further options, e.g. hard-masking.
+///
+/// # Panics
+///
+/// Panics if `size` or `stride` is zero.
+pub fn windows_with_options(
+    scaffold: &Scaffold,
+    size: usize,
+    stride: usize,
+    options: WindowOptions,
+) -> impl Iterator<Item = Window> + '_ {
+    Starts::new(scaffold.len(), size, stride, options.padding).map(move |(start, end)| {
+        let mut window = scaffold
+            .subsequence(start..end)
+            .expect("Window is within the scaffold.")
+            .into_owned();
+        if options.hard_mask {
+            self::hard_mask(&mut window, start, scaffold.masked_regions());
+        }
+        window.resize(size, Symbol::Other);
+        Window {
+            scaffold: String::from(scaffold.name()),
+            start,
+            padded: size - (end - start),
+            sequence: window,
+        }
+    })
+}
+
+/// Same as `windows` but yields each window sequence paired with the
+/// corresponding slice of per-position `labels` of the scaffold, e.g. as
+/// computed by `labels::labels_for_scaffold`. Padded positions are labeled
+/// 0, i.e. outside of any feature in all `LabelScheme`s.
+///
+/// # Panics
+///
+/// Panics if `size` or `stride` is zero or if the number of labels differs
+/// from the scaffold length.
+pub fn labeled_windows<'a>(
+    scaffold: &'a Scaffold,
+    labels: &'a [u8],
+    size: usize,
+    stride: usize,
+    padding: Padding,
+) -> impl Iterator<Item = (Vec<Symbol>, Vec<u8>)> + 'a {
+    labeled_windows_with_options(scaffold, labels, size, stride, WindowOptions::from(padding))
+}
+
+/// Same as `labeled_windows` with further options, e.g. hard-masking.
+///
+/// # Panics
+///
+/// Panics if `size` or `stride` is zero or if the number of labels differs
+/// from the scaffold length.
+pub fn labeled_windows_with_options<'a>(
+    scaffold: &'a Scaffold,
+    labels: &'a [u8],
+    size: usize,
+    stride: usize,
+    options: WindowOptions,
+) -> impl Iterator<Item = (Vec<Symbol>, Vec<u8>)> + 'a {
+    assert_eq!(
+        labels.len(),
+        scaffold.len(),
+        "Number of labels differs from the length of scaffold {}.",
+        scaffold.name()
+    );
+    windows_with_options(scaffold, size, stride, options).map(move |window| {
+        let start = window.start();
+        let mut window_labels = labels[start..start + size - window.padded()].to_vec();
+        window_labels.resize(size, 0);
+        (window.into_sequence(), window_labels)
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        labeled_windows, labeled_windows_with_options, windows, windows_with_options, Padding,
+        Window, WindowOptions,
+    };
+    use crate::data::{parse_sequence, sequence_to_string, Scaffold};
+
+    fn scaffold(sequence: &str) -> Scaffold {
+        Scaffold::new(String::from("s"), parse_sequence(sequence).unwrap())
+    }
+
+    fn render(windows: impl Iterator<Item = Window>) -> Vec<(usize, String, usize)> {
+        windows
+            .map(|w| {
+                assert_eq!(w.scaffold(), "s");
+                (w.start(), sequence_to_string(w.sequence()), w.padded())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_windows_shorter_scaffold() {
+        let scaffold = scaffold("ACG");
+        assert!(render(windows(&scaffold, 5, 2, Padding::Drop)).is_empty());
+        assert_eq!(
+            render(windows(&scaffold, 5, 2, Padding::Pad)),
+            [(0, String::from("ACGNN"), 2), (2, String::from("GNNNN"), 4)]
+        );
+        assert!(render(windows(&self::scaffold(""), 5, 2, Padding::Pad)).is_empty());
+    }
+
+    #[test]
+    fn test_windows_stride_larger_than_size() {
+        let scaffold = scaffold("ACGTACGTAC");
+        assert_eq!(
+            render(windows(&scaffold, 2, 3, Padding::Drop)),
+            [
+                (0, String::from("AC"), 0),
+                (3, String::from("TA"), 0),
+                (6, String::from("GT"), 0),
+            ]
+        );
+        assert_eq!(
+            render(windows(&scaffold, 2, 3, Padding::Pad)),
+            [
+                (0, String::from("AC"), 0),
+                (3, String::from("TA"), 0),
+                (6, String::from("GT"), 0),
+                (9, String::from("CN"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_windows_exact_multiple() {
+        let scaffold = scaffold("ACGTACGT");
+        let expected = [(0, String::from("ACGT"), 0), (4, String::from("ACGT"), 0)];
+        assert_eq!(render(windows(&scaffold, 4, 4, Padding::Drop)), expected);
+        assert_eq!(render(windows(&scaffold, 4, 4, Padding::Pad)), expected);
+
+        assert_eq!(
+            render(windows(&scaffold, 4, 2, Padding::Drop)),
+            [
+                (0, String::from("ACGT"), 0),
+                (2, String::from("GTAC"), 0),
+                (4, String::from("ACGT"), 0),
+            ]
+        );
+        assert_eq!(render(windows(&scaffold, 4, 2, Padding::Pad)).len(), 4);
+    }
+
+    #[test]
+    fn test_labeled_windows() {
+        let scaffold = scaffold("ACGTACG");
+        let labels = [0, 1, 1, 2, 2, 3, 3];
+
+        let windows: Vec<(String, Vec<u8>)> =
+            labeled_windows(&scaffold, &labels, 3, 3, Padding::Pad)
+                .map(|(sequence, labels)| (sequence_to_string(&sequence), labels))
+                .collect();
+        assert_eq!(
+            windows,
+            [
+                (String::from("ACG"), vec![0, 1, 1]),
+                (String::from("TAC"), vec![2, 2, 3]),
+                (String::from("GNN"), vec![3, 0, 0]),
+            ]
+        );
+        assert_eq!(
+            labeled_windows(&scaffold, &labels, 3, 3, Padding::Drop).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_windows_hard_mask() {
+        let scaffold = scaffold("ACGTACGTAC").with_masked_regions(vec![1..3, 5..7, 9..10]);
+        let expected = render(windows(&scaffold.hard_masked(), 4, 3, Padding::Pad));
+        assert_eq!(
+            render(windows_with_options(
+                &scaffold,
+                4,
+                3,
+                WindowOptions {
+                    padding: Padding::Pad,
+                    hard_mask: true,
+                }
+            )),
+            expected
+        );
+        assert_eq!(
+            expected,
+            [
+                (0, String::from("ANNT"), 0),
+                (3, String::from("TANN"), 0),
+                (6, String::from("NTAN"), 0),
+                (9, String::from("NNNN"), 3),
+            ]
+        );
+        // Without the option masking is ignored.
+        assert_eq!(render(windows(&scaffold, 4, 3, Padding::Drop))[0].1, "ACGT");
+
+        let labels = [1; 10];
+        let (sequence, labels) = labeled_windows_with_options(
+            &scaffold,
+            &labels,
+            4,
+            4,
+            WindowOptions {
+                padding: Padding::Drop,
+                hard_mask: true,
+            },
+        )
+        .next()
+        .unwrap();
+        assert_eq!(sequence_to_string(&sequence), "ANNT");
+        assert_eq!(labels, [1; 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_labeled_windows_length_mismatch() {
+        let scaffold = scaffold("ACGT");
+        let _ = labeled_windows(&scaffold, &[0, 1], 2, 2, Padding::Drop);
+    }
+}